@@ -0,0 +1,36 @@
+-- Partial indexes for soft-deleted filtering
+-- Read paths only ever want live rows, so these indexes skip tombstones
+-- (rows with deleted_at set) and stay small and hot in cache.
+
+-- ============================================================================
+-- MESSAGES
+-- ============================================================================
+
+-- Serves: get_user_inbox (recipient_id = $1 ORDER BY created_at DESC),
+-- the recipient side of search_messages and the unread counts.
+CREATE INDEX messages_recipient_live_idx
+    ON messages(recipient_id, created_at DESC)
+    WHERE deleted_at IS NULL;
+
+-- Serves: the sender side of search_messages and get_user_conversations.
+CREATE INDEX messages_sender_live_idx
+    ON messages(sender_id, created_at DESC)
+    WHERE deleted_at IS NULL;
+
+-- Serves: get_thread_messages (thread_id = $1 ORDER BY created_at ASC).
+CREATE INDEX messages_thread_live_idx
+    ON messages(thread_id, created_at)
+    WHERE deleted_at IS NULL;
+
+-- ============================================================================
+-- BROADCAST COMMENTS
+-- ============================================================================
+
+-- Serves: get_broadcast_comments (broadcast_id = $1 AND deleted_at IS NULL
+-- ORDER BY created_at ASC).
+CREATE INDEX broadcast_comments_live_idx
+    ON broadcast_comments(broadcast_id, created_at)
+    WHERE deleted_at IS NULL;
+
+-- Broadcasts have no soft-delete column yet; their partial index ships with
+-- the migration that introduces broadcasts.deleted_at.
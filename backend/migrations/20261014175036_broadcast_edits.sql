@@ -0,0 +1,14 @@
+-- Broadcast editing with version history
+
+ALTER TABLE broadcasts ADD COLUMN edited_at TIMESTAMPTZ;
+
+-- Broadcast Edit History (one row per superseded version)
+CREATE TABLE broadcast_edits (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    broadcast_id UUID NOT NULL REFERENCES broadcasts(id) ON DELETE CASCADE,
+    old_content TEXT NOT NULL,
+    edited_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    edited_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX broadcast_edits_broadcast_id_idx ON broadcast_edits(broadcast_id, edited_at);
@@ -0,0 +1,8 @@
+-- Broadcast drafts
+-- A draft is only visible to its author. Drafts always keep the author in
+-- sender_id (even when is_anonymous is set) so the author can edit and
+-- publish them; publishing clears sender_id for anonymous broadcasts.
+
+ALTER TABLE broadcasts ADD COLUMN is_draft BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX broadcasts_drafts_idx ON broadcasts(sender_id, created_at DESC) WHERE is_draft;
@@ -0,0 +1,46 @@
+-- Weighted search document for broadcasts
+-- Content is weighted above metadata. The author's username is only indexed
+-- for non-anonymous broadcasts, so searching for a person never surfaces
+-- their anonymous posts. Private messages keep indexing content only: their
+-- sender is always hidden from the recipient.
+
+CREATE FUNCTION broadcast_search_doc(content TEXT, is_anonymous BOOLEAN, sender_id UUID)
+RETURNS tsvector AS $$
+    SELECT setweight(to_tsvector('english', content), 'A') ||
+        setweight(to_tsvector('simple', coalesce(
+            CASE WHEN NOT is_anonymous THEN (SELECT username FROM users WHERE id = sender_id) END,
+            ''
+        )), 'B')
+$$ LANGUAGE sql STABLE;
+
+ALTER TABLE broadcasts ADD COLUMN search_doc tsvector;
+
+UPDATE broadcasts SET search_doc = broadcast_search_doc(content, is_anonymous, sender_id);
+
+CREATE FUNCTION broadcasts_search_doc_trigger() RETURNS trigger AS $$
+BEGIN
+    NEW.search_doc := broadcast_search_doc(NEW.content, NEW.is_anonymous, NEW.sender_id);
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER broadcasts_search_doc_update
+    BEFORE INSERT OR UPDATE OF content, is_anonymous, sender_id ON broadcasts
+    FOR EACH ROW EXECUTE FUNCTION broadcasts_search_doc_trigger();
+
+-- Keep documents current when an author renames themselves
+CREATE FUNCTION users_refresh_broadcast_search_doc() RETURNS trigger AS $$
+BEGIN
+    UPDATE broadcasts
+    SET search_doc = broadcast_search_doc(content, is_anonymous, sender_id)
+    WHERE sender_id = NEW.id AND NOT is_anonymous;
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_username_search_doc_update
+    AFTER UPDATE OF username ON users
+    FOR EACH ROW WHEN (OLD.username IS DISTINCT FROM NEW.username)
+    EXECUTE FUNCTION users_refresh_broadcast_search_doc();
+
+CREATE INDEX broadcasts_search_doc_idx ON broadcasts USING gin(search_doc);
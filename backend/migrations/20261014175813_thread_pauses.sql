@@ -0,0 +1,10 @@
+-- Thread Pauses (a participant freezes one thread; the other party can't reply)
+CREATE TABLE thread_pauses (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    thread_id UUID NOT NULL,
+    paused_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    paused_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(thread_id, paused_by)
+);
+
+CREATE INDEX thread_pauses_thread_id_idx ON thread_pauses(thread_id);
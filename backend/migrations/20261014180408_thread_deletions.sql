@@ -0,0 +1,13 @@
+-- Per-user thread deletion
+-- Deleting a thread only hides it for the user who deleted it: messages up
+-- to deleted_at disappear from their views, while anything newer (a later
+-- reply) brings the thread back. The other participant is unaffected.
+CREATE TABLE thread_deletions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    thread_id UUID NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    deleted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(thread_id, user_id)
+);
+
+CREATE INDEX thread_deletions_user_id_idx ON thread_deletions(user_id);
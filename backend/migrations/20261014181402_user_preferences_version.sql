@@ -0,0 +1,3 @@
+-- Optimistic locking for preferences: bumped on every update so clients can
+-- send back the version they read and get a conflict if another tab won.
+ALTER TABLE user_preferences ADD COLUMN version BIGINT NOT NULL DEFAULT 0;
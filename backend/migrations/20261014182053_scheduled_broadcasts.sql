@@ -0,0 +1,58 @@
+-- Recurring broadcasts: a background worker turns each due occurrence into a
+-- regular broadcast. Times are wall-clock in `timezone`, so a daily 09:00
+-- stays at 09:00 local across DST changes.
+CREATE TABLE scheduled_broadcasts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    author_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    is_anonymous BOOLEAN NOT NULL DEFAULT FALSE,
+    frequency TEXT NOT NULL CHECK (frequency IN ('daily', 'weekly')),
+    -- 0 = Sunday ... 6 = Saturday, as in EXTRACT(DOW); weekly schedules only
+    weekday SMALLINT CHECK (weekday BETWEEN 0 AND 6),
+    time_of_day TIME NOT NULL,
+    timezone TEXT NOT NULL,
+    -- End conditions; either, both or neither may be set
+    ends_at TIMESTAMPTZ,
+    max_occurrences INTEGER CHECK (max_occurrences > 0),
+    occurrence_count INTEGER NOT NULL DEFAULT 0,
+    -- NULL once the schedule has ended or been cancelled
+    next_run_at TIMESTAMPTZ,
+    last_run_at TIMESTAMPTZ,
+    cancelled_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((frequency = 'weekly') = (weekday IS NOT NULL))
+);
+
+CREATE INDEX scheduled_broadcasts_author_id_idx ON scheduled_broadcasts(author_id);
+CREATE INDEX scheduled_broadcasts_due_idx ON scheduled_broadcasts(next_run_at)
+    WHERE next_run_at IS NOT NULL;
+
+-- First occurrence strictly after `after`, or NULL past `until`.
+CREATE FUNCTION scheduled_broadcast_next_run(
+    frequency TEXT,
+    weekday SMALLINT,
+    time_of_day TIME,
+    tz TEXT,
+    after TIMESTAMPTZ,
+    until TIMESTAMPTZ
+) RETURNS TIMESTAMPTZ AS $$
+DECLARE
+    local_day DATE := (after AT TIME ZONE tz)::date;
+    candidate TIMESTAMPTZ;
+BEGIN
+    -- A weekly slot is at most 7 days past today's; one extra day covers
+    -- today's slot having already passed
+    FOR i IN 0..8 LOOP
+        CONTINUE WHEN frequency = 'weekly'
+            AND EXTRACT(DOW FROM local_day + i) <> weekday;
+        candidate := ((local_day + i) + time_of_day) AT TIME ZONE tz;
+        IF candidate > after THEN
+            IF until IS NOT NULL AND candidate > until THEN
+                RETURN NULL;
+            END IF;
+            RETURN candidate;
+        END IF;
+    END LOOP;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql STABLE;
@@ -0,0 +1,4 @@
+-- A user may react to a message with several different emoji, but each only once.
+ALTER TABLE message_reactions
+    DROP CONSTRAINT message_reactions_message_id_user_id_key,
+    ADD CONSTRAINT message_reactions_message_id_user_id_emoji_key UNIQUE (message_id, user_id, emoji);
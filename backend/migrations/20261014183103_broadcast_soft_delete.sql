@@ -0,0 +1,7 @@
+-- Authors can delete their broadcasts; deleted rows are kept but hidden
+ALTER TABLE broadcasts ADD COLUMN deleted_at TIMESTAMPTZ;
+
+-- Serves: get_broadcasts (live, published, ORDER BY created_at DESC)
+CREATE INDEX broadcasts_live_idx
+    ON broadcasts(created_at DESC)
+    WHERE deleted_at IS NULL AND NOT is_draft;
@@ -0,0 +1,7 @@
+-- Who wrote a broadcast, kept even when it was posted anonymously (sender_id
+-- is cleared then). Server-side only: it lets the API tell viewers which
+-- broadcasts are their own and is never returned to anyone else.
+ALTER TABLE broadcasts ADD COLUMN author_id UUID REFERENCES users(id) ON DELETE SET NULL;
+
+-- Anonymous broadcasts published before this have no recorded author
+UPDATE broadcasts SET author_id = sender_id;
@@ -0,0 +1,12 @@
+-- Imported Threads (conversations recreated from another instance's export)
+-- The importer is stored as both sender and recipient of their own messages;
+-- the other party's messages have no sender. Their identity survives only as
+-- the free-text `other_party` pseudonym, which is never matched to a user.
+CREATE TABLE imported_threads (
+    thread_id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    other_party TEXT NOT NULL,
+    imported_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX imported_threads_user_id_idx ON imported_threads(user_id);
@@ -0,0 +1,11 @@
+-- Origin fingerprints on anonymous posts (see src/fingerprint.rs)
+-- A keyed hash of IP + user agent, only written when ABUSE_FINGERPRINT_KEY is
+-- set. It lets moderation link anonymous posts from one origin without
+-- storing the IP itself. Never exposed to regular users.
+ALTER TABLE messages ADD COLUMN origin_fingerprint TEXT;
+ALTER TABLE broadcasts ADD COLUMN origin_fingerprint TEXT;
+
+CREATE INDEX messages_origin_fingerprint_idx ON messages(origin_fingerprint)
+    WHERE origin_fingerprint IS NOT NULL;
+CREATE INDEX broadcasts_origin_fingerprint_idx ON broadcasts(origin_fingerprint)
+    WHERE origin_fingerprint IS NOT NULL;
@@ -0,0 +1,4 @@
+-- When each user last opened the broadcast feed, for the "new since your last
+-- visit" count. NULL until the first visit; counting then starts at signup.
+-- The count itself is served by broadcasts_live_idx (created_at, live rows).
+ALTER TABLE users ADD COLUMN last_broadcast_seen_at TIMESTAMPTZ;
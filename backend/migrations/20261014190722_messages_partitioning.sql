@@ -0,0 +1,168 @@
+-- Optional monthly range partitioning of messages on created_at
+-- Nothing changes here on its own: these functions are only called when the
+-- server runs with MESSAGES_PARTITIONING=true (see README "Partitioning").
+--
+-- Once partitioned:
+--   * the primary key is (id, created_at), since Postgres requires the
+--     partition key in every unique constraint; ids are still UUIDs
+--   * message_reactions, message_edits and pinned_messages can't keep a
+--     foreign key to messages(id), so their ON DELETE CASCADE is done by the
+--     messages_delete_dependents trigger instead
+
+-- Deletes what used to cascade through the foreign keys to messages(id)
+CREATE FUNCTION messages_delete_dependents() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    DELETE FROM message_reactions WHERE message_id = OLD.id;
+    DELETE FROM message_edits WHERE message_id = OLD.id;
+    DELETE FROM pinned_messages WHERE message_id = OLD.id;
+    RETURN OLD;
+END;
+$$;
+
+-- Create the partitions for this month and the next `months_ahead` months.
+-- Returns how many were created; 0 if messages isn't partitioned.
+CREATE FUNCTION create_messages_partitions(months_ahead INT) RETURNS INT
+LANGUAGE plpgsql AS $$
+DECLARE
+    month_start DATE;
+    partition_name TEXT;
+    created INT := 0;
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_partitioned_table WHERE partrelid = 'messages'::regclass
+    ) THEN
+        RETURN 0;
+    END IF;
+
+    FOR month_offset IN 0..months_ahead LOOP
+        month_start := (date_trunc('month', NOW()) + make_interval(months => month_offset))::date;
+        partition_name := 'messages_' || to_char(month_start, 'YYYY_MM');
+
+        -- Months already covered (by the legacy partition, say) are skipped
+        CONTINUE WHEN to_regclass(partition_name) IS NOT NULL OR EXISTS (
+            SELECT 1
+            FROM pg_inherits inh
+            JOIN pg_class c ON c.oid = inh.inhrelid
+            WHERE inh.inhparent = 'messages'::regclass
+              AND NOT c.relname = 'messages_default'
+              AND month_start < COALESCE(
+                  (regexp_match(pg_get_expr(c.relpartbound, c.oid), 'TO \(''([^'']+)''\)'))[1]::timestamptz,
+                  'infinity'
+              )
+              AND month_start >= COALESCE(
+                  (regexp_match(pg_get_expr(c.relpartbound, c.oid), 'FROM \(''([^'']+)''\)'))[1]::timestamptz,
+                  '-infinity'
+              )
+        );
+
+        EXECUTE format(
+            'CREATE TABLE %I PARTITION OF messages FOR VALUES FROM (%L) TO (%L)',
+            partition_name,
+            month_start,
+            (month_start + INTERVAL '1 month')::date
+        );
+        created := created + 1;
+    END LOOP;
+
+    RETURN created;
+END;
+$$;
+
+-- Convert an unpartitioned messages table in place. The existing table
+-- becomes the messages_legacy partition, covering everything before next
+-- month; later months get their own partitions, and messages_default
+-- catches anything no partition covers yet.
+-- Takes an ACCESS EXCLUSIVE lock on messages and indexes the old table once
+-- more for the new primary key. Returns false if already partitioned.
+CREATE FUNCTION partition_messages_table() RETURNS BOOLEAN
+LANGUAGE plpgsql AS $$
+DECLARE
+    boundary DATE;
+    fk RECORD;
+    idx RECORD;
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM pg_partitioned_table WHERE partrelid = 'messages'::regclass
+    ) THEN
+        RETURN false;
+    END IF;
+
+    LOCK TABLE messages IN ACCESS EXCLUSIVE MODE;
+
+    -- Foreign keys into messages must go; refuse to drop any we don't replace
+    FOR fk IN
+        SELECT conname, conrelid::regclass AS child
+        FROM pg_constraint
+        WHERE contype = 'f' AND confrelid = 'messages'::regclass
+    LOOP
+        IF fk.child::text NOT IN ('message_reactions', 'message_edits', 'pinned_messages') THEN
+            RAISE EXCEPTION 'partition_messages_table: % references messages and has no cascade trigger', fk.child;
+        END IF;
+        EXECUTE format('ALTER TABLE %s DROP CONSTRAINT %I', fk.child, fk.conname);
+    END LOOP;
+
+    -- Replaced by the (id, created_at) key of the partitioned table
+    EXECUTE format(
+        'ALTER TABLE messages DROP CONSTRAINT %I',
+        (SELECT conname FROM pg_constraint WHERE contype = 'p' AND conrelid = 'messages'::regclass)
+    );
+
+    ALTER TABLE messages RENAME TO messages_legacy;
+    FOR idx IN
+        SELECT indexrelid::regclass::text AS name
+        FROM pg_index
+        WHERE indrelid = 'messages_legacy'::regclass
+    LOOP
+        EXECUTE format('ALTER INDEX %I RENAME TO %I', idx.name, idx.name || '_legacy');
+    END LOOP;
+    FOR fk IN
+        SELECT conname FROM pg_constraint
+        WHERE contype = 'f' AND conrelid = 'messages_legacy'::regclass
+    LOOP
+        EXECUTE format(
+            'ALTER TABLE messages_legacy RENAME CONSTRAINT %I TO %I', fk.conname, fk.conname || '_legacy'
+        );
+    END LOOP;
+
+    CREATE TABLE messages (
+        LIKE messages_legacy INCLUDING DEFAULTS INCLUDING CONSTRAINTS,
+        PRIMARY KEY (id, created_at),
+        CONSTRAINT messages_recipient_id_fkey
+            FOREIGN KEY (recipient_id) REFERENCES users(id) ON DELETE CASCADE,
+        CONSTRAINT messages_sender_id_fkey
+            FOREIGN KEY (sender_id) REFERENCES users(id) ON DELETE SET NULL,
+        CONSTRAINT messages_deleted_by_fkey
+            FOREIGN KEY (deleted_by) REFERENCES users(id)
+    ) PARTITION BY RANGE (created_at);
+
+    -- Same secondary indexes as before; the legacy ones are attached, not rebuilt
+    FOR idx IN
+        SELECT indexrelid::regclass::text AS name, pg_get_indexdef(indexrelid) AS def
+        FROM pg_index
+        WHERE indrelid = 'messages_legacy'::regclass
+    LOOP
+        EXECUTE replace(
+            replace(idx.def, ' ON public.messages_legacy ', ' ON public.messages '),
+            'INDEX ' || idx.name || ' ',
+            'INDEX ' || left(idx.name, -length('_legacy')) || ' '
+        );
+    END LOOP;
+
+    SELECT (date_trunc('month', GREATEST(NOW(), MAX(created_at))) + INTERVAL '1 month')::date
+    INTO boundary
+    FROM messages_legacy;
+
+    EXECUTE format(
+        'ALTER TABLE messages ATTACH PARTITION messages_legacy FOR VALUES FROM (MINVALUE) TO (%L)',
+        boundary
+    );
+    CREATE TABLE messages_default PARTITION OF messages DEFAULT;
+
+    CREATE TRIGGER messages_delete_dependents
+        AFTER DELETE ON messages
+        FOR EACH ROW EXECUTE FUNCTION messages_delete_dependents();
+
+    RETURN true;
+END;
+$$;
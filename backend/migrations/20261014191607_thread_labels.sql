@@ -0,0 +1,12 @@
+-- Thread Labels (private, per-user folders such as "Work" or "Friends")
+-- A label exists only while at least one thread carries it.
+CREATE TABLE thread_labels (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    thread_id UUID NOT NULL,
+    label TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, thread_id, label)
+);
+
+-- Serves: the ?label= filter on the conversation list and label listing.
+CREATE INDEX thread_labels_user_label_idx ON thread_labels(user_id, label);
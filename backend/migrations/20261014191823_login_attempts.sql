@@ -0,0 +1,9 @@
+-- Login Attempts (consecutive password failures, for account lockout)
+-- A row exists only while a user has failures since their last good login.
+CREATE TABLE login_attempts (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    failed_count INT NOT NULL DEFAULT 0,
+    last_failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Set once failed_count reaches the limit; logins are refused until then
+    locked_until TIMESTAMPTZ
+);
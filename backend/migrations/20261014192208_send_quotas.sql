@@ -0,0 +1,8 @@
+-- Send Quotas (messages sent per user per UTC day, for DAILY_SEND_QUOTA)
+-- One row per user: a send on a new day overwrites the old day and count,
+-- so the quota resets without a cleanup job.
+CREATE TABLE send_quotas (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    count INT NOT NULL
+);
@@ -0,0 +1,14 @@
+-- Quote-replies: the message a reply quotes and, optionally, which part of it
+-- as [quote_start, quote_end) in characters. There's no foreign key because a
+-- partitioned messages table is keyed by (id, created_at); deletes are soft
+-- anyway, so the quoted row stays around.
+ALTER TABLE messages
+    ADD COLUMN reply_to_message_id UUID,
+    ADD COLUMN quote_start INT,
+    ADD COLUMN quote_end INT,
+    ADD CONSTRAINT messages_quote_range_check CHECK (
+        (quote_start IS NULL) = (quote_end IS NULL)
+        AND (quote_start IS NULL OR (
+            reply_to_message_id IS NOT NULL AND quote_start >= 0 AND quote_start < quote_end
+        ))
+    );
@@ -0,0 +1,9 @@
+-- Pending requests to link a provider identity to a local account. Started
+-- by the signed-in owner; the token lives in their browser and is used up
+-- when the provider's callback comes back.
+CREATE TABLE account_link_requests (
+    token TEXT PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Serves: the user directory's ?q= search, a case-insensitive substring match
+-- (ILIKE '%q%') that a btree index on username can't help with.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX users_username_trgm_idx ON users USING gin (username gin_trgm_ops);
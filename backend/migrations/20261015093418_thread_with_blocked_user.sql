@@ -0,0 +1,16 @@
+-- Whether `viewer` blocked the other participant of `thread`. The other
+-- participant is whoever is on the far side of any of the thread's messages
+-- as seen by the viewer; anonymous senders can't be blocked, so a thread
+-- that only has those never matches. Shared by the conversation list and the
+-- unread counts so both hide the same threads.
+CREATE FUNCTION thread_with_blocked_user(thread UUID, viewer UUID) RETURNS BOOLEAN
+LANGUAGE sql STABLE AS $$
+    SELECT EXISTS (
+        SELECT 1
+        FROM messages m
+        JOIN user_blocks ub
+          ON ub.blocker_id = viewer
+         AND ub.blocked_id = CASE WHEN m.sender_id = viewer THEN m.recipient_id ELSE m.sender_id END
+        WHERE m.thread_id = thread
+    )
+$$;
@@ -0,0 +1,14 @@
+-- Web Push subscriptions (W3C PushSubscription), one per browser.
+-- Pushed to when a new message arrives and the user has no open SSE stream.
+-- A push service answering 404/410 means the browser dropped it; the row is
+-- deleted then.
+CREATE TABLE push_subscriptions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    endpoint TEXT NOT NULL UNIQUE,
+    p256dh TEXT NOT NULL,
+    auth TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX push_subscriptions_user_id_idx ON push_subscriptions(user_id);
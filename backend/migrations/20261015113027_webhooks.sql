@@ -0,0 +1,13 @@
+-- Outbound webhooks registered by admins. Each matching event is POSTed to
+-- `url` as JSON, signed with an HMAC-SHA256 of the body under `secret`.
+-- `events` lists the event types the hook wants (see webhooks.rs).
+CREATE TABLE webhooks (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    events TEXT[] NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX webhooks_events_idx ON webhooks USING GIN (events);
@@ -0,0 +1,6 @@
+-- Message search matches and ranks on to_tsvector('english', content), which
+-- only this expression index makes cheap. The initial schema creates it;
+-- recreate it on databases that lost it (e.g. to a manual rebuild) rather
+-- than have every search scan the table.
+CREATE INDEX IF NOT EXISTS messages_content_search_idx
+    ON messages USING gin(to_tsvector('english', content));
@@ -0,0 +1,28 @@
+-- Abuse reports on messages and broadcasts, for the moderation queue.
+-- message_id has no foreign key because a partitioned messages table is keyed
+-- by (id, created_at). Reports outlive the reporter's account so moderators
+-- can still act on them. One report per reporter and item.
+CREATE TABLE message_reports (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    message_id UUID NOT NULL,
+    reporter_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    reason TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'resolved', 'dismissed')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (message_id, reporter_id)
+);
+
+CREATE TABLE broadcast_reports (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    broadcast_id UUID NOT NULL REFERENCES broadcasts(id) ON DELETE CASCADE,
+    reporter_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    reason TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'resolved', 'dismissed')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (broadcast_id, reporter_id)
+);
+
+CREATE INDEX message_reports_pending_idx ON message_reports(created_at) WHERE status = 'pending';
+CREATE INDEX broadcast_reports_pending_idx ON broadcast_reports(created_at) WHERE status = 'pending';
@@ -0,0 +1,13 @@
+-- Admins can review reports and moderate content. ADMIN_USERS still grants
+-- the same rights, so the first admin can be set without touching the
+-- database.
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT false;
+
+-- Who handled a report, and when
+ALTER TABLE message_reports
+    ADD COLUMN resolved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN resolved_at TIMESTAMPTZ;
+
+ALTER TABLE broadcast_reports
+    ADD COLUMN resolved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN resolved_at TIMESTAMPTZ;
@@ -0,0 +1,4 @@
+-- Bans by an admin. A banned user can't sign in or use an existing session,
+-- their broadcasts leave the feed and their messages leave inboxes. Unbanning
+-- clears the column and brings all of it back.
+ALTER TABLE users ADD COLUMN banned_at TIMESTAMPTZ;
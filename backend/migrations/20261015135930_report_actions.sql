@@ -0,0 +1,22 @@
+-- What a report was about when it was filed, so moderators judge what was
+-- reported even if it was edited since. Existing reports take the current
+-- content, the closest there is.
+ALTER TABLE message_reports ADD COLUMN content_snapshot TEXT;
+ALTER TABLE broadcast_reports ADD COLUMN content_snapshot TEXT;
+
+UPDATE message_reports r SET content_snapshot = m.content
+FROM messages m WHERE m.id = r.message_id;
+
+UPDATE broadcast_reports r SET content_snapshot = b.content
+FROM broadcasts b WHERE b.id = r.broadcast_id;
+
+-- What the resolving admin did: dismissed it, deleted the content, or also
+-- banned its author
+ALTER TABLE message_reports ADD COLUMN action TEXT
+    CHECK (action IN ('dismiss', 'delete_content', 'ban_user'));
+ALTER TABLE broadcast_reports ADD COLUMN action TEXT
+    CHECK (action IN ('dismiss', 'delete_content', 'ban_user'));
+
+-- Counting the reporters of one item
+CREATE INDEX message_reports_message_idx ON message_reports(message_id) WHERE status = 'pending';
+CREATE INDEX broadcast_reports_broadcast_idx ON broadcast_reports(broadcast_id) WHERE status = 'pending';
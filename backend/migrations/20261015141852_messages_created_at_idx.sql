@@ -0,0 +1,3 @@
+-- The retention purge job finds messages by age alone; without this index
+-- each batch would scan the whole table (or every partition).
+CREATE INDEX IF NOT EXISTS messages_created_at_idx ON messages(created_at);
@@ -0,0 +1,9 @@
+-- Self-destructing messages. `ephemeral` messages get an `expires_at` once
+-- their recipient opens the thread; a TTL sets it at send time. Reads skip
+-- messages past `expires_at`, and the expired_messages job deletes them.
+ALTER TABLE messages
+    ADD COLUMN ephemeral BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN expires_at TIMESTAMPTZ;
+
+CREATE INDEX messages_expires_at_idx ON messages(expires_at)
+    WHERE expires_at IS NOT NULL;
@@ -0,0 +1,16 @@
+-- Messages composed now and delivered later. A scheduled message is stored
+-- with its thread but left out of every read until the scheduled_messages
+-- job delivers it at `deliver_at`, resetting created_at to that moment.
+-- `scheduled_by` lets the author list and cancel it even when no sender is
+-- stored; it is cleared on delivery. It has no foreign key, since
+-- partition_messages_table only recreates the foreign keys it knows about.
+ALTER TABLE messages
+    ADD COLUMN status TEXT NOT NULL DEFAULT 'delivered'
+        CHECK (status IN ('scheduled', 'delivered')),
+    ADD COLUMN deliver_at TIMESTAMPTZ,
+    ADD COLUMN scheduled_by UUID;
+
+CREATE INDEX messages_scheduled_due_idx ON messages(deliver_at)
+    WHERE status = 'scheduled';
+CREATE INDEX messages_scheduled_by_idx ON messages(scheduled_by)
+    WHERE status = 'scheduled';
@@ -0,0 +1,5 @@
+-- Forwarded messages: the thread the content was copied from. Only the
+-- content is copied; the original sender never is. Like reply_to_message_id
+-- there's no foreign key, as a partitioned messages table has no unique key
+-- on thread_id to point at anyway.
+ALTER TABLE messages ADD COLUMN forwarded_from_thread UUID;
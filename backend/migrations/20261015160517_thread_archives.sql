@@ -0,0 +1,11 @@
+-- Per-user thread archiving
+-- Archiving hides a thread from its user's conversation list without
+-- deleting anything. A thread counts as archived only while nothing newer
+-- than archived_at arrived, so a new message brings it back on its own. The
+-- other participant is unaffected.
+CREATE TABLE thread_archives (
+    thread_id UUID NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, thread_id)
+);
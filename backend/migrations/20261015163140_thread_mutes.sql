@@ -0,0 +1,11 @@
+-- Per-user thread mutes
+-- A muted thread sends its user no SSE events or pushes for new messages,
+-- but still shows up in their list with its unread count. NULL muted_until
+-- mutes until unmuted; a past muted_until is simply no longer in effect.
+CREATE TABLE thread_mutes (
+    thread_id UUID NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    muted_until TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, thread_id)
+);
@@ -0,0 +1,11 @@
+-- Shareable inbox links
+-- Anyone holding an active token can message its user without signing in.
+-- Rotating a link revokes the old token; a user has at most one active one.
+CREATE TABLE inbox_tokens (
+    token TEXT PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked BOOLEAN NOT NULL DEFAULT false
+);
+
+CREATE UNIQUE INDEX idx_inbox_tokens_active_user ON inbox_tokens (user_id) WHERE NOT revoked;
@@ -0,0 +1,2 @@
+-- Lets users hide their public profile (GET /api/users/by-username/{username})
+ALTER TABLE user_preferences ADD COLUMN public_profile BOOLEAN NOT NULL DEFAULT true;
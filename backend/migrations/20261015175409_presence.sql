@@ -0,0 +1,4 @@
+-- Presence: when a user's last event stream opened or closed, and whether
+-- they share being online at all
+ALTER TABLE users ADD COLUMN last_seen TIMESTAMPTZ;
+ALTER TABLE user_preferences ADD COLUMN share_presence BOOLEAN NOT NULL DEFAULT true;
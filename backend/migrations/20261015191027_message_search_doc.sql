@@ -0,0 +1,95 @@
+-- Weighted search document for messages
+-- Content is weighted above metadata, as for broadcasts. A thread's starter
+-- is never named to the other side, whether or not a sender is stored; the
+-- person they wrote to is, since the starter picked them. So the sender's
+-- username is only indexed on messages from the recipient of the thread's
+-- opening message. Searching for a username never surfaces what its owner
+-- sent while hidden, and anonymous messages (no sender stored) never match.
+--
+-- Usernames are parsed with the 'english' configuration searches use, so a
+-- username stems the same way in the document as in the query.
+
+CREATE FUNCTION message_search_doc(content TEXT, thread_id UUID, sender_id UUID)
+RETURNS tsvector AS $$
+    SELECT setweight(to_tsvector('english', content), 'A') ||
+        setweight(to_tsvector('english', coalesce(
+            (SELECT u.username FROM users u
+             WHERE u.id = message_search_doc.sender_id
+               AND u.id = (
+                   SELECT first.recipient_id FROM messages first
+                   WHERE first.thread_id = message_search_doc.thread_id
+                   ORDER BY first.created_at
+                   LIMIT 1
+               )),
+            ''
+        )), 'B')
+$$ LANGUAGE sql STABLE;
+
+ALTER TABLE messages ADD COLUMN search_doc tsvector;
+
+UPDATE messages SET search_doc = message_search_doc(content, thread_id, sender_id);
+
+CREATE FUNCTION messages_search_doc_trigger() RETURNS trigger AS $$
+BEGIN
+    NEW.search_doc := message_search_doc(NEW.content, NEW.thread_id, NEW.sender_id);
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER messages_search_doc_update
+    BEFORE INSERT OR UPDATE OF content, sender_id ON messages
+    FOR EACH ROW EXECUTE FUNCTION messages_search_doc_trigger();
+
+-- Keep documents current when a sender renames themselves
+CREATE FUNCTION users_refresh_message_search_doc() RETURNS trigger AS $$
+BEGIN
+    UPDATE messages
+    SET search_doc = message_search_doc(content, thread_id, sender_id)
+    WHERE sender_id = NEW.id;
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_username_message_search_doc_update
+    AFTER UPDATE OF username ON users
+    FOR EACH ROW WHEN (OLD.username IS DISTINCT FROM NEW.username)
+    EXECUTE FUNCTION users_refresh_message_search_doc();
+
+-- Replaced by search_doc
+DROP INDEX messages_content_search_idx;
+CREATE INDEX messages_search_doc_idx ON messages USING gin(search_doc);
+
+-- Broadcasts parsed usernames with 'simple', which never matches a query
+-- for a username the english stemmer changes ("alice" searches for "alic")
+CREATE OR REPLACE FUNCTION broadcast_search_doc(content TEXT, is_anonymous BOOLEAN, sender_id UUID)
+RETURNS tsvector AS $$
+    SELECT setweight(to_tsvector('english', content), 'A') ||
+        setweight(to_tsvector('english', coalesce(
+            CASE WHEN NOT is_anonymous THEN (SELECT username FROM users WHERE id = sender_id) END,
+            ''
+        )), 'B')
+$$ LANGUAGE sql STABLE;
+
+UPDATE broadcasts SET search_doc = broadcast_search_doc(content, is_anonymous, sender_id)
+WHERE NOT is_anonymous;
+
+-- Row triggers on the old table stay on the messages_legacy partition when
+-- messages is partitioned; move this one to the partitioned table so every
+-- partition gets it.
+ALTER FUNCTION partition_messages_table() RENAME TO partition_messages_table_base;
+
+CREATE FUNCTION partition_messages_table() RETURNS BOOLEAN
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF NOT partition_messages_table_base() THEN
+        RETURN false;
+    END IF;
+
+    DROP TRIGGER messages_search_doc_update ON messages_legacy;
+    CREATE TRIGGER messages_search_doc_update
+        BEFORE INSERT OR UPDATE OF content, sender_id ON messages
+        FOR EACH ROW EXECUTE FUNCTION messages_search_doc_trigger();
+
+    RETURN true;
+END;
+$$;
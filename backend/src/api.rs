@@ -1,20 +1,35 @@
 use axum::response::sse::{Event, KeepAlive};
 use axum::{
+    body::Body,
     extract::{FromRef, FromRequestParts, State},
+    http::header,
     http::StatusCode,
-    response::{Json, Sse},
+    response::{IntoResponse, Json, Response, Sse},
     routing::{get, post},
     Router,
 };
-use futures_util::stream::{self, Stream};
+use futures_util::stream::{self, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use sqlx::{types::time::OffsetDateTime, PgPool};
+use sqlx::{types::time::OffsetDateTime, PgExecutor, PgPool};
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
 use std::sync::Arc;
-use tracing::{info, warn};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use crate::state::{NotificationHub, SseEvent};
+use crate::config::ApiConfig;
+use crate::db::{ReportAction, ResolveOutcome};
+use crate::error::ApiError;
+use crate::fingerprint::{ClientIp, OriginFingerprint};
+use crate::integrity::LatestReport;
+use crate::pagination::{PageQuery, PaginatedResponse};
+use crate::push::{PushNotifier, PushPayload};
+use crate::sessions::IndexedSessionStore;
+use crate::state::{AnonMessageLimiter, NotificationHub, SseEvent, StampedEvent};
+use crate::telemetry;
+use crate::webhooks::{WebhookDispatcher, WebhookEvent};
 use authkestra::axum::AuthSession;
 
 pub fn public_router<S>() -> Router<S>
@@ -25,54 +40,193 @@ where
     Router::new().route("/health", get(health_handler))
 }
 
+/// Admin-only routes. They live under `/api/admin` so a proxy can firewall
+/// them apart from the rest of the API.
+fn admin_router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    Arc<PgPool>: FromRef<S>,
+    Arc<ApiConfig>: FromRef<S>,
+    LatestReport: FromRef<S>,
+    Arc<IndexedSessionStore>: FromRef<S>,
+    AuthSession: FromRequestParts<S>,
+{
+    Router::new()
+        .route("/integrity", get(integrity_report_handler))
+        .route("/stats", get(admin_stats_handler))
+        .route(
+            "/webhooks",
+            get(list_webhooks_handler).post(create_webhook_handler),
+        )
+        .route(
+            "/webhooks/{id}",
+            axum::routing::delete(delete_webhook_handler),
+        )
+        .route("/reports", get(list_reports_handler))
+        .route("/reports/{id}/resolve", post(resolve_report_handler))
+        .route("/users/{id}/ban", post(ban_user_handler))
+        .route("/users/{id}/unban", post(unban_user_handler))
+}
+
 pub fn api_router<S>() -> Router<S>
 where
     S: Clone + Send + Sync + 'static,
     Arc<PgPool>: FromRef<S>,
     NotificationHub: FromRef<S>,
+    Arc<ApiConfig>: FromRef<S>,
+    LatestReport: FromRef<S>,
+    Arc<PushNotifier>: FromRef<S>,
+    WebhookDispatcher: FromRef<S>,
+    AnonMessageLimiter: FromRef<S>,
+    Arc<IndexedSessionStore>: FromRef<S>,
     AuthSession: FromRequestParts<S>,
 {
     Router::new()
         .route("/me", get(me_handler))
         .route("/me", post(update_profile_handler))
         .route("/me", axum::routing::delete(delete_account_handler))
+        .route(
+            "/me/inbox-link",
+            get(get_inbox_link_handler)
+                .post(rotate_inbox_link_handler)
+                .delete(revoke_inbox_link_handler),
+        )
+        .route("/anon/{token}", post(anon_message_handler))
         .route("/users", get(list_users_handler))
+        .route("/users/by-username/{username}", get(public_profile_handler))
         .route("/debug/users", get(debug_list_users_handler))
+        .nest("/admin", admin_router())
         // Messaging
         .route("/messages", post(send_message_handler))
         .route("/messages/inbox", get(inbox_handler))
+        .route("/messages/unread-count", get(unread_count_handler))
         .route("/messages/search", get(search_messages_handler))
+        .route("/messages/scheduled", get(list_scheduled_messages_handler))
+        .route(
+            "/messages/scheduled/{id}",
+            axum::routing::delete(cancel_scheduled_message_handler),
+        )
         .route("/messages/{id}/react", post(react_message_handler))
+        .route(
+            "/messages/reactions/batch",
+            post(batch_react_messages_handler),
+        )
         .route("/messages/{id}/reply", post(reply_message_handler))
+        .route("/messages/{id}/forward", post(forward_message_handler))
         .route("/messages/{id}/edit", post(edit_message_handler))
         .route(
             "/messages/{id}/delete",
             axum::routing::delete(delete_message_handler),
         )
+        .route(
+            "/messages/{id}/unsend",
+            axum::routing::delete(unsend_message_handler),
+        )
         .route("/messages/{id}/pin", post(toggle_pin_message_handler))
+        .route("/messages/{id}/read", post(mark_message_read_handler))
+        .route("/messages/{id}/report", post(report_message_handler))
         // Conversations (threads)
-        .route("/conversations", get(list_conversations_handler))
+        .route(
+            "/conversations",
+            get(list_conversations_handler).post(start_conversation_handler),
+        )
+        .route("/conversations/import", post(import_conversation_handler))
+        .route("/conversations/labels", get(list_labels_handler))
+        .route(
+            "/conversations/pinned",
+            get(list_pinned_conversations_handler),
+        )
+        .route(
+            "/conversations/archived",
+            get(list_archived_conversations_handler),
+        )
         .route("/conversations/{thread_id}", get(get_thread_handler))
         .route(
             "/conversations/{thread_id}/delete",
             axum::routing::delete(delete_thread_handler),
         )
+        .route(
+            "/conversations/{thread_id}/reply",
+            post(reply_thread_handler),
+        )
         .route(
             "/conversations/{thread_id}/pin",
             post(toggle_pin_thread_handler),
         )
+        .route(
+            "/conversations/{thread_id}/pinned",
+            get(get_pinned_messages_handler),
+        )
+        .route(
+            "/conversations/{thread_id}/labels",
+            post(add_thread_label_handler),
+        )
+        .route(
+            "/conversations/{thread_id}/labels/{label}",
+            axum::routing::delete(remove_thread_label_handler),
+        )
+        .route(
+            "/conversations/{thread_id}/archive",
+            post(archive_thread_handler),
+        )
+        .route(
+            "/conversations/{thread_id}/unarchive",
+            post(unarchive_thread_handler),
+        )
+        .route("/conversations/{thread_id}/mute", post(mute_thread_handler))
+        .route(
+            "/conversations/{thread_id}/unmute",
+            post(unmute_thread_handler),
+        )
+        .route(
+            "/conversations/{thread_id}/pause",
+            post(pause_thread_handler),
+        )
+        .route(
+            "/conversations/{thread_id}/resume",
+            post(resume_thread_handler),
+        )
         .route(
             "/conversations/{thread_id}/typing",
-            post(typing_indicator_handler),
+            post(typing_indicator_handler).delete(stop_typing_handler),
+        )
+        .route(
+            "/conversations/{thread_id}/search",
+            get(search_thread_handler),
         )
         // User Blocking
+        .route("/users/{id}/presence", get(presence_handler))
         .route("/users/{id}/block", post(block_user_handler))
         .route("/users/{id}/unblock", post(unblock_user_handler))
         .route("/users/blocked", get(get_blocked_users_handler))
+        .route("/contacts", get(list_contacts_handler))
         // Broadcasts
         .route("/broadcasts", post(create_broadcast_handler))
         .route("/broadcasts", get(list_broadcasts_handler))
+        .route("/broadcasts/drafts", get(list_broadcast_drafts_handler))
+        .route("/broadcasts/search", get(search_broadcasts_handler))
+        .route(
+            "/broadcasts/unseen-count",
+            get(unseen_broadcasts_count_handler),
+        )
+        .route(
+            "/broadcasts/schedules",
+            get(list_scheduled_broadcasts_handler).post(create_scheduled_broadcast_handler),
+        )
+        .route(
+            "/broadcasts/schedules/{id}",
+            axum::routing::delete(cancel_scheduled_broadcast_handler),
+        )
+        .route("/broadcasts/{id}/publish", post(publish_broadcast_handler))
         .route("/broadcasts/{id}/view", post(view_broadcast_handler))
+        .route("/broadcasts/{id}/report", post(report_broadcast_handler))
+        .route(
+            "/broadcasts/{id}",
+            axum::routing::delete(delete_broadcast_handler),
+        )
+        .route("/broadcasts/{id}/edit", post(edit_broadcast_handler))
+        .route("/broadcasts/{id}/history", get(broadcast_history_handler))
+        .route("/broadcasts/{id}/viewers", get(broadcast_viewers_handler))
         .route(
             "/broadcasts/{id}/comments",
             get(get_broadcast_comments_handler),
@@ -81,6 +235,15 @@ where
             "/broadcasts/{id}/comments",
             post(create_broadcast_comment_handler),
         )
+        .route(
+            "/broadcasts/{id}/comments/mine",
+            axum::routing::delete(delete_my_comments_handler),
+        )
+        .route("/broadcasts/{id}/export", get(export_broadcast_handler))
+        .route(
+            "/broadcasts/{id}/reactions/top",
+            get(broadcast_reaction_leaderboard_handler),
+        )
         .route(
             "/broadcasts/comments/{id}/react",
             post(react_to_comment_handler),
@@ -92,6 +255,9 @@ where
         // User Preferences
         .route("/preferences", get(get_preferences_handler))
         .route("/preferences", post(update_preferences_handler))
+        // Web Push
+        .route("/push/public-key", get(push_public_key_handler))
+        .route("/push/subscribe", post(push_subscribe_handler))
         // SSE real-time event stream
         .route("/events", get(sse_handler))
 }
@@ -109,32 +275,68 @@ struct DebugUserResponse {
     created_at: OffsetDateTime,
 }
 
+#[derive(Serialize)]
+struct AdminStatsResponse {
+    pool: PoolStatsResponse,
+    /// Estimated from planner statistics, not exact
+    tables: TableCountsResponse,
+}
+
+#[derive(Serialize)]
+struct PoolStatsResponse {
+    size: u32,
+    idle: usize,
+    in_use: usize,
+}
+
+#[derive(Serialize)]
+struct TableCountsResponse {
+    users: i64,
+    messages: i64,
+    broadcasts: i64,
+}
+
 // Health check endpoint
 #[derive(Serialize)]
 struct HealthResponse {
-    status: String,
-    database: String,
+    status: &'static str,
+    db: &'static str,
 }
 
-async fn health_handler(
-    State(pool): State<Arc<PgPool>>,
-) -> Result<Json<HealthResponse>, StatusCode> {
-    // Check database connection
-    let db_status = match sqlx::query("SELECT 1").fetch_one(pool.as_ref()).await {
-        Ok(_) => "healthy",
-        Err(_) => "unhealthy",
-    };
-
-    let overall_status = if db_status == "healthy" {
-        "ok"
-    } else {
-        "degraded"
-    };
+/// How long the readiness query may take before the database counts as down.
+const HEALTH_DB_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Readiness probe: 200 while the database answers, 503 otherwise. Polled
+/// every few seconds, so it's deliberately not instrumented.
+async fn health_handler(State(pool): State<Arc<PgPool>>) -> (StatusCode, Json<HealthResponse>) {
+    let query = sqlx::query("SELECT 1").fetch_one(pool.as_ref());
+    match tokio::time::timeout(HEALTH_DB_TIMEOUT, query).await {
+        Ok(Ok(_)) => (
+            StatusCode::OK,
+            Json(HealthResponse {
+                status: "ok",
+                db: "up",
+            }),
+        ),
+        Ok(Err(e)) => {
+            warn!("Health check failed: {}", e);
+            health_down()
+        }
+        Err(_) => {
+            warn!("Health check timed out after {:?}", HEALTH_DB_TIMEOUT);
+            health_down()
+        }
+    }
+}
 
-    Ok(Json(HealthResponse {
-        status: overall_status.to_string(),
-        database: db_status.to_string(),
-    }))
+fn health_down() -> (StatusCode, Json<HealthResponse>) {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(HealthResponse {
+            status: "unavailable",
+            db: "down",
+        }),
+    )
 }
 
 #[derive(Serialize)]
@@ -159,11 +361,121 @@ struct UpdateProfileRequest {
 struct SendMessageRequest {
     recipient_id: Uuid,
     content: String,
+    /// true = keep the sender server-side so the recipient can reply (identity
+    /// stays hidden); false = store no sender at all, making the thread one-way
+    #[serde(default = "default_allow_reply")]
+    allow_reply: bool,
+    /// Disappear shortly after the recipient opens the thread
+    #[serde(default)]
+    ephemeral: bool,
+    /// Disappear this many seconds after sending, read or not
+    expires_in_secs: Option<u64>,
+    /// Hold the message until this instant; ignored unless it's in the future
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    deliver_at: Option<OffsetDateTime>,
+}
+
+fn default_allow_reply() -> bool {
+    true
+}
+
+/// Longest `expires_in_secs` accepted: one week.
+const MAX_MESSAGE_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Rows deleted per statement by `expire_messages`.
+const EXPIRED_MESSAGE_BATCH: i64 = 500;
+
+/// Furthest ahead a message may be scheduled: 30 days.
+const MAX_DELIVERY_DELAY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Messages delivered per statement by `deliver_scheduled_messages`.
+const SCHEDULED_MESSAGE_BATCH: i64 = 100;
+
+/// A message waiting for `deliver_at`, as `GET /messages/scheduled` lists it.
+#[derive(Serialize)]
+struct ScheduledMessageResponse {
+    id: Uuid,
+    recipient_id: Uuid,
+    recipient_username: String,
+    content: String,
+    #[serde(with = "time::serde::rfc3339")]
+    deliver_at: OffsetDateTime,
+    allow_reply: bool,
+    ephemeral: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct StartConversationRequest {
+    /// Matched case-insensitively
+    recipient_username: String,
+    content: String,
+    /// true = store no sender at all (see `SendMessageRequest::allow_reply`)
+    #[serde(default)]
+    anonymous: bool,
+}
+
+#[derive(Serialize)]
+struct StartConversationResponse {
+    thread_id: Uuid,
+    message_id: Uuid,
+    recipient_id: Uuid,
+}
+
+#[derive(Deserialize, Debug)]
+struct ForwardMessageRequest {
+    recipient_id: Uuid,
+}
+
+#[derive(Serialize)]
+struct ForwardMessageResponse {
+    message_id: Uuid,
+    thread_id: Uuid,
+}
+
+/// A thread as returned by `GET /conversations/{thread_id}`; fields other
+/// than these (ids, reactions, read state) are ignored.
+#[derive(Deserialize, Debug)]
+struct ImportConversationRequest {
+    /// Pseudonym to remember the other participant by
+    #[serde(default)]
+    other_party: Option<String>,
+    messages: Vec<ImportedMessageRequest>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ImportedMessageRequest {
+    content: String,
+    is_mine: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+}
+
+#[derive(Serialize)]
+struct ImportConversationResponse {
+    thread_id: Uuid,
+    imported: usize,
+    other_party: String,
 }
 
+const MAX_IMPORT_MESSAGES: usize = 5000;
+const MAX_PSEUDONYM_LENGTH: usize = 50;
+
 #[derive(Deserialize, Debug)]
 struct ReplyRequest {
     content: String,
+    /// Message in the same thread this reply quotes
+    reply_to_message_id: Option<Uuid>,
+    /// Quote only part of it; on `/messages/{id}/reply` this alone quotes
+    /// the path message
+    quote_range: Option<QuoteRange>,
+}
+
+/// Character offsets into the quoted message, end exclusive. Characters are
+/// Unicode scalar values, as in `check_content_length`.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+struct QuoteRange {
+    start: usize,
+    end: usize,
 }
 
 /// Message response sent to clients — sender_id is intentionally omitted to preserve anonymity.
@@ -177,7 +489,11 @@ struct MessageResponse {
     #[serde(with = "time::serde::rfc3339")]
     created_at: OffsetDateTime,
     is_read: bool,
-    reactions: Option<serde_json::Value>,
+    /// Whether the content was edited after sending
+    edited: bool,
+    #[serde(with = "time::serde::rfc3339::option")]
+    edited_at: Option<OffsetDateTime>,
+    reactions: crate::db::ReactionCounts,
     /// Number of unread messages in this thread for the current user (used in thread list)
     #[serde(skip_serializing_if = "Option::is_none")]
     unread_count: Option<i64>,
@@ -185,6 +501,183 @@ struct MessageResponse {
     /// Receivers always get null to preserve anonymity.
     #[serde(skip_serializing_if = "Option::is_none")]
     to_username: Option<String>,
+    /// Whether the thread is paused (set in the thread list and thread view)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    paused: Option<bool>,
+    /// Thread view: whether either participant pinned the message (pinned
+    /// messages come first). Conversation list: whether the viewer pinned the thread
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_pinned: Option<bool>,
+    /// The viewer's private labels on the thread (set in the thread list)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    labels: Option<Vec<String>>,
+    /// Whether the viewer archived the thread (set in the thread list)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archived: Option<bool>,
+    /// Whether the viewer muted the thread (set in the thread list)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    muted: Option<bool>,
+    /// The message this one quotes (thread view only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to_message_id: Option<Uuid>,
+    /// Which part of it; absent for a whole-message quote or once the quoted
+    /// message is deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quote_range: Option<QuoteRange>,
+    /// Up to 100 characters of the quoted part, or of the whole message
+    /// replied to, for "in reply to" context (thread view only); absent once
+    /// that message is deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to_preview: Option<String>,
+    /// Thread view: whether this is a forwarded copy of another message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    forwarded: Option<bool>,
+    /// When the message disappears (thread view only)
+    #[serde(
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    expires_at: Option<OffsetDateTime>,
+    /// Thread view, your own messages: when the edit window closes. Absent
+    /// when edits have no time limit
+    #[serde(
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    editable_until: Option<OffsetDateTime>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct MuteThreadRequest {
+    /// Unmute on its own at this instant; absent mutes until unmuted
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    muted_until: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize, Debug)]
+struct MuteThreadResponse {
+    muted: bool,
+    #[serde(with = "time::serde::rfc3339::option")]
+    muted_until: Option<OffsetDateTime>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ThreadQuery {
+    /// Keep deleted messages as "This message was deleted" placeholders
+    #[serde(default)]
+    tombstones: bool,
+}
+
+/// Reject content longer than `max` characters. Counts Unicode scalar values,
+/// so an emoji is one character however many bytes it takes.
+fn check_content_length(content: &str, max: usize) -> Result<(), ApiError> {
+    if content.chars().count() > max {
+        return Err(ApiError::Validation(format!(
+            "Content can be at most {} characters",
+            max
+        )));
+    }
+    Ok(())
+}
+
+/// Count a send against the user's daily quota, or reject it with 429 and
+/// when the quota resets (midnight UTC) once it's used up.
+async fn consume_send_quota<'e>(
+    executor: impl PgExecutor<'e>,
+    api_config: &ApiConfig,
+    user_id: Uuid,
+) -> Result<(), ApiError> {
+    if api_config.daily_send_quota <= 0 || api_config.send_quota_exempt.contains(&user_id) {
+        return Ok(());
+    }
+
+    let counted = crate::db::consume_send_quota(executor, user_id, api_config.daily_send_quota)
+        .await
+        .map_err(|e| {
+            warn!("Failed to count send quota: {}", e);
+            ApiError::Internal
+        })?;
+    if !counted {
+        let resets_at = OffsetDateTime::now_utc()
+            .date()
+            .next_day()
+            .unwrap_or(time::Date::MAX)
+            .midnight()
+            .assume_utc();
+        info!("User {} hit the daily send quota", user_id);
+        return Err(ApiError::TooManyRequests(format!(
+            "Daily limit of {} messages reached; resets at {}",
+            api_config.daily_send_quota,
+            resets_at
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default()
+        )));
+    }
+    Ok(())
+}
+
+/// Check a quote-reply's target: a live message in `thread_id`, with
+/// `range` inside its content. Returns the range as stored.
+async fn check_quote<'e>(
+    executor: impl PgExecutor<'e>,
+    thread_id: Uuid,
+    reply_to_message_id: Option<Uuid>,
+    range: Option<QuoteRange>,
+) -> Result<Option<(i32, i32)>, ApiError> {
+    let Some(reply_to_message_id) = reply_to_message_id else {
+        if range.is_some() {
+            return Err(ApiError::Validation(
+                "quote_range needs reply_to_message_id".into(),
+            ));
+        }
+        return Ok(None);
+    };
+
+    let quoted = crate::db::get_quoted_message(executor, reply_to_message_id)
+        .await
+        .map_err(|e| {
+            warn!(
+                "Failed to load quoted message {}: {}",
+                reply_to_message_id, e
+            );
+            ApiError::Internal
+        })?
+        .filter(|m| m.thread_id == thread_id)
+        .ok_or_else(|| ApiError::NotFound("Quoted message not found".into()))?;
+    if quoted.deleted {
+        return Err(ApiError::Unprocessable(
+            "Deleted messages can't be quoted".into(),
+        ));
+    }
+
+    let Some(range) = range else {
+        return Ok(None);
+    };
+    let length = quoted.content.chars().count();
+    if range.start >= range.end || range.end > length {
+        return Err(ApiError::Validation(format!(
+            "quote_range must be a non-empty range within the quoted message's {} characters",
+            length
+        )));
+    }
+    // Both fit: content length is capped far below i32::MAX
+    Ok(Some((range.start as i32, range.end as i32)))
+}
+
+/// `?dry_run=true` on a destructive endpoint reports what it would affect
+/// and changes nothing.
+#[derive(Deserialize, Debug)]
+struct DryRunQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+struct BroadcastViewerResponse {
+    user_id: Uuid,
+    username: String,
+    #[serde(with = "time::serde::rfc3339")]
+    viewed_at: OffsetDateTime,
 }
 
 #[derive(Deserialize, Debug)]
@@ -192,10 +685,48 @@ struct ReactMessageRequest {
     emoji: String,
 }
 
+/// Whether the caller's reaction is in place after a react call.
+#[derive(Serialize)]
+struct ReactionToggleResponse {
+    reacted: bool,
+}
+
+const MAX_REACTION_BATCH: usize = 50;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ReactionAction {
+    Add,
+    Remove,
+}
+
+#[derive(Deserialize, Debug)]
+struct BatchReactionItem {
+    message_id: Uuid,
+    emoji: String,
+    action: ReactionAction,
+}
+
+#[derive(Serialize)]
+struct BatchReactionResult {
+    message_id: Uuid,
+    emoji: String,
+    action: ReactionAction,
+    /// "applied", "not_found" or "forbidden"
+    status: &'static str,
+}
+
 #[derive(Deserialize, Debug)]
 struct CreateBroadcastRequest {
     content: String,
     is_anonymous: bool,
+    /// false = save as a draft visible only to the author
+    #[serde(default = "default_publish")]
+    publish: bool,
+}
+
+fn default_publish() -> bool {
+    true
 }
 
 #[derive(Serialize)]
@@ -207,20 +738,51 @@ struct BroadcastResponse {
     #[serde(with = "time::serde::rfc3339")]
     created_at: OffsetDateTime,
     view_count: i64,
+    #[serde(with = "time::serde::rfc3339::option")]
+    edited_at: Option<OffsetDateTime>,
+    edit_count: i64,
+    /// Whether the viewer wrote it, including their own anonymous broadcasts
+    is_mine: bool,
 }
 
-#[tracing::instrument(skip(session, pool))]
+#[derive(Deserialize, Debug)]
+struct EditBroadcastRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct BroadcastVersionResponse {
+    content: String,
+    /// When this version was replaced by a newer one
+    #[serde(with = "time::serde::rfc3339")]
+    edited_at: OffsetDateTime,
+}
+
+/// The signed-in user. A banned user gets 403 on every request, whichever
+/// session they use.
 async fn resolve_user(
     session: &mut AuthSession,
     pool: &PgPool,
-) -> Result<crate::db::User, StatusCode> {
+) -> Result<crate::db::User, ApiError> {
+    let user = lookup_session_user(session, pool).await?;
+    if user.banned_at.is_some() {
+        return Err(ApiError::Forbidden("This account is banned".into()));
+    }
+    Ok(user)
+}
+
+#[tracing::instrument(skip(session, pool))]
+async fn lookup_session_user(
+    session: &mut AuthSession,
+    pool: &PgPool,
+) -> Result<crate::db::User, ApiError> {
     let provider = session.0.identity.provider_id.clone();
     let external_id = session.0.identity.external_id.clone();
     let username = session.0.identity.username.clone();
 
     // If both are missing, we're definitely not logged in
     if external_id.is_empty() && username.is_none() {
-        return Err(StatusCode::UNAUTHORIZED);
+        return Err(ApiError::Unauthorized);
     }
 
     let username = username.unwrap_or_else(|| "Anonymous".to_string());
@@ -231,18 +793,19 @@ async fn resolve_user(
             info!("Resolving local user by UUID: {user_id}");
             return crate::db::get_user_by_id(pool, user_id).await.map_err(|e| {
                 warn!("Failed to resolve user by ID {user_id}: {e}");
-                StatusCode::UNAUTHORIZED
+                ApiError::Unauthorized
             });
         }
     }
 
-    // For GitHub/OAuth users, external_id is their provider-side ID
+    // For OAuth users (GitHub, Google), external_id is their provider-side ID:
+    // the numeric user id on GitHub, the `sub` claim on Google
     info!("Resolving {provider} user with external_id: {external_id}");
     let user = crate::db::upsert_user(pool, &username, &provider, Some(external_id))
         .await
         .map_err(|e| {
             warn!("Failed to sync user: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::Internal
         })?;
 
     Ok(user)
@@ -250,142 +813,612 @@ async fn resolve_user(
 
 // ===== SSE Helper =====
 
-/// Notify a specific user over SSE (if they are connected).
-async fn notify_user_sse(hub: &NotificationHub, user_id: Uuid, event: SseEvent) {
-    let hub = hub.lock().await;
-    if let Some(sender) = hub.get(&user_id) {
+/// Notify a specific user over SSE (if they are connected). The event is
+/// kept for replay either way, so a stream reconnecting soon still gets it.
+fn notify_user_sse(hub: &NotificationHub, user_id: Uuid, event: SseEvent) {
+    let id = hub.replay.record_for(user_id, &event);
+    if let Some(sender) = hub.channels.get(&user_id) {
         // Ignore errors — user may have disconnected
-        let _ = sender.send(event);
+        let _ = sender.send((id, event));
     }
 }
 
-/// Broadcast an SSE event to ALL connected users.
-async fn notify_all_sse(hub: &NotificationHub, event: SseEvent) {
-    let hub = hub.lock().await;
-    for sender in hub.values() {
-        let _ = sender.send(event.clone());
+/// Tell `recipient_id` about a new message: over SSE while they have a stream
+/// open, otherwise by Web Push (if they subscribed), unless they muted the
+/// thread. Also fires the `message.created` webhook.
+#[allow(clippy::too_many_arguments)]
+async fn notify_new_message(
+    pool: &PgPool,
+    hub: &NotificationHub,
+    push: &Arc<PushNotifier>,
+    webhooks: &WebhookDispatcher,
+    recipient_id: Uuid,
+    thread_id: Uuid,
+    message_id: Uuid,
+    content: String,
+) {
+    webhooks.emit(WebhookEvent::MessageCreated {
+        thread_id,
+        message_id,
+    });
+    match crate::db::is_thread_muted(pool, thread_id, recipient_id).await {
+        Ok(false) => {}
+        Ok(true) => return,
+        Err(e) => warn!(
+            "Failed to check whether thread {} is muted: {}",
+            thread_id, e
+        ),
+    }
+    if !hub.channels.contains_key(&recipient_id) {
+        push.notify(
+            pool,
+            recipient_id,
+            PushPayload {
+                kind: "new_message",
+                thread_id,
+                message_id,
+            },
+        );
+        return;
     }
+    notify_user_sse(
+        hub,
+        recipient_id,
+        SseEvent::NewMessage {
+            thread_id,
+            message_id,
+            content,
+            unread_total: unread_total(pool, recipient_id).await,
+        },
+    );
 }
 
-// ===== Handlers =====
+/// Broadcast an SSE event to ALL connected users.
+fn notify_all_sse(hub: &NotificationHub, event: SseEvent) {
+    let id = hub.replay.record_global(&event);
+    for sender in hub.channels.iter() {
+        let _ = sender.send((id, event.clone()));
+    }
+}
 
-/// SSE endpoint — streams real-time events to the authenticated user.
-#[tracing::instrument(skip(session, pool, hub))]
-async fn sse_handler(
-    mut session: AuthSession,
-    State(pool): State<Arc<PgPool>>,
-    State(hub): State<NotificationHub>,
-) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
-    let user = resolve_user(&mut session, &pool).await?;
-    let user_id = user.id;
+/// Send `server_shutting_down` to every stream, then drop all channels so each
+/// stream ends once it has delivered that event.
+pub fn close_sse_streams(hub: &NotificationHub) {
+    notify_all_sse(hub, SseEvent::ServerShuttingDown {});
+    hub.channels.clear();
+}
 
-    info!("User {} connected to SSE stream", user.username);
+// ===== Handlers =====
 
-    // Create or re-use a broadcast channel for this user
-    let receiver = {
-        let mut hub = hub.lock().await;
-        let sender = hub.entry(user_id).or_insert_with(|| {
-            let (tx, _) = tokio::sync::broadcast::channel(32);
-            tx
-        });
-        sender.subscribe()
-    };
+/// One open event stream. Dropping it (the client went away) removes the
+/// user's hub entry once no other stream is listening on it, which takes
+/// them offline.
+struct SseSubscription {
+    user_id: Uuid,
+    hub: NotificationHub,
+    pool: Arc<PgPool>,
+    rx: tokio::sync::broadcast::Receiver<StampedEvent>,
+    /// Replayed events still to send, before anything live
+    replayed: std::collections::VecDeque<StampedEvent>,
+    /// Id of the newest replayed event; live events up to it are repeats
+    replayed_through: u64,
+    /// Event types the client asked for; None for all
+    filter: Option<HashSet<&'static str>>,
+}
 
-    // Convert the broadcast receiver into a Stream of SSE Events
-    let stream = stream::unfold(receiver, |mut rx| async move {
-        match rx.recv().await {
-            Ok(evt) => {
-                let sse_event = Event::default().event(evt.event_type).data(evt.data);
-                Some((Ok(sse_event), rx))
-            }
-            Err(_) => None, // Channel closed or lagged — end stream
+impl Drop for SseSubscription {
+    fn drop(&mut self) {
+        // `rx` is still counted here. New streams subscribe while holding the
+        // same shard lock, so one opening in another tab either already counts
+        // or recreates the entry after this.
+        metrics::gauge!(telemetry::SSE_CONNECTIONS_ACTIVE).decrement(1.0);
+        let removed = self
+            .hub
+            .channels
+            .remove_if(&self.user_id, |_, sender| sender.receiver_count() <= 1);
+        if removed.is_some() {
+            debug!("Removed SSE channel for user {}", self.user_id);
+            tokio::spawn(announce_presence(
+                self.pool.clone(),
+                self.hub.clone(),
+                self.user_id,
+                false,
+            ));
         }
-    });
-
-    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+    }
 }
 
-#[tracing::instrument(skip(session, pool))]
-async fn me_handler(
-    mut session: AuthSession,
-    State(pool): State<Arc<PgPool>>,
-) -> Result<Json<UserResponse>, StatusCode> {
-    let user = resolve_user(&mut session, &pool).await?;
-
-    info!("User {} fetched profile", user.username);
-
-    Ok(Json(UserResponse {
-        id: user.id,
-        username: user.username,
-        provider: user.provider,
-        bio: user.bio,
-        avatar_url: user.avatar_url,
-        created_at: user.created_at,
-    }))
+/// Whether the user has a live event stream right now.
+fn is_online(hub: &NotificationHub, user_id: Uuid) -> bool {
+    hub.channels
+        .get(&user_id)
+        .is_some_and(|sender| sender.receiver_count() > 0)
 }
 
-#[tracing::instrument(skip(session, pool))]
-async fn update_profile_handler(
+/// The user's first stream opened or last one closed: stamp `last_seen` and,
+/// unless they turned presence off, send `presence` to their watchers.
+async fn announce_presence(pool: Arc<PgPool>, hub: NotificationHub, user_id: Uuid, online: bool) {
+    let last_seen = match crate::db::touch_last_seen(&pool, user_id).await {
+        Ok(last_seen) => last_seen,
+        Err(e) => {
+            warn!("Failed to update last_seen of user {}: {}", user_id, e);
+            return;
+        }
+    };
+    let watchers = match crate::db::presence_enabled(&pool, user_id).await {
+        Ok(true) => crate::db::get_presence_watchers(&pool, user_id).await,
+        Ok(false) => return,
+        Err(e) => Err(e),
+    };
+    let watchers = match watchers {
+        Ok(watchers) => watchers,
+        Err(e) => {
+            warn!(
+                "Failed to look up presence watchers of user {}: {}",
+                user_id, e
+            );
+            return;
+        }
+    };
+    for watcher in watchers {
+        notify_user_sse(
+            &hub,
+            watcher,
+            SseEvent::Presence {
+                user_id,
+                online,
+                last_seen: Some(last_seen),
+            },
+        );
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct SseQuery {
+    /// Stands in for the `Last-Event-ID` header, which a new `EventSource`
+    /// can't send
+    last_event_id: Option<u64>,
+    /// Comma-separated event types to send; absent sends everything
+    events: Option<String>,
+}
+
+/// Parse `?events=` into the set of event types to send. 400 on a name
+/// that isn't an event type, or on an empty list.
+fn parse_event_filter(events: &str) -> Result<HashSet<&'static str>, ApiError> {
+    let mut filter = HashSet::new();
+    for name in events.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let known = SseEvent::EVENT_TYPES
+            .iter()
+            .find(|known| **known == name)
+            .ok_or_else(|| ApiError::Validation(format!("Unknown event type: {name}")))?;
+        filter.insert(*known);
+    }
+    if filter.is_empty() {
+        return Err(ApiError::Validation(
+            "events must name at least one event type".into(),
+        ));
+    }
+    Ok(filter)
+}
+
+/// SSE endpoint — streams real-time events to the authenticated user, or
+/// only the types in `?events=`. A reconnecting client's `Last-Event-ID`
+/// gets it every kept event it missed first, or `resync` if some are gone.
+#[tracing::instrument(skip(session, pool, hub, api_config, headers))]
+async fn sse_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    Json(req): Json<UpdateProfileRequest>,
-) -> Result<Json<UserResponse>, StatusCode> {
+    State(hub): State<NotificationHub>,
+    State(api_config): State<Arc<ApiConfig>>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<SseQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+    let user_id = user.id;
+    let filter = query
+        .events
+        .as_deref()
+        .map(parse_event_filter)
+        .transpose()?;
+
+    // Create or re-use a broadcast channel for this user. Each open stream holds
+    // one receiver, so the receiver count is the user's live connection count.
+    let (receiver, came_online) = {
+        let sender = hub.channels.entry(user_id).or_insert_with(|| {
+            let (tx, _) = tokio::sync::broadcast::channel(32);
+            tx
+        });
+        if sender.receiver_count() >= api_config.sse_max_connections_per_user {
+            warn!(
+                "User {} exceeded {} concurrent SSE connections",
+                user.username, api_config.sse_max_connections_per_user
+            );
+            return Err(ApiError::TooManyRequests(format!(
+                "At most {} concurrent event streams are allowed",
+                api_config.sse_max_connections_per_user
+            )));
+        }
+        let came_online = sender.receiver_count() == 0;
+        (sender.subscribe(), came_online)
+    };
+
+    info!("User {} connected to SSE stream", user.username);
+    metrics::gauge!(telemetry::SSE_CONNECTIONS_ACTIVE).increment(1.0);
+    if came_online {
+        tokio::spawn(announce_presence(pool.clone(), hub.clone(), user_id, true));
+    }
+
+    // Subscribed before reading the replay buffer, so nothing sent in between
+    // is lost; the overlap is skipped by id below
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .or(query.last_event_id);
+    let mut replayed = std::collections::VecDeque::new();
+    let mut replayed_through = 0;
+    if let Some(after) = last_event_id {
+        let replay = hub.replay.replay(user_id, after);
+        debug!(
+            "Replaying {} SSE events to user {} after {}",
+            replay.events.len(),
+            user.username,
+            after
+        );
+        if replay.incomplete {
+            replayed.push_back((None, SseEvent::Resync { missed: 0 }));
+        }
+        replayed_through = replay.events.last().map_or(after, |(id, _)| *id);
+        replayed.extend(
+            replay
+                .events
+                .into_iter()
+                .map(|(id, event)| (Some(id), event)),
+        );
+    }
+
+    let subscription = SseSubscription {
+        user_id,
+        hub: hub.clone(),
+        pool: pool.clone(),
+        rx: receiver,
+        replayed,
+        replayed_through,
+        filter,
+    };
+
+    // Convert the broadcast receiver into a Stream of SSE Events
+    let stream = stream::unfold(subscription, |mut sub| async move {
+        let (id, evt) = loop {
+            let (id, evt) = match sub.replayed.pop_front() {
+                Some(replayed) => replayed,
+                None => match sub.rx.recv().await {
+                    Ok((Some(id), _)) if id <= sub.replayed_through => continue,
+                    Ok(stamped) => stamped,
+                    // A slow client overflowed its channel. The receiver skips
+                    // ahead to the oldest event still buffered; tell the client
+                    // to refetch.
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!(
+                            "SSE stream for user {} lagged, skipped {} events",
+                            sub.user_id, missed
+                        );
+                        (None, SseEvent::Resync { missed })
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+            };
+            let wanted = evt.is_control()
+                || sub
+                    .filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.contains(evt.event_type()));
+            if wanted {
+                break (id, evt);
+            }
+        };
+        let (event_type, data) = evt.to_parts();
+        let mut sse_event = Event::default().event(event_type).data(data);
+        if let Some(id) = id {
+            sse_event = sse_event.id(id.to_string());
+        }
+        Some((Ok(sse_event), sub))
+    });
+
+    let keep_alive = KeepAlive::new()
+        .interval(api_config.sse_keepalive)
+        .text("keep-alive");
+    Ok(Sse::new(stream).keep_alive(keep_alive))
+}
+
+/// The caller's profile along with its `ETag` header.
+type ProfileResponse = ([(axum::http::HeaderName, String); 1], Json<UserResponse>);
+
+/// Strong validator for the caller's profile, covering every field a profile
+/// update can change plus `updated_at`: the first 8 bytes of a SHA-256 over
+/// them, so it stays the same across builds and instances. Each field is
+/// length-prefixed, and a missing one is told apart from an empty one.
+fn profile_etag(user: &crate::db::User) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    for field in [
+        Some(user.username.as_str()),
+        user.bio.as_deref(),
+        user.avatar_url.as_deref(),
+    ] {
+        match field {
+            Some(value) => {
+                hasher.update([1]);
+                hasher.update((value.len() as u64).to_be_bytes());
+                hasher.update(value);
+            }
+            None => hasher.update([0]),
+        }
+    }
+    hasher.update(user.updated_at.unix_timestamp_nanos().to_be_bytes());
+    let digest = hasher.finalize();
+    let hex: String = digest[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("\"{hex}\"")
+}
+
+/// Whether an `If-Match` header value (`*` or a list of entity tags) matches.
+fn if_match_satisfied(if_match: &str, etag: &str) -> bool {
+    if_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+#[tracing::instrument(skip(session, pool))]
+async fn me_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<ProfileResponse, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    let updated_user =
-        crate::db::update_user_profile(&pool, user.id, req.username, req.bio, req.avatar_url)
+    info!("User {} fetched profile", user.username);
+
+    let etag = profile_etag(&user);
+    Ok((
+        [(axum::http::header::ETAG, etag)],
+        Json(UserResponse {
+            id: user.id,
+            username: user.username,
+            provider: user.provider,
+            bio: user.bio,
+            avatar_url: user.avatar_url,
+            created_at: user.created_at,
+        }),
+    ))
+}
+
+#[derive(Serialize)]
+struct PublicProfileResponse {
+    username: String,
+    bio: Option<String>,
+    avatar_url: Option<String>,
+    /// Whether they have an inbox link, so anyone can message them
+    accepts_anonymous_messages: bool,
+}
+
+/// A user's public profile, for strangers about to message them; needs no
+/// session. 404 when there's no such user or they hid their profile, so the
+/// two can't be told apart.
+#[tracing::instrument(skip(pool))]
+async fn public_profile_handler(
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(username): axum::extract::Path<String>,
+) -> Result<Json<PublicProfileResponse>, ApiError> {
+    let profile = crate::db::get_public_profile(&pool, &username)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load public profile: {}", e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("User not found".into()))?;
+
+    Ok(Json(PublicProfileResponse {
+        username: profile.username,
+        bio: profile.bio,
+        avatar_url: profile.avatar_url,
+        accepts_anonymous_messages: profile.accepts_anonymous_messages,
+    }))
+}
+
+#[derive(Serialize)]
+struct PresenceResponse {
+    online: bool,
+    /// When their last stream opened or closed; null if never, or hidden
+    #[serde(with = "time::serde::rfc3339::option")]
+    last_seen: Option<OffsetDateTime>,
+}
+
+/// Whether a user is online, going by open event streams. Only the user and
+/// those who'd get their `presence` events may ask; to anyone else the user
+/// doesn't exist. Users who turned presence off always look offline and
+/// never seen.
+#[tracing::instrument(skip(session, pool, hub))]
+async fn presence_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(hub): State<NotificationHub>,
+    axum::extract::Path(user_id): axum::extract::Path<Uuid>,
+) -> Result<Json<PresenceResponse>, ApiError> {
+    let viewer = resolve_user(&mut session, &pool).await?;
+
+    if viewer.id != user_id {
+        let watcher = crate::db::is_presence_watcher(&pool, user_id, viewer.id)
             .await
             .map_err(|e| {
-                warn!("Failed to update profile for user {}: {}", user.id, e);
-                StatusCode::INTERNAL_SERVER_ERROR
+                warn!("Failed to check presence access to user {}: {}", user_id, e);
+                ApiError::Internal
             })?;
+        if !watcher {
+            return Err(ApiError::NotFound("User not found".into()));
+        }
+    }
 
-    info!("User {} updated profile", updated_user.username);
+    let presence = crate::db::get_presence(&pool, user_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load presence of user {}: {}", user_id, e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("User not found".into()))?;
+    if !presence.share_presence {
+        return Ok(Json(PresenceResponse {
+            online: false,
+            last_seen: None,
+        }));
+    }
 
-    Ok(Json(UserResponse {
-        id: updated_user.id,
-        username: updated_user.username,
-        provider: updated_user.provider,
-        bio: updated_user.bio,
-        avatar_url: updated_user.avatar_url,
-        created_at: updated_user.created_at,
+    Ok(Json(PresenceResponse {
+        online: is_online(&hub, user_id),
+        last_seen: presence.last_seen,
     }))
 }
 
+/// Update the caller's profile. With `If-Match`, the update is rejected with
+/// 412 if the profile changed since the client read that ETag.
+#[tracing::instrument(skip(session, pool, headers))]
+async fn update_profile_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<UpdateProfileRequest>,
+) -> Result<ProfileResponse, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let expected_updated_at = match headers.get(axum::http::header::IF_MATCH) {
+        Some(value) => {
+            let value = value
+                .to_str()
+                .map_err(|_| ApiError::Validation("Invalid If-Match header".into()))?;
+            if !if_match_satisfied(value, &profile_etag(&user)) {
+                info!("Rejected stale profile update for user {}", user.id);
+                return Err(ApiError::PreconditionFailed(
+                    "Profile changed since it was last read".into(),
+                ));
+            }
+            // Guards against a write landing between the check and the update
+            Some(user.updated_at)
+        }
+        None => None,
+    };
+
+    let updated_user = crate::db::update_user_profile(
+        &pool,
+        user.id,
+        req.username,
+        req.bio,
+        req.avatar_url,
+        expected_updated_at,
+    )
+    .await
+    .map_err(|e| {
+        warn!("Failed to update profile for user {}: {}", user.id, e);
+        ApiError::Internal
+    })?
+    .ok_or_else(|| ApiError::PreconditionFailed("Profile changed since it was last read".into()))?;
+
+    info!("User {} updated profile", updated_user.username);
+
+    let etag = profile_etag(&updated_user);
+    Ok((
+        [(axum::http::header::ETAG, etag)],
+        Json(UserResponse {
+            id: updated_user.id,
+            username: updated_user.username,
+            provider: updated_user.provider,
+            bio: updated_user.bio,
+            avatar_url: updated_user.avatar_url,
+            created_at: updated_user.created_at,
+        }),
+    ))
+}
+
 #[tracing::instrument(skip(session, pool))]
 async fn delete_account_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-) -> Result<StatusCode, StatusCode> {
+    axum::extract::Query(query): axum::extract::Query<DryRunQuery>,
+) -> Result<Response, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
+    if query.dry_run {
+        let impact = crate::db::get_account_deletion_impact(&pool, user.id)
+            .await
+            .map_err(|e| {
+                warn!("Failed to count account deletion impact: {}", e);
+                ApiError::Internal
+            })?;
+        return Ok(Json(serde_json::json!({
+            "dry_run": true,
+            "messages_received": impact.messages_received,
+            "messages_sent": impact.messages_sent,
+            "broadcasts": impact.broadcasts,
+            "comments": impact.comments,
+            "reactions": impact.reactions,
+            "scheduled_broadcasts": impact.scheduled_broadcasts,
+        }))
+        .into_response());
+    }
+
     crate::db::delete_user(&pool, user.id).await.map_err(|e| {
         warn!("Failed to delete user {}: {}", user.id, e);
-        StatusCode::INTERNAL_SERVER_ERROR
+        ApiError::Internal
     })?;
 
     info!("User {} deleted their account", user.username);
 
     // Note: session logout should ideally be handled by the client redirecting to /logout
-    Ok(StatusCode::NO_CONTENT)
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+const MAX_USERS_PAGE: i64 = 100;
+
+#[derive(Deserialize, Debug)]
+struct ListUsersQuery {
+    #[serde(default = "default_limit")]
+    limit: i64,
+    #[serde(default)]
+    offset: i64,
+    /// Only usernames containing this, ignoring case
+    q: Option<String>,
+}
+
+/// A page of the user directory; `total` counts every match, not just this page.
+#[derive(Serialize)]
+struct UsersPageResponse {
+    users: Vec<UserResponse>,
+    total: i64,
 }
 
 #[tracing::instrument(skip(session, pool))]
 async fn list_users_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-) -> Result<Json<Vec<UserResponse>>, StatusCode> {
+    axum::extract::Query(query): axum::extract::Query<ListUsersQuery>,
+) -> Result<Json<UsersPageResponse>, ApiError> {
     // Resolve the current user so we can exclude them from the list
     let current_user = resolve_user(&mut session, &pool).await?;
 
-    let users = crate::db::get_all_users(&pool).await.map_err(|e| {
-        warn!("Failed to fetch users: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let limit = query.limit.clamp(1, MAX_USERS_PAGE);
+    let offset = query.offset.max(0);
+    let q = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    let (users, total) = crate::db::search_users(&pool, current_user.id, q, limit, offset)
+        .await
+        .map_err(|e| {
+            warn!("Failed to fetch users: {}", e);
+            ApiError::Internal
+        })?;
 
-    let filtered: Vec<UserResponse> = users
+    let users: Vec<UserResponse> = users
         .into_iter()
-        .filter(|u| u.id != current_user.id) // exclude self
         .map(|u| UserResponse {
             id: u.id,
             username: u.username,
@@ -396,18 +1429,22 @@ async fn list_users_handler(
         })
         .collect();
 
-    info!("Fetched {} users (excluding self)", filtered.len());
-    Ok(Json(filtered))
+    info!(
+        "Fetched {} of {} users (excluding self)",
+        users.len(),
+        total
+    );
+    Ok(Json(UsersPageResponse { users, total }))
 }
 
 #[tracing::instrument(skip(_session, pool))]
 async fn debug_list_users_handler(
     _session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-) -> Result<Json<Vec<DebugUserResponse>>, StatusCode> {
+) -> Result<Json<Vec<DebugUserResponse>>, ApiError> {
     let users = crate::db::get_all_users(&pool).await.map_err(|e| {
         warn!("Failed to fetch users: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
+        ApiError::Internal
     })?;
 
     Ok(Json(
@@ -425,855 +1462,4168 @@ async fn debug_list_users_handler(
     ))
 }
 
-/// Send a new anonymous message (starts a new thread).
-#[tracing::instrument(skip(session, pool, hub))]
-async fn send_message_handler(
+/// Connection pool usage and estimated table sizes. Admins only; the same
+/// numbers are gauges on `/metrics`.
+#[tracing::instrument(skip(session, pool, api_config))]
+async fn admin_stats_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    State(hub): State<NotificationHub>,
-    Json(req): Json<SendMessageRequest>,
-) -> Result<StatusCode, StatusCode> {
-    if req.content.trim().is_empty() {
-        warn!("Attempted to send empty message");
-        return Err(StatusCode::BAD_REQUEST);
-    }
+    State(api_config): State<Arc<ApiConfig>>,
+) -> Result<Json<AdminStatsResponse>, ApiError> {
+    require_admin(&mut session, &pool, &api_config).await?;
+    let pool_stats = crate::db::pool_stats(&pool);
+    let counts = crate::db::table_counts(&pool).await.map_err(|e| {
+        warn!("Failed to fetch table counts: {}", e);
+        ApiError::Internal
+    })?;
 
-    // Resolve sender — may be None for fully anonymous (unauthenticated) sends
-    let sender_id = resolve_user(&mut session, &pool).await.ok().map(|u| u.id);
+    Ok(Json(AdminStatsResponse {
+        pool: PoolStatsResponse {
+            size: pool_stats.size,
+            idle: pool_stats.idle,
+            in_use: pool_stats.in_use,
+        },
+        tables: TableCountsResponse {
+            users: counts.users,
+            messages: counts.messages,
+            broadcasts: counts.broadcasts,
+        },
+    }))
+}
 
-    let (message_id, thread_id) =
-        crate::db::create_message(&pool, sender_id, req.recipient_id, &req.content)
-            .await
-            .map_err(|e| {
-                warn!("Failed to create message: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+/// The signed-in user if they're an admin (`is_admin`, or listed in
+/// `ADMIN_USERS`); 403 otherwise.
+async fn require_admin(
+    session: &mut AuthSession,
+    pool: &PgPool,
+    api_config: &ApiConfig,
+) -> Result<crate::db::User, ApiError> {
+    let user = resolve_user(session, pool).await?;
+    if api_config.admin_users.contains(&user.id) {
+        return Ok(user);
+    }
+    let user = crate::db::get_user_by_id(pool, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load user {}: {}", user.id, e);
+            ApiError::Internal
+        })?;
+    if !user.is_admin {
+        return Err(ApiError::Forbidden("Admins only".into()));
+    }
+    Ok(user)
+}
 
-    info!(
-        "Anonymous message {} sent to user {}",
-        message_id, req.recipient_id
-    );
+/// The latest data integrity report (see `integrity.rs`), or `null` before
+/// the first run. Admins only.
+#[tracing::instrument(skip(session, pool, api_config, latest))]
+async fn integrity_report_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    State(latest): State<LatestReport>,
+) -> Result<Json<Option<crate::integrity::Report>>, ApiError> {
+    require_admin(&mut session, &pool, &api_config).await?;
+    let report = latest.read().unwrap_or_else(|e| e.into_inner()).clone();
+    Ok(Json(report))
+}
 
-    // Push SSE notification to recipient (if online)
-    let payload = serde_json::json!({
-        "message_id": message_id,
-        "thread_id": thread_id,
-        "content": req.content,
-    })
-    .to_string();
-    notify_user_sse(
-        &hub,
-        req.recipient_id,
-        SseEvent {
-            event_type: "new_message".to_string(),
-            data: payload,
-        },
-    )
-    .await;
+#[derive(Deserialize, Debug)]
+struct CreateWebhookRequest {
+    url: String,
+    secret: String,
+    /// Event types to deliver, from `webhooks::EVENT_TYPES`
+    events: Vec<String>,
+}
 
-    Ok(StatusCode::CREATED)
+/// A registered webhook. The secret is write-only and never returned.
+#[derive(Serialize)]
+struct WebhookResponse {
+    id: Uuid,
+    url: String,
+    events: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
 }
 
-/// Reply to an existing thread.
-#[tracing::instrument(skip(session, pool, hub))]
-async fn reply_message_handler(
+impl From<crate::db::Webhook> for WebhookResponse {
+    fn from(w: crate::db::Webhook) -> Self {
+        Self {
+            id: w.id,
+            url: w.url,
+            events: w.events,
+            created_at: w.created_at,
+        }
+    }
+}
+
+/// Register a webhook (see `webhooks.rs`). Admins only.
+#[tracing::instrument(skip(session, pool, api_config, req))]
+async fn create_webhook_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    State(hub): State<NotificationHub>,
-    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
-    Json(req): Json<ReplyRequest>,
-) -> Result<StatusCode, StatusCode> {
-    if req.content.trim().is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+    State(api_config): State<Arc<ApiConfig>>,
+    Json(mut req): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<WebhookResponse>), ApiError> {
+    let user = require_admin(&mut session, &pool, &api_config).await?;
+
+    let url = reqwest::Url::parse(req.url.trim())
+        .map_err(|_| ApiError::Validation("url must be an absolute URL".into()))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(ApiError::Validation("url must be http or https".into()));
+    }
+    if req.secret.len() < crate::webhooks::MIN_SECRET_LENGTH {
+        return Err(ApiError::Validation(format!(
+            "secret must be at least {} bytes",
+            crate::webhooks::MIN_SECRET_LENGTH
+        )));
+    }
+    req.events.sort();
+    req.events.dedup();
+    if req.events.is_empty() {
+        return Err(ApiError::Validation("events cannot be empty".into()));
+    }
+    if let Some(unknown) = req
+        .events
+        .iter()
+        .find(|e| !crate::webhooks::EVENT_TYPES.contains(&e.as_str()))
+    {
+        return Err(ApiError::Validation(format!(
+            "Unknown event type: {unknown}"
+        )));
     }
 
-    let user = resolve_user(&mut session, &pool).await?;
-
-    // Load the original message to find thread_id and who to reply to
-    let original = crate::db::get_message_by_id(&pool, message_id)
+    let webhook = crate::db::create_webhook(&pool, user.id, url.as_str(), &req.secret, &req.events)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
-
-    // The reply goes to: if the current user is the recipient, reply to the sender;
-    // if the current user is the sender, reply to the recipient.
-    let reply_recipient_id = if original.recipient_id == user.id {
-        // User is the recipient — reply to original sender (if known)
-        original.sender_id.ok_or_else(|| {
-            warn!("Cannot reply: original sender is anonymous (no sender_id stored)");
-            StatusCode::BAD_REQUEST
-        })?
-    } else if original.sender_id == Some(user.id) {
-        // User is the sender — reply to the recipient
-        original.recipient_id
-    } else {
-        warn!(
-            "User {} tried to reply to a message they're not part of",
-            user.id
-        );
-        return Err(StatusCode::FORBIDDEN);
-    };
-
-    let new_message_id = crate::db::create_reply(
-        &pool,
-        original.thread_id,
-        user.id,
-        reply_recipient_id,
-        &req.content,
-    )
-    .await
-    .map_err(|e| {
-        warn!("Failed to create reply: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+        .map_err(|e| {
+            warn!("Failed to create webhook: {}", e);
+            ApiError::Internal
+        })?;
 
     info!(
-        "Reply {} in thread {} sent",
-        new_message_id, original.thread_id
+        "User {} registered webhook {} for {:?}",
+        user.username, webhook.id, webhook.events
     );
-
-    // Notify recipient over SSE
-    let payload = serde_json::json!({
-        "message_id": new_message_id,
-        "thread_id": original.thread_id,
-        "content": req.content,
-    })
-    .to_string();
-    notify_user_sse(
-        &hub,
-        reply_recipient_id,
-        SseEvent {
-            event_type: "new_message".to_string(),
-            data: payload,
-        },
-    )
-    .await;
-
-    Ok(StatusCode::CREATED)
+    Ok((StatusCode::CREATED, Json(webhook.into())))
 }
 
-/// List all conversations (threads) the current user participates in.
-#[tracing::instrument(skip(session, pool))]
-async fn list_conversations_handler(
+/// Every registered webhook. Admins only.
+#[tracing::instrument(skip(session, pool, api_config))]
+async fn list_webhooks_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-) -> Result<Json<Vec<MessageResponse>>, StatusCode> {
-    let user = resolve_user(&mut session, &pool).await?;
+    State(api_config): State<Arc<ApiConfig>>,
+) -> Result<Json<Vec<WebhookResponse>>, ApiError> {
+    require_admin(&mut session, &pool, &api_config).await?;
 
-    let threads = crate::db::get_user_conversations(&pool, user.id)
-        .await
-        .map_err(|e| {
-            warn!("Failed to fetch conversations: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let webhooks = crate::db::get_webhooks(&pool).await.map_err(|e| {
+        warn!("Failed to fetch webhooks: {}", e);
+        ApiError::Internal
+    })?;
 
-    Ok(Json(
-        threads
-            .into_iter()
-            .map(|t| MessageResponse {
-                id: t.id,
-                thread_id: t.thread_id,
-                content: t.content,
-                is_mine: t.sender_id == Some(user.id),
-                created_at: t.created_at,
-                is_read: t.is_read,
-                reactions: None,
-                unread_count: Some(t.unread_count),
-                to_username: t.recipient_username, // null for recipients, name for senders
-            })
-            .collect(),
-    ))
+    Ok(Json(webhooks.into_iter().map(Into::into).collect()))
 }
 
-/// Get all messages in a thread. Also marks received messages as read.
-#[tracing::instrument(skip(session, pool))]
-async fn get_thread_handler(
+/// Stop delivering to a webhook. Deliveries already under way still finish.
+/// Admins only.
+#[tracing::instrument(skip(session, pool, api_config))]
+async fn delete_webhook_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
-) -> Result<Json<Vec<MessageResponse>>, StatusCode> {
-    let user = resolve_user(&mut session, &pool).await?;
+    State(api_config): State<Arc<ApiConfig>>,
+    axum::extract::Path(webhook_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = require_admin(&mut session, &pool, &api_config).await?;
 
-    let msgs = crate::db::get_thread_messages(&pool, thread_id)
+    let deleted = crate::db::delete_webhook(&pool, webhook_id)
         .await
         .map_err(|e| {
-            warn!("Failed to fetch thread {}: {}", thread_id, e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to delete webhook: {}", e);
+            ApiError::Internal
         })?;
-
-    // Ensure the user is part of this thread
-    let is_participant = msgs
-        .iter()
-        .any(|m| m.recipient_id == user.id || m.sender_id == Some(user.id));
-    if !msgs.is_empty() && !is_participant {
-        return Err(StatusCode::FORBIDDEN);
+    if !deleted {
+        return Err(ApiError::NotFound("Webhook not found".into()));
     }
 
-    // Mark messages received by this user as read
-    if let Err(e) = crate::db::mark_thread_as_read(&pool, thread_id, user.id).await {
-        warn!("Failed to mark thread as read: {}", e);
-    }
+    info!("User {} deleted webhook {}", user.username, webhook_id);
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    Ok(Json(
-        msgs.into_iter()
-            .map(|m| MessageResponse {
-                id: m.id,
-                thread_id: m.thread_id,
-                content: m.content,
-                is_mine: m.sender_id == Some(user.id),
-                created_at: m.created_at,
-                is_read: m.is_read,
-                reactions: m.reactions,
-                unread_count: None,
-                to_username: None, // individual messages don't need this
-            })
-            .collect(),
-    ))
+/// Send a new anonymous message (starts a new thread).
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(session, pool, api_config, hub, push, webhooks, fingerprint))]
+async fn send_message_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    State(hub): State<NotificationHub>,
+    State(push): State<Arc<PushNotifier>>,
+    State(webhooks): State<WebhookDispatcher>,
+    OriginFingerprint(fingerprint): OriginFingerprint,
+    Json(req): Json<SendMessageRequest>,
+) -> Result<StatusCode, ApiError> {
+    if req.content.trim().is_empty() {
+        warn!("Attempted to send empty message");
+        return Err(ApiError::Validation(
+            "Message content cannot be empty".into(),
+        ));
+    }
+    check_content_length(&req.content, api_config.max_content_length)?;
+    let ttl = match req.expires_in_secs {
+        Some(secs) if secs == 0 || secs > MAX_MESSAGE_TTL_SECS => {
+            return Err(ApiError::Validation(format!(
+                "expires_in_secs must be between 1 and {}",
+                MAX_MESSAGE_TTL_SECS
+            )));
+        }
+        secs => secs.map(Duration::from_secs),
+    };
+    let expiry = crate::db::MessageExpiry {
+        ephemeral: req.ephemeral,
+        ttl,
+    };
+    let now = OffsetDateTime::now_utc();
+    let deliver_at = req.deliver_at.filter(|at| *at > now);
+    if deliver_at.is_some_and(|at| at - now > MAX_DELIVERY_DELAY) {
+        return Err(ApiError::Validation(
+            "deliver_at can be at most 30 days ahead".into(),
+        ));
+    }
+
+    // Resolve sender — None for unauthenticated sends, or when the sender
+    // opted out of replies. Signed-in senders count against their quota
+    // either way, and a banned session is refused rather than sent as
+    // anonymous.
+    let user = match resolve_user(&mut session, &pool).await {
+        Ok(user) => Some(user),
+        Err(ApiError::Unauthorized) => None,
+        Err(e) => return Err(e),
+    };
+    if let Some(user) = &user {
+        if user.id == req.recipient_id {
+            return Err(ApiError::Validation("You cannot message yourself".into()));
+        }
+    }
+    check_recipient_exists(&*pool, req.recipient_id).await?;
+    if let Some(user) = &user {
+        consume_send_quota(&*pool, &api_config, user.id).await?;
+    }
+    let sender_id = if req.allow_reply {
+        user.as_ref().map(|u| u.id)
+    } else {
+        None
+    };
+
+    if let Some(deliver_at) = deliver_at {
+        // Only a signed-in sender can see or cancel what they scheduled
+        let Some(user) = user else {
+            return Err(ApiError::Unauthorized);
+        };
+        let message_id = crate::db::create_scheduled_message(
+            &pool,
+            user.id,
+            sender_id,
+            req.recipient_id,
+            &req.content,
+            fingerprint.as_deref(),
+            expiry,
+            deliver_at,
+        )
+        .await
+        .map_err(|e| {
+            warn!("Failed to schedule message: {}", e);
+            ApiError::Internal
+        })?;
+        info!("Message {} scheduled for {}", message_id, deliver_at);
+        return Ok(StatusCode::ACCEPTED);
+    }
+
+    deliver_new_message(
+        &pool,
+        &hub,
+        &push,
+        &webhooks,
+        sender_id,
+        req.recipient_id,
+        req.content,
+        fingerprint.as_deref(),
+        expiry,
+        None,
+    )
+    .await?;
+
+    Ok(StatusCode::CREATED)
 }
 
+/// 404 if `recipient_id` isn't an account, so nothing is stored for nobody to
+/// read. A failed lookup stays a 500.
+async fn check_recipient_exists<'e>(
+    executor: impl PgExecutor<'e>,
+    recipient_id: Uuid,
+) -> Result<(), ApiError> {
+    let exists = crate::db::user_exists(executor, recipient_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to look up recipient {}: {}", recipient_id, e);
+            ApiError::Internal
+        })?;
+    if !exists {
+        warn!("Message addressed to unknown user {}", recipient_id);
+        return Err(ApiError::NotFound("Recipient not found".into()));
+    }
+    Ok(())
+}
+
+/// The user's unread total for SSE payloads; left out if it can't be computed.
+async fn unread_total(pool: &PgPool, user_id: Uuid) -> Option<i64> {
+    match crate::db::get_unread_summary(pool, user_id).await {
+        Ok(summary) => Some(summary.total),
+        Err(e) => {
+            warn!("Failed to count unread messages: {}", e);
+            None
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct UnreadCountResponse {
+    total: i64,
+    threads: i64,
+}
+
+/// Cheap unread totals for the notification badge.
 #[tracing::instrument(skip(session, pool))]
-async fn inbox_handler(
+async fn unread_count_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-) -> Result<Json<Vec<MessageResponse>>, StatusCode> {
+) -> Result<Json<UnreadCountResponse>, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    let messages = crate::db::get_user_inbox(&pool, user.id)
+    let summary = crate::db::get_unread_summary(&pool, user.id)
         .await
         .map_err(|e| {
-            warn!("Failed to fetch inbox: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to count unread messages: {}", e);
+            ApiError::Internal
         })?;
 
-    info!("User {} fetched {} messages", user.username, messages.len());
+    Ok(Json(UnreadCountResponse {
+        total: summary.total,
+        threads: summary.threads,
+    }))
+}
+
+/// The caller's scheduled messages that haven't gone out yet, soonest first.
+#[tracing::instrument(skip(session, pool))]
+async fn list_scheduled_messages_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<Vec<ScheduledMessageResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let scheduled = crate::db::get_scheduled_messages(&pool, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to fetch scheduled messages: {}", e);
+            ApiError::Internal
+        })?;
 
     Ok(Json(
-        messages
+        scheduled
             .into_iter()
-            .map(|m| MessageResponse {
+            .map(|m| ScheduledMessageResponse {
                 id: m.id,
-                thread_id: m.thread_id,
+                recipient_id: m.recipient_id,
+                recipient_username: m.recipient_username,
                 content: m.content,
-                is_mine: false, // inbox = always received
-                created_at: m.created_at,
-                is_read: m.is_read,
-                reactions: m.reactions,
-                unread_count: None,
-                to_username: None,
+                deliver_at: m.deliver_at,
+                allow_reply: m.allow_reply,
+                ephemeral: m.ephemeral,
             })
             .collect(),
     ))
 }
 
+/// Cancel a scheduled message before it goes out. 404 once it was delivered,
+/// or if the caller didn't schedule it.
 #[tracing::instrument(skip(session, pool))]
-async fn react_message_handler(
+async fn cancel_scheduled_message_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
     axum::extract::Path(message_id): axum::extract::Path<Uuid>,
-    Json(req): Json<ReactMessageRequest>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    crate::db::add_message_reaction(&pool, message_id, user.id, &req.emoji)
+    let cancelled = crate::db::cancel_scheduled_message(&pool, message_id, user.id)
         .await
         .map_err(|e| {
-            warn!("Failed to add reaction: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to cancel scheduled message {}: {}", message_id, e);
+            ApiError::Internal
         })?;
+    if !cancelled {
+        return Err(ApiError::NotFound("Scheduled message not found".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    Ok(StatusCode::OK)
+/// Deliver every scheduled message that's due: it shows up in the thread as
+/// if sent now, and the recipient is notified as for any new message.
+pub async fn deliver_scheduled_messages(
+    pool: &PgPool,
+    hub: &NotificationHub,
+    push: &Arc<PushNotifier>,
+    webhooks: &WebhookDispatcher,
+) -> Result<(), sqlx::Error> {
+    loop {
+        let delivered =
+            crate::db::deliver_scheduled_messages(pool, SCHEDULED_MESSAGE_BATCH).await?;
+        for m in &delivered {
+            info!(
+                "Scheduled message {} delivered to user {}",
+                m.id, m.recipient_id
+            );
+            metrics::counter!(telemetry::MESSAGES_SENT, "kind" => "new").increment(1);
+            notify_new_message(
+                pool,
+                hub,
+                push,
+                webhooks,
+                m.recipient_id,
+                m.thread_id,
+                m.id,
+                m.content.clone(),
+            )
+            .await;
+        }
+        if (delivered.len() as i64) < SCHEDULED_MESSAGE_BATCH {
+            return Ok(());
+        }
+    }
 }
 
-#[tracing::instrument(skip(session, pool, hub))]
-async fn create_broadcast_handler(
+/// Store a message that starts a new thread and push it to the recipient.
+/// Returns `(message_id, thread_id)`.
+#[allow(clippy::too_many_arguments)]
+async fn deliver_new_message(
+    pool: &PgPool,
+    hub: &NotificationHub,
+    push: &Arc<PushNotifier>,
+    webhooks: &WebhookDispatcher,
+    sender_id: Option<Uuid>,
+    recipient_id: Uuid,
+    content: String,
+    origin_fingerprint: Option<&str>,
+    expiry: crate::db::MessageExpiry,
+    forwarded_from_thread: Option<Uuid>,
+) -> Result<(Uuid, Uuid), ApiError> {
+    let (message_id, thread_id) = crate::db::create_message(
+        pool,
+        sender_id,
+        recipient_id,
+        &content,
+        origin_fingerprint,
+        expiry,
+        forwarded_from_thread,
+    )
+    .await
+    .map_err(|e| {
+        warn!("Failed to create message: {}", e);
+        ApiError::Internal
+    })?;
+
+    info!(
+        "Anonymous message {} sent to user {}",
+        message_id, recipient_id
+    );
+    metrics::counter!(telemetry::MESSAGES_SENT, "kind" => "new").increment(1);
+
+    notify_new_message(
+        pool,
+        hub,
+        push,
+        webhooks,
+        recipient_id,
+        thread_id,
+        message_id,
+        content,
+    )
+    .await;
+
+    Ok((message_id, thread_id))
+}
+
+/// Forward a message the caller sent or received to someone else, as a new
+/// thread from the caller. Only the content is copied: whoever sent the
+/// original stays unknown to the new recipient.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(session, pool, api_config, hub, push, webhooks))]
+async fn forward_message_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
     State(hub): State<NotificationHub>,
-    Json(req): Json<CreateBroadcastRequest>,
-) -> Result<StatusCode, StatusCode> {
+    State(push): State<Arc<PushNotifier>>,
+    State(webhooks): State<WebhookDispatcher>,
+    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
+    Json(req): Json<ForwardMessageRequest>,
+) -> Result<(StatusCode, Json<ForwardMessageResponse>), ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let source = crate::db::get_forward_source(&pool, message_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load message {}: {}", message_id, e);
+            ApiError::Internal
+        })?
+        .filter(|m| m.recipient_id == user.id || m.sender_id == Some(user.id))
+        .ok_or_else(|| ApiError::NotFound("Message not found".into()))?;
+    if source.disappearing {
+        return Err(ApiError::Unprocessable(
+            "Disappearing messages can't be forwarded".into(),
+        ));
+    }
+
+    if req.recipient_id == user.id {
+        return Err(ApiError::Validation("You cannot message yourself".into()));
+    }
+    check_recipient_exists(&*pool, req.recipient_id).await?;
+    let blocked = crate::db::is_blocked(&pool, req.recipient_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to check block status: {}", e);
+            ApiError::Internal
+        })?;
+    if blocked {
+        return Err(ApiError::Forbidden("You can't message this user".into()));
+    }
+    consume_send_quota(&*pool, &api_config, user.id).await?;
+
+    let (message_id, thread_id) = deliver_new_message(
+        &pool,
+        &hub,
+        &push,
+        &webhooks,
+        Some(user.id),
+        req.recipient_id,
+        source.content,
+        None,
+        Default::default(),
+        Some(source.thread_id),
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ForwardMessageResponse {
+            message_id,
+            thread_id,
+        }),
+    ))
+}
+
+/// Start a conversation with someone by username in one call, instead of
+/// looking up their id and then sending.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(session, pool, api_config, hub, push, webhooks, fingerprint, req))]
+async fn start_conversation_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    State(hub): State<NotificationHub>,
+    State(push): State<Arc<PushNotifier>>,
+    State(webhooks): State<WebhookDispatcher>,
+    OriginFingerprint(fingerprint): OriginFingerprint,
+    Json(req): Json<StartConversationRequest>,
+) -> Result<(StatusCode, Json<StartConversationResponse>), ApiError> {
     if req.content.trim().is_empty() {
-        warn!("Attempted to create empty broadcast");
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::Validation(
+            "Message content cannot be empty".into(),
+        ));
     }
+    check_content_length(&req.content, api_config.max_content_length)?;
 
     let user = resolve_user(&mut session, &pool).await?;
-    let sender_id = if req.is_anonymous {
-        None
-    } else {
-        Some(user.id)
-    };
 
-    let broadcast_id =
-        crate::db::create_broadcast(&pool, sender_id, &req.content, req.is_anonymous)
-            .await
-            .map_err(|e| {
-                warn!("Failed to create broadcast: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+    let recipient = crate::db::get_user_by_username(&pool, req.recipient_username.trim())
+        .await
+        .map_err(|e| {
+            warn!("Failed to look up recipient: {}", e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("User not found".into()))?;
+    if recipient.id == user.id {
+        return Err(ApiError::Validation("You cannot message yourself".into()));
+    }
+
+    let blocked = crate::db::is_blocked(&pool, recipient.id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to check block status: {}", e);
+            ApiError::Internal
+        })?;
+    if blocked {
+        return Err(ApiError::Forbidden("You can't message this user".into()));
+    }
+
+    consume_send_quota(&*pool, &api_config, user.id).await?;
+
+    let sender_id = if req.anonymous { None } else { Some(user.id) };
+    let (message_id, thread_id) = deliver_new_message(
+        &pool,
+        &hub,
+        &push,
+        &webhooks,
+        sender_id,
+        recipient.id,
+        req.content,
+        fingerprint.as_deref(),
+        Default::default(),
+        None,
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(StartConversationResponse {
+            thread_id,
+            message_id,
+            recipient_id: recipient.id,
+        }),
+    ))
+}
+
+/// Recreate a thread exported from another instance. Nothing in the export
+/// identifies the other participant, so their messages are stored without a
+/// sender and the thread is read-only; `other_party` is only a label.
+#[tracing::instrument(skip(session, pool, api_config, req))]
+async fn import_conversation_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    Json(req): Json<ImportConversationRequest>,
+) -> Result<(StatusCode, Json<ImportConversationResponse>), ApiError> {
+    if req.messages.is_empty() {
+        return Err(ApiError::Validation(
+            "An import needs at least one message".into(),
+        ));
+    }
+    if req.messages.len() > MAX_IMPORT_MESSAGES {
+        return Err(ApiError::PayloadTooLarge(format!(
+            "An import can hold at most {} messages",
+            MAX_IMPORT_MESSAGES
+        )));
+    }
+
+    let other_party = req
+        .other_party
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .unwrap_or("Anonymous")
+        .to_string();
+    if other_party.chars().count() > MAX_PSEUDONYM_LENGTH {
+        return Err(ApiError::Validation(format!(
+            "other_party can be at most {} characters",
+            MAX_PSEUDONYM_LENGTH
+        )));
+    }
+
+    let now = OffsetDateTime::now_utc();
+    let mut messages = Vec::with_capacity(req.messages.len());
+    for m in req.messages {
+        if m.content.trim().is_empty() {
+            return Err(ApiError::Validation(
+                "Imported messages cannot be empty".into(),
+            ));
+        }
+        check_content_length(&m.content, api_config.max_content_length)?;
+        if m.created_at > now {
+            return Err(ApiError::Validation(
+                "Imported messages cannot be dated in the future".into(),
+            ));
+        }
+        messages.push(crate::db::ImportedMessage {
+            content: m.content,
+            is_mine: m.is_mine,
+            created_at: m.created_at,
+        });
+    }
+    messages.sort_by_key(|m| m.created_at);
+
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let thread_id = crate::db::import_thread(&pool, user.id, &other_party, &messages)
+        .await
+        .map_err(|e| {
+            warn!("Failed to import conversation: {}", e);
+            ApiError::Internal
+        })?;
+
+    info!(
+        "User {} imported {} messages into thread {}",
+        user.id,
+        messages.len(),
+        thread_id
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ImportConversationResponse {
+            thread_id,
+            imported: messages.len(),
+            other_party,
+        }),
+    ))
+}
+
+/// Reply to an existing thread.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(session, pool, api_config, hub, push, webhooks))]
+async fn reply_message_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    State(hub): State<NotificationHub>,
+    State(push): State<Arc<PushNotifier>>,
+    State(webhooks): State<WebhookDispatcher>,
+    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
+    Json(req): Json<ReplyRequest>,
+) -> Result<StatusCode, ApiError> {
+    if req.content.trim().is_empty() {
+        return Err(ApiError::Validation("Reply content cannot be empty".into()));
+    }
+    check_content_length(&req.content, api_config.max_content_length)?;
+
+    let user = resolve_user(&mut session, &pool).await?;
+
+    // Loading the original, the pause and quota checks and the insert run in
+    // one transaction so a failure part-way leaves nothing behind. The
+    // recipient is only notified once it has committed.
+    let user_id = user.id;
+    let content = req.content.clone();
+    let (reply_to_message_id, quote_range) = (req.reply_to_message_id, req.quote_range);
+    let (thread_id, reply_recipient_id, new_message_id) = crate::db::transaction(&pool, |tx| {
+        Box::pin(async move {
+            // Load the original message to find thread_id and who to reply to
+            let original = crate::db::get_message_by_id(&mut **tx, message_id)
+                .await
+                .map_err(|e| {
+                    warn!("Failed to load message {}: {}", message_id, e);
+                    ApiError::Internal
+                })?
+                .ok_or_else(|| ApiError::NotFound("Message not found".into()))?;
+
+            let imported = crate::db::is_thread_imported(&mut **tx, original.thread_id)
+                .await
+                .map_err(|e| {
+                    warn!("Failed to check whether thread was imported: {}", e);
+                    ApiError::Internal
+                })?;
+            if imported {
+                return Err(ApiError::Unprocessable(
+                    "Imported conversations are read-only".into(),
+                ));
+            }
+
+            // The reply goes to: if the current user is the recipient, reply to the sender;
+            // if the current user is the sender, reply to the recipient.
+            let reply_recipient_id = if original.recipient_id == user_id {
+                // User is the recipient — reply to original sender (if known)
+                original.sender_id.ok_or_else(|| {
+                    warn!("Cannot reply: this sender cannot be replied to (no sender_id stored)");
+                    ApiError::Unprocessable("This sender cannot be replied to".into())
+                })?
+            } else if original.sender_id == Some(user_id) {
+                // User is the sender — reply to the recipient
+                original.recipient_id
+            } else {
+                warn!(
+                    "User {} tried to reply to a message they're not part of",
+                    user_id
+                );
+                return Err(ApiError::Forbidden(
+                    "You are not part of this conversation".into(),
+                ));
+            };
+
+            // The other side may have deleted their account since
+            check_recipient_exists(&mut **tx, reply_recipient_id).await?;
+
+            let paused = crate::db::is_thread_paused_for(&mut **tx, original.thread_id, user_id)
+                .await
+                .map_err(|e| {
+                    warn!("Failed to check pause state: {}", e);
+                    ApiError::Internal
+                })?;
+            if paused {
+                warn!(
+                    "User {} tried to reply in paused thread {}",
+                    user_id, original.thread_id
+                );
+                return Err(ApiError::Locked(
+                    "This conversation has been paused by the other participant".into(),
+                ));
+            }
+
+            // A bare quote_range quotes the message being replied to
+            let reply_to_message_id = reply_to_message_id.or(quote_range.map(|_| message_id));
+            let quote_range = check_quote(
+                &mut **tx,
+                original.thread_id,
+                reply_to_message_id,
+                quote_range,
+            )
+            .await?;
+
+            consume_send_quota(&mut **tx, &api_config, user_id).await?;
+
+            let new_message_id = crate::db::create_reply(
+                &mut **tx,
+                original.thread_id,
+                user_id,
+                reply_recipient_id,
+                &content,
+                reply_to_message_id,
+                quote_range,
+            )
+            .await
+            .map_err(|e| {
+                warn!("Failed to create reply: {}", e);
+                ApiError::Internal
+            })?;
+
+            Ok((original.thread_id, reply_recipient_id, new_message_id))
+        })
+    })
+    .await?;
+
+    info!("Reply {} in thread {} sent", new_message_id, thread_id);
+    metrics::counter!(telemetry::MESSAGES_SENT, "kind" => "reply").increment(1);
+
+    notify_new_message(
+        &pool,
+        &hub,
+        &push,
+        &webhooks,
+        reply_recipient_id,
+        thread_id,
+        new_message_id,
+        req.content,
+    )
+    .await;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[derive(Serialize)]
+struct ThreadReplyResponse {
+    message_id: Uuid,
+}
+
+/// Append to a thread without naming a message to reply to. The other
+/// participant comes from the thread as a whole (see
+/// `db::get_thread_counterpart`); the per-message reply stays for quote-replies.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(session, pool, api_config, hub, push, webhooks, req))]
+async fn reply_thread_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    State(hub): State<NotificationHub>,
+    State(push): State<Arc<PushNotifier>>,
+    State(webhooks): State<WebhookDispatcher>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+    Json(req): Json<ReplyRequest>,
+) -> Result<(StatusCode, Json<ThreadReplyResponse>), ApiError> {
+    if req.content.trim().is_empty() {
+        return Err(ApiError::Validation("Reply content cannot be empty".into()));
+    }
+    check_content_length(&req.content, api_config.max_content_length)?;
+
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let user_id = user.id;
+    let content = req.content.clone();
+    let (reply_to_message_id, quote_range) = (req.reply_to_message_id, req.quote_range);
+    let (recipient_id, message_id) = crate::db::transaction(&pool, |tx| {
+        Box::pin(async move {
+            let counterpart = crate::db::get_thread_counterpart(&mut **tx, thread_id, user_id)
+                .await
+                .map_err(|e| {
+                    warn!("Failed to load participants of thread {}: {}", thread_id, e);
+                    ApiError::Internal
+                })?
+                .ok_or_else(|| ApiError::NotFound("Conversation not found".into()))?;
+
+            let imported = crate::db::is_thread_imported(&mut **tx, thread_id)
+                .await
+                .map_err(|e| {
+                    warn!("Failed to check whether thread was imported: {}", e);
+                    ApiError::Internal
+                })?;
+            if imported {
+                return Err(ApiError::Unprocessable(
+                    "Imported conversations are read-only".into(),
+                ));
+            }
+
+            let recipient_id = counterpart.ok_or_else(|| {
+                ApiError::Unprocessable("This sender cannot be replied to".into())
+            })?;
+            check_recipient_exists(&mut **tx, recipient_id).await?;
+
+            let paused = crate::db::is_thread_paused_for(&mut **tx, thread_id, user_id)
+                .await
+                .map_err(|e| {
+                    warn!("Failed to check pause state: {}", e);
+                    ApiError::Internal
+                })?;
+            if paused {
+                return Err(ApiError::Locked(
+                    "This conversation has been paused by the other participant".into(),
+                ));
+            }
+
+            let quote_range =
+                check_quote(&mut **tx, thread_id, reply_to_message_id, quote_range).await?;
+
+            consume_send_quota(&mut **tx, &api_config, user_id).await?;
+
+            let message_id = crate::db::create_reply(
+                &mut **tx,
+                thread_id,
+                user_id,
+                recipient_id,
+                &content,
+                reply_to_message_id,
+                quote_range,
+            )
+            .await
+            .map_err(|e| {
+                warn!("Failed to create reply: {}", e);
+                ApiError::Internal
+            })?;
+
+            Ok((recipient_id, message_id))
+        })
+    })
+    .await?;
+
+    info!("Reply {} in thread {} sent", message_id, thread_id);
+    metrics::counter!(telemetry::MESSAGES_SENT, "kind" => "reply").increment(1);
+
+    notify_new_message(
+        &pool,
+        &hub,
+        &push,
+        &webhooks,
+        recipient_id,
+        thread_id,
+        message_id,
+        req.content,
+    )
+    .await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ThreadReplyResponse { message_id }),
+    ))
+}
+
+/// Mark a single received message as read (e.g. as it scrolls into view).
+/// Messages the caller didn't receive are left untouched.
+#[tracing::instrument(skip(session, pool))]
+async fn mark_message_read_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    crate::db::mark_message_read(&pool, message_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to mark message {} as read: {}", message_id, e);
+            ApiError::Internal
+        })?;
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize, Debug)]
+struct ConversationsQuery {
+    /// Only threads the user gave this label
+    label: Option<String>,
+    /// Only threads with messages the user hasn't read
+    #[serde(default)]
+    unread_only: bool,
+    /// Also list threads the user archived
+    #[serde(default)]
+    include_archived: bool,
+}
+
+/// List all conversations (threads) the current user participates in.
+#[tracing::instrument(skip(session, pool))]
+async fn list_conversations_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Query(query): axum::extract::Query<ConversationsQuery>,
+) -> Result<Json<Vec<MessageResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let label = query
+        .label
+        .as_deref()
+        .map(str::trim)
+        .filter(|l| !l.is_empty());
+    let archived = if query.include_archived {
+        None
+    } else {
+        Some(false)
+    };
+    let threads = crate::db::get_user_conversations(
+        &pool,
+        user.id,
+        label,
+        query.unread_only,
+        false,
+        archived,
+    )
+    .await
+    .map_err(|e| {
+        warn!("Failed to fetch conversations: {}", e);
+        ApiError::Internal
+    })?;
+
+    Ok(Json(
+        threads
+            .into_iter()
+            .map(|t| thread_summary_response(t, user.id))
+            .collect(),
+    ))
+}
+
+/// The caller's pinned conversations, in the conversation list's shape.
+#[tracing::instrument(skip(session, pool))]
+async fn list_pinned_conversations_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<Vec<MessageResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let threads = crate::db::get_user_conversations(&pool, user.id, None, false, true, None)
+        .await
+        .map_err(|e| {
+            warn!("Failed to fetch pinned conversations: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(
+        threads
+            .into_iter()
+            .map(|t| thread_summary_response(t, user.id))
+            .collect(),
+    ))
+}
+
+/// The caller's archived conversations, in the conversation list's shape.
+#[tracing::instrument(skip(session, pool))]
+async fn list_archived_conversations_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<Vec<MessageResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let threads = crate::db::get_user_conversations(&pool, user.id, None, false, false, Some(true))
+        .await
+        .map_err(|e| {
+            warn!("Failed to fetch archived conversations: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(
+        threads
+            .into_iter()
+            .map(|t| thread_summary_response(t, user.id))
+            .collect(),
+    ))
+}
+
+/// Archive a thread for the caller only: it leaves their conversation list
+/// until a new message arrives in it. Nothing is deleted.
+#[tracing::instrument(skip(session, pool))]
+async fn archive_thread_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let is_participant = crate::db::is_thread_participant(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to check thread participation: {}", e);
+            ApiError::Internal
+        })?;
+    if !is_participant {
+        return Err(ApiError::Forbidden(
+            "You are not part of this conversation".into(),
+        ));
+    }
+
+    crate::db::archive_thread(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to archive thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(serde_json::json!({ "archived": true })))
+}
+
+/// Bring an archived thread back into the caller's conversation list.
+#[tracing::instrument(skip(session, pool))]
+async fn unarchive_thread_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    crate::db::unarchive_thread(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to unarchive thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(serde_json::json!({ "archived": false })))
+}
+
+/// Mute a thread for the caller: new messages in it stop sending them SSE
+/// events and pushes, but still count as unread. The body is optional.
+#[tracing::instrument(skip(session, pool))]
+async fn mute_thread_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+    body: Option<Json<MuteThreadRequest>>,
+) -> Result<Json<MuteThreadResponse>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+    let Json(req) = body.unwrap_or_default();
+
+    if req
+        .muted_until
+        .is_some_and(|until| until <= OffsetDateTime::now_utc())
+    {
+        return Err(ApiError::Validation(
+            "muted_until must be in the future".into(),
+        ));
+    }
+
+    let is_participant = crate::db::is_thread_participant(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to check thread participation: {}", e);
+            ApiError::Internal
+        })?;
+    if !is_participant {
+        return Err(ApiError::Forbidden(
+            "You are not part of this conversation".into(),
+        ));
+    }
+
+    crate::db::mute_thread(&pool, thread_id, user.id, req.muted_until)
+        .await
+        .map_err(|e| {
+            warn!("Failed to mute thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(MuteThreadResponse {
+        muted: true,
+        muted_until: req.muted_until,
+    }))
+}
+
+/// Lift the caller's mute on a thread.
+#[tracing::instrument(skip(session, pool))]
+async fn unmute_thread_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    crate::db::unmute_thread(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to unmute thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(serde_json::json!({ "muted": false })))
+}
+
+/// A conversation list entry: the thread's latest message plus thread state.
+fn thread_summary_response(t: crate::db::ThreadSummary, viewer_id: Uuid) -> MessageResponse {
+    MessageResponse {
+        id: t.id,
+        thread_id: t.thread_id,
+        content: t.content,
+        is_mine: t.sender_id == Some(viewer_id),
+        created_at: t.created_at,
+        is_read: t.is_read,
+        edited: t.edited_at.is_some(),
+        edited_at: t.edited_at,
+        reactions: Default::default(),
+        unread_count: Some(t.unread_count),
+        to_username: t.recipient_username, // null for recipients, name for senders
+        paused: Some(t.paused),
+        is_pinned: Some(t.pinned),
+        labels: Some(t.labels),
+        archived: Some(t.archived),
+        muted: Some(t.muted),
+        reply_to_message_id: None,
+        quote_range: None,
+        reply_to_preview: None,
+        forwarded: None,
+        expires_at: None,
+        editable_until: None,
+    }
+}
+
+/// Get all messages in a thread. Also marks received messages as read and
+/// starts the countdown on ephemeral ones.
+#[tracing::instrument(skip(session, pool, api_config, hub))]
+async fn get_thread_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    State(hub): State<NotificationHub>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+    axum::extract::Query(query): axum::extract::Query<ThreadQuery>,
+) -> Result<Json<Vec<MessageResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let mut msgs =
+        crate::db::get_thread_messages(&pool, thread_id, user.id, query.tombstones, false)
+            .await
+            .map_err(|e| {
+                warn!("Failed to fetch thread {}: {}", thread_id, e);
+                ApiError::Internal
+            })?;
+
+    // Ensure the user is part of this thread
+    let is_participant = msgs
+        .iter()
+        .any(|m| m.recipient_id == user.id || m.sender_id == Some(user.id));
+    if !msgs.is_empty() && !is_participant {
+        return Err(ApiError::Forbidden(
+            "You are not part of this conversation".into(),
+        ));
+    }
+
+    let paused = crate::db::is_thread_paused(&pool, thread_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to check pause state of thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+
+    // Mark messages received by this user as read
+    match crate::db::mark_thread_as_read(&pool, thread_id, user.id).await {
+        Ok(read) if !read.is_empty() => {
+            notify_read_receipts(&pool, &hub, user.id, thread_id, &read).await
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Failed to mark thread as read: {}", e),
+    }
+
+    // Opening the thread starts the countdown on ephemeral messages it holds
+    // for this user; the response carries their new expiry
+    match crate::db::schedule_ephemeral_expiry(
+        &pool,
+        thread_id,
+        user.id,
+        api_config.ephemeral_read_ttl,
+    )
+    .await
+    {
+        Ok(scheduled) if !scheduled.is_empty() => {
+            for m in &mut msgs {
+                if let Some((_, expires_at)) = scheduled.iter().find(|(id, _)| *id == m.id) {
+                    m.expires_at = Some(*expires_at);
+                }
+            }
+            let (pool, hub) = (pool.clone(), hub.clone());
+            let ttl = api_config.ephemeral_read_ttl;
+            tokio::spawn(async move {
+                tokio::time::sleep(ttl).await;
+                if let Err(e) = expire_messages(&pool, &hub).await {
+                    warn!("Failed to delete expired messages: {}", e);
+                }
+            });
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Failed to schedule ephemeral messages: {}", e),
+    }
+
+    Ok(Json(
+        msgs.into_iter()
+            .map(|m| thread_message_response(m, user.id, paused, api_config.edit_window))
+            .collect(),
+    ))
+}
+
+/// The messages pinned in a thread, oldest pin first. Pins are shared, so
+/// these are the ones either participant pinned. Unlike the thread view,
+/// nothing is marked as read.
+#[tracing::instrument(skip(session, pool, api_config))]
+async fn get_pinned_messages_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+) -> Result<Json<Vec<MessageResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let is_participant = crate::db::is_thread_participant(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to check thread participation: {}", e);
+            ApiError::Internal
+        })?;
+    if !is_participant {
+        return Err(ApiError::Forbidden(
+            "You are not part of this conversation".into(),
+        ));
+    }
+
+    let msgs = crate::db::get_thread_messages(&pool, thread_id, user.id, false, true)
+        .await
+        .map_err(|e| {
+            warn!(
+                "Failed to fetch pinned messages of thread {}: {}",
+                thread_id, e
+            );
+            ApiError::Internal
+        })?;
+
+    let paused = crate::db::is_thread_paused(&pool, thread_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to check pause state of thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(
+        msgs.into_iter()
+            .map(|m| thread_message_response(m, user.id, paused, api_config.edit_window))
+            .collect(),
+    ))
+}
+
+/// A message as the thread view shows it to `viewer_id`.
+fn thread_message_response(
+    m: crate::db::Message,
+    viewer_id: Uuid,
+    paused: bool,
+    edit_window: Option<Duration>,
+) -> MessageResponse {
+    let is_mine = m.sender_id == Some(viewer_id);
+    MessageResponse {
+        id: m.id,
+        thread_id: m.thread_id,
+        content: m.content,
+        is_mine,
+        created_at: m.created_at,
+        is_read: m.is_read,
+        edited: m.edited_at.is_some(),
+        edited_at: m.edited_at,
+        reactions: m.reactions,
+        unread_count: None,
+        to_username: None, // individual messages don't need this
+        paused: Some(paused),
+        is_pinned: Some(m.pinned),
+        labels: None,
+        archived: None,
+        muted: None,
+        reply_to_message_id: m.reply_to_message_id,
+        quote_range: m
+            .quote_start
+            .zip(m.quote_end)
+            .map(|(start, end)| QuoteRange {
+                start: start as usize,
+                end: end as usize,
+            }),
+        reply_to_preview: m.reply_to_preview,
+        forwarded: Some(m.forwarded),
+        expires_at: m.expires_at,
+        editable_until: edit_window
+            .filter(|_| is_mine)
+            .map(|window| m.created_at + window),
+    }
+}
+
+/// Tell each sender their messages were read, if both they and the reader
+/// have read receipts on. The event never identifies the reader.
+async fn notify_read_receipts(
+    pool: &PgPool,
+    hub: &NotificationHub,
+    reader_id: Uuid,
+    thread_id: Uuid,
+    read: &[crate::db::ReadMessage],
+) {
+    match crate::db::read_receipts_enabled(pool, reader_id).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => {
+            warn!("Failed to load read receipt preference: {}", e);
+            return;
+        }
+    }
+
+    // Group the newly read message ids by who sent them
+    let mut senders: Vec<(Uuid, OffsetDateTime, Vec<Uuid>)> = Vec::new();
+    for m in read {
+        let Some(sender_id) = m.sender_id else {
+            continue;
+        };
+        match senders.iter_mut().find(|(id, _, _)| *id == sender_id) {
+            Some((_, _, message_ids)) => message_ids.push(m.id),
+            None => senders.push((sender_id, m.read_at, vec![m.id])),
+        }
+    }
+
+    for (sender_id, read_at, message_ids) in senders {
+        match crate::db::read_receipts_enabled(pool, sender_id).await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                warn!("Failed to load read receipt preference: {}", e);
+                continue;
+            }
+        }
+
+        notify_user_sse(
+            hub,
+            sender_id,
+            SseEvent::ReadReceipt {
+                thread_id,
+                message_ids,
+                read_at,
+            },
+        );
+    }
+}
+
+#[tracing::instrument(skip(session, pool))]
+async fn inbox_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Query(query): axum::extract::Query<PageQuery>,
+) -> Result<Json<PaginatedResponse<MessageResponse>>, ApiError> {
+    let limit = query.limit();
+    let cursor = query.cursor()?;
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let messages = crate::db::get_user_inbox(
+        &pool,
+        user.id,
+        limit,
+        cursor.map(|c| c.at),
+        cursor.map(|c| c.id),
+    )
+    .await
+    .map_err(|e| {
+        warn!("Failed to fetch inbox: {}", e);
+        ApiError::Internal
+    })?;
+
+    info!("User {} fetched {} messages", user.username, messages.len());
+
+    Ok(Json(PaginatedResponse::new(
+        messages,
+        limit,
+        |m| (m.created_at, m.id),
+        |m| MessageResponse {
+            id: m.id,
+            thread_id: m.thread_id,
+            content: m.content,
+            is_mine: false, // inbox = always received
+            created_at: m.created_at,
+            is_read: m.is_read,
+            edited: m.edited_at.is_some(),
+            edited_at: m.edited_at,
+            reactions: m.reactions,
+            unread_count: None,
+            to_username: None,
+            paused: None,
+            is_pinned: None,
+            labels: None,
+            archived: None,
+            muted: None,
+            reply_to_message_id: None,
+            quote_range: None,
+            reply_to_preview: None,
+            forwarded: None,
+            expires_at: None,
+            editable_until: None,
+        },
+    )))
+}
+
+#[tracing::instrument(skip(session, pool))]
+async fn react_message_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
+    Json(req): Json<ReactMessageRequest>,
+) -> Result<Json<ReactionToggleResponse>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    // Reacting again with the same emoji takes the reaction back
+    let removed = crate::db::remove_message_reaction(&pool, message_id, user.id, &req.emoji)
+        .await
+        .map_err(|e| {
+            warn!("Failed to remove reaction: {}", e);
+            ApiError::Internal
+        })?;
+    if removed {
+        return Ok(Json(ReactionToggleResponse { reacted: false }));
+    }
+
+    crate::db::add_message_reaction(&pool, message_id, user.id, &req.emoji)
+        .await
+        .map_err(|e| {
+            warn!("Failed to add reaction: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(ReactionToggleResponse { reacted: true }))
+}
+
+/// Add or remove reactions on several messages at once. Items for messages
+/// the caller isn't part of are reported per item rather than failing the batch.
+#[tracing::instrument(skip(session, pool, items))]
+async fn batch_react_messages_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    Json(items): Json<Vec<BatchReactionItem>>,
+) -> Result<Json<Vec<BatchReactionResult>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    if items.len() > MAX_REACTION_BATCH {
+        warn!(
+            "User {} sent a reaction batch of {} items",
+            user.id,
+            items.len()
+        );
+        return Err(ApiError::PayloadTooLarge(format!(
+            "At most {MAX_REACTION_BATCH} reactions per batch"
+        )));
+    }
+    if items.iter().any(|i| i.emoji.trim().is_empty()) {
+        return Err(ApiError::Validation("Emoji cannot be empty".into()));
+    }
+
+    let changes: Vec<(Uuid, String, bool)> = items
+        .iter()
+        .map(|i| {
+            (
+                i.message_id,
+                i.emoji.clone(),
+                matches!(i.action, ReactionAction::Add),
+            )
+        })
+        .collect();
+
+    let outcomes = crate::db::apply_message_reactions(&pool, user.id, &changes)
+        .await
+        .map_err(|e| {
+            warn!("Failed to apply reaction batch: {}", e);
+            ApiError::Internal
+        })?;
+
+    info!(
+        "User {} applied a batch of {} reactions",
+        user.username,
+        items.len()
+    );
+
+    Ok(Json(
+        items
+            .into_iter()
+            .zip(outcomes)
+            .map(|(item, outcome)| BatchReactionResult {
+                message_id: item.message_id,
+                emoji: item.emoji,
+                action: item.action,
+                status: match outcome {
+                    crate::db::ReactionOutcome::Applied => "applied",
+                    crate::db::ReactionOutcome::NotFound => "not_found",
+                    crate::db::ReactionOutcome::Forbidden => "forbidden",
+                },
+            })
+            .collect(),
+    ))
+}
+
+#[tracing::instrument(skip(session, pool, api_config, hub, webhooks, fingerprint))]
+async fn create_broadcast_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    State(hub): State<NotificationHub>,
+    State(webhooks): State<WebhookDispatcher>,
+    OriginFingerprint(fingerprint): OriginFingerprint,
+    Json(req): Json<CreateBroadcastRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), ApiError> {
+    if req.content.trim().is_empty() {
+        warn!("Attempted to create empty broadcast");
+        return Err(ApiError::Validation(
+            "Broadcast content cannot be empty".into(),
+        ));
+    }
+    check_content_length(&req.content, api_config.max_content_length)?;
+
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let broadcast_id = crate::db::create_broadcast(
+        &*pool,
+        user.id,
+        &req.content,
+        req.is_anonymous,
+        !req.publish,
+        fingerprint.as_deref(),
+    )
+    .await
+    .map_err(|e| {
+        warn!("Failed to create broadcast: {}", e);
+        ApiError::Internal
+    })?;
+
+    info!(
+        "Broadcast {} created (anonymous: {}, draft: {})",
+        broadcast_id, req.is_anonymous, !req.publish
+    );
+    metrics::counter!(telemetry::BROADCASTS_CREATED, "source" => "user").increment(1);
+
+    let body = Json(serde_json::json!({ "id": broadcast_id }));
+    if !req.publish {
+        return Ok((StatusCode::CREATED, body));
+    }
+
+    // Push SSE event to ALL connected users so their broadcasts page updates
+    notify_all_sse(&hub, SseEvent::NewBroadcast { broadcast_id });
+    webhooks.emit(WebhookEvent::BroadcastPublished { broadcast_id });
+
+    Ok((StatusCode::CREATED, body))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum RecurrenceFrequency {
+    Daily,
+    Weekly,
+}
+
+impl RecurrenceFrequency {
+    fn as_str(self) -> &'static str {
+        match self {
+            RecurrenceFrequency::Daily => "daily",
+            RecurrenceFrequency::Weekly => "weekly",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RecurrenceRule {
+    frequency: RecurrenceFrequency,
+    /// 0 = Sunday ... 6 = Saturday; required for weekly, absent for daily
+    #[serde(default)]
+    weekday: Option<i16>,
+    /// Local wall-clock time, `HH:MM`
+    time: String,
+    /// IANA time zone name, e.g. `Europe/Berlin`
+    timezone: String,
+    /// Stop after this instant
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    ends_at: Option<OffsetDateTime>,
+    /// Stop after this many broadcasts
+    #[serde(default)]
+    max_occurrences: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CreateScheduledBroadcastRequest {
+    content: String,
+    #[serde(default)]
+    is_anonymous: bool,
+    recurrence: RecurrenceRule,
+}
+
+#[derive(Serialize)]
+struct ScheduledBroadcastResponse {
+    id: Uuid,
+    content: String,
+    is_anonymous: bool,
+    recurrence: RecurrenceRule,
+    occurrence_count: i32,
+    /// None once the schedule has ended or been cancelled
+    #[serde(with = "time::serde::rfc3339::option")]
+    next_run_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    last_run_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    cancelled_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+}
+
+impl From<crate::db::ScheduledBroadcast> for ScheduledBroadcastResponse {
+    fn from(s: crate::db::ScheduledBroadcast) -> Self {
+        Self {
+            id: s.id,
+            content: s.content,
+            is_anonymous: s.is_anonymous,
+            recurrence: RecurrenceRule {
+                frequency: if s.frequency == "weekly" {
+                    RecurrenceFrequency::Weekly
+                } else {
+                    RecurrenceFrequency::Daily
+                },
+                weekday: s.weekday,
+                time: format!("{:02}:{:02}", s.time_of_day.hour(), s.time_of_day.minute()),
+                timezone: s.timezone,
+                ends_at: s.ends_at,
+                max_occurrences: s.max_occurrences,
+            },
+            occurrence_count: s.occurrence_count,
+            next_run_at: s.next_run_at,
+            last_run_at: s.last_run_at,
+            cancelled_at: s.cancelled_at,
+            created_at: s.created_at,
+        }
+    }
+}
+
+/// Parse `HH:MM` (24-hour).
+fn parse_time_of_day(value: &str) -> Option<time::Time> {
+    let (hour, minute) = value.split_once(':')?;
+    if hour.len() != 2 || minute.len() != 2 {
+        return None;
+    }
+    time::Time::from_hms(hour.parse().ok()?, minute.parse().ok()?, 0).ok()
+}
+
+/// Set up a broadcast that is published automatically on a daily or weekly
+/// schedule. The first run is the next matching slot after now.
+#[tracing::instrument(skip(session, pool, api_config))]
+async fn create_scheduled_broadcast_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    Json(req): Json<CreateScheduledBroadcastRequest>,
+) -> Result<(StatusCode, Json<ScheduledBroadcastResponse>), ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    if req.content.trim().is_empty() {
+        return Err(ApiError::Validation(
+            "Broadcast content cannot be empty".into(),
+        ));
+    }
+    check_content_length(&req.content, api_config.max_content_length)?;
+    let rule = &req.recurrence;
+    match (rule.frequency, rule.weekday) {
+        (RecurrenceFrequency::Weekly, Some(0..=6)) | (RecurrenceFrequency::Daily, None) => {}
+        (RecurrenceFrequency::Weekly, _) => {
+            return Err(ApiError::Validation(
+                "Weekly schedules need a weekday from 0 (Sunday) to 6 (Saturday)".into(),
+            ))
+        }
+        (RecurrenceFrequency::Daily, Some(_)) => {
+            return Err(ApiError::Validation(
+                "Daily schedules don't take a weekday".into(),
+            ))
+        }
+    }
+    let time_of_day = parse_time_of_day(&rule.time)
+        .ok_or_else(|| ApiError::Validation("time must be HH:MM (24-hour)".into()))?;
+    if rule.max_occurrences.is_some_and(|n| n < 1) {
+        return Err(ApiError::Validation(
+            "max_occurrences must be at least 1".into(),
+        ));
+    }
+    if rule.ends_at.is_some_and(|t| t <= OffsetDateTime::now_utc()) {
+        return Err(ApiError::Validation("ends_at must be in the future".into()));
+    }
+    let known_timezone = crate::db::is_known_timezone(&pool, &rule.timezone)
+        .await
+        .map_err(|e| {
+            warn!("Failed to look up time zone: {}", e);
+            ApiError::Internal
+        })?;
+    if !known_timezone {
+        return Err(ApiError::Validation(format!(
+            "Unknown time zone: {}",
+            rule.timezone
+        )));
+    }
+
+    let schedule = crate::db::NewScheduledBroadcast {
+        content: &req.content,
+        is_anonymous: req.is_anonymous,
+        frequency: rule.frequency.as_str(),
+        weekday: rule.weekday,
+        time_of_day,
+        timezone: &rule.timezone,
+        ends_at: rule.ends_at,
+        max_occurrences: rule.max_occurrences,
+    };
+    let created = crate::db::create_scheduled_broadcast(&pool, user.id, &schedule)
+        .await
+        .map_err(|e| {
+            warn!("Failed to create scheduled broadcast: {}", e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| {
+            ApiError::Unprocessable("The schedule ends before its first occurrence".into())
+        })?;
+
+    info!(
+        "Scheduled broadcast {} created ({}, first run {:?})",
+        created.id, created.frequency, created.next_run_at
+    );
+    Ok((StatusCode::CREATED, Json(created.into())))
+}
+
+/// The caller's recurring broadcasts, including ended and cancelled ones.
+#[tracing::instrument(skip(session, pool))]
+async fn list_scheduled_broadcasts_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<Vec<ScheduledBroadcastResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let schedules = crate::db::get_scheduled_broadcasts(&pool, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to fetch scheduled broadcasts: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(schedules.into_iter().map(Into::into).collect()))
+}
+
+#[tracing::instrument(skip(session, pool))]
+async fn cancel_scheduled_broadcast_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(schedule_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let cancelled = crate::db::cancel_scheduled_broadcast(&pool, schedule_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to cancel scheduled broadcast: {}", e);
+            ApiError::Internal
+        })?;
+    if !cancelled {
+        return Err(ApiError::NotFound("Scheduled broadcast not found".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Publish every scheduled broadcast that has come due and announce each
+/// like a newly created one. Run periodically as the `scheduled_broadcasts` job.
+pub async fn publish_scheduled_broadcasts(
+    pool: &PgPool,
+    hub: &NotificationHub,
+    webhooks: &WebhookDispatcher,
+) -> Result<(), sqlx::Error> {
+    let published = crate::db::run_due_scheduled_broadcasts(pool).await?;
+    if !published.is_empty() {
+        info!("Published {} scheduled broadcasts", published.len());
+        metrics::counter!(telemetry::BROADCASTS_CREATED, "source" => "schedule")
+            .increment(published.len() as u64);
+    }
+
+    for broadcast_id in published {
+        notify_all_sse(hub, SseEvent::NewBroadcast { broadcast_id });
+        webhooks.emit(WebhookEvent::BroadcastPublished { broadcast_id });
+    }
+    Ok(())
+}
+
+/// The caller's unpublished broadcast drafts.
+#[tracing::instrument(skip(session, pool))]
+async fn list_broadcast_drafts_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<Vec<BroadcastResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let drafts = crate::db::get_broadcast_drafts(&pool, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to fetch broadcast drafts: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(
+        drafts
+            .into_iter()
+            .map(|b| BroadcastResponse {
+                id: b.id,
+                sender_username: b.sender_username,
+                content: b.content,
+                is_anonymous: b.is_anonymous,
+                created_at: b.created_at,
+                view_count: b.view_count.unwrap_or(0),
+                edited_at: b.edited_at,
+                edit_count: b.edit_count.unwrap_or(0),
+                is_mine: b.author_id == Some(user.id),
+            })
+            .collect(),
+    ))
+}
+
+/// Publish one of the caller's drafts and announce it to everyone.
+#[tracing::instrument(skip(session, pool, hub, webhooks))]
+async fn publish_broadcast_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(hub): State<NotificationHub>,
+    State(webhooks): State<WebhookDispatcher>,
+    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let published = crate::db::publish_broadcast(&pool, broadcast_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to publish broadcast: {}", e);
+            ApiError::Internal
+        })?;
+
+    if !published {
+        return Err(ApiError::NotFound("Draft not found".into()));
+    }
+
+    info!(
+        "User {} published broadcast {}",
+        user.username, broadcast_id
+    );
+
+    notify_all_sse(&hub, SseEvent::NewBroadcast { broadcast_id });
+    webhooks.emit(WebhookEvent::BroadcastPublished { broadcast_id });
+
+    Ok(StatusCode::OK)
+}
+
+/// The public feed, newest first. Opening the first page counts as a visit
+/// for the unseen count.
+#[tracing::instrument(skip(session, pool))]
+async fn list_broadcasts_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Query(query): axum::extract::Query<PageQuery>,
+) -> Result<Json<PaginatedResponse<BroadcastResponse>>, ApiError> {
+    let limit = query.limit();
+    let cursor = query.cursor()?;
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let broadcasts =
+        crate::db::get_broadcasts(&pool, limit, cursor.map(|c| c.at), cursor.map(|c| c.id))
+            .await
+            .map_err(|e| {
+                warn!("Failed to fetch broadcasts: {}", e);
+                ApiError::Internal
+            })?;
+
+    info!("Fetched {} broadcasts", broadcasts.len());
+
+    if cursor.is_none() {
+        if let Err(e) = crate::db::mark_broadcasts_seen(&pool, user.id).await {
+            warn!("Failed to record broadcast feed visit: {}", e);
+        }
+    }
+
+    Ok(Json(PaginatedResponse::new(
+        broadcasts,
+        limit,
+        |b| (b.created_at, b.id),
+        |b| BroadcastResponse {
+            id: b.id,
+            sender_username: b.sender_username,
+            content: b.content,
+            is_anonymous: b.is_anonymous,
+            created_at: b.created_at,
+            view_count: b.view_count.unwrap_or(0),
+            edited_at: b.edited_at,
+            edit_count: b.edit_count.unwrap_or(0),
+            is_mine: b.author_id == Some(user.id),
+        },
+    )))
+}
+
+#[derive(Serialize)]
+struct UnseenBroadcastsResponse {
+    count: i64,
+}
+
+/// How many broadcasts arrived since the caller last opened the feed.
+#[tracing::instrument(skip(session, pool))]
+async fn unseen_broadcasts_count_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<UnseenBroadcastsResponse>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let count = crate::db::count_unseen_broadcasts(&pool, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to count unseen broadcasts: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(UnseenBroadcastsResponse { count }))
+}
+
+#[tracing::instrument(skip(session, pool))]
+async fn view_broadcast_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    crate::db::track_broadcast_view(&pool, broadcast_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to track view: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Edit a broadcast. Anonymous broadcasts have no provable owner and can't be edited.
+#[tracing::instrument(skip(session, pool, api_config))]
+async fn edit_broadcast_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
+    Json(req): Json<EditBroadcastRequest>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    if req.content.trim().is_empty() {
+        return Err(ApiError::Validation(
+            "Broadcast content cannot be empty".into(),
+        ));
+    }
+    check_content_length(&req.content, api_config.max_content_length)?;
+
+    let broadcast = crate::db::get_broadcast_by_id(&pool, broadcast_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load broadcast {}: {}", broadcast_id, e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("Broadcast not found".into()))?;
+
+    if broadcast.sender_id != Some(user.id) {
+        return Err(if broadcast.is_draft {
+            ApiError::NotFound("Broadcast not found".into())
+        } else if broadcast.sender_id.is_none() {
+            ApiError::Forbidden("Anonymous broadcasts can't be edited".into())
+        } else {
+            ApiError::Forbidden("Only the author can edit this broadcast".into())
+        });
+    }
+
+    crate::db::edit_broadcast(&pool, broadcast_id, user.id, &req.content)
+        .await
+        .map_err(|e| {
+            warn!("Failed to edit broadcast: {}", e);
+            ApiError::Internal
+        })?;
+
+    info!("User {} edited broadcast {}", user.username, broadcast_id);
+    Ok(StatusCode::OK)
+}
+
+/// Soft-delete one of the caller's broadcasts. Anonymous broadcasts have no
+/// recorded author, so nobody can prove ownership and they can't be deleted.
+#[tracing::instrument(skip(session, pool))]
+async fn delete_broadcast_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let broadcast = crate::db::get_broadcast_by_id(&pool, broadcast_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load broadcast {}: {}", broadcast_id, e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("Broadcast not found".into()))?;
+
+    if broadcast.sender_id != Some(user.id) {
+        return Err(if broadcast.is_draft {
+            ApiError::NotFound("Broadcast not found".into())
+        } else if broadcast.sender_id.is_none() {
+            ApiError::Forbidden("Anonymous broadcasts can't be deleted".into())
+        } else {
+            ApiError::Forbidden("Only the author can delete this broadcast".into())
+        });
+    }
+
+    let deleted = crate::db::delete_broadcast(&pool, broadcast_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to delete broadcast: {}", e);
+            ApiError::Internal
+        })?;
+    if !deleted {
+        return Err(ApiError::NotFound("Broadcast not found".into()));
+    }
+
+    info!("User {} deleted broadcast {}", user.username, broadcast_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Prior versions of a broadcast, oldest first. Author-only unless
+/// `BROADCAST_HISTORY_PUBLIC` is enabled.
+#[tracing::instrument(skip(session, pool, api_config))]
+async fn broadcast_history_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
+) -> Result<Json<Vec<BroadcastVersionResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let broadcast = crate::db::get_broadcast_by_id(&pool, broadcast_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load broadcast {}: {}", broadcast_id, e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("Broadcast not found".into()))?;
+
+    let is_author = broadcast.sender_id == Some(user.id);
+    if broadcast.is_draft && !is_author {
+        return Err(ApiError::NotFound("Broadcast not found".into()));
+    }
+    if !api_config.broadcast_history_public && !is_author {
+        return Err(ApiError::Forbidden(
+            "Only the author can view edit history".into(),
+        ));
+    }
+
+    let edits = crate::db::get_broadcast_history(&pool, broadcast_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to fetch broadcast history: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(
+        edits
+            .into_iter()
+            .map(|e| BroadcastVersionResponse {
+                content: e.old_content,
+                edited_at: e.edited_at,
+            })
+            .collect(),
+    ))
+}
+
+/// Who viewed a broadcast, most recent first. Only its author may ask;
+/// anonymous broadcasts have no author to ask.
+#[tracing::instrument(skip(session, pool))]
+async fn broadcast_viewers_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
+    axum::extract::Query(query): axum::extract::Query<PageQuery>,
+) -> Result<Json<PaginatedResponse<BroadcastViewerResponse>>, ApiError> {
+    let limit = query.limit();
+    let cursor = query.cursor()?;
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let broadcast = crate::db::get_broadcast_by_id(&pool, broadcast_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load broadcast {}: {}", broadcast_id, e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("Broadcast not found".into()))?;
+
+    if broadcast.sender_id != Some(user.id) {
+        return Err(if broadcast.is_draft {
+            ApiError::NotFound("Broadcast not found".into())
+        } else if broadcast.sender_id.is_none() {
+            ApiError::Forbidden("Anonymous broadcasts have no viewer list".into())
+        } else {
+            ApiError::Forbidden("Only the author can see who viewed this broadcast".into())
+        });
+    }
+
+    let viewers = crate::db::get_broadcast_viewers(
+        &pool,
+        broadcast_id,
+        limit,
+        cursor.map(|c| c.at),
+        cursor.map(|c| c.id),
+    )
+    .await
+    .map_err(|e| {
+        warn!("Failed to fetch broadcast viewers: {}", e);
+        ApiError::Internal
+    })?;
+
+    Ok(Json(PaginatedResponse::new(
+        viewers,
+        limit,
+        |v| (v.viewed_at, v.user_id),
+        |v| BroadcastViewerResponse {
+            user_id: v.user_id,
+            username: v.username,
+            viewed_at: v.viewed_at,
+        },
+    )))
+}
+
+// ===== Enhanced Features Handlers =====
+
+// Message Search
+#[derive(Deserialize, Debug)]
+struct SearchQuery {
+    q: String,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+fn default_limit() -> i64 {
+    50
+}
+
+#[derive(Serialize)]
+struct SearchResultResponse {
+    #[serde(flatten)]
+    message: MessageResponse,
+    /// HTML snippet: the content escaped, matched terms wrapped in `<mark>`
+    highlight: String,
+}
+
+/// Full-text search over the caller's messages, best matches first.
+#[tracing::instrument(skip(session, pool))]
+async fn search_messages_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Query(query): axum::extract::Query<SearchQuery>,
+) -> Result<Json<Vec<SearchResultResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    if query.q.trim().is_empty() {
+        return Ok(Json(vec![]));
+    }
+
+    let messages = crate::db::search_messages(&pool, user.id, &query.q, query.limit)
+        .await
+        .map_err(|e| {
+            warn!("Failed to search messages: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(
+        messages
+            .into_iter()
+            .map(|hit| {
+                let m = hit.message;
+                SearchResultResponse {
+                    message: MessageResponse {
+                        id: m.id,
+                        thread_id: m.thread_id,
+                        content: m.content,
+                        is_mine: m.sender_id == Some(user.id),
+                        created_at: m.created_at,
+                        is_read: m.is_read,
+                        edited: m.edited_at.is_some(),
+                        edited_at: m.edited_at,
+                        reactions: m.reactions,
+                        unread_count: None,
+                        to_username: None,
+                        paused: None,
+                        is_pinned: None,
+                        labels: None,
+                        archived: None,
+                        muted: None,
+                        reply_to_message_id: None,
+                        quote_range: None,
+                        reply_to_preview: None,
+                        forwarded: None,
+                        expires_at: None,
+                        editable_until: None,
+                    },
+                    highlight: hit.highlight,
+                }
+            })
+            .collect(),
+    ))
+}
+
+/// Full-text search of the public broadcast feed, best matches first.
+#[tracing::instrument(skip(session, pool))]
+async fn search_broadcasts_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Query(query): axum::extract::Query<SearchQuery>,
+) -> Result<Json<Vec<BroadcastResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    if query.q.trim().is_empty() {
+        return Ok(Json(vec![]));
+    }
+
+    let broadcasts = crate::db::search_broadcasts(&pool, &query.q, query.limit)
+        .await
+        .map_err(|e| {
+            warn!("Failed to search broadcasts: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(
+        broadcasts
+            .into_iter()
+            .map(|b| BroadcastResponse {
+                id: b.id,
+                sender_username: b.sender_username,
+                content: b.content,
+                is_anonymous: b.is_anonymous,
+                created_at: b.created_at,
+                view_count: b.view_count.unwrap_or(0),
+                edited_at: b.edited_at,
+                edit_count: b.edit_count.unwrap_or(0),
+                is_mine: b.author_id == Some(user.id),
+            })
+            .collect(),
+    ))
+}
+
+/// Full-text search scoped to one conversation the caller takes part in.
+#[tracing::instrument(skip(session, pool))]
+async fn search_thread_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+    axum::extract::Query(query): axum::extract::Query<SearchQuery>,
+) -> Result<Json<Vec<MessageResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let is_participant = crate::db::is_thread_participant(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to check thread participation: {}", e);
+            ApiError::Internal
+        })?;
+    if !is_participant {
+        return Err(ApiError::Forbidden(
+            "You are not part of this conversation".into(),
+        ));
+    }
+
+    if query.q.trim().is_empty() {
+        return Ok(Json(vec![]));
+    }
+
+    let messages =
+        crate::db::search_thread_messages(&pool, thread_id, user.id, &query.q, query.limit)
+            .await
+            .map_err(|e| {
+                warn!("Failed to search thread {}: {}", thread_id, e);
+                ApiError::Internal
+            })?;
+
+    Ok(Json(
+        messages
+            .into_iter()
+            .map(|m| MessageResponse {
+                id: m.id,
+                thread_id: m.thread_id,
+                content: m.content,
+                is_mine: m.sender_id == Some(user.id),
+                created_at: m.created_at,
+                is_read: m.is_read,
+                edited: m.edited_at.is_some(),
+                edited_at: m.edited_at,
+                reactions: m.reactions,
+                unread_count: None,
+                to_username: None,
+                paused: None,
+                is_pinned: None,
+                labels: None,
+                archived: None,
+                muted: None,
+                reply_to_message_id: None,
+                quote_range: None,
+                reply_to_preview: None,
+                forwarded: None,
+                expires_at: None,
+                editable_until: None,
+            })
+            .collect(),
+    ))
+}
+
+// Message Deletion
+#[tracing::instrument(skip(session, pool))]
+async fn delete_message_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let deleted = crate::db::delete_message(&pool, message_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to delete message: {}", e);
+            ApiError::Internal
+        })?;
+    if deleted == 0 {
+        return Err(ApiError::NotFound("Message not found".into()));
+    }
+
+    info!("User {} deleted message {}", user.username, message_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Take back a message just sent: within the unsend window its sender
+/// deletes it for good and the recipient's client is told to drop it.
+/// Afterwards, or once it's reported, this is the same soft delete as
+/// `/messages/{id}/delete`.
+#[tracing::instrument(skip(session, pool, api_config, hub))]
+async fn unsend_message_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    State(hub): State<NotificationHub>,
+    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let unsent = crate::db::unsend_message(&pool, message_id, user.id, api_config.unsend_window)
+        .await
+        .map_err(|e| {
+            warn!("Failed to unsend message {}: {}", message_id, e);
+            ApiError::Internal
+        })?;
+
+    let Some((thread_id, recipient_id)) = unsent else {
+        let deleted = crate::db::delete_message(&pool, message_id, user.id)
+            .await
+            .map_err(|e| {
+                warn!("Failed to delete message: {}", e);
+                ApiError::Internal
+            })?;
+        if deleted == 0 {
+            return Err(ApiError::NotFound("Message not found".into()));
+        }
+        info!("User {} deleted message {}", user.username, message_id);
+        return Ok(Json(serde_json::json!({ "unsent": false })));
+    };
+
+    info!("User {} unsent message {}", user.username, message_id);
+    notify_user_sse(
+        &hub,
+        recipient_id,
+        SseEvent::MessageUnsent {
+            thread_id,
+            message_id,
+        },
+    );
+    Ok(Json(serde_json::json!({ "unsent": true })))
+}
+
+// Thread Deletion
+#[tracing::instrument(skip(session, pool))]
+async fn delete_thread_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+    axum::extract::Query(query): axum::extract::Query<DryRunQuery>,
+) -> Result<Response, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    if query.dry_run {
+        let messages = crate::db::count_deletable_thread_messages(&pool, thread_id, user.id)
+            .await
+            .map_err(|e| {
+                warn!("Failed to count messages in thread {}: {}", thread_id, e);
+                ApiError::Internal
+            })?;
+        return Ok(
+            Json(serde_json::json!({ "dry_run": true, "messages": messages })).into_response(),
+        );
+    }
+
+    crate::db::delete_thread(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to delete thread: {}", e);
+            ApiError::Internal
+        })?;
+
+    info!("User {} deleted thread {}", user.username, thread_id);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+// Message Editing
+#[derive(Deserialize, Debug)]
+struct EditMessageRequest {
+    content: String,
+}
+
+#[tracing::instrument(skip(session, pool, api_config))]
+async fn edit_message_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
+    Json(req): Json<EditMessageRequest>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    if req.content.trim().is_empty() {
+        return Err(ApiError::Validation(
+            "Message content cannot be empty".into(),
+        ));
+    }
+    check_content_length(&req.content, api_config.max_content_length)?;
+
+    let outcome = crate::db::edit_message(
+        &pool,
+        message_id,
+        user.id,
+        &req.content,
+        api_config.edit_window,
+    )
+    .await
+    .map_err(|e| {
+        warn!("Failed to edit message: {}", e);
+        ApiError::Internal
+    })?;
+    match outcome {
+        crate::db::EditOutcome::Edited => {}
+        crate::db::EditOutcome::NotFound => {
+            return Err(ApiError::NotFound("Message not found".into()))
+        }
+        crate::db::EditOutcome::WindowClosed => {
+            return Err(ApiError::Forbidden(
+                "This message can no longer be edited".into(),
+            ))
+        }
+    }
+
+    info!("User {} edited message {}", user.username, message_id);
+    Ok(StatusCode::OK)
+}
+
+// Pin/Unpin Message
+#[tracing::instrument(skip(session, pool, hub))]
+async fn toggle_pin_message_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(hub): State<NotificationHub>,
+    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let msg = crate::db::get_message_by_id(&*pool, message_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to fetch message {}: {}", message_id, e);
+            ApiError::Internal
+        })?
+        .filter(|m| m.recipient_id == user.id || m.sender_id == Some(user.id))
+        .ok_or_else(|| ApiError::NotFound("Message not found".into()))?;
+
+    let is_pinned = crate::db::toggle_pin_message(&pool, message_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to toggle pin message: {}", e);
+            ApiError::Internal
+        })?;
+
+    // Pins are shared, so the other participant's thread view changes too,
+    // unless they muted the thread
+    let other = if msg.sender_id == Some(user.id) {
+        Some(msg.recipient_id)
+    } else {
+        msg.sender_id
+    };
+    if let Some(other) = other.filter(|&id| id != user.id) {
+        let muted = crate::db::is_thread_muted(&pool, msg.thread_id, other)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Failed to check whether thread {} is muted: {}",
+                    msg.thread_id, e
+                );
+                false
+            });
+        if !muted {
+            notify_user_sse(
+                &hub,
+                other,
+                SseEvent::MessagePinned {
+                    thread_id: msg.thread_id,
+                    message_id,
+                    pinned: is_pinned,
+                },
+            );
+        }
+    }
+
+    Ok(Json(serde_json::json!({ "pinned": is_pinned })))
+}
+
+// Pin/Unpin Thread
+#[tracing::instrument(skip(session, pool))]
+async fn toggle_pin_thread_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let is_pinned = crate::db::toggle_pin_thread(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to toggle pin thread: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(serde_json::json!({ "pinned": is_pinned })))
+}
+
+/// Most distinct labels one user may have
+const MAX_LABELS: usize = 50;
+const MAX_LABEL_LENGTH: usize = 32;
+
+#[derive(Deserialize, Debug)]
+struct LabelRequest {
+    label: String,
+}
+
+#[derive(Serialize)]
+struct ThreadLabelsResponse {
+    labels: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct LabelResponse {
+    label: String,
+    thread_count: i64,
+}
+
+/// 404 unless the user takes part in the thread.
+async fn ensure_thread_participant(
+    pool: &PgPool,
+    thread_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), ApiError> {
+    crate::db::get_thread_counterpart(pool, thread_id, user_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load participants of thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?
+        .map(|_| ())
+        .ok_or_else(|| ApiError::NotFound("Conversation not found".into()))
+}
+
+/// The user's labels with how many threads carry each.
+#[tracing::instrument(skip(session, pool))]
+async fn list_labels_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<Vec<LabelResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let labels = crate::db::get_user_labels(&pool, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to list labels: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(
+        labels
+            .into_iter()
+            .map(|l| LabelResponse {
+                label: l.label,
+                thread_count: l.thread_count,
+            })
+            .collect(),
+    ))
+}
+
+/// Label a thread. Labels are private to the user; a new label counts
+/// towards `MAX_LABELS` until no thread carries it.
+#[tracing::instrument(skip(session, pool))]
+async fn add_thread_label_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+    Json(req): Json<LabelRequest>,
+) -> Result<Json<ThreadLabelsResponse>, ApiError> {
+    let label = req.label.trim();
+    if label.is_empty() {
+        return Err(ApiError::Validation("Label cannot be empty".into()));
+    }
+    if label.chars().count() > MAX_LABEL_LENGTH {
+        return Err(ApiError::Validation(format!(
+            "Labels can be at most {} characters",
+            MAX_LABEL_LENGTH
+        )));
+    }
+
+    let user = resolve_user(&mut session, &pool).await?;
+    ensure_thread_participant(&pool, thread_id, user.id).await?;
+
+    let existing = crate::db::get_user_labels(&pool, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to list labels: {}", e);
+            ApiError::Internal
+        })?;
+    if existing.len() >= MAX_LABELS && !existing.iter().any(|l| l.label == label) {
+        return Err(ApiError::Validation(format!(
+            "You can have at most {} labels",
+            MAX_LABELS
+        )));
+    }
+
+    crate::db::add_thread_label(&pool, user.id, thread_id, label)
+        .await
+        .map_err(|e| {
+            warn!("Failed to label thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+
+    thread_labels_response(&pool, user.id, thread_id).await
+}
+
+/// Take a label off a thread.
+#[tracing::instrument(skip(session, pool))]
+async fn remove_thread_label_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path((thread_id, label)): axum::extract::Path<(Uuid, String)>,
+) -> Result<Json<ThreadLabelsResponse>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let removed = crate::db::remove_thread_label(&pool, user.id, thread_id, label.trim())
+        .await
+        .map_err(|e| {
+            warn!("Failed to unlabel thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+    if !removed {
+        return Err(ApiError::NotFound(
+            "Label not found on this conversation".into(),
+        ));
+    }
+
+    thread_labels_response(&pool, user.id, thread_id).await
+}
+
+async fn thread_labels_response(
+    pool: &PgPool,
+    user_id: Uuid,
+    thread_id: Uuid,
+) -> Result<Json<ThreadLabelsResponse>, ApiError> {
+    let labels = crate::db::get_thread_labels(pool, user_id, thread_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load labels of thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+    Ok(Json(ThreadLabelsResponse { labels }))
+}
+
+/// Pause a thread: the other participant can no longer reply until it is resumed.
+#[tracing::instrument(skip(session, pool))]
+async fn pause_thread_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let is_participant = crate::db::is_thread_participant(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to check thread participation: {}", e);
+            ApiError::Internal
+        })?;
+    if !is_participant {
+        return Err(ApiError::Forbidden(
+            "You are not part of this conversation".into(),
+        ));
+    }
+
+    crate::db::pause_thread(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to pause thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+
+    info!("User {} paused thread {}", user.id, thread_id);
+
+    Ok(Json(serde_json::json!({ "paused": true })))
+}
+
+/// Lift the caller's pause on a thread.
+#[tracing::instrument(skip(session, pool))]
+async fn resume_thread_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    crate::db::resume_thread(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to resume thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+
+    // The other participant may still hold their own pause
+    let paused = crate::db::is_thread_paused(&pool, thread_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to check pause state of thread {}: {}", thread_id, e);
+            ApiError::Internal
+        })?;
+
+    info!("User {} resumed thread {}", user.id, thread_id);
+
+    Ok(Json(serde_json::json!({ "paused": paused })))
+}
+
+#[derive(Serialize)]
+struct TypingResponse {
+    /// Until the indicator lapses; sending again before then is a no-op
+    /// unless at most half the window is left
+    expires_in_ms: u64,
+}
+
+// Typing Indicator
+#[tracing::instrument(skip(session, pool, hub))]
+async fn typing_indicator_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(hub): State<NotificationHub>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+) -> Result<Json<TypingResponse>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let (started_at, refreshed) = crate::db::set_typing_indicator(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to set typing indicator: {}", e);
+            ApiError::Internal
+        })?;
+
+    let elapsed = OffsetDateTime::now_utc() - started_at;
+    let expires_in =
+        crate::db::TYPING_INDICATOR_WINDOW.saturating_sub(elapsed.try_into().unwrap_or_default());
+    let response = Json(TypingResponse {
+        expires_in_ms: expires_in.as_millis() as u64,
+    });
+
+    // Debounced: the other participant already knows
+    if !refreshed {
+        return Ok(response);
+    }
+
+    // Notify other participants via SSE
+    // Get other participant from the thread and notify them
+    let other_user_id = crate::db::get_other_participant(&pool, thread_id, user.id).await;
+
+    if let Ok(Some(other_user_id)) = other_user_id {
+        notify_user_sse(
+            &hub,
+            other_user_id,
+            SseEvent::Typing {
+                thread_id,
+                user_id: user.id,
+                username: user.username,
+            },
+        );
+    }
+
+    Ok(response)
+}
+
+/// The caller stopped typing before their indicator lapsed: clear it and send
+/// `typing_stopped` to the other participant. A no-op if there was none.
+#[tracing::instrument(skip(session, pool, hub))]
+async fn stop_typing_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(hub): State<NotificationHub>,
+    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let cleared = crate::db::clear_typing_indicator(&pool, thread_id, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to clear typing indicator: {}", e);
+            ApiError::Internal
+        })?;
+    if !cleared {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    let other_user_id = crate::db::get_other_participant(&pool, thread_id, user.id).await;
+    if let Ok(Some(other_user_id)) = other_user_id {
+        notify_user_sse(
+            &hub,
+            other_user_id,
+            SseEvent::TypingStopped {
+                thread_id,
+                user_id: user.id,
+            },
+        );
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Drop stale typing indicators and tell the other participant in each
+/// thread that typing stopped. Run periodically as the `typing_cleanup` job.
+pub async fn expire_typing_indicators(
+    pool: &PgPool,
+    hub: &NotificationHub,
+) -> Result<(), sqlx::Error> {
+    let removed = crate::db::cleanup_typing_indicators(pool).await?;
+    debug!("Cleaned up {} stale typing indicators", removed.len());
+
+    for (thread_id, user_id) in removed {
+        let other_user_id = match crate::db::get_other_participant(pool, thread_id, user_id).await {
+            Ok(Some(other_user_id)) => other_user_id,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Failed to find other participant: {}", e);
+                continue;
+            }
+        };
+
+        notify_user_sse(
+            hub,
+            other_user_id,
+            SseEvent::TypingStopped { thread_id, user_id },
+        );
+    }
+    Ok(())
+}
+
+/// Delete every message past its `expires_at` and send `message_expired` to
+/// both participants, so open threads drop it. Runs as a background job and
+/// after an ephemeral message's read countdown.
+pub async fn expire_messages(pool: &PgPool, hub: &NotificationHub) -> Result<(), sqlx::Error> {
+    loop {
+        let expired = crate::db::delete_expired_messages(pool, EXPIRED_MESSAGE_BATCH).await?;
+        if !expired.is_empty() {
+            debug!("Deleted {} expired messages", expired.len());
+        }
+        for m in &expired {
+            let event = SseEvent::MessageExpired {
+                thread_id: m.thread_id,
+                message_id: m.id,
+            };
+            if let Some(sender_id) = m.sender_id {
+                notify_user_sse(hub, sender_id, event.clone());
+            }
+            notify_user_sse(hub, m.recipient_id, event);
+        }
+        if (expired.len() as i64) < EXPIRED_MESSAGE_BATCH {
+            return Ok(());
+        }
+    }
+}
+
+// User Blocking
+#[tracing::instrument(skip(session, pool))]
+async fn block_user_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(blocked_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    if user.id == blocked_id {
+        return Err(ApiError::Validation("You cannot block yourself".into()));
+    }
+
+    crate::db::block_user(&pool, user.id, blocked_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to block user: {}", e);
+            ApiError::Internal
+        })?;
+
+    info!("User {} blocked user {}", user.username, blocked_id);
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(skip(session, pool))]
+async fn unblock_user_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    axum::extract::Path(blocked_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    crate::db::unblock_user(&pool, user.id, blocked_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to unblock user: {}", e);
+            ApiError::Internal
+        })?;
+
+    info!("User {} unblocked user {}", user.username, blocked_id);
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(skip(session, pool))]
+async fn get_blocked_users_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<Vec<Uuid>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let blocked_ids = crate::db::get_blocked_users(&pool, user.id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to get blocked users: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(blocked_ids))
+}
+
+#[derive(Serialize)]
+struct ContactResponse {
+    id: Uuid,
+    username: String,
+    avatar_url: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    last_interaction_at: OffsetDateTime,
+}
+
+/// Recipients of conversations the caller started, most recent first.
+#[tracing::instrument(skip(session, pool))]
+async fn list_contacts_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<Vec<ContactResponse>>, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    let contacts = crate::db::get_contacts(&pool, user.id).await.map_err(|e| {
+        warn!("Failed to get contacts for user {}: {}", user.id, e);
+        ApiError::Internal
+    })?;
+
+    Ok(Json(
+        contacts
+            .into_iter()
+            .map(|c| ContactResponse {
+                id: c.id,
+                username: c.username,
+                avatar_url: c.avatar_url,
+                last_interaction_at: c.last_interaction_at,
+            })
+            .collect(),
+    ))
+}
+
+// Broadcast Comments
+#[derive(Deserialize, Debug)]
+struct CreateCommentRequest {
+    content: String,
+    parent_comment_id: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+struct CommentResponse {
+    id: Uuid,
+    broadcast_id: Uuid,
+    /// null on "[deleted]" placeholders, like `username`
+    user_id: Option<Uuid>,
+    username: Option<String>,
+    content: String,
+    parent_comment_id: Option<Uuid>,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    reactions: crate::db::ReactionCounts,
+}
+
+#[tracing::instrument(skip(session, pool, api_config, hub))]
+async fn create_broadcast_comment_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    State(hub): State<NotificationHub>,
+    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
+    Json(req): Json<CreateCommentRequest>,
+) -> Result<StatusCode, ApiError> {
+    let user = resolve_user(&mut session, &pool).await?;
+
+    if req.content.trim().is_empty() {
+        return Err(ApiError::Validation(
+            "Comment content cannot be empty".into(),
+        ));
+    }
+    check_content_length(&req.content, api_config.max_content_length)?;
+
+    let comment_id = crate::db::create_broadcast_comment(
+        &pool,
+        broadcast_id,
+        user.id,
+        &req.content,
+        req.parent_comment_id,
+    )
+    .await
+    .map_err(|e| {
+        warn!("Failed to create comment: {}", e);
+        ApiError::Internal
+    })?;
 
     info!(
-        "Broadcast {} created (anonymous: {})",
-        broadcast_id, req.is_anonymous
+        "User {} commented on broadcast {}",
+        user.username, broadcast_id
     );
 
-    // Push SSE event to ALL connected users so their broadcasts page updates
-    let payload = serde_json::json!({
-        "broadcast_id": broadcast_id,
-    })
-    .to_string();
+    // Notify all users via SSE
     notify_all_sse(
         &hub,
-        SseEvent {
-            event_type: "new_broadcast".to_string(),
-            data: payload,
+        SseEvent::NewComment {
+            broadcast_id,
+            comment_id,
         },
-    )
-    .await;
+    );
 
     Ok(StatusCode::CREATED)
 }
 
-#[tracing::instrument(skip(_session, pool))]
-async fn list_broadcasts_handler(
-    _session: AuthSession,
+#[tracing::instrument(skip(session, pool))]
+async fn get_broadcast_comments_handler(
+    mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-) -> Result<Json<Vec<BroadcastResponse>>, StatusCode> {
-    let broadcasts = crate::db::get_broadcasts(&pool, 50).await.map_err(|e| {
-        warn!("Failed to fetch broadcasts: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
+) -> Result<Json<Vec<CommentResponse>>, ApiError> {
+    let _user = resolve_user(&mut session, &pool).await?;
 
-    info!("Fetched {} broadcasts", broadcasts.len());
+    let comments = crate::db::get_broadcast_comments(&pool, broadcast_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to get comments: {}", e);
+            ApiError::Internal
+        })?;
 
     Ok(Json(
-        broadcasts
+        comments
             .into_iter()
-            .map(|b| BroadcastResponse {
-                id: b.id,
-                sender_username: b.sender_username,
-                content: b.content,
-                is_anonymous: b.is_anonymous,
-                created_at: b.created_at,
-                view_count: b.view_count.unwrap_or(0),
+            .map(|c| CommentResponse {
+                id: c.id,
+                broadcast_id: c.broadcast_id,
+                user_id: c.user_id,
+                username: c.username,
+                content: c.content,
+                parent_comment_id: c.parent_comment_id,
+                created_at: c.created_at,
+                reactions: c.reactions,
             })
             .collect(),
     ))
 }
 
+#[derive(Serialize)]
+struct CommentTreeResponse {
+    #[serde(flatten)]
+    comment: CommentResponse,
+    replies: Vec<CommentTreeResponse>,
+}
+
+/// Nests a broadcast's comments under their parents, keeping creation order.
+/// Replies whose parent isn't in the listing are promoted to the top level.
+fn build_comment_tree(comments: Vec<crate::db::BroadcastComment>) -> Vec<CommentTreeResponse> {
+    let ids: std::collections::HashSet<Uuid> = comments.iter().map(|c| c.id).collect();
+    let mut children: HashMap<Option<Uuid>, Vec<CommentResponse>> = HashMap::new();
+    for c in comments {
+        let parent = c.parent_comment_id.filter(|p| ids.contains(p));
+        children.entry(parent).or_default().push(CommentResponse {
+            id: c.id,
+            broadcast_id: c.broadcast_id,
+            user_id: c.user_id,
+            username: c.username,
+            content: c.content,
+            parent_comment_id: c.parent_comment_id,
+            created_at: c.created_at,
+            reactions: c.reactions,
+        });
+    }
+
+    fn attach(
+        comment: CommentResponse,
+        children: &mut HashMap<Option<Uuid>, Vec<CommentResponse>>,
+    ) -> CommentTreeResponse {
+        let replies = children
+            .remove(&Some(comment.id))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| attach(c, children))
+            .collect();
+        CommentTreeResponse { comment, replies }
+    }
+
+    children
+        .remove(&None)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| attach(c, &mut children))
+        .collect()
+}
+
+#[derive(Serialize)]
+struct DailyViewsResponse {
+    day: String,
+    views: i64,
+}
+
+#[derive(Serialize)]
+struct ReactionTotalResponse {
+    emoji: String,
+    count: i64,
+}
+
+/// Extra export detail only the broadcast's author gets.
+#[derive(Serialize)]
+struct BroadcastExportAuthorView {
+    view_timeline: Vec<DailyViewsResponse>,
+    comment_reactions: Vec<ReactionTotalResponse>,
+}
+
+#[derive(Serialize)]
+struct BroadcastExportHeader {
+    broadcast: BroadcastResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<BroadcastExportAuthorView>,
+}
+
+/// Top-level comment threads loaded per query while exporting a broadcast.
+const EXPORT_THREADS_PER_PAGE: i64 = 50;
+
+/// Downloads a broadcast with its whole comment tree as one JSON document.
+/// Comments are read a page of top-level threads at a time while the body
+/// is written, so a large discussion is never held in memory at once. A
+/// database error part-way ends the download early, leaving invalid JSON.
 #[tracing::instrument(skip(session, pool))]
-async fn view_broadcast_handler(
+async fn export_broadcast_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
     axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<Response, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    crate::db::track_broadcast_view(&pool, broadcast_id, user.id)
+    let broadcast = crate::db::get_broadcast_by_id(&pool, broadcast_id)
         .await
         .map_err(|e| {
-            warn!("Failed to track view: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+            warn!("Failed to load broadcast {}: {}", broadcast_id, e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("Broadcast not found".into()))?;
 
-    Ok(StatusCode::OK)
-}
+    let is_author = broadcast.sender_id == Some(user.id);
+    if broadcast.is_draft && !is_author {
+        return Err(ApiError::NotFound("Broadcast not found".into()));
+    }
 
-// ===== Enhanced Features Handlers =====
+    let author = if is_author {
+        let view_timeline = crate::db::get_broadcast_view_timeline(&pool, broadcast_id)
+            .await
+            .map_err(|e| {
+                warn!("Failed to get view timeline: {}", e);
+                ApiError::Internal
+            })?;
+        let comment_reactions =
+            crate::db::get_broadcast_comment_reaction_totals(&pool, broadcast_id)
+                .await
+                .map_err(|e| {
+                    warn!("Failed to get comment reaction totals: {}", e);
+                    ApiError::Internal
+                })?;
+        Some(BroadcastExportAuthorView {
+            view_timeline: view_timeline
+                .into_iter()
+                .map(|d| DailyViewsResponse {
+                    day: d.day,
+                    views: d.views,
+                })
+                .collect(),
+            comment_reactions: comment_reactions
+                .into_iter()
+                .map(|(emoji, count)| ReactionTotalResponse { emoji, count })
+                .collect(),
+        })
+    } else {
+        None
+    };
 
-// Message Search
-#[derive(Deserialize, Debug)]
-struct SearchQuery {
-    q: String,
-    #[serde(default = "default_limit")]
-    limit: i64,
+    let export_header = BroadcastExportHeader {
+        broadcast: BroadcastResponse {
+            id: broadcast.id,
+            sender_username: broadcast.sender_username,
+            content: broadcast.content,
+            is_anonymous: broadcast.is_anonymous,
+            created_at: broadcast.created_at,
+            view_count: broadcast.view_count.unwrap_or(0),
+            edited_at: broadcast.edited_at,
+            edit_count: broadcast.edit_count.unwrap_or(0),
+            is_mine: broadcast.author_id == Some(user.id),
+        },
+        author,
+    };
+    let mut head = serde_json::to_string(&export_header).map_err(|e| {
+        warn!("Failed to serialize broadcast export: {}", e);
+        ApiError::Internal
+    })?;
+    // Reopen the header object so the comments can follow as its last field
+    head.pop();
+    head.push_str(",\"comments\":[");
+
+    // (next page's cursor, whether a thread was written yet), or None when done
+    let pages = stream::unfold(Some((None, false)), move |state| {
+        let pool = pool.clone();
+        async move {
+            let (after, mut wrote_any) = state?;
+            let page = crate::db::get_broadcast_comment_threads(
+                &pool,
+                broadcast_id,
+                EXPORT_THREADS_PER_PAGE,
+                after.map(|(at, _)| at),
+                after.map(|(_, id)| id),
+            )
+            .await;
+            let (last, comments) = match page {
+                Ok((Some(last), comments)) => (last, comments),
+                Ok((None, _)) => return None,
+                Err(e) => {
+                    warn!("Failed to get comments for export: {}", e);
+                    return Some((Err(e.into()), None));
+                }
+            };
+
+            let mut chunk = String::new();
+            for thread in build_comment_tree(comments) {
+                if wrote_any {
+                    chunk.push(',');
+                }
+                wrote_any = true;
+                match serde_json::to_string(&thread) {
+                    Ok(json) => chunk.push_str(&json),
+                    Err(e) => return Some((Err(e.into()), None)),
+                }
+            }
+            Some((Ok(chunk), Some((Some(last), wrote_any))))
+        }
+    });
+    let body = stream::once(async move { Ok::<_, axum::BoxError>(head) })
+        .chain(pages)
+        .chain(stream::once(async { Ok("]}".to_string()) }));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"broadcast-{}.json\"", broadcast_id),
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response())
 }
 
-fn default_limit() -> i64 {
-    50
+#[derive(Serialize)]
+struct ReactionTallyResponse {
+    emoji: String,
+    count: i64,
+    /// The caller gave this reaction on at least one comment
+    mine: bool,
+}
+
+#[derive(Serialize)]
+struct ReactionLeaderboardResponse {
+    total: i64,
+    reactions: Vec<ReactionTallyResponse>,
 }
 
+/// Emoji used across a broadcast's comments, most popular first.
 #[tracing::instrument(skip(session, pool))]
-async fn search_messages_handler(
+async fn broadcast_reaction_leaderboard_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    axum::extract::Query(query): axum::extract::Query<SearchQuery>,
-) -> Result<Json<Vec<MessageResponse>>, StatusCode> {
+    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
+) -> Result<Json<ReactionLeaderboardResponse>, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    if query.q.trim().is_empty() {
-        return Ok(Json(vec![]));
+    let broadcast = crate::db::get_broadcast_by_id(&pool, broadcast_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load broadcast {}: {}", broadcast_id, e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("Broadcast not found".into()))?;
+    if broadcast.is_draft && broadcast.author_id != Some(user.id) {
+        return Err(ApiError::NotFound("Broadcast not found".into()));
     }
 
-    let messages = crate::db::search_messages(&pool, user.id, &query.q, query.limit)
+    let tallies = crate::db::get_broadcast_reaction_leaderboard(&pool, broadcast_id, user.id)
         .await
         .map_err(|e| {
-            warn!("Failed to search messages: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!(
+                "Failed to rank reactions for broadcast {}: {}",
+                broadcast_id, e
+            );
+            ApiError::Internal
         })?;
 
-    Ok(Json(
-        messages
+    Ok(Json(ReactionLeaderboardResponse {
+        total: tallies.iter().map(|t| t.count).sum(),
+        reactions: tallies
             .into_iter()
-            .map(|m| MessageResponse {
-                id: m.id,
-                thread_id: m.thread_id,
-                content: m.content,
-                is_mine: m.sender_id == Some(user.id),
-                created_at: m.created_at,
-                is_read: m.is_read,
-                reactions: m.reactions,
-                unread_count: None,
-                to_username: None,
+            .map(|t| ReactionTallyResponse {
+                emoji: t.emoji,
+                count: t.count,
+                mine: t.mine,
             })
             .collect(),
-    ))
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+struct ReactToCommentRequest {
+    emoji: String,
 }
 
-// Message Deletion
 #[tracing::instrument(skip(session, pool))]
-async fn delete_message_handler(
+async fn react_to_comment_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
+    axum::extract::Path(comment_id): axum::extract::Path<Uuid>,
+    Json(req): Json<ReactToCommentRequest>,
+) -> Result<Json<ReactionToggleResponse>, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    crate::db::delete_message(&pool, message_id, user.id)
+    let current = crate::db::get_user_comment_reaction(&pool, comment_id, user.id)
         .await
         .map_err(|e| {
-            warn!("Failed to delete message: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to look up comment reaction: {}", e);
+            ApiError::Internal
         })?;
 
-    info!("User {} deleted message {}", user.username, message_id);
-    Ok(StatusCode::NO_CONTENT)
+    // Reacting again with the same emoji takes the reaction back
+    if current.as_deref() == Some(req.emoji.as_str()) {
+        crate::db::remove_comment_reaction(&pool, comment_id, user.id)
+            .await
+            .map_err(|e| {
+                warn!("Failed to remove comment reaction: {}", e);
+                ApiError::Internal
+            })?;
+        return Ok(Json(ReactionToggleResponse { reacted: false }));
+    }
+
+    crate::db::react_to_comment(&pool, comment_id, user.id, &req.emoji)
+        .await
+        .map_err(|e| {
+            warn!("Failed to react to comment: {}", e);
+            ApiError::Internal
+        })?;
+
+    Ok(Json(ReactionToggleResponse { reacted: true }))
 }
 
-// Thread Deletion
 #[tracing::instrument(skip(session, pool))]
-async fn delete_thread_handler(
+async fn delete_comment_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
+    axum::extract::Path(comment_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    crate::db::delete_thread(&pool, thread_id, user.id)
+    crate::db::delete_broadcast_comment(&pool, comment_id, user.id)
         .await
         .map_err(|e| {
-            warn!("Failed to delete thread: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to delete comment: {}", e);
+            ApiError::Internal
         })?;
 
-    info!("User {} deleted thread {}", user.username, thread_id);
     Ok(StatusCode::NO_CONTENT)
 }
 
-// Message Editing
-#[derive(Deserialize, Debug)]
-struct EditMessageRequest {
-    content: String,
-}
-
+/// Delete all of the caller's comments on one broadcast in a single statement.
 #[tracing::instrument(skip(session, pool))]
-async fn edit_message_handler(
+async fn delete_my_comments_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
-    Json(req): Json<EditMessageRequest>,
-) -> Result<StatusCode, StatusCode> {
+    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
+    axum::extract::Query(query): axum::extract::Query<DryRunQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    if req.content.trim().is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+    if query.dry_run {
+        let comments = crate::db::count_user_broadcast_comments(&pool, broadcast_id, user.id)
+            .await
+            .map_err(|e| {
+                warn!("Failed to count comments: {}", e);
+                ApiError::Internal
+            })?;
+        return Ok(Json(
+            serde_json::json!({ "dry_run": true, "deleted": comments }),
+        ));
     }
 
-    crate::db::edit_message(&pool, message_id, user.id, &req.content)
+    let deleted = crate::db::delete_user_broadcast_comments(&pool, broadcast_id, user.id)
         .await
         .map_err(|e| {
-            warn!("Failed to edit message: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to delete comments: {}", e);
+            ApiError::Internal
         })?;
 
-    info!("User {} edited message {}", user.username, message_id);
-    Ok(StatusCode::OK)
+    info!(
+        "User {} deleted {} comments on broadcast {}",
+        user.username, deleted, broadcast_id
+    );
+    Ok(Json(serde_json::json!({ "deleted": deleted })))
+}
+
+// User Preferences
+#[derive(Serialize, Debug, Clone)]
+pub struct PreferencesResponse {
+    theme: String,
+    notification_sound: bool,
+    browser_notifications: bool,
+    show_read_receipts: bool,
+    show_typing_indicators: bool,
+    /// Whether strangers can look the user up by username
+    public_profile: bool,
+    /// Whether others can see when the user is online
+    share_presence: bool,
+    /// Send back as `expected_version` to detect concurrent updates
+    version: i64,
+}
+
+impl From<crate::db::UserPreferences> for PreferencesResponse {
+    fn from(prefs: crate::db::UserPreferences) -> Self {
+        Self {
+            theme: prefs.theme,
+            notification_sound: prefs.notification_sound,
+            browser_notifications: prefs.browser_notifications,
+            show_read_receipts: prefs.show_read_receipts,
+            show_typing_indicators: prefs.show_typing_indicators,
+            public_profile: prefs.public_profile,
+            share_presence: prefs.share_presence,
+            version: prefs.version,
+        }
+    }
 }
 
-// Pin/Unpin Message
 #[tracing::instrument(skip(session, pool))]
-async fn toggle_pin_message_handler(
+async fn get_preferences_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Json<PreferencesResponse>, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    let is_pinned = crate::db::toggle_pin_message(&pool, message_id, user.id)
+    let prefs = crate::db::get_user_preferences(&pool, user.id)
         .await
         .map_err(|e| {
-            warn!("Failed to toggle pin message: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to get preferences: {}", e);
+            ApiError::Internal
         })?;
 
-    Ok(Json(serde_json::json!({ "pinned": is_pinned })))
+    let prefs = prefs.unwrap_or(crate::db::UserPreferences {
+        user_id: user.id,
+        theme: "dark".to_string(),
+        notification_sound: true,
+        browser_notifications: true,
+        show_read_receipts: true,
+        show_typing_indicators: true,
+        public_profile: true,
+        share_presence: true,
+        version: 0,
+    });
+
+    Ok(Json(prefs.into()))
 }
 
-// Pin/Unpin Thread
-#[tracing::instrument(skip(session, pool))]
-async fn toggle_pin_thread_handler(
+#[derive(Deserialize, Debug)]
+struct UpdatePreferencesRequest {
+    theme: Option<String>,
+    notification_sound: Option<bool>,
+    browser_notifications: Option<bool>,
+    show_read_receipts: Option<bool>,
+    show_typing_indicators: Option<bool>,
+    public_profile: Option<bool>,
+    share_presence: Option<bool>,
+    /// The `version` the client last read; omit to update unconditionally
+    expected_version: Option<i64>,
+}
+
+/// Partial preferences update: unset fields keep their stored values.
+/// With `expected_version`, returns 409 if another update landed first.
+/// The result is pushed to all of the user's streams so other devices sync.
+#[tracing::instrument(skip(session, pool, hub))]
+async fn update_preferences_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+    State(hub): State<NotificationHub>,
+    Json(req): Json<UpdatePreferencesRequest>,
+) -> Result<Json<PreferencesResponse>, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    let is_pinned = crate::db::toggle_pin_thread(&pool, thread_id, user.id)
+    let update = crate::db::PreferencesUpdate {
+        theme: req.theme,
+        notification_sound: req.notification_sound,
+        browser_notifications: req.browser_notifications,
+        show_read_receipts: req.show_read_receipts,
+        show_typing_indicators: req.show_typing_indicators,
+        public_profile: req.public_profile,
+        share_presence: req.share_presence,
+    };
+    let prefs = crate::db::upsert_user_preferences(&pool, user.id, &update, req.expected_version)
         .await
         .map_err(|e| {
-            warn!("Failed to toggle pin thread: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to update preferences: {}", e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| {
+            ApiError::Conflict("Preferences changed since they were last read".into())
         })?;
 
-    Ok(Json(serde_json::json!({ "pinned": is_pinned })))
+    info!("User {} updated preferences", user.username);
+    let prefs = PreferencesResponse::from(prefs);
+    notify_user_sse(&hub, user.id, SseEvent::PreferencesUpdated(prefs.clone()));
+    Ok(Json(prefs))
+}
+
+// ===== Web Push =====
+
+#[derive(Serialize)]
+struct PushPublicKeyResponse {
+    public_key: String,
+}
+
+/// The VAPID key to pass as `applicationServerKey` when subscribing.
+/// 404 when push isn't configured.
+async fn push_public_key_handler(
+    State(push): State<Arc<PushNotifier>>,
+) -> Result<Json<PushPublicKeyResponse>, ApiError> {
+    let public_key = push
+        .public_key()
+        .ok_or_else(|| ApiError::NotFound("Push notifications are not enabled".into()))?;
+    Ok(Json(PushPublicKeyResponse {
+        public_key: public_key.to_string(),
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+struct PushSubscriptionKeys {
+    p256dh: String,
+    auth: String,
 }
 
-// Typing Indicator
-#[tracing::instrument(skip(session, pool, hub))]
-async fn typing_indicator_handler(
+/// A browser `PushSubscription` as its `toJSON()` renders it; other fields
+/// (`expirationTime`) are ignored.
+#[derive(Deserialize, Debug)]
+struct PushSubscribeRequest {
+    endpoint: String,
+    keys: PushSubscriptionKeys,
+}
+
+/// Register this browser for pushes about new messages while the app is closed.
+#[tracing::instrument(skip(session, pool, push, req))]
+async fn push_subscribe_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    State(hub): State<NotificationHub>,
-    axum::extract::Path(thread_id): axum::extract::Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
+    State(push): State<Arc<PushNotifier>>,
+    Json(req): Json<PushSubscribeRequest>,
+) -> Result<StatusCode, ApiError> {
+    if push.public_key().is_none() {
+        return Err(ApiError::NotFound(
+            "Push notifications are not enabled".into(),
+        ));
+    }
+    if !req.endpoint.starts_with("https://") {
+        return Err(ApiError::Validation(
+            "Push endpoint must be an https URL".into(),
+        ));
+    }
+    if req.keys.p256dh.is_empty() || req.keys.auth.is_empty() {
+        return Err(ApiError::Validation(
+            "Push subscription keys are missing".into(),
+        ));
+    }
+
     let user = resolve_user(&mut session, &pool).await?;
 
-    crate::db::set_typing_indicator(&pool, thread_id, user.id)
+    let subscription = crate::db::PushSubscription {
+        endpoint: req.endpoint,
+        p256dh: req.keys.p256dh,
+        auth: req.keys.auth,
+    };
+    crate::db::save_push_subscription(&pool, user.id, &subscription)
         .await
         .map_err(|e| {
-            warn!("Failed to set typing indicator: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to save push subscription: {}", e);
+            ApiError::Internal
         })?;
 
-    // Notify other participants via SSE
-    let payload = serde_json::json!({
-        "thread_id": thread_id,
-        "user_id": user.id,
-        "username": user.username,
-    })
-    .to_string();
+    info!("User {} subscribed to push", user.username);
+    Ok(StatusCode::CREATED)
+}
 
-    // Get other participant from the thread and notify them
-    let other_user_id = sqlx::query_scalar::<_, Uuid>(
-        r#"
-        SELECT DISTINCT CASE 
-            WHEN sender_id IS NOT NULL AND sender_id != $2 THEN sender_id
-            WHEN recipient_id != $2 THEN recipient_id
-            ELSE NULL
-        END AS other_user
-        FROM messages 
-        WHERE thread_id = $1 AND (sender_id != $2 OR recipient_id != $2)
-        LIMIT 1
-        "#,
-    )
-    .bind(thread_id)
-    .bind(user.id)
-    .fetch_optional(&*pool)
-    .await;
+// ===== Inbox Links =====
 
-    if let Ok(Some(other_user_id)) = other_user_id {
-        notify_user_sse(
-            &hub,
-            other_user_id,
-            SseEvent {
-                event_type: "typing".to_string(),
-                data: payload,
-            },
-        )
-        .await;
+/// Random bytes in an inbox link token, before base64url encoding.
+const INBOX_TOKEN_BYTES: usize = 24;
+
+#[derive(Serialize)]
+struct InboxLinkResponse {
+    /// Goes in the shareable URL; `POST /api/anon/{token}` sends with it
+    token: String,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+}
+
+impl From<crate::db::InboxToken> for InboxLinkResponse {
+    fn from(t: crate::db::InboxToken) -> Self {
+        Self {
+            token: t.token,
+            created_at: t.created_at,
+        }
     }
+}
 
-    Ok(StatusCode::OK)
+#[derive(Deserialize, Debug)]
+struct AnonMessageRequest {
+    content: String,
 }
 
-// User Blocking
+/// The caller's active inbox link; 404 if they haven't made one.
 #[tracing::instrument(skip(session, pool))]
-async fn block_user_handler(
+async fn get_inbox_link_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    axum::extract::Path(blocked_id): axum::extract::Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<Json<InboxLinkResponse>, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    if user.id == blocked_id {
-        return Err(StatusCode::BAD_REQUEST);
-    }
-
-    crate::db::block_user(&pool, user.id, blocked_id)
+    let token = crate::db::get_inbox_token(&pool, user.id)
         .await
         .map_err(|e| {
-            warn!("Failed to block user: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-    info!("User {} blocked user {}", user.username, blocked_id);
-    Ok(StatusCode::OK)
+            warn!("Failed to load inbox link: {}", e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("No inbox link".into()))?;
+    Ok(Json(token.into()))
 }
 
+/// Create the caller's inbox link, or replace it: the old link stops working.
 #[tracing::instrument(skip(session, pool))]
-async fn unblock_user_handler(
+async fn rotate_inbox_link_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    axum::extract::Path(blocked_id): axum::extract::Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<(StatusCode, Json<InboxLinkResponse>), ApiError> {
+    use argon2::password_hash::rand_core::{OsRng, RngCore};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+
     let user = resolve_user(&mut session, &pool).await?;
 
-    crate::db::unblock_user(&pool, user.id, blocked_id)
+    let mut bytes = [0u8; INBOX_TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    let token = crate::db::rotate_inbox_token(&pool, user.id, &URL_SAFE_NO_PAD.encode(bytes))
         .await
         .map_err(|e| {
-            warn!("Failed to unblock user: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to rotate inbox link: {}", e);
+            ApiError::Internal
         })?;
 
-    info!("User {} unblocked user {}", user.username, blocked_id);
-    Ok(StatusCode::OK)
+    info!("User {} rotated their inbox link", user.username);
+    Ok((StatusCode::CREATED, Json(token.into())))
 }
 
+/// Turn the caller's inbox link off without making a new one.
 #[tracing::instrument(skip(session, pool))]
-async fn get_blocked_users_handler(
+async fn revoke_inbox_link_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-) -> Result<Json<Vec<Uuid>>, StatusCode> {
+) -> Result<StatusCode, ApiError> {
     let user = resolve_user(&mut session, &pool).await?;
 
-    let blocked_ids = crate::db::get_blocked_users(&pool, user.id)
+    let revoked = crate::db::revoke_inbox_token(&pool, user.id)
         .await
         .map_err(|e| {
-            warn!("Failed to get blocked users: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to revoke inbox link: {}", e);
+            ApiError::Internal
         })?;
+    if !revoked {
+        return Err(ApiError::NotFound("No inbox link".into()));
+    }
 
-    Ok(Json(blocked_ids))
-}
-
-// Broadcast Comments
-#[derive(Deserialize, Debug)]
-struct CreateCommentRequest {
-    content: String,
-    parent_comment_id: Option<Uuid>,
+    info!("User {} revoked their inbox link", user.username);
+    Ok(StatusCode::NO_CONTENT)
 }
 
-#[derive(Serialize)]
-struct CommentResponse {
-    id: Uuid,
-    broadcast_id: Uuid,
-    user_id: Uuid,
-    username: Option<String>,
-    content: String,
-    parent_comment_id: Option<Uuid>,
-    #[serde(with = "time::serde::rfc3339")]
-    created_at: OffsetDateTime,
-    reactions: Option<serde_json::Value>,
+/// Count a hit against the inbox link limiter, or reject it with 429.
+fn check_anon_rate_limit(limiter: &AnonMessageLimiter, key: &str) -> Result<(), ApiError> {
+    limiter.0.check(key).map_err(|retry_after| {
+        warn!("Rate limited anonymous message for {key}");
+        ApiError::TooManyRequests(format!(
+            "Too many messages, try again in {} seconds",
+            retry_after.as_secs().max(1)
+        ))
+    })
 }
 
-#[tracing::instrument(skip(session, pool, hub))]
-async fn create_broadcast_comment_handler(
-    mut session: AuthSession,
+/// Send an anonymous message through someone's inbox link. Needs no session
+/// and never records a sender, so the thread can't be replied to. Limited
+/// per IP and per link.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all)]
+async fn anon_message_handler(
     State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
     State(hub): State<NotificationHub>,
-    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
-    Json(req): Json<CreateCommentRequest>,
-) -> Result<StatusCode, StatusCode> {
-    let user = resolve_user(&mut session, &pool).await?;
+    State(push): State<Arc<PushNotifier>>,
+    State(webhooks): State<WebhookDispatcher>,
+    State(limiter): State<AnonMessageLimiter>,
+    ClientIp(ip): ClientIp,
+    OriginFingerprint(fingerprint): OriginFingerprint,
+    axum::extract::Path(token): axum::extract::Path<String>,
+    Json(req): Json<AnonMessageRequest>,
+) -> Result<StatusCode, ApiError> {
+    if let Some(ip) = ip {
+        check_anon_rate_limit(&limiter, &format!("ip:{ip}"))?;
+    }
+    check_anon_rate_limit(&limiter, &format!("link:{token}"))?;
 
     if req.content.trim().is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::Validation(
+            "Message content cannot be empty".into(),
+        ));
     }
+    check_content_length(&req.content, api_config.max_content_length)?;
 
-    let comment_id = crate::db::create_broadcast_comment(
-        &pool,
-        broadcast_id,
-        user.id,
-        &req.content,
-        req.parent_comment_id,
-    )
-    .await
-    .map_err(|e| {
-        warn!("Failed to create comment: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    info!(
-        "User {} commented on broadcast {}",
-        user.username, broadcast_id
-    );
-
-    // Notify all users via SSE
-    let payload = serde_json::json!({
-        "broadcast_id": broadcast_id,
-        "comment_id": comment_id,
-    })
-    .to_string();
+    let recipient_id = crate::db::resolve_inbox_token(&pool, &token)
+        .await
+        .map_err(|e| {
+            warn!("Failed to resolve inbox link: {}", e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("Inbox link not found".into()))?;
 
-    notify_all_sse(
+    deliver_new_message(
+        &pool,
         &hub,
-        SseEvent {
-            event_type: "new_comment".to_string(),
-            data: payload,
-        },
+        &push,
+        &webhooks,
+        None,
+        recipient_id,
+        req.content,
+        fingerprint.as_deref(),
+        crate::db::MessageExpiry::default(),
+        None,
     )
-    .await;
+    .await?;
 
     Ok(StatusCode::CREATED)
 }
 
-#[tracing::instrument(skip(session, pool))]
-async fn get_broadcast_comments_handler(
-    mut session: AuthSession,
-    State(pool): State<Arc<PgPool>>,
-    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
-) -> Result<Json<Vec<CommentResponse>>, StatusCode> {
-    let _user = resolve_user(&mut session, &pool).await?;
+// ===== Reports =====
 
-    let comments = crate::db::get_broadcast_comments(&pool, broadcast_id)
-        .await
-        .map_err(|e| {
-            warn!("Failed to get comments: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+/// Longest report reason accepted, in characters.
+const MAX_REPORT_REASON_LENGTH: usize = 1000;
 
-    Ok(Json(
-        comments
-            .into_iter()
-            .map(|c| CommentResponse {
-                id: c.id,
-                broadcast_id: c.broadcast_id,
-                user_id: c.user_id,
-                username: c.username,
-                content: c.content,
-                parent_comment_id: c.parent_comment_id,
-                created_at: c.created_at,
-                reactions: c.reactions,
-            })
-            .collect(),
-    ))
+#[derive(Deserialize, Debug)]
+struct ReportRequest {
+    reason: String,
 }
 
-#[derive(Deserialize, Debug)]
-struct ReactToCommentRequest {
-    emoji: String,
+#[derive(Serialize)]
+struct ReportResponse {
+    id: Uuid,
 }
 
-#[tracing::instrument(skip(session, pool))]
-async fn react_to_comment_handler(
+fn check_report_reason(reason: &str) -> Result<(), ApiError> {
+    if reason.trim().is_empty() {
+        return Err(ApiError::Validation("Report reason cannot be empty".into()));
+    }
+    if reason.chars().count() > MAX_REPORT_REASON_LENGTH {
+        return Err(ApiError::Validation(format!(
+            "Report reason can be at most {} characters",
+            MAX_REPORT_REASON_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// Report a message for moderation. Only its participants may; nobody else
+/// is told, least of all the sender.
+#[tracing::instrument(skip(session, pool, req))]
+async fn report_message_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    axum::extract::Path(comment_id): axum::extract::Path<Uuid>,
-    Json(req): Json<ReactToCommentRequest>,
-) -> Result<StatusCode, StatusCode> {
+    axum::extract::Path(message_id): axum::extract::Path<Uuid>,
+    Json(req): Json<ReportRequest>,
+) -> Result<(StatusCode, Json<ReportResponse>), ApiError> {
+    check_report_reason(&req.reason)?;
     let user = resolve_user(&mut session, &pool).await?;
 
-    crate::db::react_to_comment(&pool, comment_id, user.id, &req.emoji)
+    crate::db::get_message_by_id(&*pool, message_id)
         .await
         .map_err(|e| {
-            warn!("Failed to react to comment: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+            warn!("Failed to fetch message {}: {}", message_id, e);
+            ApiError::Internal
+        })?
+        .filter(|m| m.recipient_id == user.id || m.sender_id == Some(user.id))
+        .ok_or_else(|| ApiError::NotFound("Message not found".into()))?;
 
-    Ok(StatusCode::OK)
+    let id = crate::db::create_message_report(&pool, message_id, user.id, req.reason.trim())
+        .await
+        .map_err(|e| {
+            warn!("Failed to report message {}: {}", message_id, e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::Conflict("You already reported this message".into()))?;
+
+    info!("Message {} reported (report {})", message_id, id);
+    Ok((StatusCode::CREATED, Json(ReportResponse { id })))
 }
 
-#[tracing::instrument(skip(session, pool))]
-async fn delete_comment_handler(
+/// Report a published broadcast for moderation. The author isn't told.
+#[tracing::instrument(skip(session, pool, req))]
+async fn report_broadcast_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    axum::extract::Path(comment_id): axum::extract::Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
+    axum::extract::Path(broadcast_id): axum::extract::Path<Uuid>,
+    Json(req): Json<ReportRequest>,
+) -> Result<(StatusCode, Json<ReportResponse>), ApiError> {
+    check_report_reason(&req.reason)?;
     let user = resolve_user(&mut session, &pool).await?;
 
-    crate::db::delete_broadcast_comment(&pool, comment_id, user.id)
+    crate::db::get_broadcast_by_id(&pool, broadcast_id)
         .await
         .map_err(|e| {
-            warn!("Failed to delete comment: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+            warn!("Failed to load broadcast {}: {}", broadcast_id, e);
+            ApiError::Internal
+        })?
+        .filter(|b| !b.is_draft)
+        .ok_or_else(|| ApiError::NotFound("Broadcast not found".into()))?;
 
-    Ok(StatusCode::NO_CONTENT)
+    let id = crate::db::create_broadcast_report(&pool, broadcast_id, user.id, req.reason.trim())
+        .await
+        .map_err(|e| {
+            warn!("Failed to report broadcast {}: {}", broadcast_id, e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::Conflict("You already reported this broadcast".into()))?;
+
+    info!("Broadcast {} reported (report {})", broadcast_id, id);
+    Ok((StatusCode::CREATED, Json(ReportResponse { id })))
 }
 
-// User Preferences
 #[derive(Serialize)]
-struct PreferencesResponse {
-    theme: String,
-    notification_sound: bool,
-    browser_notifications: bool,
-    show_read_receipts: bool,
-    show_typing_indicators: bool,
+struct ModerationReportResponse {
+    id: Uuid,
+    /// `message` or `broadcast`
+    kind: String,
+    /// The reported message or broadcast
+    target_id: Uuid,
+    reason: String,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    /// As it was when reported
+    content: Option<String>,
+    content_deleted: bool,
+    /// Sender or author; set even for anonymous broadcasts, so admins only
+    author_id: Option<Uuid>,
+    /// Users with a pending report on the same item
+    reporter_count: i64,
 }
 
-#[tracing::instrument(skip(session, pool))]
-async fn get_preferences_handler(
+/// The moderation queue: pending reports, oldest first, so the cursor pages
+/// toward newer ones. Admins only.
+#[tracing::instrument(skip(session, pool, api_config))]
+async fn list_reports_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-) -> Result<Json<PreferencesResponse>, StatusCode> {
-    let user = resolve_user(&mut session, &pool).await?;
+    State(api_config): State<Arc<ApiConfig>>,
+    axum::extract::Query(query): axum::extract::Query<PageQuery>,
+) -> Result<Json<PaginatedResponse<ModerationReportResponse>>, ApiError> {
+    let limit = query.limit();
+    let cursor = query.cursor()?;
+    require_admin(&mut session, &pool, &api_config).await?;
+
+    let reports =
+        crate::db::get_pending_reports(&pool, limit, cursor.map(|c| c.at), cursor.map(|c| c.id))
+            .await
+            .map_err(|e| {
+                warn!("Failed to fetch reports: {}", e);
+                ApiError::Internal
+            })?;
 
-    let prefs = crate::db::get_user_preferences(&pool, user.id)
+    Ok(Json(PaginatedResponse::new(
+        reports,
+        limit,
+        |r| (r.created_at, r.id),
+        |r| ModerationReportResponse {
+            id: r.id,
+            kind: r.kind,
+            target_id: r.target_id,
+            reason: r.reason,
+            created_at: r.created_at,
+            content: r.content,
+            content_deleted: r.content_deleted,
+            author_id: r.author_id,
+            reporter_count: r.reporter_count,
+        },
+    )))
+}
+
+#[derive(Deserialize, Debug)]
+struct ResolveReportRequest {
+    action: ReportAction,
+}
+
+/// Act on a report: dismiss it, delete the content, or delete it and ban
+/// its author. A ban also ends the author's sessions. Admins only.
+#[tracing::instrument(skip(session, pool, api_config, sessions))]
+async fn resolve_report_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    State(sessions): State<Arc<IndexedSessionStore>>,
+    axum::extract::Path(report_id): axum::extract::Path<Uuid>,
+    Json(req): Json<ResolveReportRequest>,
+) -> Result<StatusCode, ApiError> {
+    let admin = require_admin(&mut session, &pool, &api_config).await?;
+
+    let outcome = crate::db::resolve_report(&pool, report_id, admin.id, req.action)
         .await
         .map_err(|e| {
-            warn!("Failed to get preferences: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            warn!("Failed to resolve report {}: {}", report_id, e);
+            ApiError::Internal
         })?;
+    let banned = match outcome {
+        ResolveOutcome::Resolved { banned } => banned,
+        ResolveOutcome::NotFound => {
+            return Err(ApiError::NotFound("Pending report not found".into()))
+        }
+        ResolveOutcome::NoAuthor => {
+            return Err(ApiError::Unprocessable(
+                "The reported message was sent anonymously; there is no author to ban".into(),
+            ))
+        }
+    };
 
-    let prefs = prefs.unwrap_or(crate::db::UserPreferences {
-        user_id: user.id,
-        theme: "dark".to_string(),
-        notification_sound: true,
-        browser_notifications: true,
-        show_read_receipts: true,
-        show_typing_indicators: true,
-    });
-
-    Ok(Json(PreferencesResponse {
-        theme: prefs.theme,
-        notification_sound: prefs.notification_sound,
-        browser_notifications: prefs.browser_notifications,
-        show_read_receipts: prefs.show_read_receipts,
-        show_typing_indicators: prefs.show_typing_indicators,
-    }))
+    if let Some(banned) = banned {
+        let invalidated =
+            crate::auth::revoke_sessions(&sessions, crate::auth::user_identities(&banned)).await?;
+        info!(
+            "Admin {} banned user {} over report {} ({} sessions ended)",
+            admin.username, banned.username, report_id, invalidated
+        );
+    }
+    info!(
+        "Admin {} resolved report {} ({:?})",
+        admin.username, report_id, req.action
+    );
+    Ok(StatusCode::NO_CONTENT)
 }
 
-#[derive(Deserialize, Debug)]
-struct UpdatePreferencesRequest {
-    theme: Option<String>,
-    notification_sound: Option<bool>,
-    browser_notifications: Option<bool>,
-    show_read_receipts: Option<bool>,
-    show_typing_indicators: Option<bool>,
+/// Ban a user: sign them out everywhere, refuse their requests and logins,
+/// and hide their broadcasts and messages. Admins only.
+#[tracing::instrument(skip(session, pool, api_config, sessions))]
+async fn ban_user_handler(
+    mut session: AuthSession,
+    State(pool): State<Arc<PgPool>>,
+    State(api_config): State<Arc<ApiConfig>>,
+    State(sessions): State<Arc<IndexedSessionStore>>,
+    axum::extract::Path(user_id): axum::extract::Path<Uuid>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let admin = require_admin(&mut session, &pool, &api_config).await?;
+    if admin.id == user_id {
+        return Err(ApiError::Unprocessable("You can't ban yourself".into()));
+    }
+
+    let banned = crate::db::ban_user(&*pool, user_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to ban user {}: {}", user_id, e);
+            ApiError::Internal
+        })?
+        .ok_or_else(|| ApiError::NotFound("User not found or already banned".into()))?;
+
+    let invalidated =
+        crate::auth::revoke_sessions(&sessions, crate::auth::user_identities(&banned)).await?;
+
+    info!(
+        "Admin {} banned user {} ({} sessions ended)",
+        admin.username, banned.username, invalidated
+    );
+    Ok(Json(serde_json::json!({ "invalidated": invalidated })))
 }
 
-#[tracing::instrument(skip(session, pool))]
-async fn update_preferences_handler(
+/// Lift a ban. The user signs in again as usual. Admins only.
+#[tracing::instrument(skip(session, pool, api_config))]
+async fn unban_user_handler(
     mut session: AuthSession,
     State(pool): State<Arc<PgPool>>,
-    Json(req): Json<UpdatePreferencesRequest>,
-) -> Result<StatusCode, StatusCode> {
-    let user = resolve_user(&mut session, &pool).await?;
-
-    crate::db::upsert_user_preferences(
-        &pool,
-        user.id,
-        req.theme,
-        req.notification_sound,
-        req.browser_notifications,
-        req.show_read_receipts,
-        req.show_typing_indicators,
-    )
-    .await
-    .map_err(|e| {
-        warn!("Failed to update preferences: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
+    State(api_config): State<Arc<ApiConfig>>,
+    axum::extract::Path(user_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let admin = require_admin(&mut session, &pool, &api_config).await?;
+
+    let unbanned = crate::db::unban_user(&pool, user_id).await.map_err(|e| {
+        warn!("Failed to unban user {}: {}", user_id, e);
+        ApiError::Internal
     })?;
+    if !unbanned {
+        return Err(ApiError::NotFound("User not found or not banned".into()));
+    }
 
-    info!("User {} updated preferences", user.username);
-    Ok(StatusCode::OK)
+    info!("Admin {} unbanned user {}", admin.username, user_id);
+    Ok(StatusCode::NO_CONTENT)
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,2102 @@
+//! Handlers exercised over HTTP against a test database (see `testing.rs`).
+
+use super::*;
+use crate::db::MessageExpiry;
+use crate::testing::{TestApp, TestUser};
+use axum::http::Method;
+
+/// A message from `sender` (None for anonymous) to `recipient`, in a new
+/// thread. Returns (message_id, thread_id).
+async fn message(
+    pool: &PgPool,
+    sender: Option<Uuid>,
+    recipient: Uuid,
+    content: &str,
+) -> (Uuid, Uuid) {
+    crate::db::create_message(
+        pool,
+        sender,
+        recipient,
+        content,
+        None,
+        MessageExpiry::default(),
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+// ===== Thread search =====
+
+#[sqlx::test]
+async fn thread_search_only_returns_that_thread(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (first, thread) = message(app.pool(), Some(alice.id), bob.id, "lunch on friday?").await;
+    message(app.pool(), Some(alice.id), bob.id, "friday works for lunch").await;
+    message(app.pool(), Some(bob.id), alice.id, "lunch again friday").await;
+
+    let response = app
+        .get(&format!("/api/conversations/{thread}/search?q=lunch"), &bob)
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let hits = response.json();
+    let hits = hits.as_array().unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0]["id"], first.to_string());
+    assert_eq!(hits[0]["thread_id"], thread.to_string());
+}
+
+#[sqlx::test]
+async fn thread_search_is_for_participants_only(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, eve) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("eve").await,
+    );
+    let (_, thread) = message(app.pool(), Some(alice.id), bob.id, "lunch on friday?").await;
+
+    let response = app
+        .get(&format!("/api/conversations/{thread}/search?q=lunch"), &eve)
+        .await;
+
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+}
+
+// ===== Broadcast comments =====
+
+#[sqlx::test]
+async fn deleting_all_my_comments_keeps_placeholders_for_replied_ones(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    let pool = app.pool();
+    let broadcast = crate::db::create_broadcast(pool, alice.id, "news", false, false, None)
+        .await
+        .unwrap();
+    let comment = |user: Uuid, content: &'static str, parent: Option<Uuid>| async move {
+        crate::db::create_broadcast_comment(pool, broadcast, user, content, parent)
+            .await
+            .unwrap()
+    };
+    let lone = comment(bob.id, "first", None).await;
+    let replied = comment(bob.id, "second", None).await;
+    let reply = comment(alice.id, "reply to second", Some(replied)).await;
+    let self_replied = comment(bob.id, "third", None).await;
+    let own_reply = comment(bob.id, "and another thing", Some(self_replied)).await;
+    let other = comment(carol.id, "carol's", None).await;
+
+    let response = app
+        .request(
+            Method::DELETE,
+            &format!("/api/broadcasts/{broadcast}/comments/mine"),
+            Some(&bob),
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["deleted"], 4);
+
+    let comments = app
+        .get(&format!("/api/broadcasts/{broadcast}/comments"), &alice)
+        .await
+        .json();
+    let by_id = |id: Uuid| {
+        comments
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["id"] == id.to_string())
+            .cloned()
+    };
+    for gone in [lone, self_replied, own_reply] {
+        assert_eq!(by_id(gone), None);
+    }
+    let placeholder = by_id(replied).unwrap();
+    assert_eq!(placeholder["content"], "[deleted]");
+    assert_eq!(placeholder["user_id"], serde_json::Value::Null);
+    assert_eq!(placeholder["username"], serde_json::Value::Null);
+    assert_eq!(by_id(reply).unwrap()["user_id"], alice.id.to_string());
+    assert_eq!(by_id(other).unwrap()["content"], "carol's");
+}
+
+// ===== Broadcast history =====
+
+/// A published broadcast by `author`, edited to each of `edits` in turn.
+async fn edited_broadcast(app: &TestApp, author: &TestUser, edits: &[&str]) -> Uuid {
+    let broadcast = crate::db::create_broadcast(app.pool(), author.id, "v1", false, false, None)
+        .await
+        .unwrap();
+    for content in edits {
+        let response = app
+            .post(
+                &format!("/api/broadcasts/{broadcast}/edit"),
+                author,
+                serde_json::json!({ "content": content }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "editing to {content}");
+    }
+    broadcast
+}
+
+#[sqlx::test]
+async fn broadcast_history_lists_prior_versions_oldest_first(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let alice = app.user("alice").await;
+    let broadcast = edited_broadcast(&app, &alice, &["v2", "v3"]).await;
+
+    let history = app
+        .get(&format!("/api/broadcasts/{broadcast}/history"), &alice)
+        .await
+        .json();
+
+    let contents: Vec<&str> = history
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(contents, ["v1", "v2"]);
+    let feed = app.get("/api/broadcasts", &alice).await.json();
+    let current = &feed["items"][0];
+    assert_eq!(current["id"], broadcast.to_string());
+    assert_eq!(current["content"], "v3");
+    assert_eq!(current["edit_count"], 2);
+}
+
+#[sqlx::test]
+async fn broadcast_history_is_for_the_author_unless_made_public(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let broadcast = edited_broadcast(&app, &alice, &["v2"]).await;
+    let path = format!("/api/broadcasts/{broadcast}/history");
+
+    assert_eq!(app.get(&path, &bob).await.status, StatusCode::FORBIDDEN);
+
+    let public = TestApp::with_config(
+        pool,
+        ApiConfig {
+            broadcast_history_public: true,
+            ..crate::testing::api_config()
+        },
+    );
+    let carol = public.user("carol").await;
+    let response = public.get(&path, &carol).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()[0]["content"], "v1");
+}
+
+// ===== Broadcast drafts =====
+
+/// Ids of the broadcasts on the first page of `user`'s feed.
+async fn feed_ids(app: &TestApp, user: &TestUser) -> Vec<String> {
+    app.get("/api/broadcasts", user).await.json()["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| b["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[sqlx::test]
+async fn drafts_stay_out_of_the_feed_until_published(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let mut drafts = Vec::new();
+    for is_anonymous in [false, true] {
+        let response = app
+            .post(
+                "/api/broadcasts",
+                &alice,
+                serde_json::json!({
+                    "content": "not yet",
+                    "is_anonymous": is_anonymous,
+                    "publish": false,
+                }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED);
+        drafts.push(response.json()["id"].as_str().unwrap().to_string());
+    }
+
+    for user in [&alice, &bob] {
+        assert_eq!(feed_ids(&app, user).await, Vec::<String>::new());
+    }
+    let listed = app.get("/api/broadcasts/drafts", &alice).await.json();
+    assert_eq!(listed.as_array().unwrap().len(), 2);
+    assert_eq!(
+        app.get("/api/broadcasts/drafts", &bob).await.json(),
+        serde_json::json!([])
+    );
+
+    let response = app
+        .post(
+            &format!("/api/broadcasts/{}/publish", drafts[0]),
+            &alice,
+            serde_json::json!({}),
+        )
+        .await;
+    assert!(response.status.is_success(), "{}", response.status);
+    for user in [&alice, &bob] {
+        assert_eq!(feed_ids(&app, user).await, [drafts[0].clone()]);
+    }
+}
+
+// ===== SSE =====
+
+#[sqlx::test]
+async fn sse_connections_past_the_cap_are_rejected(pool: PgPool) {
+    let app = TestApp::with_config(
+        pool,
+        ApiConfig {
+            sse_max_connections_per_user: 2,
+            ..crate::testing::api_config()
+        },
+    );
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+
+    let (first, _first_stream) = app.events(&alice, "").await;
+    let (second, second_stream) = app.events(&alice, "").await;
+    let (third, _) = app.events(&alice, "").await;
+    assert_eq!(
+        (first, second, third),
+        (
+            StatusCode::OK,
+            StatusCode::OK,
+            StatusCode::TOO_MANY_REQUESTS
+        )
+    );
+    // The cap is per user
+    assert_eq!(app.events(&bob, "").await.0, StatusCode::OK);
+
+    drop(second_stream);
+    assert_eq!(app.events(&alice, "").await.0, StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn a_reconnecting_stream_replays_what_it_missed(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (_, mut first) = app.events(&bob, "").await;
+    for content in ["seen", "missed"] {
+        send(
+            &app,
+            &alice,
+            &bob,
+            serde_json::json!({ "content": content }),
+        )
+        .await;
+    }
+    // The connection drops after the client read only "seen"
+    assert_eq!(first.next_of("new_message").await["content"], "seen");
+    drop(first);
+    // The id "seen" went out with, as bob's EventSource would remember it
+    let seen = app
+        .state
+        .notification_hub
+        .replay
+        .replay(bob.id, 0)
+        .events
+        .into_iter()
+        .find(|(_, event)| event.event_type() == "new_message")
+        .map(|(id, _)| id.to_string())
+        .unwrap();
+
+    let (status, mut events) = app
+        .events_with(
+            &bob,
+            "",
+            &[(axum::http::HeaderName::from_static("last-event-id"), &seen)],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(events.next_of("new_message").await["content"], "missed");
+}
+
+// ===== Profile ETags =====
+
+#[sqlx::test]
+async fn a_stale_if_match_is_rejected(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let alice = app.user("alice").await;
+    let read = app.get("/api/me", &alice).await;
+    let etag = read.headers[header::ETAG].to_str().unwrap().to_string();
+
+    let first = app
+        .send(
+            Method::POST,
+            "/api/me",
+            Some(&alice),
+            &[(header::IF_MATCH, &etag)],
+            Some(serde_json::json!({ "bio": "first" })),
+        )
+        .await;
+    assert_eq!(first.status, StatusCode::OK);
+    assert_ne!(first.headers[header::ETAG], etag.as_str());
+
+    // Another tab still holds the ETag from before the first update
+    let stale = app
+        .send(
+            Method::POST,
+            "/api/me",
+            Some(&alice),
+            &[(header::IF_MATCH, &etag)],
+            Some(serde_json::json!({ "bio": "second" })),
+        )
+        .await;
+    assert_eq!(stale.status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(app.get("/api/me", &alice).await.json()["bio"], "first");
+}
+
+#[sqlx::test]
+async fn the_etag_is_stable_until_the_profile_changes(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let alice = app.user("alice").await;
+
+    let first = app.get("/api/me", &alice).await.headers[header::ETAG].clone();
+    let second = app.get("/api/me", &alice).await.headers[header::ETAG].clone();
+
+    assert_eq!(first, second);
+    assert!(first.to_str().unwrap().starts_with('"'));
+}
+
+// ===== Paused threads =====
+
+#[sqlx::test]
+async fn a_paused_thread_rejects_the_other_partys_replies(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (_, thread) = message(app.pool(), Some(alice.id), bob.id, "hi bob").await;
+    let reply = |content: &'static str| serde_json::json!({ "content": content });
+
+    let paused = app
+        .post(
+            &format!("/api/conversations/{thread}/pause"),
+            &bob,
+            serde_json::json!({}),
+        )
+        .await;
+    assert!(paused.status.is_success(), "{}", paused.status);
+
+    let rejected = app
+        .post(
+            &format!("/api/conversations/{thread}/reply"),
+            &alice,
+            reply("hello?"),
+        )
+        .await;
+    assert_eq!(rejected.status, StatusCode::LOCKED);
+    assert!(rejected.json()["error"].is_string());
+
+    // The pauser can still write, and the thread view says it's paused
+    let own = app
+        .post(
+            &format!("/api/conversations/{thread}/reply"),
+            &bob,
+            reply("not now"),
+        )
+        .await;
+    assert!(own.status.is_success(), "{}", own.status);
+    let view = app
+        .get(&format!("/api/conversations/{thread}"), &alice)
+        .await
+        .json();
+    assert_eq!(view[0]["paused"], true, "{view}");
+
+    app.post(
+        &format!("/api/conversations/{thread}/resume"),
+        &bob,
+        serde_json::json!({}),
+    )
+    .await;
+    let accepted = app
+        .post(
+            &format!("/api/conversations/{thread}/reply"),
+            &alice,
+            reply("now?"),
+        )
+        .await;
+    assert!(accepted.status.is_success(), "{}", accepted.status);
+}
+
+// ===== Admin stats =====
+
+#[sqlx::test]
+async fn admin_stats_are_for_admins_only(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (admin, alice) = (app.user("admin").await, app.user("alice").await);
+    sqlx::query("UPDATE users SET is_admin = true WHERE id = $1")
+        .bind(admin.id)
+        .execute(app.pool())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        app.get("/api/admin/stats", &alice).await.status,
+        StatusCode::FORBIDDEN
+    );
+    let stats = app.get("/api/admin/stats", &admin).await;
+    assert_eq!(stats.status, StatusCode::OK);
+    assert!(stats.json()["pool"]["size"].as_u64().unwrap() >= 1);
+}
+
+// ===== Deleted messages =====
+
+fn ids(values: &serde_json::Value) -> Vec<String> {
+    values
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[sqlx::test]
+async fn a_deleted_message_leaves_the_inbox_and_the_thread(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (deleted, thread) = message(app.pool(), Some(alice.id), bob.id, "oops").await;
+    let kept = crate::db::create_reply(app.pool(), thread, bob.id, alice.id, "hm?", None, None)
+        .await
+        .unwrap();
+    let (other, _) = message(app.pool(), Some(alice.id), bob.id, "still here").await;
+
+    let response = app
+        .request(
+            Method::DELETE,
+            &format!("/api/messages/{deleted}/delete"),
+            Some(&bob),
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let inbox = app.get("/api/messages/inbox", &bob).await.json();
+    assert_eq!(ids(&inbox["items"]), vec![other.to_string()]);
+    let view = app
+        .get(&format!("/api/conversations/{thread}"), &alice)
+        .await
+        .json();
+    assert_eq!(ids(&view), vec![kept.to_string()]);
+
+    // With tombstones the thread keeps its shape, without the content
+    let view = app
+        .get(
+            &format!("/api/conversations/{thread}?tombstones=true"),
+            &alice,
+        )
+        .await
+        .json();
+    assert_eq!(ids(&view), vec![deleted.to_string(), kept.to_string()]);
+    assert_eq!(view[0]["content"], "This message was deleted");
+}
+
+#[sqlx::test]
+async fn deleting_someone_elses_message_is_not_found(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    let (message_id, _) = message(app.pool(), Some(alice.id), bob.id, "just us").await;
+
+    for action in ["delete", "unsend"] {
+        let path = format!("/api/messages/{message_id}/{action}");
+        let response = app.request(Method::DELETE, &path, Some(&carol), None).await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND, "{action}");
+    }
+    let deleted = "SELECT COUNT(*) FROM messages WHERE deleted_at IS NOT NULL";
+    assert_eq!(count(app.pool(), deleted).await, 0);
+}
+
+#[sqlx::test]
+async fn a_reported_message_is_deleted_instead_of_unsent(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (message_id, _) = message(app.pool(), Some(alice.id), bob.id, "abuse").await;
+    let report = app
+        .post(
+            &format!("/api/messages/{message_id}/report"),
+            &bob,
+            serde_json::json!({ "reason": "harassment" }),
+        )
+        .await;
+    assert_eq!(report.status, StatusCode::CREATED);
+
+    let unsend = app
+        .request(
+            Method::DELETE,
+            &format!("/api/messages/{message_id}/unsend"),
+            Some(&alice),
+            None,
+        )
+        .await;
+    assert_eq!(unsend.status, StatusCode::OK);
+    assert_eq!(unsend.json()["unsent"], false);
+
+    // The message is soft-deleted, so moderators still know who sent it
+    let kept = format!(
+        "SELECT COUNT(*) FROM messages WHERE id = '{message_id}' AND deleted_at IS NOT NULL"
+    );
+    assert_eq!(count(app.pool(), &kept).await, 1);
+    let pending = "SELECT COUNT(*) FROM message_reports WHERE status = 'pending'";
+    assert_eq!(count(app.pool(), pending).await, 1);
+}
+
+#[sqlx::test]
+async fn an_unreported_message_is_unsent_for_good(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (message_id, _) = message(app.pool(), Some(alice.id), bob.id, "typo").await;
+
+    let unsend = app
+        .request(
+            Method::DELETE,
+            &format!("/api/messages/{message_id}/unsend"),
+            Some(&alice),
+            None,
+        )
+        .await;
+    assert_eq!(unsend.json()["unsent"], true);
+    assert_eq!(count(app.pool(), "SELECT COUNT(*) FROM messages").await, 0);
+}
+
+// ===== Batch reactions =====
+
+async fn reactions(pool: &PgPool, message_id: Uuid) -> Vec<String> {
+    sqlx::query_scalar("SELECT emoji FROM message_reactions WHERE message_id = $1 ORDER BY emoji")
+        .bind(message_id)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn a_reaction_batch_reports_each_item(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    let (first, _) = message(app.pool(), Some(alice.id), bob.id, "one").await;
+    let (second, _) = message(app.pool(), Some(alice.id), bob.id, "two").await;
+    let (theirs, _) = message(app.pool(), Some(alice.id), carol.id, "not for bob").await;
+    crate::db::add_message_reaction(app.pool(), second, bob.id, "👍")
+        .await
+        .unwrap();
+    let missing = Uuid::new_v4();
+
+    let response = app
+        .post(
+            "/api/messages/reactions/batch",
+            &bob,
+            serde_json::json!([
+                { "message_id": first, "emoji": "👍", "action": "add" },
+                { "message_id": second, "emoji": "👍", "action": "remove" },
+                { "message_id": theirs, "emoji": "👍", "action": "add" },
+                { "message_id": missing, "emoji": "👍", "action": "add" },
+            ]),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let statuses: Vec<_> = response
+        .json()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["status"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(statuses, ["applied", "applied", "forbidden", "not_found"]);
+    assert_eq!(reactions(app.pool(), first).await, ["👍"]);
+    assert!(reactions(app.pool(), second).await.is_empty());
+    assert!(reactions(app.pool(), theirs).await.is_empty());
+}
+
+#[sqlx::test]
+async fn an_oversized_reaction_batch_is_rejected(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let bob = app.user("bob").await;
+    let item = serde_json::json!({ "message_id": Uuid::new_v4(), "emoji": "👍", "action": "add" });
+    let batch = vec![item; MAX_REACTION_BATCH + 1];
+
+    let response = app
+        .post(
+            "/api/messages/reactions/batch",
+            &bob,
+            serde_json::json!(batch),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+// ===== Reply modes =====
+
+/// Send through `POST /api/messages` from `sender` to `recipient`, which
+/// answers 201 with no body. Returns the new message's id.
+async fn send(
+    app: &TestApp,
+    sender: &TestUser,
+    recipient: &TestUser,
+    mut body: serde_json::Value,
+) -> Uuid {
+    body["recipient_id"] = serde_json::json!(recipient.id);
+    let response = app.post("/api/messages", sender, body).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    sqlx::query_scalar(
+        "SELECT id FROM messages WHERE recipient_id = $1 ORDER BY created_at DESC LIMIT 1",
+    )
+    .bind(recipient.id)
+    .fetch_one(app.pool())
+    .await
+    .unwrap()
+}
+
+#[sqlx::test]
+async fn a_repliable_message_routes_replies_back_without_naming_the_sender(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let message_id = send(
+        &app,
+        &alice,
+        &bob,
+        serde_json::json!({ "content": "guess who", "allow_reply": true }),
+    )
+    .await;
+
+    let inbox = app.get("/api/messages/inbox", &bob).await;
+    assert!(!String::from_utf8_lossy(&inbox.body).contains("alice"));
+
+    let reply = app
+        .post(
+            &format!("/api/messages/{message_id}/reply"),
+            &bob,
+            serde_json::json!({ "content": "who?" }),
+        )
+        .await;
+    assert!(reply.status.is_success(), "{}", reply.status);
+    let alice_inbox = app.get("/api/messages/inbox", &alice).await.json();
+    assert_eq!(alice_inbox["items"][0]["content"], "who?");
+}
+
+#[sqlx::test]
+async fn a_one_way_message_cannot_be_replied_to(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let message_id = send(
+        &app,
+        &alice,
+        &bob,
+        serde_json::json!({ "content": "no replies", "allow_reply": false }),
+    )
+    .await;
+
+    let reply = app
+        .post(
+            &format!("/api/messages/{message_id}/reply"),
+            &bob,
+            serde_json::json!({ "content": "but why" }),
+        )
+        .await;
+
+    assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(reply.json()["message"], "This sender cannot be replied to");
+    let stored: Option<Uuid> = sqlx::query_scalar("SELECT sender_id FROM messages WHERE id = $1")
+        .bind(message_id)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(stored, None);
+}
+
+// ===== Transactions =====
+
+#[sqlx::test]
+async fn a_reply_that_fails_to_insert_leaves_nothing_behind(pool: PgPool) {
+    let app = TestApp::with_config(
+        pool,
+        ApiConfig {
+            daily_send_quota: 10,
+            ..crate::testing::api_config()
+        },
+    );
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (message_id, thread) = message(app.pool(), Some(alice.id), bob.id, "hi").await;
+    // Fail the insert itself, after the quota was already counted
+    sqlx::raw_sql(
+        r#"
+        CREATE FUNCTION fail_insert() RETURNS trigger AS $$
+        BEGIN
+            RAISE EXCEPTION 'insert failed';
+        END
+        $$ LANGUAGE plpgsql;
+        CREATE TRIGGER fail_insert BEFORE INSERT ON messages
+            FOR EACH ROW WHEN (NEW.content = 'boom') EXECUTE FUNCTION fail_insert();
+        "#,
+    )
+    .execute(app.pool())
+    .await
+    .unwrap();
+
+    let response = app
+        .post(
+            &format!("/api/messages/{message_id}/reply"),
+            &bob,
+            serde_json::json!({ "content": "boom" }),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::INTERNAL_SERVER_ERROR);
+    let quota_rows: i64 = sqlx::query_scalar("SELECT count(*) FROM send_quotas")
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(quota_rows, 0, "the quota count was rolled back");
+    let messages: i64 = sqlx::query_scalar("SELECT count(*) FROM messages WHERE thread_id = $1")
+        .bind(thread)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(messages, 1);
+}
+
+// ===== Read receipts =====
+
+#[sqlx::test]
+async fn the_sender_is_told_when_their_message_is_read(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (message_id, thread) = message(app.pool(), Some(alice.id), bob.id, "hi").await;
+    let (status, mut events) = app.events(&alice, "").await;
+    assert_eq!(status, StatusCode::OK);
+
+    app.get(&format!("/api/conversations/{thread}"), &bob).await;
+
+    let receipt = events.next_of("read_receipt").await;
+    assert_eq!(receipt["thread_id"], thread.to_string());
+    assert_eq!(receipt["message_ids"], serde_json::json!([message_id]));
+    assert!(receipt["read_at"].is_string());
+    // Only that it was read, never by whom
+    assert!(
+        !receipt.to_string().contains(&bob.id.to_string()),
+        "{receipt}"
+    );
+}
+
+// ===== Preferences =====
+
+#[sqlx::test]
+async fn interleaved_partial_preference_updates_keep_both_fields(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let alice = app.user("alice").await;
+    let version = app.get("/api/preferences", &alice).await.json()["version"].clone();
+
+    // Two tabs read the same version, then each changes a different field
+    let first = app
+        .post(
+            "/api/preferences",
+            &alice,
+            serde_json::json!({ "theme": "dark" }),
+        )
+        .await;
+    assert_eq!(first.status, StatusCode::OK);
+    let second = app
+        .post(
+            "/api/preferences",
+            &alice,
+            serde_json::json!({ "show_typing_indicators": false }),
+        )
+        .await;
+    assert_eq!(second.status, StatusCode::OK);
+
+    let prefs = app.get("/api/preferences", &alice).await.json();
+    assert_eq!(prefs["theme"], "dark");
+    assert_eq!(prefs["show_typing_indicators"], false);
+
+    // A tab that asks to update only the version it read loses the race
+    let stale = app
+        .post(
+            "/api/preferences",
+            &alice,
+            serde_json::json!({ "theme": "light", "expected_version": version }),
+        )
+        .await;
+    assert_eq!(stale.status, StatusCode::CONFLICT);
+    assert_eq!(
+        app.get("/api/preferences", &alice).await.json()["theme"],
+        "dark"
+    );
+}
+
+#[sqlx::test]
+async fn a_partial_preference_update_returns_the_merged_preferences(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let alice = app.user("alice").await;
+    app.post(
+        "/api/preferences",
+        &alice,
+        serde_json::json!({ "theme": "dark", "share_presence": false }),
+    )
+    .await;
+    let before = app.get("/api/preferences", &alice).await.json();
+
+    let response = app
+        .post(
+            "/api/preferences",
+            &alice,
+            serde_json::json!({ "notification_sound": false }),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let merged = response.json();
+    assert_eq!(merged["notification_sound"], false);
+    assert_eq!(merged["theme"], "dark");
+    assert_eq!(merged["share_presence"], false);
+    for (field, value) in before.as_object().unwrap() {
+        if !["notification_sound", "version"].contains(&field.as_str()) {
+            assert_eq!(&merged[field], value, "{field}");
+        }
+    }
+    assert_eq!(merged, app.get("/api/preferences", &alice).await.json());
+}
+
+#[sqlx::test]
+async fn a_preference_change_reaches_every_open_device(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let alice = app.user("alice").await;
+    let (_, mut phone) = app.events(&alice, "").await;
+    let (_, mut laptop) = app.events(&alice, "").await;
+
+    let response = app
+        .post(
+            "/api/preferences",
+            &alice,
+            serde_json::json!({ "theme": "dark" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    for device in [&mut phone, &mut laptop] {
+        let event = device.next_of("preferences_updated").await;
+        assert_eq!(event, response.json());
+    }
+}
+
+// ===== Broadcast export =====
+
+async fn comment(pool: &PgPool, broadcast: Uuid, user: &TestUser, parent: Option<Uuid>) -> Uuid {
+    crate::db::create_broadcast_comment(pool, broadcast, user.id, "a comment", parent)
+        .await
+        .unwrap()
+}
+
+/// Each comment of an exported tree as (depth, id), depth first.
+fn flatten(threads: &serde_json::Value, depth: usize, out: &mut Vec<(usize, String)>) {
+    for thread in threads.as_array().unwrap() {
+        out.push((depth, thread["id"].as_str().unwrap().to_string()));
+        flatten(&thread["replies"], depth + 1, out);
+    }
+}
+
+#[sqlx::test]
+async fn an_export_nests_replies_under_their_parents(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let broadcast = crate::db::create_broadcast(app.pool(), alice.id, "hello", false, false, None)
+        .await
+        .unwrap();
+    let first = comment(app.pool(), broadcast, &bob, None).await;
+    let reply = comment(app.pool(), broadcast, &alice, Some(first)).await;
+    let nested = comment(app.pool(), broadcast, &bob, Some(reply)).await;
+    let second = comment(app.pool(), broadcast, &alice, None).await;
+    sqlx::query("UPDATE broadcast_comments SET deleted_at = NOW() WHERE id = $1")
+        .bind(first)
+        .execute(app.pool())
+        .await
+        .unwrap();
+
+    let response = app
+        .get(&format!("/api/broadcasts/{broadcast}/export"), &bob)
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let export = response.json();
+    assert_eq!(export["broadcast"]["id"], broadcast.to_string());
+    assert!(
+        export.get("author").is_none(),
+        "only the author gets metadata"
+    );
+    let mut tree = Vec::new();
+    flatten(&export["comments"], 0, &mut tree);
+    let expected = [(0, first), (1, reply), (2, nested), (0, second)];
+    assert_eq!(
+        tree,
+        expected.map(|(depth, id)| (depth, id.to_string())).to_vec()
+    );
+    // The deleted comment stays as a placeholder so its replies keep a parent
+    assert_eq!(export["comments"][0]["content"], "[deleted]");
+    assert_eq!(export["comments"][0]["user_id"], serde_json::Value::Null);
+
+    let own = app
+        .get(&format!("/api/broadcasts/{broadcast}/export"), &alice)
+        .await
+        .json();
+    assert!(own["author"]["view_timeline"].is_array());
+}
+
+#[sqlx::test]
+async fn an_export_spans_several_pages_of_threads(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let alice = app.user("alice").await;
+    let broadcast = crate::db::create_broadcast(app.pool(), alice.id, "hello", false, false, None)
+        .await
+        .unwrap();
+    let mut roots = Vec::new();
+    for _ in 0..EXPORT_THREADS_PER_PAGE + 1 {
+        let root = comment(app.pool(), broadcast, &alice, None).await;
+        comment(app.pool(), broadcast, &alice, Some(root)).await;
+        roots.push(root.to_string());
+    }
+
+    let export = app
+        .get(&format!("/api/broadcasts/{broadcast}/export"), &alice)
+        .await
+        .json();
+
+    let threads = export["comments"].as_array().unwrap();
+    let ids: Vec<_> = threads.iter().map(|t| t["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, roots);
+    assert!(threads
+        .iter()
+        .all(|t| t["replies"].as_array().unwrap().len() == 1));
+}
+
+// ===== Starting conversations =====
+
+#[sqlx::test]
+async fn a_conversation_starts_by_username_in_any_case(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("Bob").await);
+
+    let response = app
+        .post(
+            "/api/conversations",
+            &alice,
+            serde_json::json!({ "recipient_username": "bOB", "content": "hi" }),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::CREATED);
+    let started = response.json();
+    assert_eq!(started["recipient_id"], bob.id.to_string());
+    let thread = started["thread_id"].as_str().unwrap();
+    let view = app
+        .get(&format!("/api/conversations/{thread}"), &bob)
+        .await
+        .json();
+    assert_eq!(ids(&view), vec![started["message_id"].as_str().unwrap()]);
+}
+
+#[sqlx::test]
+async fn a_conversation_with_an_unknown_username_is_404(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let alice = app.user("alice").await;
+
+    let response = app
+        .post(
+            "/api/conversations",
+            &alice,
+            serde_json::json!({ "recipient_username": "nobody", "content": "hi" }),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let messages: i64 = sqlx::query_scalar("SELECT count(*) FROM messages")
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(messages, 0);
+}
+
+// ===== Broadcast ownership =====
+
+/// Broadcast `id` as it appears in `user`'s feed.
+async fn feed_item(app: &TestApp, user: &TestUser, id: Uuid) -> serde_json::Value {
+    let feed = app.get("/api/broadcasts", user).await.json();
+    feed["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|b| b["id"] == id.to_string())
+        .expect("the broadcast is in the feed")
+        .clone()
+}
+
+#[sqlx::test]
+async fn is_mine_covers_the_viewers_own_anonymous_broadcasts(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let anonymous = crate::db::create_broadcast(app.pool(), alice.id, "guess", true, false, None)
+        .await
+        .unwrap();
+    let named = crate::db::create_broadcast(app.pool(), bob.id, "hi all", false, false, None)
+        .await
+        .unwrap();
+
+    let own_anonymous = feed_item(&app, &alice, anonymous).await;
+    assert_eq!(own_anonymous["is_mine"], true);
+    assert_eq!(own_anonymous["sender_username"], serde_json::Value::Null);
+    assert_eq!(feed_item(&app, &alice, named).await["is_mine"], false);
+
+    assert_eq!(feed_item(&app, &bob, anonymous).await["is_mine"], false);
+    assert_eq!(feed_item(&app, &bob, named).await["is_mine"], true);
+}
+
+// ===== Conversation import =====
+
+#[sqlx::test]
+async fn an_exported_thread_imports_back_as_it_was(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (_, thread) = message(app.pool(), Some(alice.id), bob.id, "hi bob").await;
+    crate::db::create_reply(app.pool(), thread, bob.id, alice.id, "hi!", None, None)
+        .await
+        .unwrap();
+    let export = app
+        .get(&format!("/api/conversations/{thread}"), &bob)
+        .await
+        .json();
+
+    let response = app
+        .post(
+            "/api/conversations/import",
+            &bob,
+            serde_json::json!({ "other_party": "Alice", "messages": export }),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::CREATED);
+    let imported = response.json()["thread_id"].as_str().unwrap().to_string();
+    let view = app
+        .get(&format!("/api/conversations/{imported}"), &bob)
+        .await
+        .json();
+    let shape = |messages: &serde_json::Value| -> Vec<(String, bool, String)> {
+        messages
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| {
+                (
+                    m["content"].as_str().unwrap().to_string(),
+                    m["is_mine"].as_bool().unwrap(),
+                    m["created_at"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    };
+    assert_eq!(shape(&view), shape(&export));
+
+    // Alice's side keeps no sender, and the copy is read-only
+    let senders: Vec<Option<Uuid>> = sqlx::query_scalar(
+        "SELECT sender_id FROM messages WHERE thread_id = $1 ORDER BY created_at",
+    )
+    .bind(imported.parse::<Uuid>().unwrap())
+    .fetch_all(app.pool())
+    .await
+    .unwrap();
+    assert_eq!(senders, vec![None, Some(bob.id)]);
+    let reply = app
+        .post(
+            &format!("/api/conversations/{imported}/reply"),
+            &bob,
+            serde_json::json!({ "content": "again" }),
+        )
+        .await;
+    assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+// ===== SSE hub =====
+
+#[sqlx::test]
+async fn the_hub_forgets_a_user_once_their_last_stream_closes(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let alice = app.user("alice").await;
+    let hub = app.state.notification_hub.clone();
+
+    let (_, first_tab) = app.events(&alice, "").await;
+    let (_, second_tab) = app.events(&alice, "").await;
+    assert!(hub.channels.contains_key(&alice.id));
+
+    drop(first_tab);
+    assert!(
+        hub.channels.contains_key(&alice.id),
+        "another tab is still listening"
+    );
+
+    drop(second_tab);
+    assert!(!hub.channels.contains_key(&alice.id));
+}
+
+// ===== Dry runs =====
+
+async fn count(pool: &PgPool, query: &str) -> i64 {
+    sqlx::query_scalar(query).fetch_one(pool).await.unwrap()
+}
+
+#[sqlx::test]
+async fn an_account_deletion_dry_run_counts_without_deleting(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (to_bob, _) = message(app.pool(), Some(alice.id), bob.id, "one").await;
+    message(app.pool(), Some(alice.id), bob.id, "two").await;
+    message(app.pool(), Some(bob.id), alice.id, "back").await;
+    let broadcast = crate::db::create_broadcast(app.pool(), alice.id, "hi", false, false, None)
+        .await
+        .unwrap();
+    comment(app.pool(), broadcast, &alice, None).await;
+    crate::db::add_message_reaction(app.pool(), to_bob, alice.id, "👍")
+        .await
+        .unwrap();
+
+    let response = app
+        .request(Method::DELETE, "/api/me?dry_run=true", Some(&alice), None)
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.json(),
+        serde_json::json!({
+            "dry_run": true,
+            "messages_received": 1,
+            "messages_sent": 2,
+            "broadcasts": 1,
+            "comments": 1,
+            "reactions": 1,
+            "scheduled_broadcasts": 0,
+        })
+    );
+    assert_eq!(app.get("/api/me", &alice).await.status, StatusCode::OK);
+    assert_eq!(count(app.pool(), "SELECT count(*) FROM messages").await, 3);
+    assert_eq!(
+        count(app.pool(), "SELECT count(*) FROM broadcast_comments").await,
+        1
+    );
+}
+
+#[sqlx::test]
+async fn a_thread_deletion_dry_run_counts_without_deleting(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (_, thread) = message(app.pool(), Some(alice.id), bob.id, "one").await;
+    crate::db::create_reply(app.pool(), thread, bob.id, alice.id, "two", None, None)
+        .await
+        .unwrap();
+
+    let response = app
+        .request(
+            Method::DELETE,
+            &format!("/api/conversations/{thread}/delete?dry_run=true"),
+            Some(&bob),
+            None,
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.json(),
+        serde_json::json!({ "dry_run": true, "messages": 2 })
+    );
+    let view = app
+        .get(&format!("/api/conversations/{thread}"), &bob)
+        .await
+        .json();
+    assert_eq!(view.as_array().unwrap().len(), 2);
+}
+
+#[sqlx::test]
+async fn a_comment_bulk_delete_dry_run_counts_without_deleting(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let broadcast = crate::db::create_broadcast(app.pool(), alice.id, "hi", false, false, None)
+        .await
+        .unwrap();
+    comment(app.pool(), broadcast, &bob, None).await;
+    comment(app.pool(), broadcast, &bob, None).await;
+    comment(app.pool(), broadcast, &alice, None).await;
+
+    let response = app
+        .request(
+            Method::DELETE,
+            &format!("/api/broadcasts/{broadcast}/comments/mine?dry_run=true"),
+            Some(&bob),
+            None,
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.json(),
+        serde_json::json!({ "dry_run": true, "deleted": 2 })
+    );
+    assert_eq!(
+        count(
+            app.pool(),
+            "SELECT count(*) FROM broadcast_comments WHERE deleted_at IS NULL"
+        )
+        .await,
+        3
+    );
+}
+
+// ===== Origin fingerprints =====
+
+#[sqlx::test]
+async fn anonymous_broadcasts_from_one_origin_share_a_fingerprint(pool: PgPool) {
+    let app = TestApp::with_config(
+        pool,
+        ApiConfig {
+            abuse_fingerprint_key: Some(crate::fingerprint::FingerprintKey::new(
+                "0123456789abcdef0123456789abcdef",
+            )),
+            ..crate::testing::api_config()
+        },
+    );
+    let alice = app.user("alice").await;
+    let post = |content: &'static str, is_anonymous: bool, agent: &'static str| {
+        let alice = &alice;
+        let app = &app;
+        async move {
+            let response = app
+                .send(
+                    Method::POST,
+                    "/api/broadcasts",
+                    Some(alice),
+                    &[(header::USER_AGENT, agent)],
+                    Some(serde_json::json!({ "content": content, "is_anonymous": is_anonymous })),
+                )
+                .await;
+            assert!(response.status.is_success(), "{}", response.status);
+            assert!(!String::from_utf8_lossy(&response.body).contains("fingerprint"));
+        }
+    };
+    post("one", true, "Firefox").await;
+    post("two", true, "Firefox").await;
+    post("three", true, "Chrome").await;
+    post("named", false, "Firefox").await;
+
+    let fingerprints: Vec<(String, Option<String>)> =
+        sqlx::query_as("SELECT content, origin_fingerprint FROM broadcasts ORDER BY created_at")
+            .fetch_all(app.pool())
+            .await
+            .unwrap();
+    let of = |content: &str| {
+        fingerprints
+            .iter()
+            .find(|(c, _)| c == content)
+            .unwrap()
+            .1
+            .clone()
+    };
+    assert!(of("one").is_some());
+    assert_eq!(of("one"), of("two"));
+    assert_ne!(of("one"), of("three"));
+    assert_eq!(of("named"), None);
+}
+
+#[sqlx::test]
+async fn no_fingerprint_is_stored_without_a_key(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let alice = app.user("alice").await;
+
+    let response = app
+        .post(
+            "/api/broadcasts",
+            &alice,
+            serde_json::json!({ "content": "hi", "is_anonymous": true }),
+        )
+        .await;
+
+    assert!(response.status.is_success(), "{}", response.status);
+    let stored: i64 =
+        sqlx::query_scalar("SELECT count(*) FROM broadcasts WHERE origin_fingerprint IS NOT NULL")
+            .fetch_one(app.pool())
+            .await
+            .unwrap();
+    assert_eq!(stored, 0);
+}
+
+// ===== Thread replies =====
+
+async fn recipient_of(pool: &PgPool, message_id: &serde_json::Value) -> Uuid {
+    sqlx::query_scalar("SELECT recipient_id FROM messages WHERE id = $1")
+        .bind(message_id.as_str().unwrap().parse::<Uuid>().unwrap())
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn a_thread_reply_goes_to_the_other_participant(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    let (_, thread) = message(app.pool(), Some(alice.id), bob.id, "hi").await;
+    crate::db::create_reply(app.pool(), thread, bob.id, alice.id, "hey", None, None)
+        .await
+        .unwrap();
+    crate::db::create_reply(
+        app.pool(),
+        thread,
+        alice.id,
+        bob.id,
+        "how are you",
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let path = format!("/api/conversations/{thread}/reply");
+    let reply = |user| app.post(&path, user, serde_json::json!({ "content": "fine" }));
+
+    let from_bob = reply(&bob).await;
+    assert_eq!(from_bob.status, StatusCode::CREATED);
+    assert_eq!(
+        recipient_of(app.pool(), &from_bob.json()["message_id"]).await,
+        alice.id
+    );
+
+    let from_alice = reply(&alice).await;
+    assert_eq!(from_alice.status, StatusCode::CREATED);
+    assert_eq!(
+        recipient_of(app.pool(), &from_alice.json()["message_id"]).await,
+        bob.id
+    );
+
+    assert!(reply(&carol).await.status.is_client_error());
+}
+
+// ===== Unseen broadcasts =====
+
+async fn unseen(app: &TestApp, user: &TestUser) -> serde_json::Value {
+    app.get("/api/broadcasts/unseen-count", user).await.json()["count"].clone()
+}
+
+#[sqlx::test]
+async fn unseen_broadcasts_count_new_posts_except_own_and_blocked(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    app.get("/api/broadcasts", &alice).await;
+    assert_eq!(unseen(&app, &alice).await, 0);
+
+    let post = |author: &TestUser, anonymous| {
+        crate::db::create_broadcast(app.pool(), author.id, "news", anonymous, false, None)
+    };
+    post(&bob, false).await.unwrap();
+    post(&bob, true).await.unwrap();
+    post(&carol, false).await.unwrap();
+    post(&alice, false).await.unwrap();
+    crate::db::block_user(app.pool(), alice.id, carol.id)
+        .await
+        .unwrap();
+    assert_eq!(unseen(&app, &alice).await, 2);
+
+    app.get("/api/broadcasts", &alice).await;
+    assert_eq!(unseen(&app, &alice).await, 0);
+}
+
+// ===== Reaction leaderboard =====
+
+#[sqlx::test]
+async fn the_reaction_leaderboard_ranks_emoji_and_flags_the_callers(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    let broadcast = crate::db::create_broadcast(app.pool(), alice.id, "hello", false, false, None)
+        .await
+        .unwrap();
+    let first = comment(app.pool(), broadcast, &bob, None).await;
+    let second = comment(app.pool(), broadcast, &carol, None).await;
+    for (comment, user, emoji) in [
+        (first, &alice, "😂"),
+        (first, &bob, "😂"),
+        (first, &carol, "👍"),
+        (second, &bob, "😂"),
+        (second, &carol, "👍"),
+        (second, &alice, "🔥"),
+    ] {
+        crate::db::react_to_comment(app.pool(), comment, user.id, emoji)
+            .await
+            .unwrap();
+    }
+
+    let response = app
+        .get(
+            &format!("/api/broadcasts/{broadcast}/reactions/top"),
+            &alice,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let leaderboard = response.json();
+    assert_eq!(leaderboard["total"], 6);
+    let ranked: Vec<(&str, i64, bool)> = leaderboard["reactions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            (
+                r["emoji"].as_str().unwrap(),
+                r["count"].as_i64().unwrap(),
+                r["mine"].as_bool().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(ranked, [("😂", 3, true), ("👍", 2, false), ("🔥", 1, true)]);
+}
+
+// ===== Content length =====
+
+#[sqlx::test]
+async fn content_length_counts_characters_not_bytes(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let max = crate::testing::api_config().max_content_length;
+    let at_limit = "😀".repeat(max);
+    let over_limit = "😀".repeat(max + 1);
+    assert!(at_limit.len() > max, "the emoji are multibyte");
+    let broadcast = crate::db::create_broadcast(app.pool(), alice.id, "hello", false, false, None)
+        .await
+        .unwrap();
+
+    let attempts = [
+        (
+            "/api/messages".to_string(),
+            serde_json::json!({ "recipient_id": bob.id }),
+        ),
+        (
+            "/api/broadcasts".to_string(),
+            serde_json::json!({ "is_anonymous": false }),
+        ),
+        (
+            format!("/api/broadcasts/{broadcast}/comments"),
+            serde_json::json!({ "parent_comment_id": null }),
+        ),
+    ];
+    for (path, mut body) in attempts {
+        body["content"] = serde_json::json!(at_limit);
+        let response = app.post(&path, &alice, body.clone()).await;
+        assert!(response.status.is_success(), "{path} at the limit");
+
+        body["content"] = serde_json::json!(over_limit);
+        let response = app.post(&path, &alice, body).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{path} over it");
+        assert_eq!(
+            response.json()["message"],
+            format!("Content can be at most {max} characters")
+        );
+    }
+}
+
+// ===== Pinned messages =====
+
+#[sqlx::test]
+async fn pinning_a_message_notifies_the_other_participant(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (message_id, thread) = message(app.pool(), Some(alice.id), bob.id, "remember").await;
+    let (_, mut events) = app.events(&bob, "").await;
+
+    let pin = app
+        .post(
+            &format!("/api/messages/{message_id}/pin"),
+            &alice,
+            serde_json::json!({}),
+        )
+        .await;
+    assert_eq!(pin.status, StatusCode::OK);
+
+    let event = events.next_of("message_pinned").await;
+    assert_eq!(event["thread_id"], thread.to_string());
+    assert_eq!(event["message_id"], message_id.to_string());
+    assert_eq!(event["pinned"], true);
+}
+
+#[sqlx::test]
+async fn pinning_in_a_muted_thread_sends_no_event(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (message_id, thread) = message(app.pool(), Some(alice.id), bob.id, "remember").await;
+    let (_, mut events) = app.events(&bob, "").await;
+    let pin_path = format!("/api/messages/{message_id}/pin");
+    let toggle_pin = || app.post(&pin_path, &alice, serde_json::json!({}));
+
+    let mute = app
+        .post(
+            &format!("/api/conversations/{thread}/mute"),
+            &bob,
+            serde_json::json!({}),
+        )
+        .await;
+    assert_eq!(mute.status, StatusCode::OK);
+    assert_eq!(toggle_pin().await.json()["pinned"], true);
+
+    let unmute = app
+        .post(
+            &format!("/api/conversations/{thread}/unmute"),
+            &bob,
+            serde_json::json!({}),
+        )
+        .await;
+    assert_eq!(unmute.status, StatusCode::OK);
+    assert_eq!(toggle_pin().await.json()["pinned"], false);
+
+    // The first event bob gets is the unpin; the pin while muted never came
+    let event = events.next_of("message_pinned").await;
+    assert_eq!(event["pinned"], false);
+}
+
+#[sqlx::test]
+async fn pinned_messages_are_flagged_and_listed(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (first, thread) = message(app.pool(), Some(alice.id), bob.id, "remember").await;
+    let reply = reply_in(&app, &bob, thread).await;
+    let pin = app
+        .post(
+            &format!("/api/messages/{first}/pin"),
+            &alice,
+            serde_json::json!({}),
+        )
+        .await;
+    assert_eq!(pin.status, StatusCode::OK);
+
+    // Pins are shared: bob sees alice's pin, and it comes first
+    let view = app
+        .get(&format!("/api/conversations/{thread}"), &bob)
+        .await
+        .json();
+    assert_eq!(view[0]["id"], first.to_string());
+    assert_eq!(view[0]["is_pinned"], true);
+    assert_eq!(view[1]["id"], reply);
+    assert_eq!(view[1]["is_pinned"], false);
+
+    let pinned = app
+        .get(&format!("/api/conversations/{thread}/pinned"), &bob)
+        .await
+        .json();
+    let pinned = pinned.as_array().unwrap();
+    assert_eq!(pinned.len(), 1);
+    assert_eq!(pinned[0]["id"], first.to_string());
+    assert_eq!(pinned[0]["is_pinned"], true);
+}
+
+// ===== Muted threads =====
+
+/// Mute `thread` for `user`, until `until` if given.
+async fn mute(app: &TestApp, user: &TestUser, thread: Uuid, until: Option<&str>) {
+    let response = app
+        .post(
+            &format!("/api/conversations/{thread}/mute"),
+            user,
+            serde_json::json!({ "muted_until": until }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+/// Have `sender` reply in `thread`; returns the new message's id.
+async fn reply_in(app: &TestApp, sender: &TestUser, thread: Uuid) -> serde_json::Value {
+    let response = app
+        .post(
+            &format!("/api/conversations/{thread}/reply"),
+            sender,
+            serde_json::json!({ "content": "ping" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    response.json()["message_id"].clone()
+}
+
+#[sqlx::test]
+async fn new_messages_in_a_muted_thread_send_no_event(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (_, muted) = message(app.pool(), Some(alice.id), bob.id, "hi").await;
+    let (_, other) = message(app.pool(), Some(alice.id), bob.id, "hello").await;
+    mute(&app, &bob, muted, None).await;
+    let (_, mut events) = app.events(&bob, "").await;
+
+    reply_in(&app, &alice, muted).await;
+    let delivered = reply_in(&app, &alice, other).await;
+
+    // The muted thread's message never came, so the other one is first
+    let event = events.next_of("new_message").await;
+    assert_eq!(event["message_id"], delivered);
+    assert_eq!(event["thread_id"], other.to_string());
+}
+
+/// The `muted` flag on the first thread in `user`'s conversation list.
+async fn listed_muted(app: &TestApp, user: &TestUser) -> serde_json::Value {
+    app.get("/api/conversations", user).await.json()[0]["muted"].clone()
+}
+
+#[sqlx::test]
+async fn a_mute_with_muted_until_ends_on_its_own(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (_, thread) = message(app.pool(), Some(alice.id), bob.id, "hi").await;
+    mute(&app, &bob, thread, Some("2999-01-01T00:00:00Z")).await;
+    assert_eq!(listed_muted(&app, &bob).await, true);
+
+    sqlx::query("UPDATE thread_mutes SET muted_until = NOW() - INTERVAL '1 second'")
+        .execute(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(listed_muted(&app, &bob).await, false);
+
+    let (_, mut events) = app.events(&bob, "").await;
+    let delivered = reply_in(&app, &alice, thread).await;
+    assert_eq!(events.next_of("new_message").await["message_id"], delivered);
+}
+
+// ===== Conversation labels =====
+
+/// Thread ids in `user`'s conversation list for `query`.
+async fn conversation_threads(app: &TestApp, user: &TestUser, query: &str) -> Vec<String> {
+    app.get(&format!("/api/conversations{query}"), user)
+        .await
+        .json()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["thread_id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+async fn label(app: &TestApp, user: &TestUser, thread: Uuid, label: &str) -> StatusCode {
+    app.post(
+        &format!("/api/conversations/{thread}/labels"),
+        user,
+        serde_json::json!({ "label": label }),
+    )
+    .await
+    .status
+}
+
+#[sqlx::test]
+async fn the_label_filter_lists_only_labelled_threads(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    let (_, work) = message(app.pool(), Some(alice.id), bob.id, "the report").await;
+    let (_, friends) = message(app.pool(), Some(carol.id), alice.id, "drinks?").await;
+    assert_eq!(label(&app, &alice, work, "Work").await, StatusCode::OK);
+    assert_eq!(
+        label(&app, &alice, friends, "Friends").await,
+        StatusCode::OK
+    );
+
+    assert_eq!(
+        conversation_threads(&app, &alice, "?label=Work").await,
+        [work.to_string()]
+    );
+    assert_eq!(
+        conversation_threads(&app, &alice, "?label=Friends").await,
+        [friends.to_string()]
+    );
+    assert_eq!(conversation_threads(&app, &alice, "").await.len(), 2);
+}
+
+#[sqlx::test]
+async fn labels_are_private_to_each_participant(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    let (_, thread) = message(app.pool(), Some(alice.id), bob.id, "hi").await;
+    assert_eq!(label(&app, &alice, thread, "Work").await, StatusCode::OK);
+    assert_eq!(label(&app, &bob, thread, "Friends").await, StatusCode::OK);
+    assert_eq!(
+        label(&app, &carol, thread, "Spying").await,
+        StatusCode::NOT_FOUND
+    );
+
+    for (user, labels) in [(&alice, ["Work"]), (&bob, ["Friends"])] {
+        let list = app.get("/api/conversations", user).await.json();
+        assert_eq!(list[0]["labels"], serde_json::json!(labels));
+    }
+    assert!(conversation_threads(&app, &bob, "?label=Work")
+        .await
+        .is_empty());
+    let bobs_labels = app.get("/api/conversations/labels", &bob).await.json();
+    assert_eq!(
+        bobs_labels,
+        serde_json::json!([{ "label": "Friends", "thread_count": 1 }])
+    );
+}
+
+// ===== Typing =====
+
+#[sqlx::test]
+async fn typing_reports_how_long_the_indicator_has_left(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (_, thread) = message(app.pool(), Some(alice.id), bob.id, "hi").await;
+    let path = format!("/api/conversations/{thread}/typing");
+    let window = crate::db::TYPING_INDICATOR_WINDOW.as_millis() as u64;
+    let age_indicator = |secs: i32| {
+        sqlx::query(
+            "UPDATE typing_indicators SET started_at = NOW() - make_interval(secs => $1) \
+             WHERE thread_id = $2",
+        )
+        .bind(secs)
+        .bind(thread)
+        .execute(app.pool())
+    };
+
+    let expires_in = |response: crate::testing::TestResponse| {
+        assert_eq!(response.status, StatusCode::OK);
+        response.json()["expires_in_ms"].as_u64().unwrap()
+    };
+    let fresh = expires_in(app.post(&path, &alice, serde_json::json!({})).await);
+    assert!(fresh <= window && fresh > window - 1000, "{fresh}");
+
+    // Within the debounce the indicator keeps its start, so less is left
+    age_indicator(2).await.unwrap();
+    let debounced = expires_in(app.post(&path, &alice, serde_json::json!({})).await);
+    assert!(
+        debounced <= window - 2000 && debounced > window - 3000,
+        "{debounced}"
+    );
+
+    // Past it the indicator restarts with a full window
+    age_indicator(3).await.unwrap();
+    let refreshed = expires_in(app.post(&path, &alice, serde_json::json!({})).await);
+    assert!(
+        refreshed <= window && refreshed > window - 1000,
+        "{refreshed}"
+    );
+}
+
+// ===== Unread conversations =====
+
+#[sqlx::test]
+async fn unread_only_skips_read_threads_even_when_pinned(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    let (_, unread) = message(app.pool(), Some(bob.id), alice.id, "new").await;
+    let (_, read) = message(app.pool(), Some(carol.id), alice.id, "seen").await;
+    let (_, sent) = message(app.pool(), Some(alice.id), carol.id, "mine").await;
+    crate::db::mark_thread_as_read(app.pool(), read, alice.id)
+        .await
+        .unwrap();
+    let pin = app
+        .post(
+            &format!("/api/conversations/{read}/pin"),
+            &alice,
+            serde_json::json!({}),
+        )
+        .await;
+    assert_eq!(pin.status, StatusCode::OK);
+
+    assert_eq!(
+        conversation_threads(&app, &alice, "?unread_only=true").await,
+        [unread.to_string()]
+    );
+    let mut all = conversation_threads(&app, &alice, "").await;
+    all.sort();
+    let mut expected = [unread, read, sent].map(|t| t.to_string());
+    expected.sort();
+    assert_eq!(all, expected);
+}
+
+// ===== Quote ranges =====
+
+#[sqlx::test]
+async fn a_quote_range_is_checked_against_the_quoted_message(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    // 9 characters, 12 bytes
+    let (quoted, thread) = message(app.pool(), Some(alice.id), bob.id, "see 😀 you").await;
+    let path = format!("/api/conversations/{thread}/reply");
+    let quote = |start: usize, end: usize| {
+        serde_json::json!({
+            "content": "this",
+            "reply_to_message_id": quoted,
+            "quote_range": { "start": start, "end": end },
+        })
+    };
+
+    let reply = app.post(&path, &bob, quote(4, 9)).await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    let reply_id = reply.json()["message_id"].clone();
+    let view = app
+        .get(&format!("/api/conversations/{thread}"), &alice)
+        .await
+        .json();
+    let stored = view
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["id"] == reply_id)
+        .unwrap();
+    assert_eq!(stored["reply_to_message_id"], quoted.to_string());
+    assert_eq!(
+        stored["quote_range"],
+        serde_json::json!({ "start": 4, "end": 9 })
+    );
+
+    for (start, end) in [(4, 10), (5, 5), (7, 6)] {
+        let response = app.post(&path, &bob, quote(start, end)).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{start}..{end}");
+    }
+
+    sqlx::query("UPDATE messages SET deleted_at = NOW(), deleted_by = $1 WHERE id = $2")
+        .bind(alice.id)
+        .bind(quoted)
+        .execute(app.pool())
+        .await
+        .unwrap();
+    let response = app.post(&path, &bob, quote(0, 3)).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+// ===== Messaging yourself =====
+
+#[sqlx::test]
+async fn messaging_yourself_is_rejected(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    let (received, _) = message(app.pool(), Some(bob.id), alice.id, "hi").await;
+
+    let attempts = [
+        (
+            "/api/messages".to_string(),
+            serde_json::json!({ "recipient_id": alice.id, "content": "note to self" }),
+        ),
+        (
+            "/api/conversations".to_string(),
+            serde_json::json!({ "recipient_username": "ALICE", "content": "note to self" }),
+        ),
+        (
+            format!("/api/messages/{received}/forward"),
+            serde_json::json!({ "recipient_id": alice.id }),
+        ),
+    ];
+    for (path, body) in attempts {
+        let response = app.post(&path, &alice, body).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{path}");
+        assert_eq!(response.json()["message"], "You cannot message yourself");
+    }
+    assert_eq!(count(app.pool(), "SELECT COUNT(*) FROM messages").await, 1);
+}
+
+// ===== Pinned conversations =====
+
+#[sqlx::test]
+async fn a_pinned_thread_is_listed_and_flagged(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    let (_, pinned) = message(app.pool(), Some(bob.id), alice.id, "keep this").await;
+    let (_, other) = message(app.pool(), Some(carol.id), alice.id, "newer").await;
+    let pin = app
+        .post(
+            &format!("/api/conversations/{pinned}/pin"),
+            &alice,
+            serde_json::json!({}),
+        )
+        .await;
+    assert_eq!(pin.json()["pinned"], true);
+
+    let list = app.get("/api/conversations/pinned", &alice).await.json();
+    let list = list.as_array().unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0]["thread_id"], pinned.to_string());
+    assert_eq!(list[0]["is_pinned"], true);
+    assert_eq!(list[0]["content"], "keep this");
+
+    let all = app.get("/api/conversations", &alice).await.json();
+    let flag = |thread: Uuid| {
+        all.as_array()
+            .unwrap()
+            .iter()
+            .find(|t| t["thread_id"] == thread.to_string())
+            .unwrap()["is_pinned"]
+            .clone()
+    };
+    assert_eq!(flag(pinned), true);
+    assert_eq!(flag(other), false);
+    // Pins are per user
+    assert!(app
+        .get("/api/conversations/pinned", &bob)
+        .await
+        .json()
+        .as_array()
+        .unwrap()
+        .is_empty());
+}
+
+// ===== Blocking =====
+
+/// Usernames in `user`'s directory.
+async fn directory(app: &TestApp, user: &TestUser) -> Vec<String> {
+    app.get("/api/users", user).await.json()["users"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|u| u["username"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[sqlx::test]
+async fn a_blocked_user_leaves_the_blockers_directory_and_conversations(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    let (_, with_bob) = message(app.pool(), Some(alice.id), bob.id, "hi bob").await;
+    let (_, with_carol) = message(app.pool(), Some(carol.id), alice.id, "hi alice").await;
+    let path = |action| format!("/api/users/{}/{action}", bob.id);
+
+    let block = app
+        .post(&path("block"), &alice, serde_json::json!({}))
+        .await;
+    assert_eq!(block.status, StatusCode::OK);
+    assert_eq!(directory(&app, &alice).await, ["carol"]);
+    assert_eq!(
+        conversation_threads(&app, &alice, "").await,
+        [with_carol.to_string()]
+    );
+
+    // One-sided: bob's view doesn't change
+    assert!(directory(&app, &bob).await.contains(&"alice".to_string()));
+    assert_eq!(
+        conversation_threads(&app, &bob, "").await,
+        [with_bob.to_string()]
+    );
+
+    let unblock = app
+        .post(&path("unblock"), &alice, serde_json::json!({}))
+        .await;
+    assert_eq!(unblock.status, StatusCode::OK);
+    assert_eq!(directory(&app, &alice).await.len(), 2);
+    assert_eq!(conversation_threads(&app, &alice, "").await.len(), 2);
+}
+
+// ===== Presence =====
+
+#[sqlx::test]
+async fn only_presence_watchers_can_see_a_users_presence(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob, carol) = (
+        app.user("alice").await,
+        app.user("bob").await,
+        app.user("carol").await,
+    );
+    // Alice picked bob, so she knows who he is; bob only saw an anonymous sender
+    message(app.pool(), Some(alice.id), bob.id, "hi").await;
+    let presence_of = |user: &TestUser| format!("/api/users/{}/presence", user.id);
+
+    assert_eq!(
+        app.get(&presence_of(&bob), &alice).await.status,
+        StatusCode::OK
+    );
+    assert_eq!(
+        app.get(&presence_of(&bob), &bob).await.status,
+        StatusCode::OK
+    );
+    assert_eq!(
+        app.get(&presence_of(&alice), &bob).await.status,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        app.get(&presence_of(&bob), &carol).await.status,
+        StatusCode::NOT_FOUND
+    );
+
+    crate::db::block_user(app.pool(), bob.id, alice.id)
+        .await
+        .unwrap();
+    assert_eq!(
+        app.get(&presence_of(&bob), &alice).await.status,
+        StatusCode::NOT_FOUND
+    );
+}
+
+// ===== Bans =====
+
+#[sqlx::test]
+async fn a_banned_session_cannot_send_even_anonymously(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (alice, bob) = (app.user("alice").await, app.user("bob").await);
+    sqlx::query("UPDATE users SET banned_at = NOW() WHERE id = $1")
+        .bind(alice.id)
+        .execute(app.pool())
+        .await
+        .unwrap();
+
+    for allow_reply in [true, false] {
+        let response = app
+            .post(
+                "/api/messages",
+                &alice,
+                serde_json::json!({
+                    "recipient_id": bob.id,
+                    "content": "still here",
+                    "allow_reply": allow_reply,
+                }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN);
+    }
+    assert_eq!(count(app.pool(), "SELECT COUNT(*) FROM messages").await, 0);
+}
+
+// ===== Pagination =====
+
+#[sqlx::test]
+async fn a_cursor_that_does_not_decode_is_a_bad_request(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let alice = app.user("alice").await;
+
+    let response = app.get("/api/messages/inbox?cursor=garbage", &alice).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let body = response.json();
+    assert_eq!(body["error"], "validation_error");
+    assert_eq!(body["message"], "Invalid cursor");
+}
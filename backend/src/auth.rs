@@ -1,22 +1,32 @@
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
+    Algorithm, Argon2, Version,
 };
 use authkestra::axum::helpers::{create_axum_cookie, logout};
+use authkestra::axum::AuthSession;
 use authkestra::flow::SessionStoreState;
 use authkestra::session::{Identity, SessionStore};
 use axum::{
-    extract::{Json, State},
+    extract::{Json, Path, Request, State},
     http::StatusCode,
-    response::IntoResponse,
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
 };
 use serde::Deserialize;
 use sqlx::PgPool;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tower_cookies::Cookies;
-use tracing::{info, warn};
-
+use std::time::Duration;
+use tower_cookies::cookie::SameSite;
+use tower_cookies::{Cookie, Cookies};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::config::PasswordConfig;
+use crate::error::ApiError;
+use crate::fingerprint::ClientIp;
+use crate::ratelimit::RateLimiter;
+use crate::sessions::IndexedSessionStore;
 use crate::state::AppState;
 
 #[derive(Deserialize, Debug)]
@@ -25,23 +35,60 @@ pub struct LoginRequest {
     password: String,
 }
 
+/// Count an attempt against `key`, or reject it with 429 when over the limit.
+fn check_rate_limit(limiter: &RateLimiter, key: &str) -> Result<(), ApiError> {
+    limiter.check(key).map_err(|retry_after| {
+        warn!("Rate limited auth attempt for {key}");
+        ApiError::TooManyRequests(format!(
+            "Too many attempts, try again in {} seconds",
+            retry_after.as_secs().max(1)
+        ))
+    })
+}
+
+/// Argon2id hasher with the configured costs. Verifying with it still uses
+/// the parameters stored in the hash being checked.
+fn password_hasher(config: &PasswordConfig) -> Argon2<'static> {
+    // Validated when the config was loaded
+    let params = config.params().expect("valid Argon2 parameters");
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+fn lockout_error(locked_until: time::OffsetDateTime) -> ApiError {
+    let minutes = ((locked_until - time::OffsetDateTime::now_utc()).whole_seconds() + 59) / 60;
+    let minutes = minutes.max(1);
+    ApiError::Forbidden(format!(
+        "Account locked after too many failed login attempts; try again in {} minute{}",
+        minutes,
+        if minutes == 1 { "" } else { "s" }
+    ))
+}
+
 #[tracing::instrument(skip(cookies, state))]
 pub async fn login_handler(
     cookies: Cookies,
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     Json(req): Json<LoginRequest>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     let pool = &state.db_pool;
 
+    // Limit both who is guessing and which account is being guessed
+    if let Some(ip) = ip {
+        check_rate_limit(&state.auth_limiter, &format!("ip:{ip}"))?;
+    }
+    let username_key = format!("user:{}", req.username.to_lowercase());
+    check_rate_limit(&state.auth_limiter, &username_key)?;
+
     let user = crate::db::get_user_by_username(pool, &req.username)
         .await
         .map_err(|e| {
             warn!("DB error during login: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::Internal
         })?
         .ok_or_else(|| {
             warn!("Login failed: user '{}' not found", req.username);
-            StatusCode::UNAUTHORIZED
+            ApiError::InvalidCredentials
         })?;
 
     let password_hash = user.password_hash.as_ref().ok_or_else(|| {
@@ -49,26 +96,73 @@ pub async fn login_handler(
             "Login failed: user '{}' has no password (OAuth only?)",
             user.username
         );
-        StatusCode::UNAUTHORIZED
+        ApiError::InvalidCredentials
     })?;
 
+    // Checked before Argon2 so a locked account costs no hashing
+    let config = &state.api_config;
+    if config.login_max_failures > 0 {
+        let locked_until = crate::db::get_login_lockout(pool, user.id)
+            .await
+            .map_err(|e| {
+                warn!("Failed to check lockout for user {}: {e}", user.username);
+                ApiError::Internal
+            })?;
+        if let Some(locked_until) = locked_until {
+            warn!("Login refused: user '{}' is locked out", user.username);
+            return Err(lockout_error(locked_until));
+        }
+    }
+
     let parsed_hash = PasswordHash::new(password_hash).map_err(|e| {
         warn!(
             "Failed to parse password hash for user {}: {e}",
             user.username
         );
-        StatusCode::INTERNAL_SERVER_ERROR
+        ApiError::Internal
     })?;
 
-    Argon2::default()
-        .verify_password(req.password.as_bytes(), &parsed_hash)
-        .map_err(|e| {
-            warn!(
-                "Password verification failed for user {}: {e}",
-                user.username
-            );
-            StatusCode::UNAUTHORIZED
-        })?;
+    if let Err(e) =
+        password_hasher(&config.password).verify_password(req.password.as_bytes(), &parsed_hash)
+    {
+        warn!(
+            "Password verification failed for user {}: {e}",
+            user.username
+        );
+        if config.login_max_failures > 0 {
+            match crate::db::record_failed_login(
+                pool,
+                user.id,
+                config.login_max_failures,
+                config.login_lockout,
+            )
+            .await
+            {
+                Ok(Some(locked_until)) => {
+                    warn!("User '{}' locked out after failed logins", user.username);
+                    return Err(lockout_error(locked_until));
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Failed to record failed login: {e}"),
+            }
+        }
+        return Err(ApiError::InvalidCredentials);
+    }
+
+    if config.login_max_failures > 0 {
+        if let Err(e) = crate::db::clear_failed_logins(pool, user.id).await {
+            warn!("Failed to clear failed logins: {e}");
+        }
+    }
+
+    // Earlier typos shouldn't count against the owner's next login
+    state.auth_limiter.reset(&username_key);
+
+    // Only said after the password checks out, so it can't be probed
+    if user.banned_at.is_some() {
+        warn!("Login refused: user '{}' is banned", user.username);
+        return Err(ApiError::Forbidden("This account is banned".into()));
+    }
 
     // Password verified — create a server-side session
     info!(
@@ -90,7 +184,7 @@ pub async fn login_handler(
         .await
         .map_err(|e| {
             warn!("Failed to create session: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::Internal
         })?;
 
     let cookie = create_axum_cookie(&state.authkestra.session_config, session.id);
@@ -117,18 +211,25 @@ pub struct RegisterRequest {
 pub async fn register_handler(
     cookies: Cookies,
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     Json(req): Json<RegisterRequest>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     let pool = &state.db_pool;
 
+    if let Some(ip) = ip {
+        check_rate_limit(&state.auth_limiter, &format!("ip:{ip}"))?;
+    }
+
     if req.username.trim().is_empty() {
         warn!("Registration failed: empty username");
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::Validation("Username cannot be empty".into()));
     }
 
     if req.password.len() < 6 {
         warn!("Registration failed: password too short");
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::Validation(
+            "Password must be at least 6 characters".into(),
+        ));
     }
 
     // Check if user exists (case-insensitive)
@@ -136,7 +237,7 @@ pub async fn register_handler(
         .await
         .map_err(|e| {
             warn!("DB error during registration check: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::Internal
         })?
         .is_some();
 
@@ -145,16 +246,15 @@ pub async fn register_handler(
             "Registration failed: user '{}' already exists",
             req.username
         );
-        return Err(StatusCode::CONFLICT);
+        return Err(ApiError::Conflict("Username is already taken".into()));
     }
 
     let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
+    let password_hash = password_hasher(&state.api_config.password)
         .hash_password(req.password.as_bytes(), &salt)
         .map_err(|e| {
             warn!("Hashing failed: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::Internal
         })?
         .to_string();
 
@@ -162,7 +262,7 @@ pub async fn register_handler(
         .await
         .map_err(|e| {
             warn!("Failed to create local user: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::Internal
         })?;
 
     info!("New user registered: {}, id: {}", user.username, user.id);
@@ -182,7 +282,7 @@ pub async fn register_handler(
         .await
         .map_err(|e| {
             warn!("Failed to create session after registration: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::Internal
         })?;
 
     let cookie = create_axum_cookie(&state.authkestra.session_config, session.id);
@@ -217,6 +317,260 @@ pub async fn logout_handler(cookies: Cookies, State(state): State<AppState>) ->
     }
 }
 
+/// Log out of every device: deletes all sessions of the current user,
+/// including this one, and reports how many were still live.
+/// See `sessions.rs` for why this only covers sessions this instance knows of.
+#[tracing::instrument(skip(session, cookies, state))]
+pub async fn logout_all_handler(
+    session: AuthSession,
+    cookies: Cookies,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let identity = &session.0.identity;
+
+    // A linked account signs in as two identities: its password login and
+    // the provider it was linked to
+    let mut identities = vec![(identity.provider_id.clone(), identity.external_id.clone())];
+    let user = crate::db::get_user_by_identity(
+        &state.db_pool,
+        &identity.provider_id,
+        &identity.external_id,
+    )
+    .await
+    .map_err(|e| {
+        warn!("Failed to load user for logout: {e}");
+        ApiError::Internal
+    })?;
+    if let Some(user) = user {
+        identities.extend(user_identities(&user));
+    }
+    let invalidated = revoke_sessions(&state.sessions, identities).await?;
+
+    let mut cookie = create_axum_cookie(&state.authkestra.session_config, String::new());
+    cookie.set_max_age(Some(tower_cookies::cookie::time::Duration::ZERO));
+    cookies.remove(cookie);
+
+    info!(
+        "Logged out everywhere: {} sessions of {} user {}",
+        invalidated, identity.provider_id, identity.external_id
+    );
+
+    Ok(Json(serde_json::json!({ "invalidated": invalidated })))
+}
+
+/// Every identity `user` signs in as: their password login, and the provider
+/// they signed up with or linked.
+pub fn user_identities(user: &crate::db::User) -> Vec<(String, String)> {
+    let mut identities = Vec::new();
+    if user.password_hash.is_some() {
+        identities.push(("local".to_string(), user.id.to_string()));
+    }
+    if let Some(provider_id) = &user.provider_id {
+        identities.push((user.provider.clone(), provider_id.clone()));
+    }
+    identities
+}
+
+/// Delete every session of these `(provider_id, external_id)` identities
+/// and return how many were live. See `sessions.rs` for which sessions
+/// that reaches.
+pub async fn revoke_sessions(
+    sessions: &IndexedSessionStore,
+    mut identities: Vec<(String, String)>,
+) -> Result<usize, ApiError> {
+    identities.sort();
+    identities.dedup();
+
+    let mut invalidated = 0;
+    for (provider_id, external_id) in &identities {
+        invalidated += sessions
+            .delete_identity_sessions(provider_id, external_id)
+            .await
+            .map_err(|e| {
+                warn!("Failed to delete sessions: {e}");
+                ApiError::Internal
+            })?;
+    }
+    Ok(invalidated)
+}
+
+/// Browser cookie carrying a pending account-link token from
+/// `link_start_handler` through the provider's sign-in to
+/// `link_complete_handler`.
+const LINK_COOKIE: &str = "anonyma_link";
+
+/// How long the user has to finish signing in with the provider
+const LINK_REQUEST_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Start linking a GitHub/Google identity to the caller's local account.
+/// Only a password session can start it, so nobody can attach an account
+/// they don't own. Returns `{url}`, the provider sign-in to send the browser
+/// to; its callback finishes in `link_complete_handler`.
+#[tracing::instrument(skip(session, cookies, state))]
+pub async fn link_start_handler(
+    session: AuthSession,
+    cookies: Cookies,
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let identity = &session.0.identity;
+    if identity.provider_id != "local" {
+        return Err(ApiError::Forbidden(
+            "Sign in with your password to link an account".into(),
+        ));
+    }
+    if !state.authkestra.providers.contains_key(&provider) {
+        return Err(ApiError::NotFound("Unknown provider".into()));
+    }
+
+    let user_id = Uuid::parse_str(&identity.external_id).map_err(|_| ApiError::Unauthorized)?;
+    let user = crate::db::get_user_by_id(&state.db_pool, user_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load user {user_id} for linking: {e}");
+            ApiError::Unauthorized
+        })?;
+    if user.provider != "local" || user.provider_id.is_some() {
+        return Err(ApiError::Conflict(format!(
+            "This account is already linked to {}",
+            user.provider
+        )));
+    }
+
+    let token = Uuid::new_v4().to_string();
+    crate::db::create_link_request(&state.db_pool, &token, user.id, &provider, LINK_REQUEST_TTL)
+        .await
+        .map_err(|e| {
+            warn!("Failed to create link request: {e}");
+            ApiError::Internal
+        })?;
+
+    let mut cookie = Cookie::new(LINK_COOKIE, token);
+    cookie.set_path("/auth/link");
+    cookie.set_http_only(true);
+    // Lax still sends it on the provider's top-level redirect back to us
+    cookie.set_same_site(SameSite::Lax);
+    cookie.set_secure(state.authkestra.session_config.secure);
+    cookie.set_max_age(Some(tower_cookies::cookie::time::Duration::seconds(
+        LINK_REQUEST_TTL.as_secs() as i64,
+    )));
+    cookies.add(cookie);
+
+    info!("User {} started linking {provider}", user.username);
+
+    Ok(Json(serde_json::json!({
+        "url": format!("/auth/{provider}?success_url=%2Fauth%2Flink%2Fcomplete"),
+    })))
+}
+
+/// Provider callback target for a link started by `link_start_handler`. The
+/// session is now the provider identity; if the browser still holds a valid
+/// link token for that provider, the identity is attached to the account
+/// that asked for it. Redirects to the settings page with `?linked=` or
+/// `?link_error=`.
+#[tracing::instrument(skip(session, cookies, state))]
+pub async fn link_complete_handler(
+    session: AuthSession,
+    cookies: Cookies,
+    State(state): State<AppState>,
+) -> Redirect {
+    let settings_url = format!("{}/dashboard/settings", state.frontend_url);
+    let identity = &session.0.identity;
+
+    let Some(token) = cookies.get(LINK_COOKIE).map(|c| c.value().to_string()) else {
+        return Redirect::to(&format!("{settings_url}?link_error=expired"));
+    };
+    let mut removal = Cookie::new(LINK_COOKIE, "");
+    removal.set_path("/auth/link");
+    cookies.remove(removal);
+
+    let pool = &state.db_pool;
+    let user_id = match crate::db::take_link_request(pool, &token, &identity.provider_id).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => return Redirect::to(&format!("{settings_url}?link_error=expired")),
+        Err(e) => {
+            warn!("Failed to load link request: {e}");
+            return Redirect::to(&format!("{settings_url}?link_error=failed"));
+        }
+    };
+
+    match crate::db::link_provider(pool, user_id, &identity.provider_id, &identity.external_id)
+        .await
+    {
+        Ok(true) => {
+            info!("User {user_id} linked {}", identity.provider_id);
+            Redirect::to(&format!("{settings_url}?linked={}", identity.provider_id))
+        }
+        Ok(false) => {
+            warn!(
+                "User {user_id} couldn't link {}: already linked or identity in use",
+                identity.provider_id
+            );
+            Redirect::to(&format!("{settings_url}?link_error=taken"))
+        }
+        Err(e) => {
+            warn!("Failed to link provider: {e}");
+            Redirect::to(&format!("{settings_url}?link_error=failed"))
+        }
+    }
+}
+
+/// Sliding session renewal. Runs after the handler: once less than half of the
+/// session lifetime is left, the session is extended by a full `max_age` and
+/// the cookie is re-issued so the browser-side expiry moves with it. Requests
+/// that don't trigger a renewal get no `Set-Cookie`.
+pub async fn renew_session(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+
+    let session_config = &state.authkestra.session_config;
+    let Some(max_age) = session_config.max_age else {
+        return response;
+    };
+    let Some(session_id) = cookies
+        .get(&session_config.cookie_name)
+        .map(|c| c.value().to_string())
+    else {
+        return response;
+    };
+
+    let session_store: Arc<dyn SessionStore> = state.authkestra.session_store.get_store();
+    let mut session = match session_store.load_session(&session_id).await {
+        Ok(Some(session)) => session,
+        // Logged out during this request, or never existed
+        Ok(None) => return response,
+        Err(e) => {
+            warn!("Failed to load session for renewal: {e}");
+            return response;
+        }
+    };
+
+    let now = chrono::Utc::now();
+    let remaining = session.expires_at - now;
+    // Expired sessions are not revived; fresh ones don't need renewing yet
+    if remaining <= chrono::Duration::zero() || remaining > max_age / 2 {
+        return response;
+    }
+
+    session.expires_at = now + max_age;
+    if let Err(e) = session_store.save_session(&session).await {
+        warn!("Failed to renew session: {e}");
+        return response;
+    }
+
+    cookies.add(create_axum_cookie(session_config, session.id));
+    debug!(
+        "Renewed session with {}s remaining",
+        remaining.num_seconds()
+    );
+
+    response
+}
+
 #[allow(dead_code)]
 #[tracing::instrument(skip(session, _pool))]
 pub async fn me_handler(
@@ -235,3 +589,163 @@ pub async fn me_handler(
 
     format!("Logged in as: {username}")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ApiConfig;
+    use crate::testing::{session_cookie, TestApp};
+
+    /// Move the session in `cookie` to expire `remaining` from now.
+    async fn expire_session_in(app: &TestApp, cookie: &str, remaining: chrono::Duration) {
+        let session_id = cookie.split_once('=').unwrap().1;
+        let mut session = app
+            .state
+            .sessions
+            .load_session(session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        session.expires_at = chrono::Utc::now() + remaining;
+        app.state.sessions.save_session(&session).await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn a_session_near_expiry_gets_a_refreshed_cookie(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let alice = app.user("alice").await;
+        expire_session_in(&app, &alice.cookie, chrono::Duration::minutes(5)).await;
+
+        let response = app.get("/api/me", &alice).await;
+
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.json()["id"], alice.id.to_string());
+        assert_eq!(
+            session_cookie(&response.headers),
+            Some(alice.cookie.clone())
+        );
+        let session_id = alice.cookie.split_once('=').unwrap().1;
+        let session = app
+            .state
+            .sessions
+            .load_session(session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.expires_at - chrono::Utc::now() > chrono::Duration::hours(23));
+    }
+
+    #[sqlx::test]
+    async fn a_fresh_session_gets_no_cookie(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let alice = app.user("alice").await;
+
+        let response = app.get("/api/me", &alice).await;
+
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(session_cookie(&response.headers), None);
+    }
+
+    /// Sign in as `alice` with `password`, from `ip` when the app trusts
+    /// `X-Forwarded-For`.
+    async fn login(app: &TestApp, ip: &str, password: &str) -> crate::testing::TestResponse {
+        app.send(
+            axum::http::Method::POST,
+            "/auth/login",
+            None,
+            &[(axum::http::HeaderName::from_static("x-forwarded-for"), ip)],
+            Some(serde_json::json!({ "username": "alice", "password": password })),
+        )
+        .await
+    }
+
+    #[sqlx::test]
+    async fn too_many_bad_passwords_lock_the_account(pool: PgPool) {
+        let app = TestApp::new(pool);
+        app.user("alice").await;
+        let max = app.state.api_config.login_max_failures;
+
+        for _ in 1..max {
+            let response = login(&app, "203.0.113.1", "wrong").await;
+            assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        }
+        let response = login(&app, "203.0.113.1", "wrong").await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN);
+        assert_eq!(
+            response.json()["message"],
+            "Account locked after too many failed login attempts; try again in 15 minutes"
+        );
+
+        // The right password doesn't get in while the lock lasts
+        let response = login(&app, "203.0.113.1", "password").await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN);
+        assert!(response.json()["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Account locked"));
+    }
+
+    #[sqlx::test]
+    async fn a_good_login_starts_the_failure_count_over(pool: PgPool) {
+        let app = TestApp::new(pool);
+        app.user("alice").await;
+        let max = app.state.api_config.login_max_failures;
+
+        for _ in 0..2 {
+            for _ in 1..max {
+                let response = login(&app, "203.0.113.1", "wrong").await;
+                assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+            }
+            let response = login(&app, "203.0.113.1", "password").await;
+            assert_eq!(response.status, StatusCode::OK);
+        }
+    }
+
+    /// An app whose password endpoints allow three attempts per key a minute,
+    /// trusting `X-Forwarded-For` so each attempt can come from its own IP.
+    fn rate_limited_app(pool: PgPool) -> TestApp {
+        let config = ApiConfig {
+            trust_forwarded_for: true,
+            ..crate::testing::api_config()
+        };
+        TestApp::with_auth_limiter(pool, config, RateLimiter::new(3, Duration::from_secs(60)))
+    }
+
+    #[sqlx::test]
+    async fn guessing_one_username_past_the_limit_is_refused(pool: PgPool) {
+        let app = rate_limited_app(pool);
+        app.user("alice").await;
+
+        for ip in ["203.0.113.1", "203.0.113.2", "203.0.113.3"] {
+            let response = login(&app, ip, "wrong").await;
+            assert_eq!(response.status, StatusCode::UNAUTHORIZED, "from {ip}");
+        }
+        let response = login(&app, "203.0.113.4", "password").await;
+        assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.json()["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Too many attempts, try again in"));
+    }
+
+    #[sqlx::test]
+    async fn a_good_login_frees_the_username_again(pool: PgPool) {
+        let app = rate_limited_app(pool);
+        app.user("alice").await;
+
+        for ip in ["203.0.113.1", "203.0.113.2"] {
+            assert_eq!(
+                login(&app, ip, "wrong").await.status,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        let response = login(&app, "203.0.113.3", "password").await;
+        assert_eq!(response.status, StatusCode::OK);
+
+        // Without the reset the username would already be over its limit
+        for ip in ["203.0.113.4", "203.0.113.5", "203.0.113.6"] {
+            let response = login(&app, ip, "wrong").await;
+            assert_eq!(response.status, StatusCode::UNAUTHORIZED, "from {ip}");
+        }
+    }
+}
@@ -1,14 +1,128 @@
+use axum::http::HeaderValue;
 use std::env;
+use std::time::Duration;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use uuid::Uuid;
+
+use crate::fingerprint::{FingerprintKey, MIN_KEY_LENGTH};
+use crate::push::VapidConfig;
+use crate::retention::RetentionPolicy;
 
 pub struct Config {
     pub client_id: String,
     pub client_secret: String,
     pub base_url: String,
     pub bind_address: String,
-    pub redirect_uri: String,
+    /// Set to offer Google sign-in next to GitHub; off when unset
+    pub google_client_id: Option<String>,
+    pub google_client_secret: Option<String>,
     pub database_url: String,
+    /// `scheme://host[:port]` with no trailing slash; used for redirects
     pub frontend_url: String,
+    /// Origins allowed to make credentialed cross-origin requests
+    pub cors_origins: Vec<HeaderValue>,
+    /// How often stale typing indicators are swept
+    pub typing_cleanup_interval: Duration,
+    /// How often due scheduled broadcasts are published
+    pub scheduled_broadcast_interval: Duration,
+    /// How often due scheduled messages are delivered
+    pub scheduled_message_interval: Duration,
+    /// Login/registration attempts allowed per IP or username in each window
+    pub auth_rate_limit: u32,
+    pub auth_rate_limit_window: Duration,
+    /// Messages allowed per IP or inbox link in each window on the
+    /// unauthenticated inbox link endpoint
+    pub anon_message_rate_limit: u32,
+    pub anon_message_rate_limit_window: Duration,
+    /// How long in-flight requests get to finish once shutdown starts
+    pub shutdown_grace_period: Duration,
+    /// Range-partition messages by month on startup and keep partitions ahead
+    pub messages_partitioning: bool,
+    /// How many months of message partitions to keep created in advance
+    pub message_partitions_ahead: i32,
+    /// How often the message partition job runs
+    pub message_partition_interval: Duration,
+    /// How often the data integrity checks run; 0 turns them off
+    pub integrity_check_interval: Duration,
+    /// How long messages and broadcasts are kept before they're purged
+    pub retention: RetentionPolicy,
+    /// How often the retention purge runs, when any retention is set
+    pub retention_purge_interval: Duration,
+    /// How often expired ephemeral messages are deleted
+    pub expired_message_interval: Duration,
+    /// SSE events kept per user (and for everyone) for replay on reconnect;
+    /// 0 turns replay off
+    pub sse_replay_limit: usize,
+    /// How long SSE events are kept for replay
+    pub sse_replay_max_age: Duration,
+    /// Set to send Web Push notifications; off when unset
+    pub vapid: Option<VapidConfig>,
+    pub api: ApiConfig,
+}
+
+/// Settings consulted by request handlers, shared through `AppState`.
+#[derive(Debug, Clone)]
+pub struct ApiConfig {
+    /// Whether anyone may read a broadcast's edit history, or only its author
+    pub broadcast_history_public: bool,
+    /// Longest message, reply, broadcast or comment accepted, in characters
+    pub max_content_length: usize,
+    /// Concurrent SSE streams allowed per user; further connects get 429
+    pub sse_max_connections_per_user: usize,
+    /// Idle time after which an SSE stream gets a keep-alive comment; keep it
+    /// under the proxy's idle timeout
+    pub sse_keepalive: Duration,
+    /// Set to fingerprint anonymous posts for abuse correlation; off when unset
+    pub abuse_fingerprint_key: Option<FingerprintKey>,
+    /// Take the client IP from `X-Forwarded-For` (only behind a trusted proxy)
+    pub trust_forwarded_for: bool,
+    /// Consecutive wrong passwords that lock an account; 0 disables lockout
+    pub login_max_failures: i32,
+    /// How long a locked account refuses logins
+    pub login_lockout: Duration,
+    /// Messages one user may send per UTC day; 0 means no cap
+    pub daily_send_quota: i32,
+    /// Users the daily cap doesn't apply to (there are no roles to key on)
+    pub send_quota_exempt: Vec<Uuid>,
+    /// Argon2 costs for hashing new passwords
+    pub password: PasswordConfig,
+    /// Users allowed on the `/api/admin` endpoints
+    pub admin_users: Vec<Uuid>,
+    /// How long an ephemeral message stays readable once its recipient opens it
+    pub ephemeral_read_ttl: Duration,
+    /// How long after sending a message can still be edited; None = forever
+    pub edit_window: Option<Duration>,
+    /// How long after sending its sender can unsend a message; 0 turns unsend off
+    pub unsend_window: Duration,
+}
+
+/// Argon2id cost parameters. Only new hashes use them: a stored hash carries
+/// its own parameters and is verified with those, so retuning doesn't
+/// invalidate existing passwords.
+#[derive(Debug, Clone, Copy)]
+pub struct PasswordConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl PasswordConfig {
+    /// Defaults to the `argon2` crate's (OWASP's minimum recommendation).
+    fn from_env() -> Self {
+        let config = Self {
+            memory_kib: parse_env("PASSWORD_MEMORY_KIB", argon2::Params::DEFAULT_M_COST),
+            iterations: parse_env("PASSWORD_ITERATIONS", argon2::Params::DEFAULT_T_COST),
+            parallelism: parse_env("PASSWORD_PARALLELISM", argon2::Params::DEFAULT_P_COST),
+        };
+        if let Err(e) = config.params() {
+            panic!("Invalid Argon2 parameters {config:?}: {e}");
+        }
+        config
+    }
+
+    pub fn params(&self) -> argon2::Result<argon2::Params> {
+        argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+    }
 }
 
 impl Config {
@@ -21,21 +135,118 @@ impl Config {
         let scheme = std::env::var("APP_SCHEME").unwrap_or_else(|_| "http".to_string());
         let base_url = format!("{scheme}://{host}:{port}");
         let bind_address = format!("{host}:{port}");
-        let redirect_uri = format!("{base_url}/auth/github/callback");
+        let google_client_id = env::var("GOOGLE_CLIENT_ID").ok().filter(|v| !v.is_empty());
+        let google_client_secret = env::var("GOOGLE_CLIENT_SECRET")
+            .ok()
+            .filter(|v| !v.is_empty());
+        if google_client_id.is_some() != google_client_secret.is_some() {
+            panic!("GOOGLE_CLIENT_ID and GOOGLE_CLIENT_SECRET must be set together");
+        }
         let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let frontend_url = env::var("FRONTEND_URL").expect("FRONTEND_URL must be set");
+        let frontend_url = parse_origin_url(
+            "FRONTEND_URL",
+            &env::var("FRONTEND_URL").expect("FRONTEND_URL must be set"),
+        );
+        let cors_origins = parse_cors_origins(&frontend_url);
+        let typing_cleanup_interval =
+            Duration::from_secs(parse_env("TYPING_CLEANUP_INTERVAL_SECS", 10));
+        let scheduled_broadcast_interval =
+            Duration::from_secs(parse_env("SCHEDULED_BROADCAST_INTERVAL_SECS", 30));
+        let scheduled_message_interval =
+            Duration::from_secs(parse_env("SCHEDULED_MESSAGE_INTERVAL_SECS", 15));
+        let auth_rate_limit = parse_env("AUTH_RATE_LIMIT", 10);
+        let auth_rate_limit_window =
+            Duration::from_secs(parse_env("AUTH_RATE_LIMIT_WINDOW_SECS", 60));
+        let anon_message_rate_limit = parse_env("ANON_MESSAGE_RATE_LIMIT", 5);
+        let anon_message_rate_limit_window =
+            Duration::from_secs(parse_env("ANON_MESSAGE_RATE_LIMIT_WINDOW_SECS", 60 * 60));
+        let shutdown_grace_period =
+            Duration::from_secs(parse_env("SHUTDOWN_GRACE_PERIOD_SECS", 10));
+        let messages_partitioning = parse_env("MESSAGES_PARTITIONING", false);
+        let message_partitions_ahead = parse_env("MESSAGE_PARTITIONS_AHEAD", 3);
+        if message_partitions_ahead < 1 {
+            panic!("MESSAGE_PARTITIONS_AHEAD must be at least 1");
+        }
+        let message_partition_interval =
+            Duration::from_secs(parse_env("MESSAGE_PARTITION_INTERVAL_SECS", 6 * 60 * 60));
+        let vapid = parse_vapid_config(&frontend_url);
+        let integrity_check_interval =
+            Duration::from_secs(parse_env("INTEGRITY_CHECK_INTERVAL_SECS", 60 * 60));
+        let retention = RetentionPolicy {
+            message_days: parse_env("MESSAGE_RETENTION_DAYS", 0),
+            broadcast_days: parse_env("BROADCAST_RETENTION_DAYS", 0),
+        };
+        if retention.message_days < 0 || retention.broadcast_days < 0 {
+            panic!("MESSAGE_RETENTION_DAYS and BROADCAST_RETENTION_DAYS must not be negative");
+        }
+        let retention_purge_interval =
+            Duration::from_secs(parse_env("RETENTION_PURGE_INTERVAL_SECS", 60 * 60));
+        let expired_message_interval =
+            Duration::from_secs(parse_env("EXPIRED_MESSAGE_INTERVAL_SECS", 60));
+        let sse_replay_limit = parse_env("SSE_REPLAY_LIMIT", 100);
+        let sse_replay_max_age = Duration::from_secs(parse_env("SSE_REPLAY_MAX_AGE_SECS", 5 * 60));
+        if sse_replay_max_age.is_zero() {
+            panic!("SSE_REPLAY_MAX_AGE_SECS must be at least 1");
+        }
+        let api = ApiConfig {
+            broadcast_history_public: parse_env("BROADCAST_HISTORY_PUBLIC", false),
+            max_content_length: parse_env("MAX_CONTENT_LENGTH", 4000),
+            sse_max_connections_per_user: parse_env("SSE_MAX_CONNECTIONS_PER_USER", 5),
+            sse_keepalive: parse_sse_keepalive(),
+            abuse_fingerprint_key: parse_fingerprint_key(),
+            trust_forwarded_for: parse_env("TRUST_FORWARDED_FOR", false),
+            login_max_failures: parse_env("LOGIN_MAX_FAILURES", 5),
+            login_lockout: Duration::from_secs(parse_env("LOGIN_LOCKOUT_SECS", 15 * 60)),
+            daily_send_quota: parse_env("DAILY_SEND_QUOTA", 0),
+            send_quota_exempt: parse_uuid_list("SEND_QUOTA_EXEMPT_USERS"),
+            password: PasswordConfig::from_env(),
+            admin_users: parse_uuid_list("ADMIN_USERS"),
+            ephemeral_read_ttl: Duration::from_secs(parse_env("EPHEMERAL_READ_TTL_SECS", 30)),
+            edit_window: match parse_env("EDIT_WINDOW_SECONDS", 15 * 60) {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+            unsend_window: Duration::from_secs(parse_env("UNSEND_WINDOW_SECONDS", 30)),
+        };
 
         Self {
             client_id,
             client_secret,
             base_url,
             bind_address,
-            redirect_uri,
+            google_client_id,
+            google_client_secret,
             database_url,
             frontend_url,
+            cors_origins,
+            typing_cleanup_interval,
+            scheduled_broadcast_interval,
+            scheduled_message_interval,
+            auth_rate_limit,
+            auth_rate_limit_window,
+            anon_message_rate_limit,
+            anon_message_rate_limit_window,
+            shutdown_grace_period,
+            messages_partitioning,
+            message_partitions_ahead,
+            message_partition_interval,
+            integrity_check_interval,
+            retention,
+            retention_purge_interval,
+            expired_message_interval,
+            sse_replay_limit,
+            sse_replay_max_age,
+            vapid,
+            api,
         }
     }
 
+    /// Where `provider` sends the user back after they sign in; must match
+    /// the redirect URI registered with the provider.
+    pub fn redirect_uri(&self, provider: &str) -> String {
+        format!("{}/auth/{provider}/callback", self.base_url)
+    }
+
     pub fn setup_tracing() {
         tracing_subscriber::registry()
             .with(
@@ -49,3 +260,108 @@ impl Config {
             .init();
     }
 }
+
+/// Read an optional env var, falling back to `default` when unset.
+/// A value that is set but doesn't parse is a startup error.
+fn parse_env<T: std::str::FromStr>(key: &str, default: T) -> T {
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{key} has an invalid value: {value}")),
+        Err(_) => default,
+    }
+}
+
+/// `SSE_KEEPALIVE_SECONDS`, default 15 (axum's own default). 0 would flood
+/// streams with comments, so it's a startup error.
+fn parse_sse_keepalive() -> Duration {
+    let secs = parse_env("SSE_KEEPALIVE_SECONDS", 15);
+    if secs == 0 {
+        panic!("SSE_KEEPALIVE_SECONDS must be at least 1");
+    }
+    Duration::from_secs(secs)
+}
+
+/// `ABUSE_FINGERPRINT_KEY`, if set and non-empty. A short key is a startup error since the
+/// whole point is that fingerprints can't be brute-forced back to an IP.
+fn parse_fingerprint_key() -> Option<FingerprintKey> {
+    let key = env::var("ABUSE_FINGERPRINT_KEY")
+        .ok()
+        .filter(|key| !key.is_empty())?;
+    if key.len() < MIN_KEY_LENGTH {
+        panic!("ABUSE_FINGERPRINT_KEY must be at least {MIN_KEY_LENGTH} bytes");
+    }
+    Some(FingerprintKey::new(&key))
+}
+
+/// `VAPID_PUBLIC_KEY` and `VAPID_PRIVATE_KEY`, if set; one without the other
+/// is a startup error. `VAPID_SUBJECT` defaults to the frontend URL.
+fn parse_vapid_config(frontend_url: &str) -> Option<VapidConfig> {
+    let public_key = env::var("VAPID_PUBLIC_KEY").ok().filter(|v| !v.is_empty());
+    let private_key = env::var("VAPID_PRIVATE_KEY").ok().filter(|v| !v.is_empty());
+    match (public_key, private_key) {
+        (Some(public_key), Some(private_key)) => Some(VapidConfig {
+            public_key,
+            private_key,
+            subject: env::var("VAPID_SUBJECT").unwrap_or_else(|_| frontend_url.to_string()),
+        }),
+        (None, None) => None,
+        _ => panic!("VAPID_PUBLIC_KEY and VAPID_PRIVATE_KEY must be set together"),
+    }
+}
+
+/// Comma-separated user ids; empty when unset.
+fn parse_uuid_list(key: &str) -> Vec<Uuid> {
+    env::var(key)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            Uuid::parse_str(id).unwrap_or_else(|_| panic!("{key} has an invalid user id: {id}"))
+        })
+        .collect()
+}
+
+/// Comma-separated `CORS_ORIGINS`, each validated like `FRONTEND_URL`.
+/// Defaults to just the frontend's own origin.
+fn parse_cors_origins(frontend_url: &str) -> Vec<HeaderValue> {
+    let origins: Vec<String> = match env::var("CORS_ORIGINS") {
+        Ok(value) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| parse_origin_url("CORS_ORIGINS", origin))
+            .collect(),
+        Err(_) => vec![frontend_url.to_string()],
+    };
+    if origins.is_empty() {
+        panic!("CORS_ORIGINS must list at least one origin");
+    }
+    origins
+        .iter()
+        .map(|origin| {
+            HeaderValue::from_str(origin)
+                .unwrap_or_else(|_| panic!("CORS_ORIGINS has an invalid origin: {origin}"))
+        })
+        .collect()
+}
+
+/// Validate a URL used as a CORS origin, so a typo fails at startup instead
+/// of breaking every redirect and preflight at request time.
+fn parse_origin_url(key: &str, value: &str) -> String {
+    let trimmed = value.trim().trim_end_matches('/');
+    let uri: axum::http::Uri = trimmed
+        .parse()
+        .unwrap_or_else(|e| panic!("{key} is not a valid URL ({e}): {value}"));
+    if !matches!(uri.scheme_str(), Some("http" | "https")) {
+        panic!("{key} must start with http:// or https://: {value}");
+    }
+    if uri.authority().is_none_or(|a| a.host().is_empty()) {
+        panic!("{key} must include a host: {value}");
+    }
+    if uri.path() != "/" || uri.query().is_some() {
+        panic!("{key} must not have a path or query: {value}");
+    }
+    trimmed.to_string()
+}
@@ -1,4 +1,9 @@
-use sqlx::{types::time::OffsetDateTime, FromRow, PgPool, Result};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    types::time::OffsetDateTime, FromRow, PgExecutor, PgPool, Postgres, Result, Transaction,
+};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
 pub async fn init_db(database_url: &str) -> Result<PgPool> {
@@ -7,8 +12,37 @@ pub async fn init_db(database_url: &str) -> Result<PgPool> {
     Ok(pool)
 }
 
+/// Run `f` inside a transaction: committed if it returns `Ok`, rolled back
+/// otherwise. Pass `&mut **tx` to the executor-generic functions below.
+///
+/// ```ignore
+/// db::transaction(&pool, |tx| Box::pin(async move {
+///     let msg = db::get_message_by_id(&mut **tx, id).await?;
+///     ...
+/// }))
+/// ```
+pub async fn transaction<T, E, F>(pool: &PgPool, f: F) -> std::result::Result<T, E>
+where
+    F: for<'c> FnOnce(
+        &'c mut Transaction<'static, Postgres>,
+    ) -> BoxFuture<'c, std::result::Result<T, E>>,
+    E: From<sqlx::Error>,
+{
+    let mut tx = pool.begin().await?;
+    // An early return drops `tx` unfinished, which rolls it back
+    let value = f(&mut tx).await?;
+    tx.commit().await?;
+    Ok(value)
+}
+
 // ===== Models =====
 
+/// Reaction tallies keyed by emoji, decoded from a `json_object_agg(emoji, count)`
+/// column. Serializes as a JSON object in emoji order; `{}` when there are none.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ReactionCounts(pub BTreeMap<String, i64>);
+
 #[allow(dead_code)]
 #[derive(Debug, FromRow)]
 pub struct User {
@@ -20,6 +54,14 @@ pub struct User {
     pub bio: Option<String>,
     pub avatar_url: Option<String>,
     pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+    /// Only selected by `get_user_by_id`
+    #[sqlx(default)]
+    pub is_admin: bool,
+    /// Set while an admin has banned the account. Selected by the lookups
+    /// that sign users in and resolve sessions
+    #[sqlx(default)]
+    pub banned_at: Option<OffsetDateTime>,
 }
 
 #[allow(dead_code)]
@@ -32,7 +74,51 @@ pub struct Message {
     pub content: String,
     pub created_at: OffsetDateTime,
     pub is_read: bool,
-    pub reactions: Option<serde_json::Value>,
+    /// When the content was last edited; None if never (or deleted)
+    pub edited_at: Option<OffsetDateTime>,
+    #[sqlx(json)]
+    pub reactions: ReactionCounts,
+    /// Pinned by either participant; only selected by `get_thread_messages`
+    #[sqlx(default)]
+    pub pinned: bool,
+    /// The message this one quotes; only selected by `get_thread_messages`
+    #[sqlx(default)]
+    pub reply_to_message_id: Option<Uuid>,
+    /// Quoted part of that message in characters, end exclusive; left out
+    /// once the quoted message is deleted
+    #[sqlx(default)]
+    pub quote_start: Option<i32>,
+    #[sqlx(default)]
+    pub quote_end: Option<i32>,
+    /// The first 100 characters of what the reply answers: the quoted part,
+    /// or else the whole parent message. None once the parent is deleted or
+    /// gone; only selected by `get_thread_messages`
+    #[sqlx(default)]
+    pub reply_to_preview: Option<String>,
+    /// Copied from another thread with `forward`; only selected by
+    /// `get_thread_messages`
+    #[sqlx(default)]
+    pub forwarded: bool,
+    /// When the message disappears; only selected by `get_thread_messages`
+    #[sqlx(default)]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+/// How a new message disappears on its own. The default never does.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessageExpiry {
+    /// Disappear shortly after the recipient opens the thread
+    pub ephemeral: bool,
+    /// Disappear this long after sending, read or not
+    pub ttl: Option<std::time::Duration>,
+}
+
+/// What a quote-reply needs to know about the message it quotes.
+#[derive(Debug, FromRow)]
+pub struct QuotedMessage {
+    pub thread_id: Uuid,
+    pub content: String,
+    pub deleted: bool,
 }
 
 #[allow(dead_code)]
@@ -40,11 +126,77 @@ pub struct Message {
 pub struct Broadcast {
     pub id: Uuid,
     pub sender_id: Option<Uuid>,
+    /// Set even for anonymous broadcasts; never expose it
+    pub author_id: Option<Uuid>,
     pub sender_username: Option<String>,
     pub content: String,
     pub is_anonymous: bool,
     pub created_at: OffsetDateTime,
     pub view_count: Option<i64>,
+    pub edited_at: Option<OffsetDateTime>,
+    pub edit_count: Option<i64>,
+    /// Drafts are only visible to their author
+    pub is_draft: bool,
+}
+
+/// A recurring broadcast and where it is in its run.
+#[derive(Debug, FromRow)]
+pub struct ScheduledBroadcast {
+    pub id: Uuid,
+    pub content: String,
+    pub is_anonymous: bool,
+    /// `daily` or `weekly`
+    pub frequency: String,
+    /// 0 = Sunday ... 6 = Saturday; weekly schedules only
+    pub weekday: Option<i16>,
+    pub time_of_day: sqlx::types::time::Time,
+    pub timezone: String,
+    pub ends_at: Option<OffsetDateTime>,
+    pub max_occurrences: Option<i32>,
+    pub occurrence_count: i32,
+    /// `None` once the schedule has ended or been cancelled
+    pub next_run_at: Option<OffsetDateTime>,
+    pub last_run_at: Option<OffsetDateTime>,
+    pub cancelled_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+/// The recurrence and content of a schedule being created.
+#[derive(Debug)]
+pub struct NewScheduledBroadcast<'a> {
+    pub content: &'a str,
+    pub is_anonymous: bool,
+    pub frequency: &'a str,
+    pub weekday: Option<i16>,
+    pub time_of_day: sqlx::types::time::Time,
+    pub timezone: &'a str,
+    pub ends_at: Option<OffsetDateTime>,
+    pub max_occurrences: Option<i32>,
+}
+
+/// A message that has just been marked read.
+#[derive(Debug, FromRow)]
+pub struct ReadMessage {
+    pub id: Uuid,
+    pub sender_id: Option<Uuid>,
+    pub read_at: OffsetDateTime,
+}
+
+/// Someone the user knows by name from their own conversations.
+#[derive(Debug, FromRow)]
+pub struct Contact {
+    pub id: Uuid,
+    pub username: String,
+    pub avatar_url: Option<String>,
+    pub last_interaction_at: OffsetDateTime,
+}
+
+/// A superseded version of a broadcast's content.
+#[derive(Debug, FromRow)]
+pub struct BroadcastEdit {
+    pub old_content: String,
+    /// When this version was replaced
+    pub edited_at: OffsetDateTime,
 }
 
 /// A conversation thread summary — the latest message in each thread,
@@ -59,15 +211,31 @@ pub struct ThreadSummary {
     pub content: String,
     pub created_at: OffsetDateTime,
     pub is_read: bool,
+    pub edited_at: Option<OffsetDateTime>,
     /// How many unread messages are in this thread for the current viewer.
     pub unread_count: i64,
     /// Recipient's username — only populated when the viewer is the sender.
     /// Receivers always see NULL (anonymity preserved).
     pub recipient_username: Option<String>,
+    /// Whether either participant has paused this thread.
+    pub paused: bool,
+    /// Whether the viewer pinned this thread.
+    pub pinned: bool,
+    /// The viewer's own labels on this thread, alphabetical.
+    pub labels: Vec<String>,
+    /// Whether the viewer archived this thread and nothing newer arrived since.
+    pub archived: bool,
+    /// Whether the viewer has muted this thread right now.
+    pub muted: bool,
 }
 
 // ===== User Operations =====
 
+/// Find the user signed in through `provider`, or create them. Accounts are
+/// only ever matched by provider id: a new identity whose name is already
+/// taken gets a free variant of it (see `available_username`) rather than
+/// the existing account, which stays reachable only by explicit linking
+/// (`link_provider`).
 #[tracing::instrument(skip(pool))]
 pub async fn upsert_user(
     pool: &PgPool,
@@ -78,7 +246,7 @@ pub async fn upsert_user(
     // 1. Try to find user by provider and provider_id
     let existing_by_provider = sqlx::query_as::<_, User>(
         r#"
-        SELECT id, username, password_hash, provider, provider_id, created_at, bio, avatar_url
+        SELECT id, username, password_hash, provider, provider_id, created_at, bio, avatar_url, updated_at, banned_at
         FROM users
         WHERE provider = $1 AND provider_id = $2
         "#,
@@ -89,14 +257,18 @@ pub async fn upsert_user(
     .await?;
 
     if let Some(user) = existing_by_provider {
-        // Update username if it changed on the provider's side
-        if user.username != username {
+        // Follow a rename on the provider's side, unless the name was chosen
+        // here (a linked local account) or someone else has it now
+        if user.username != username
+            && user.password_hash.is_none()
+            && !username_taken(pool, username, Some(user.id)).await?
+        {
             let updated = sqlx::query_as::<_, User>(
                 r#"
                 UPDATE users
                 SET username = $1
                 WHERE id = $2
-                RETURNING id, username, password_hash, provider, provider_id, bio, avatar_url, created_at
+                RETURNING id, username, password_hash, provider, provider_id, bio, avatar_url, created_at, updated_at, banned_at
                 "#,
             )
             .bind(username)
@@ -108,74 +280,147 @@ pub async fn upsert_user(
         return Ok(user);
     }
 
-    // 2. Try to find user by username to handle linking or collisions
-    let existing_by_username = sqlx::query_as::<_, User>(
+    // 2. Insert new user, under a free username
+    let username = available_username(pool, username).await?;
+    let new_user = sqlx::query_as::<_, User>(
         r#"
-        SELECT id, username, password_hash, provider, provider_id, created_at, bio, avatar_url
-        FROM users
-        WHERE LOWER(username) = LOWER($1)
+        INSERT INTO users (id, username, provider, provider_id, created_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        RETURNING id, username, password_hash, provider, provider_id, bio, avatar_url, created_at, updated_at, banned_at
         "#,
     )
+    .bind(Uuid::new_v4())
+    .bind(&username)
+    .bind(provider)
+    .bind(provider_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(new_user)
+}
+
+/// Whether anyone other than `except` has `username`, ignoring case.
+async fn username_taken(pool: &PgPool, username: &str, except: Option<Uuid>) -> Result<bool> {
+    let taken = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(username) = LOWER($1) AND id IS DISTINCT FROM $2)",
+    )
     .bind(username)
-    .fetch_optional(pool)
+    .bind(except)
+    .fetch_one(pool)
     .await?;
+    Ok(taken)
+}
 
-    if let Some(user) = existing_by_username {
-        // If the existing user is 'local' and we are now logging in with 'github',
-        // we "link" them by updating the provider if it's currently local.
-        if (user.provider == "local" || user.provider == "github") && user.provider_id.is_none() {
-            let updated = sqlx::query_as::<_, User>(
-                r#"
-                UPDATE users
-                SET provider = $1, provider_id = $2
-                WHERE id = $3
-                RETURNING id, username, password_hash, provider, provider_id, bio, avatar_url, created_at
-                "#,
-            )
-            .bind(provider)
-            .bind(provider_id)
-            .bind(user.id)
-            .fetch_one(pool)
-            .await?;
-            return Ok(updated);
-        }
-        // If it's already a different provider but matching provider_id, we update username if changed
-        if user.provider == provider && user.provider_id == provider_id && user.username != username
-        {
-            let updated = sqlx::query_as::<_, User>(
-                r#"
-                UPDATE users
-                SET username = $1
-                WHERE id = $2
-                RETURNING id, username, password_hash, provider, provider_id, bio, avatar_url, created_at
-                "#,
-            )
-            .bind(username)
-            .bind(user.id)
-            .fetch_one(pool)
-            .await?;
-            return Ok(updated);
+/// `username` if it's free, otherwise the first free one of `username-2`,
+/// `username-3`, ...
+async fn available_username(pool: &PgPool, username: &str) -> Result<String> {
+    if !username_taken(pool, username, None).await? {
+        return Ok(username.to_string());
+    }
+    let mut suffix = 2;
+    loop {
+        let candidate = format!("{username}-{suffix}");
+        if !username_taken(pool, &candidate, None).await? {
+            return Ok(candidate);
         }
-
-        return Ok(user);
+        suffix += 1;
     }
+}
 
-    // 3. Insert new user
-    let new_user = sqlx::query_as::<_, User>(
+/// The account a session identity signs in to, if it exists yet: local
+/// sessions carry the user id, provider sessions the provider's user id.
+pub async fn get_user_by_identity(
+    pool: &PgPool,
+    provider: &str,
+    external_id: &str,
+) -> Result<Option<User>> {
+    let local_id = if provider == "local" {
+        Uuid::parse_str(external_id).ok()
+    } else {
+        None
+    };
+    let user = sqlx::query_as::<_, User>(
         r#"
-        INSERT INTO users (id, username, provider, provider_id, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        RETURNING id, username, password_hash, provider, provider_id, bio, avatar_url, created_at
+        SELECT id, username, password_hash, provider, provider_id, created_at, bio, avatar_url, updated_at, banned_at
+        FROM users
+        WHERE id = $1 OR (provider = $2 AND provider_id = $3)
         "#,
     )
-    .bind(Uuid::new_v4())
-    .bind(username)
+    .bind(local_id)
+    .bind(provider)
+    .bind(external_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(user)
+}
+
+/// Attach a provider identity to a local account, at the account owner's
+/// request. Returns false if the account was already linked or gone, or the
+/// identity already belongs to an account of its own.
+pub async fn link_provider(
+    pool: &PgPool,
+    user_id: Uuid,
+    provider: &str,
+    provider_id: &str,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET provider = $2, provider_id = $3, updated_at = NOW()
+        WHERE id = $1 AND provider = 'local' AND provider_id IS NULL
+          AND NOT EXISTS (SELECT 1 FROM users WHERE provider = $2 AND provider_id = $3)
+        "#,
+    )
+    .bind(user_id)
     .bind(provider)
     .bind(provider_id)
-    .fetch_one(pool)
+    .execute(pool)
     .await?;
+    Ok(result.rows_affected() == 1)
+}
 
-    Ok(new_user)
+/// Remember that `user_id` started linking `provider`, under a one-time
+/// `token` held in their browser. Expired requests are cleared on the way.
+pub async fn create_link_request(
+    pool: &PgPool,
+    token: &str,
+    user_id: Uuid,
+    provider: &str,
+    ttl: std::time::Duration,
+) -> Result<()> {
+    sqlx::query("DELETE FROM account_link_requests WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO account_link_requests (token, user_id, provider, expires_at)
+        VALUES ($1, $2, $3, NOW() + $4)
+        "#,
+    )
+    .bind(token)
+    .bind(user_id)
+    .bind(provider)
+    .bind(ttl)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Use up a link request. Returns the user who made it, if it's still valid
+/// and was for `provider`.
+pub async fn take_link_request(pool: &PgPool, token: &str, provider: &str) -> Result<Option<Uuid>> {
+    let user_id = sqlx::query_scalar::<_, Option<Uuid>>(
+        r#"
+        DELETE FROM account_link_requests
+        WHERE token = $1
+        RETURNING CASE WHEN provider = $2 AND expires_at > NOW() THEN user_id END
+        "#,
+    )
+    .bind(token)
+    .bind(provider)
+    .fetch_optional(pool)
+    .await?;
+    Ok(user_id.flatten())
 }
 
 pub async fn create_local_user(pool: &PgPool, username: &str, password_hash: &str) -> Result<User> {
@@ -183,7 +428,7 @@ pub async fn create_local_user(pool: &PgPool, username: &str, password_hash: &st
         r#"
         INSERT INTO users (id, username, password_hash, provider, created_at)
         VALUES ($1, $2, $3, 'local', NOW())
-        RETURNING id, username, password_hash, provider, provider_id, bio, avatar_url, created_at
+        RETURNING id, username, password_hash, provider, provider_id, bio, avatar_url, created_at, updated_at
         "#,
     )
     .bind(Uuid::new_v4())
@@ -197,7 +442,7 @@ pub async fn create_local_user(pool: &PgPool, username: &str, password_hash: &st
 pub async fn get_user_by_username(pool: &PgPool, username: &str) -> Result<Option<User>> {
     let user = sqlx::query_as::<_, User>(
         r#"
-        SELECT id, username, password_hash, provider, provider_id, bio, avatar_url, created_at
+        SELECT id, username, password_hash, provider, provider_id, bio, avatar_url, created_at, updated_at, banned_at
         FROM users
         WHERE LOWER(username) = LOWER($1)
         "#,
@@ -209,11 +454,95 @@ pub async fn get_user_by_username(pool: &PgPool, username: &str) -> Result<Optio
     Ok(user)
 }
 
+/// What anyone may see of a user, signed in or not.
+#[derive(Debug, FromRow)]
+pub struct PublicProfile {
+    pub username: String,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    /// Whether they have an active inbox link
+    pub accepts_anonymous_messages: bool,
+}
+
+/// The public profile of `username` (any case). None if there's no such
+/// user, they hid their profile, or they are banned.
+pub async fn get_public_profile(pool: &PgPool, username: &str) -> Result<Option<PublicProfile>> {
+    sqlx::query_as::<_, PublicProfile>(
+        r#"
+        SELECT u.username, u.bio, u.avatar_url,
+            EXISTS(
+                SELECT 1 FROM inbox_tokens t WHERE t.user_id = u.id AND NOT t.revoked
+            ) as accepts_anonymous_messages
+        FROM users u
+        LEFT JOIN user_preferences p ON p.user_id = u.id
+        WHERE LOWER(u.username) = LOWER($1)
+          AND u.banned_at IS NULL
+          AND COALESCE(p.public_profile, true)
+        "#,
+    )
+    .bind(username)
+    .fetch_optional(pool)
+    .await
+}
+
+/// When the user's lockout ends, if they are locked out right now.
+pub async fn get_login_lockout(pool: &PgPool, user_id: Uuid) -> Result<Option<OffsetDateTime>> {
+    let locked_until = sqlx::query_scalar(
+        "SELECT locked_until FROM login_attempts WHERE user_id = $1 AND locked_until > NOW()",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(locked_until)
+}
+
+/// Count a failed password for the user. The `max_failures`th consecutive
+/// failure locks the account for `lockout` and starts the count over;
+/// returns when that lockout ends.
+pub async fn record_failed_login(
+    pool: &PgPool,
+    user_id: Uuid,
+    max_failures: i32,
+    lockout: std::time::Duration,
+) -> Result<Option<OffsetDateTime>> {
+    let locked_until = sqlx::query_scalar(
+        r#"
+        INSERT INTO login_attempts AS la (user_id, failed_count, last_failed_at, locked_until)
+        VALUES (
+            $1,
+            CASE WHEN $2 <= 1 THEN 0 ELSE 1 END,
+            NOW(),
+            CASE WHEN $2 <= 1 THEN NOW() + $3 END
+        )
+        ON CONFLICT (user_id) DO UPDATE SET
+            failed_count = CASE WHEN la.failed_count + 1 >= $2 THEN 0 ELSE la.failed_count + 1 END,
+            last_failed_at = NOW(),
+            locked_until = CASE WHEN la.failed_count + 1 >= $2 THEN NOW() + $3 ELSE la.locked_until END
+        RETURNING CASE WHEN failed_count = 0 THEN locked_until END
+        "#,
+    )
+    .bind(user_id)
+    .bind(max_failures)
+    .bind(lockout)
+    .fetch_one(pool)
+    .await?;
+    Ok(locked_until)
+}
+
+/// Forget the user's failed passwords after a successful login.
+pub async fn clear_failed_logins(pool: &PgPool, user_id: Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM login_attempts WHERE user_id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 #[allow(dead_code)]
 pub async fn get_user_by_id(pool: &PgPool, user_id: Uuid) -> Result<User> {
     let user = sqlx::query_as::<_, User>(
         r#"
-        SELECT id, username, password_hash, provider, provider_id, bio, avatar_url, created_at
+        SELECT id, username, password_hash, provider, provider_id, bio, avatar_url, created_at, updated_at, is_admin, banned_at
         FROM users
         WHERE id = $1
         "#,
@@ -225,10 +554,18 @@ pub async fn get_user_by_id(pool: &PgPool, user_id: Uuid) -> Result<User> {
     Ok(user)
 }
 
+/// Whether `user_id` names an existing account.
+pub async fn user_exists<'e>(executor: impl PgExecutor<'e>, user_id: Uuid) -> Result<bool> {
+    sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
+        .bind(user_id)
+        .fetch_one(executor)
+        .await
+}
+
 pub async fn get_all_users(pool: &PgPool) -> Result<Vec<User>> {
     let users = sqlx::query_as::<_, User>(
         r#"
-        SELECT id, username, password_hash, provider, provider_id, bio, avatar_url, created_at
+        SELECT id, username, password_hash, provider, provider_id, bio, avatar_url, created_at, updated_at
         FROM users
         ORDER BY created_at DESC
         "#,
@@ -239,6 +576,54 @@ pub async fn get_all_users(pool: &PgPool) -> Result<Vec<User>> {
     Ok(users)
 }
 
+/// A page of the user directory, newest accounts first, leaving out
+/// `exclude_id` and everyone they blocked. With `query`, only usernames
+/// containing it (ignoring case). Also returns how many users match in total.
+pub async fn search_users(
+    pool: &PgPool,
+    exclude_id: Uuid,
+    query: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<User>, i64)> {
+    // Match the text literally: % and _ in the query aren't wildcards
+    let pattern = query.map(|q| {
+        let escaped = q
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        format!("%{escaped}%")
+    });
+    let total = sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT count(*) FROM users
+        WHERE id <> $1 AND ($2::text IS NULL OR username ILIKE $2)
+          AND NOT EXISTS (SELECT 1 FROM user_blocks WHERE blocker_id = $1 AND blocked_id = users.id)
+        "#,
+    )
+    .bind(exclude_id)
+    .bind(&pattern)
+    .fetch_one(pool)
+    .await?;
+    let users = sqlx::query_as::<_, User>(
+        r#"
+        SELECT id, username, password_hash, provider, provider_id, bio, avatar_url, created_at, updated_at
+        FROM users
+        WHERE id <> $1 AND ($2::text IS NULL OR username ILIKE $2)
+          AND NOT EXISTS (SELECT 1 FROM user_blocks WHERE blocker_id = $1 AND blocked_id = users.id)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3 OFFSET $4
+        "#,
+    )
+    .bind(exclude_id)
+    .bind(&pattern)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+    Ok((users, total))
+}
+
 // ===== Message Operations =====
 
 /// Create a new message in a new thread. Returns (message_id, thread_id).
@@ -248,14 +633,22 @@ pub async fn create_message(
     sender_id: Option<Uuid>,
     recipient_id: Uuid,
     content: &str,
+    origin_fingerprint: Option<&str>,
+    expiry: MessageExpiry,
+    forwarded_from_thread: Option<Uuid>,
 ) -> Result<(Uuid, Uuid)> {
     let message_id = Uuid::new_v4();
     let thread_id = Uuid::new_v4();
+    // Only messages without a stored sender need another way to be correlated
+    let origin_fingerprint = origin_fingerprint.filter(|_| sender_id.is_none());
 
     sqlx::query(
         r#"
-        INSERT INTO messages (id, thread_id, sender_id, recipient_id, content, created_at, is_read)
-        VALUES ($1, $2, $3, $4, $5, NOW(), false)
+        INSERT INTO messages (
+            id, thread_id, sender_id, recipient_id, content, created_at, is_read,
+            origin_fingerprint, ephemeral, expires_at, forwarded_from_thread
+        )
+        VALUES ($1, $2, $3, $4, $5, NOW(), false, $6, $7, NOW() + make_interval(secs => $8), $9)
         "#,
     )
     .bind(message_id)
@@ -263,148 +656,479 @@ pub async fn create_message(
     .bind(sender_id)
     .bind(recipient_id)
     .bind(content)
+    .bind(origin_fingerprint)
+    .bind(expiry.ephemeral)
+    .bind(expiry.ttl.map(|ttl| ttl.as_secs_f64()))
+    .bind(forwarded_from_thread)
     .execute(pool)
     .await?;
 
     Ok((message_id, thread_id))
 }
 
-/// Reply in an existing thread. Returns new message_id.
+/// Store a message that starts a new thread but stays hidden until the
+/// scheduled_messages job delivers it at `deliver_at`. `scheduled_by` may
+/// list and cancel it until then. A TTL in `expiry` counts from delivery.
+/// Returns the message id.
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(skip(pool))]
-pub async fn create_reply(
+pub async fn create_scheduled_message(
     pool: &PgPool,
-    thread_id: Uuid,
-    sender_id: Uuid,
+    scheduled_by: Uuid,
+    sender_id: Option<Uuid>,
     recipient_id: Uuid,
     content: &str,
+    origin_fingerprint: Option<&str>,
+    expiry: MessageExpiry,
+    deliver_at: OffsetDateTime,
 ) -> Result<Uuid> {
     let message_id = Uuid::new_v4();
+    let origin_fingerprint = origin_fingerprint.filter(|_| sender_id.is_none());
 
     sqlx::query(
         r#"
-        INSERT INTO messages (id, thread_id, sender_id, recipient_id, content, created_at, is_read)
-        VALUES ($1, $2, $3, $4, $5, NOW(), false)
+        INSERT INTO messages (
+            id, thread_id, sender_id, recipient_id, content, created_at, is_read,
+            origin_fingerprint, ephemeral, expires_at, status, deliver_at, scheduled_by
+        )
+        VALUES (
+            $1, $2, $3, $4, $5, $8, false,
+            $6, $7, $8 + make_interval(secs => $9), 'scheduled', $8, $10
+        )
         "#,
     )
     .bind(message_id)
-    .bind(thread_id)
+    .bind(Uuid::new_v4())
     .bind(sender_id)
     .bind(recipient_id)
     .bind(content)
+    .bind(origin_fingerprint)
+    .bind(expiry.ephemeral)
+    .bind(deliver_at)
+    .bind(expiry.ttl.map(|ttl| ttl.as_secs_f64()))
+    .bind(scheduled_by)
     .execute(pool)
     .await?;
 
     Ok(message_id)
 }
 
-/// Get all messages in a thread, ordered chronologically.
-/// Never exposes sender_id to the caller — that stays server-side.
-#[tracing::instrument(skip(pool))]
-pub async fn get_thread_messages(pool: &PgPool, thread_id: Uuid) -> Result<Vec<Message>> {
-    let messages = sqlx::query_as::<_, Message>(
+/// A message waiting to be delivered, as its author sees it.
+#[derive(Debug, FromRow)]
+pub struct ScheduledMessage {
+    pub id: Uuid,
+    pub recipient_id: Uuid,
+    pub recipient_username: String,
+    pub content: String,
+    pub deliver_at: OffsetDateTime,
+    /// false when no sender will be stored, so it can't be replied to
+    pub allow_reply: bool,
+    pub ephemeral: bool,
+}
+
+/// Messages `user_id` scheduled that haven't gone out yet, soonest first.
+pub async fn get_scheduled_messages(pool: &PgPool, user_id: Uuid) -> Result<Vec<ScheduledMessage>> {
+    sqlx::query_as::<_, ScheduledMessage>(
         r#"
-        SELECT 
+        SELECT
             m.id,
-            m.thread_id,
-            m.sender_id,
             m.recipient_id,
+            u.username as recipient_username,
             m.content,
-            m.created_at,
-            m.is_read,
-            (
-                SELECT json_object_agg(emoji, count)
-                FROM (
-                    SELECT emoji, count(*) as count
-                    FROM message_reactions
-                    WHERE message_id = m.id
-                    GROUP BY emoji
-                ) s
-            ) as reactions
+            m.deliver_at,
+            m.sender_id IS NOT NULL as allow_reply,
+            m.ephemeral
         FROM messages m
-        WHERE m.thread_id = $1
-        ORDER BY m.created_at ASC
+        JOIN users u ON u.id = m.recipient_id
+        WHERE m.scheduled_by = $1 AND m.status = 'scheduled'
+        ORDER BY m.deliver_at, m.id
         "#,
     )
-    .bind(thread_id)
+    .bind(user_id)
     .fetch_all(pool)
-    .await?;
-
-    Ok(messages)
+    .await
 }
 
-/// Get all threads where the user is either sender or recipient.
-/// Returns the latest message per thread, with unread count and
-/// recipient username (only visible to the sender).
-#[tracing::instrument(skip(pool))]
-pub async fn get_user_conversations(pool: &PgPool, user_id: Uuid) -> Result<Vec<ThreadSummary>> {
-    let threads = sqlx::query_as::<_, ThreadSummary>(
-        r#"
-        WITH latest_messages AS (
-            SELECT DISTINCT ON (thread_id)
-                id,
-                thread_id,
-                sender_id,
-                recipient_id,
-                content,
-                created_at,
-                is_read
-            FROM messages
-            WHERE sender_id = $1 OR recipient_id = $1
-            ORDER BY thread_id, created_at DESC
-        )
-        SELECT 
-            lm.id,
-            lm.thread_id,
-            lm.sender_id,
-            lm.recipient_id,
-            lm.content,
-            lm.created_at,
-            lm.is_read,
-            -- Unread count for the current user as recipient
-            (
-                SELECT count(*)::bigint FROM messages
-                WHERE thread_id = lm.thread_id
-                  AND recipient_id = $1
-                  AND is_read = false
-            ) as unread_count,
-            -- Recipient username: only shown to the sender
-            -- For messages where current user is sender, show recipient's name
-            CASE 
-                WHEN lm.sender_id = $1 THEN (SELECT username FROM users WHERE id = lm.recipient_id)
-                ELSE NULL 
-            END as recipient_username
-        FROM latest_messages lm
-        ORDER BY lm.created_at DESC
-        "#,
+/// Delete a message `user_id` scheduled, if it hasn't been delivered yet.
+/// Returns false otherwise.
+pub async fn cancel_scheduled_message(
+    pool: &PgPool,
+    message_id: Uuid,
+    user_id: Uuid,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "DELETE FROM messages WHERE id = $1 AND scheduled_by = $2 AND status = 'scheduled'",
     )
+    .bind(message_id)
     .bind(user_id)
-    .fetch_all(pool)
+    .execute(pool)
     .await?;
+    Ok(result.rows_affected() > 0)
+}
 
-    Ok(threads)
+/// A message `deliver_scheduled_messages` just delivered.
+#[derive(Debug, FromRow)]
+pub struct DeliveredMessage {
+    pub id: Uuid,
+    pub thread_id: Uuid,
+    pub recipient_id: Uuid,
+    pub content: String,
 }
 
-/// Mark all messages in a thread as read for a given recipient.
-pub async fn mark_thread_as_read(pool: &PgPool, thread_id: Uuid, reader_id: Uuid) -> Result<()> {
+/// Deliver up to `batch` scheduled messages that are due: they become
+/// visible with `created_at` set to now, and a TTL starts counting from now.
+/// Rows another instance is delivering are skipped.
+pub async fn deliver_scheduled_messages(
+    pool: &PgPool,
+    batch: i64,
+) -> Result<Vec<DeliveredMessage>> {
+    sqlx::query_as::<_, DeliveredMessage>(
+        r#"
+        UPDATE messages m
+        SET status = 'delivered',
+            created_at = NOW(),
+            expires_at = m.expires_at + (NOW() - m.deliver_at),
+            scheduled_by = NULL
+        FROM (
+            SELECT id, created_at FROM messages
+            WHERE status = 'scheduled' AND deliver_at <= NOW()
+            ORDER BY deliver_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        ) due
+        WHERE m.id = due.id AND m.created_at = due.created_at
+        RETURNING m.id, m.thread_id, m.recipient_id, m.content
+        "#,
+    )
+    .bind(batch)
+    .fetch_all(pool)
+    .await
+}
+
+/// Reply in an existing thread. Returns new message_id.
+#[tracing::instrument(skip(executor))]
+pub async fn create_reply<'e>(
+    executor: impl PgExecutor<'e>,
+    thread_id: Uuid,
+    sender_id: Uuid,
+    recipient_id: Uuid,
+    content: &str,
+    reply_to_message_id: Option<Uuid>,
+    quote_range: Option<(i32, i32)>,
+) -> Result<Uuid> {
+    let message_id = Uuid::new_v4();
+
     sqlx::query(
+        r#"
+        INSERT INTO messages (
+            id, thread_id, sender_id, recipient_id, content, created_at, is_read,
+            reply_to_message_id, quote_start, quote_end
+        )
+        VALUES ($1, $2, $3, $4, $5, NOW(), false, $6, $7, $8)
+        "#,
+    )
+    .bind(message_id)
+    .bind(thread_id)
+    .bind(sender_id)
+    .bind(recipient_id)
+    .bind(content)
+    .bind(reply_to_message_id)
+    .bind(quote_range.map(|(start, _)| start))
+    .bind(quote_range.map(|(_, end)| end))
+    .execute(executor)
+    .await?;
+
+    Ok(message_id)
+}
+
+/// Get all messages in a thread, ordered chronologically.
+/// Never exposes sender_id to the caller — that stays server-side.
+/// Deleted messages are left out unless `tombstones` is set, in which case
+/// they keep their place with placeholder content so reply chains stay intact.
+/// Messages from before `viewer_id` deleted the thread are always left out.
+/// Pinned messages come first (oldest pin first), then the rest in order;
+/// with `pinned_only`, only the pinned ones.
+#[tracing::instrument(skip(pool))]
+pub async fn get_thread_messages(
+    pool: &PgPool,
+    thread_id: Uuid,
+    viewer_id: Uuid,
+    tombstones: bool,
+    pinned_only: bool,
+) -> Result<Vec<Message>> {
+    let messages = sqlx::query_as::<_, Message>(
+        r#"
+        SELECT 
+            m.id,
+            m.thread_id,
+            m.sender_id,
+            m.recipient_id,
+            CASE WHEN m.deleted_at IS NULL THEN m.content ELSE 'This message was deleted' END as content,
+            m.created_at,
+            m.is_read,
+            CASE WHEN m.deleted_at IS NULL THEN m.edited_at END as edited_at,
+            CASE WHEN m.deleted_at IS NULL THEN COALESCE((
+                SELECT json_object_agg(emoji, count)
+                FROM (
+                    SELECT emoji, count(*) as count
+                    FROM message_reactions
+                    WHERE message_id = m.id
+                    GROUP BY emoji
+                ) s
+            ), '{}') ELSE '{}' END as reactions,
+            pm.pinned_at IS NOT NULL as pinned,
+            CASE WHEN m.deleted_at IS NULL THEN m.reply_to_message_id END as reply_to_message_id,
+            CASE WHEN m.deleted_at IS NULL AND q.deleted_at IS NULL THEN m.quote_start END as quote_start,
+            CASE WHEN m.deleted_at IS NULL AND q.deleted_at IS NULL THEN m.quote_end END as quote_end,
+            CASE
+                WHEN m.deleted_at IS NULL AND q.deleted_at IS NULL AND q.status = 'delivered'
+                    AND (q.expires_at IS NULL OR q.expires_at > NOW())
+                THEN left(
+                    CASE WHEN m.quote_start IS NULL THEN q.content
+                    ELSE substr(q.content, m.quote_start + 1, m.quote_end - m.quote_start) END,
+                    100
+                )
+            END as reply_to_preview,
+            m.forwarded_from_thread IS NOT NULL as forwarded,
+            m.expires_at
+        FROM messages m
+        LEFT JOIN LATERAL (
+            SELECT min(pinned_at) as pinned_at
+            FROM pinned_messages
+            WHERE message_id = m.id AND m.deleted_at IS NULL
+        ) pm ON true
+        LEFT JOIN messages q ON q.id = m.reply_to_message_id
+        WHERE m.thread_id = $1
+          AND ($3 OR m.deleted_at IS NULL)
+          AND (m.expires_at IS NULL OR m.expires_at > NOW())
+          AND m.status = 'delivered'
+          AND (NOT $4 OR pm.pinned_at IS NOT NULL)
+          AND NOT EXISTS (
+            SELECT 1 FROM thread_deletions td
+            WHERE td.thread_id = m.thread_id AND td.user_id = $2 AND m.created_at <= td.deleted_at
+          )
+        ORDER BY pm.pinned_at ASC NULLS LAST, m.created_at ASC
+        "#,
+    )
+    .bind(thread_id)
+    .bind(viewer_id)
+    .bind(tombstones)
+    .bind(pinned_only)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(messages)
+}
+
+/// Get all threads where the user is either sender or recipient.
+/// Returns the latest message per thread, with unread count and
+/// recipient username (only visible to the sender).
+/// Threads with someone the user blocked are left out. With `label`, only
+/// threads the user gave that label; with `pinned_only`, only threads they
+/// pinned. `archived` keeps only archived (`Some(true)`) or unarchived
+/// (`Some(false)`) threads; None keeps both.
+#[tracing::instrument(skip(pool))]
+pub async fn get_user_conversations(
+    pool: &PgPool,
+    user_id: Uuid,
+    label: Option<&str>,
+    unread_only: bool,
+    pinned_only: bool,
+    archived: Option<bool>,
+) -> Result<Vec<ThreadSummary>> {
+    let threads = sqlx::query_as::<_, ThreadSummary>(
+        r#"
+        WITH latest_messages AS (
+            SELECT DISTINCT ON (thread_id)
+                id,
+                thread_id,
+                sender_id,
+                recipient_id,
+                content,
+                created_at,
+                is_read,
+                edited_at
+            FROM messages m
+            WHERE (m.sender_id = $1 OR m.recipient_id = $1)
+              AND (m.expires_at IS NULL OR m.expires_at > NOW())
+              AND m.status = 'delivered'
+              AND NOT EXISTS (
+                SELECT 1 FROM thread_deletions td
+                WHERE td.thread_id = m.thread_id AND td.user_id = $1 AND m.created_at <= td.deleted_at
+              )
+              AND NOT thread_with_blocked_user(m.thread_id, $1)
+            ORDER BY m.thread_id, m.created_at DESC
+        ),
+        conversations AS (
+            SELECT 
+                lm.id,
+                lm.thread_id,
+                lm.sender_id,
+                lm.recipient_id,
+                lm.content,
+                lm.created_at,
+                lm.is_read,
+                lm.edited_at,
+                -- Unread count for the current user as recipient
+                (
+                    SELECT count(*)::bigint FROM messages m
+                    WHERE m.thread_id = lm.thread_id
+                      AND m.recipient_id = $1
+                      AND m.is_read = false
+                      AND (m.expires_at IS NULL OR m.expires_at > NOW())
+                      AND m.status = 'delivered'
+                      AND NOT EXISTS (
+                        SELECT 1 FROM thread_deletions td
+                        WHERE td.thread_id = m.thread_id AND td.user_id = $1 AND m.created_at <= td.deleted_at
+                      )
+                ) as unread_count,
+                -- Recipient username: only shown to the sender
+                -- For messages where current user is sender, show recipient's name
+                CASE 
+                    WHEN lm.sender_id = $1 THEN (SELECT username FROM users WHERE id = lm.recipient_id)
+                    ELSE NULL 
+                END as recipient_username,
+                EXISTS(SELECT 1 FROM thread_pauses WHERE thread_id = lm.thread_id) as paused,
+                EXISTS(
+                    SELECT 1 FROM pinned_threads WHERE user_id = $1 AND thread_id = lm.thread_id
+                ) as pinned,
+                ARRAY(
+                    SELECT label FROM thread_labels
+                    WHERE user_id = $1 AND thread_id = lm.thread_id
+                    ORDER BY label
+                ) as labels,
+                EXISTS(
+                    SELECT 1 FROM thread_archives
+                    WHERE user_id = $1 AND thread_id = lm.thread_id AND archived_at >= lm.created_at
+                ) as archived,
+                EXISTS(
+                    SELECT 1 FROM thread_mutes
+                    WHERE user_id = $1 AND thread_id = lm.thread_id
+                      AND (muted_until IS NULL OR muted_until > NOW())
+                ) as muted
+            FROM latest_messages lm
+            WHERE $2::text IS NULL OR EXISTS (
+                SELECT 1 FROM thread_labels
+                WHERE user_id = $1 AND thread_id = lm.thread_id AND label = $2
+            )
+        )
+        SELECT * FROM conversations
+        WHERE (NOT $3 OR unread_count > 0)
+          AND (NOT $4 OR pinned)
+          AND ($5::bool IS NULL OR archived = $5)
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .bind(label)
+    .bind(unread_only)
+    .bind(pinned_only)
+    .bind(archived)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(threads)
+}
+
+/// Unread messages waiting for a user, for the notification badge.
+#[derive(Debug, FromRow)]
+pub struct UnreadSummary {
+    pub total: i64,
+    /// Distinct threads with at least one unread message
+    pub threads: i64,
+}
+
+/// Counts the same messages the conversation list does: live, unread, and
+/// not in a thread the user has deleted from their view or shares with
+/// someone they blocked.
+pub async fn get_unread_summary(pool: &PgPool, recipient_id: Uuid) -> Result<UnreadSummary> {
+    let summary = sqlx::query_as::<_, UnreadSummary>(
+        r#"
+        SELECT count(*) as total, count(DISTINCT m.thread_id) as threads
+        FROM messages m
+        WHERE m.recipient_id = $1
+          AND m.is_read = false
+          AND m.deleted_at IS NULL
+          AND (m.expires_at IS NULL OR m.expires_at > NOW())
+          AND m.status = 'delivered'
+          AND NOT EXISTS (
+            SELECT 1 FROM thread_deletions td
+            WHERE td.thread_id = m.thread_id AND td.user_id = $1 AND m.created_at <= td.deleted_at
+          )
+          AND NOT thread_with_blocked_user(m.thread_id, $1)
+        "#,
+    )
+    .bind(recipient_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(summary)
+}
+
+/// Mark all messages in a thread as read for a given recipient.
+/// Returns the messages that were unread until now. Safe to call from several
+/// devices at once: a message is only reported by whichever call reads it
+/// first, and `read_at` always keeps the earliest read.
+pub async fn mark_thread_as_read(
+    pool: &PgPool,
+    thread_id: Uuid,
+    reader_id: Uuid,
+) -> Result<Vec<ReadMessage>> {
+    let read = sqlx::query_as::<_, ReadMessage>(
         r#"
         UPDATE messages
-        SET is_read = true
+        SET is_read = true, read_at = COALESCE(read_at, NOW())
         WHERE thread_id = $1 AND recipient_id = $2 AND is_read = false
+          AND (expires_at IS NULL OR expires_at > NOW())
+          AND status = 'delivered'
+        RETURNING id, sender_id, read_at
         "#,
     )
     .bind(thread_id)
     .bind(reader_id)
-    .execute(pool)
+    .fetch_all(pool)
     .await?;
-    Ok(())
+    Ok(read)
 }
 
-/// Find the other participant in a thread (i.e. everyone who isn't `user_id`).
-/// Returns the UUID of the other user, or None if not found.
-#[allow(dead_code)]
+/// Start the countdown on the ephemeral messages `reader_id` received in a
+/// thread: each now expires `after` from now, unless it was due sooner.
+/// Returns the messages whose expiry moved, with their new `expires_at`.
+pub async fn schedule_ephemeral_expiry(
+    pool: &PgPool,
+    thread_id: Uuid,
+    reader_id: Uuid,
+    after: std::time::Duration,
+) -> Result<Vec<(Uuid, OffsetDateTime)>> {
+    let scheduled = sqlx::query_as(
+        r#"
+        UPDATE messages
+        SET expires_at = NOW() + make_interval(secs => $3)
+        WHERE thread_id = $1 AND recipient_id = $2 AND ephemeral
+          AND deleted_at IS NULL
+          AND (expires_at IS NULL OR expires_at > NOW() + make_interval(secs => $3))
+          AND status = 'delivered'
+        RETURNING id, expires_at
+        "#,
+    )
+    .bind(thread_id)
+    .bind(reader_id)
+    .bind(after.as_secs_f64())
+    .fetch_all(pool)
+    .await?;
+    Ok(scheduled)
+}
+
+/// One page of messages received by `recipient_id`, newest first.
+/// `before`/`before_id` is the cursor from the previous page; `id` breaks ties
+/// between messages sharing a timestamp so no message is skipped or repeated.
 #[tracing::instrument(skip(pool))]
-pub async fn get_user_inbox(pool: &PgPool, recipient_id: Uuid) -> Result<Vec<Message>> {
+pub async fn get_user_inbox(
+    pool: &PgPool,
+    recipient_id: Uuid,
+    limit: i64,
+    before: Option<OffsetDateTime>,
+    before_id: Option<Uuid>,
+) -> Result<Vec<Message>> {
     let messages = sqlx::query_as::<_, Message>(
         r#"
         SELECT 
@@ -415,7 +1139,8 @@ pub async fn get_user_inbox(pool: &PgPool, recipient_id: Uuid) -> Result<Vec<Mes
             m.content, 
             m.created_at, 
             m.is_read,
-            (
+            m.edited_at,
+            COALESCE((
                 SELECT json_object_agg(emoji, count)
                 FROM (
                     SELECT emoji, count(*) as count
@@ -423,13 +1148,32 @@ pub async fn get_user_inbox(pool: &PgPool, recipient_id: Uuid) -> Result<Vec<Mes
                     WHERE message_id = m.id
                     GROUP BY emoji
                 ) s
-            ) as reactions
+            ), '{}') as reactions
         FROM messages m
         WHERE m.recipient_id = $1
-        ORDER BY m.created_at DESC
+          AND m.deleted_at IS NULL
+          AND (m.expires_at IS NULL OR m.expires_at > NOW())
+          AND m.status = 'delivered'
+          AND NOT EXISTS (
+            SELECT 1 FROM thread_deletions td
+            WHERE td.thread_id = m.thread_id AND td.user_id = $1 AND m.created_at <= td.deleted_at
+          )
+          AND NOT EXISTS (
+            SELECT 1 FROM users s WHERE s.id = m.sender_id AND s.banned_at IS NOT NULL
+          )
+          AND (
+            $2::timestamptz IS NULL
+            OR ($3::uuid IS NULL AND m.created_at < $2)
+            OR (m.created_at, m.id) < ($2, $3)
+          )
+        ORDER BY m.created_at DESC, m.id DESC
+        LIMIT $4
         "#,
     )
     .bind(recipient_id)
+    .bind(before)
+    .bind(before_id)
+    .bind(limit)
     .fetch_all(pool)
     .await?;
 
@@ -446,8 +1190,7 @@ pub async fn add_message_reaction(
         r#"
         INSERT INTO message_reactions (message_id, user_id, emoji)
         VALUES ($1, $2, $3)
-        ON CONFLICT (message_id, user_id) 
-        DO UPDATE SET emoji = $3
+        ON CONFLICT (message_id, user_id, emoji) DO NOTHING
         "#,
     )
     .bind(message_id)
@@ -459,66 +1202,344 @@ pub async fn add_message_reaction(
     Ok(())
 }
 
+/// Take back one of the user's reactions. Returns false if they hadn't used that emoji.
+pub async fn remove_message_reaction(
+    pool: &PgPool,
+    message_id: Uuid,
+    user_id: Uuid,
+    emoji: &str,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "DELETE FROM message_reactions WHERE message_id = $1 AND user_id = $2 AND emoji = $3",
+    )
+    .bind(message_id)
+    .bind(user_id)
+    .bind(emoji)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Result of one item in a batch reaction update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReactionOutcome {
+    Applied,
+    NotFound,
+    /// The caller isn't a participant in the message's thread
+    Forbidden,
+}
+
+/// Add (`add = true`) or remove reactions on several messages in one
+/// transaction. Items the caller may not react to are reported and skipped;
+/// the rest are applied together.
+pub async fn apply_message_reactions(
+    pool: &PgPool,
+    user_id: Uuid,
+    items: &[(Uuid, String, bool)],
+) -> Result<Vec<ReactionOutcome>> {
+    let mut tx = pool.begin().await?;
+    let mut outcomes = Vec::with_capacity(items.len());
+
+    for (message_id, emoji, add) in items {
+        let participants: Option<(Option<Uuid>, Uuid)> = sqlx::query_as(
+            r#"
+            SELECT sender_id, recipient_id FROM messages
+            WHERE id = $1 AND deleted_at IS NULL AND status = 'delivered'
+              AND (expires_at IS NULL OR expires_at > NOW())
+            "#,
+        )
+        .bind(message_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let outcome = match participants {
+            None => ReactionOutcome::NotFound,
+            Some((sender_id, recipient_id))
+                if sender_id != Some(user_id) && recipient_id != user_id =>
+            {
+                ReactionOutcome::Forbidden
+            }
+            Some(_) => {
+                if *add {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO message_reactions (message_id, user_id, emoji)
+                        VALUES ($1, $2, $3)
+                        ON CONFLICT (message_id, user_id, emoji) DO NOTHING
+                        "#,
+                    )
+                    .bind(message_id)
+                    .bind(user_id)
+                    .bind(emoji)
+                    .execute(&mut *tx)
+                    .await?;
+                } else {
+                    sqlx::query(
+                        "DELETE FROM message_reactions WHERE message_id = $1 AND user_id = $2 AND emoji = $3",
+                    )
+                    .bind(message_id)
+                    .bind(user_id)
+                    .bind(emoji)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                ReactionOutcome::Applied
+            }
+        };
+        outcomes.push(outcome);
+    }
+
+    tx.commit().await?;
+    Ok(outcomes)
+}
+
 /// Get a message by its ID (used for reply — to find thread info).
-pub async fn get_message_by_id(pool: &PgPool, message_id: Uuid) -> Result<Option<Message>> {
+pub async fn get_message_by_id<'e>(
+    executor: impl PgExecutor<'e>,
+    message_id: Uuid,
+) -> Result<Option<Message>> {
     let msg = sqlx::query_as::<_, Message>(
         r#"
-        SELECT id, thread_id, sender_id, recipient_id, content, created_at, is_read, NULL::jsonb as reactions
+        SELECT id, thread_id, sender_id, recipient_id, content, created_at, is_read, edited_at, '{}'::json as reactions
         FROM messages
-        WHERE id = $1
+        WHERE id = $1 AND (expires_at IS NULL OR expires_at > NOW()) AND status = 'delivered'
+        "#,
+    )
+    .bind(message_id)
+    .fetch_optional(executor)
+    .await?;
+    Ok(msg)
+}
+
+/// What forwarding needs to know about a message.
+#[derive(Debug, FromRow)]
+pub struct ForwardSource {
+    pub thread_id: Uuid,
+    pub sender_id: Option<Uuid>,
+    pub recipient_id: Uuid,
+    pub content: String,
+    /// Ephemeral or timed, so it was never meant to be kept
+    pub disappearing: bool,
+}
+
+/// A live message, for `forward`. None if it doesn't exist, was deleted,
+/// hasn't been delivered yet or has expired.
+pub async fn get_forward_source(pool: &PgPool, message_id: Uuid) -> Result<Option<ForwardSource>> {
+    sqlx::query_as::<_, ForwardSource>(
+        r#"
+        SELECT
+            thread_id,
+            sender_id,
+            recipient_id,
+            content,
+            ephemeral OR expires_at IS NOT NULL as disappearing
+        FROM messages
+        WHERE id = $1 AND deleted_at IS NULL AND status = 'delivered'
+          AND (expires_at IS NULL OR expires_at > NOW())
         "#,
     )
     .bind(message_id)
     .fetch_optional(pool)
+    .await
+}
+
+pub async fn get_quoted_message<'e>(
+    executor: impl PgExecutor<'e>,
+    message_id: Uuid,
+) -> Result<Option<QuotedMessage>> {
+    let msg = sqlx::query_as::<_, QuotedMessage>(
+        r#"
+        SELECT thread_id, content, deleted_at IS NOT NULL as deleted FROM messages
+        WHERE id = $1 AND (expires_at IS NULL OR expires_at > NOW()) AND status = 'delivered'
+        "#,
+    )
+    .bind(message_id)
+    .fetch_optional(executor)
     .await?;
     Ok(msg)
 }
 
-// ===== Broadcast Operations =====
+/// Who `user_id` is talking to in a thread, judged from the whole thread
+/// rather than one message: the recipient of their latest sent message,
+/// otherwise the sender of their latest received one.
+/// `None` if they aren't part of the thread; `Some(None)` if they are but the
+/// other side left no sender to reply to.
+#[tracing::instrument(skip(executor))]
+pub async fn get_thread_counterpart<'e>(
+    executor: impl PgExecutor<'e>,
+    thread_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<Option<Uuid>>> {
+    let row: (Option<bool>, Option<Uuid>) = sqlx::query_as(
+        r#"
+        SELECT
+            bool_or(recipient_id = $2 OR sender_id = $2),
+            COALESCE(
+                (array_agg(recipient_id ORDER BY created_at DESC)
+                    FILTER (WHERE sender_id = $2 AND recipient_id <> $2))[1],
+                (array_agg(sender_id ORDER BY created_at DESC)
+                    FILTER (WHERE recipient_id = $2 AND sender_id IS NOT NULL AND sender_id <> $2))[1]
+            )
+        FROM messages
+        WHERE thread_id = $1
+        "#,
+    )
+    .bind(thread_id)
+    .bind(user_id)
+    .fetch_one(executor)
+    .await?;
 
-#[tracing::instrument(skip(pool))]
-pub async fn create_broadcast(
+    Ok(match row {
+        (Some(true), counterpart) => Some(counterpart),
+        _ => None,
+    })
+}
+
+/// One message from an exported thread, as seen by the importer.
+#[derive(Debug)]
+pub struct ImportedMessage {
+    pub content: String,
+    pub is_mine: bool,
+    pub created_at: OffsetDateTime,
+}
+
+/// Recreate an exported thread for `user_id` under a new thread id.
+/// The importer's messages are stored as sent to themselves and the other
+/// party's with no sender, so nobody on this instance can be linked to them.
+/// Everything is already read. Returns the new thread_id.
+#[tracing::instrument(skip(pool, messages))]
+pub async fn import_thread(
     pool: &PgPool,
-    sender_id: Option<Uuid>,
+    user_id: Uuid,
+    other_party: &str,
+    messages: &[ImportedMessage],
+) -> Result<Uuid> {
+    let thread_id = Uuid::new_v4();
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO imported_threads (thread_id, user_id, other_party) VALUES ($1, $2, $3)",
+    )
+    .bind(thread_id)
+    .bind(user_id)
+    .bind(other_party)
+    .execute(&mut *tx)
+    .await?;
+
+    for m in messages {
+        sqlx::query(
+            r#"
+            INSERT INTO messages (id, thread_id, sender_id, recipient_id, content, created_at, is_read, read_at)
+            VALUES ($1, $2, $3, $4, $5, $6, true, $6)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(thread_id)
+        .bind(m.is_mine.then_some(user_id))
+        .bind(user_id)
+        .bind(&m.content)
+        .bind(m.created_at)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(thread_id)
+}
+
+/// Whether a thread was imported. Imported threads are read-only: there is
+/// nobody on this instance to deliver a reply to.
+pub async fn is_thread_imported<'e>(
+    executor: impl PgExecutor<'e>,
+    thread_id: Uuid,
+) -> Result<bool> {
+    let imported: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM imported_threads WHERE thread_id = $1)")
+            .bind(thread_id)
+            .fetch_one(executor)
+            .await?;
+    Ok(imported)
+}
+
+// ===== Broadcast Operations =====
+
+#[tracing::instrument(skip(executor))]
+/// Published anonymous broadcasts store no sender; drafts keep theirs until
+/// published (see `publish_broadcast`). `author_id` is always recorded.
+pub async fn create_broadcast<'e>(
+    executor: impl PgExecutor<'e>,
+    author_id: Uuid,
     content: &str,
     is_anonymous: bool,
+    is_draft: bool,
+    origin_fingerprint: Option<&str>,
 ) -> Result<Uuid> {
     let broadcast_id = Uuid::new_v4();
+    let sender_id = if is_anonymous && !is_draft {
+        None
+    } else {
+        Some(author_id)
+    };
+    let origin_fingerprint = origin_fingerprint.filter(|_| is_anonymous);
 
     sqlx::query(
         r#"
-        INSERT INTO broadcasts (id, sender_id, content, is_anonymous, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
+        INSERT INTO broadcasts (id, sender_id, author_id, content, is_anonymous, is_draft, created_at, origin_fingerprint)
+        VALUES ($1, $2, $3, $4, $5, $6, NOW(), $7)
         "#,
     )
     .bind(broadcast_id)
     .bind(sender_id)
+    .bind(author_id)
     .bind(content)
     .bind(is_anonymous)
-    .execute(pool)
+    .bind(is_draft)
+    .bind(origin_fingerprint)
+    .execute(executor)
     .await?;
 
     Ok(broadcast_id)
 }
 
 #[tracing::instrument(skip(pool))]
-pub async fn get_broadcasts(pool: &PgPool, limit: i64) -> Result<Vec<Broadcast>> {
+/// Published broadcasts, newest first, paged by `(created_at, id)` like the inbox.
+pub async fn get_broadcasts(
+    pool: &PgPool,
+    limit: i64,
+    before: Option<OffsetDateTime>,
+    before_id: Option<Uuid>,
+) -> Result<Vec<Broadcast>> {
     let broadcasts = sqlx::query_as::<_, Broadcast>(
         r#"
         SELECT 
             b.id, 
             b.sender_id, 
+            b.author_id,
             u.username as sender_username,
             b.content, 
             b.is_anonymous, 
             b.created_at,
-            (SELECT count(*) FROM broadcast_views WHERE broadcast_id = b.id) as view_count
+            (SELECT count(*) FROM broadcast_views WHERE broadcast_id = b.id) as view_count,
+            b.edited_at,
+            (SELECT count(*) FROM broadcast_edits WHERE broadcast_id = b.id) as edit_count,
+            b.is_draft
         FROM broadcasts b
         LEFT JOIN users u ON b.sender_id = u.id
-        ORDER BY b.created_at DESC
-        LIMIT $1
+        WHERE NOT b.is_draft AND b.deleted_at IS NULL
+          AND NOT EXISTS (
+            SELECT 1 FROM users a WHERE a.id = b.author_id AND a.banned_at IS NOT NULL
+          )
+          AND (
+            $1::timestamptz IS NULL
+            OR ($2::uuid IS NULL AND b.created_at < $1)
+            OR (b.created_at, b.id) < ($1, $2)
+          )
+        ORDER BY b.created_at DESC, b.id DESC
+        LIMIT $3
         "#,
     )
+    .bind(before)
+    .bind(before_id)
     .bind(limit)
     .fetch_all(pool)
     .await?;
@@ -526,24 +1547,258 @@ pub async fn get_broadcasts(pool: &PgPool, limit: i64) -> Result<Vec<Broadcast>>
     Ok(broadcasts)
 }
 
-pub async fn track_broadcast_view(pool: &PgPool, broadcast_id: Uuid, user_id: Uuid) -> Result<()> {
-    sqlx::query(
-        "INSERT INTO broadcast_views (broadcast_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+/// Full-text search of published broadcasts over `search_doc`, best matches
+/// first (newest first on ties). Matches the author's username too, except
+/// on anonymous broadcasts.
+pub async fn search_broadcasts(pool: &PgPool, query: &str, limit: i64) -> Result<Vec<Broadcast>> {
+    let broadcasts = sqlx::query_as::<_, Broadcast>(
+        r#"
+        SELECT
+            b.id,
+            b.sender_id,
+            b.author_id,
+            u.username as sender_username,
+            b.content,
+            b.is_anonymous,
+            b.created_at,
+            (SELECT count(*) FROM broadcast_views WHERE broadcast_id = b.id) as view_count,
+            b.edited_at,
+            (SELECT count(*) FROM broadcast_edits WHERE broadcast_id = b.id) as edit_count,
+            b.is_draft
+        FROM broadcasts b
+        LEFT JOIN users u ON b.sender_id = u.id
+        CROSS JOIN plainto_tsquery('english', $1) q
+        WHERE NOT b.is_draft AND b.deleted_at IS NULL
+          AND NOT EXISTS (
+            SELECT 1 FROM users a WHERE a.id = b.author_id AND a.banned_at IS NOT NULL
+          )
+          AND b.search_doc @@ q
+        ORDER BY ts_rank(b.search_doc, q) DESC, b.created_at DESC
+        LIMIT $2
+        "#,
     )
-    .bind(broadcast_id)
-    .bind(user_id)
-    .execute(pool)
+    .bind(query)
+    .bind(limit)
+    .fetch_all(pool)
     .await?;
-    Ok(())
+
+    Ok(broadcasts)
 }
 
-pub async fn update_user_profile(
-    pool: &PgPool,
-    user_id: Uuid,
+/// Record that the user just looked at the broadcast feed.
+pub async fn mark_broadcasts_seen(pool: &PgPool, user_id: Uuid) -> Result<()> {
+    sqlx::query("UPDATE users SET last_broadcast_seen_at = NOW() WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Published broadcasts posted since the user last opened the feed (or since
+/// they signed up), leaving out their own and those by authors they blocked.
+/// Anonymous broadcasts always count, so the number never hints at who
+/// posted them.
+#[tracing::instrument(skip(pool))]
+pub async fn count_unseen_broadcasts(pool: &PgPool, user_id: Uuid) -> Result<i64> {
+    let count: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)
+        FROM broadcasts b, users me
+        WHERE me.id = $1
+          AND b.created_at > COALESCE(me.last_broadcast_seen_at, me.created_at)
+          AND b.deleted_at IS NULL
+          AND NOT b.is_draft
+          AND b.author_id IS DISTINCT FROM $1
+          AND NOT EXISTS (
+            SELECT 1 FROM user_blocks
+            WHERE blocker_id = $1 AND blocked_id = b.sender_id
+          )
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(count)
+}
+
+pub async fn get_broadcast_by_id(pool: &PgPool, broadcast_id: Uuid) -> Result<Option<Broadcast>> {
+    let broadcast = sqlx::query_as::<_, Broadcast>(
+        r#"
+        SELECT 
+            b.id, 
+            b.sender_id, 
+            b.author_id,
+            u.username as sender_username,
+            b.content, 
+            b.is_anonymous, 
+            b.created_at,
+            (SELECT count(*) FROM broadcast_views WHERE broadcast_id = b.id) as view_count,
+            b.edited_at,
+            (SELECT count(*) FROM broadcast_edits WHERE broadcast_id = b.id) as edit_count,
+            b.is_draft
+        FROM broadcasts b
+        LEFT JOIN users u ON b.sender_id = u.id
+        WHERE b.id = $1 AND b.deleted_at IS NULL
+        "#,
+    )
+    .bind(broadcast_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(broadcast)
+}
+
+/// The author's unpublished drafts, newest first.
+pub async fn get_broadcast_drafts(pool: &PgPool, author_id: Uuid) -> Result<Vec<Broadcast>> {
+    let drafts = sqlx::query_as::<_, Broadcast>(
+        r#"
+        SELECT 
+            b.id, 
+            b.sender_id, 
+            b.author_id,
+            u.username as sender_username,
+            b.content, 
+            b.is_anonymous, 
+            b.created_at,
+            0::bigint as view_count,
+            b.edited_at,
+            (SELECT count(*) FROM broadcast_edits WHERE broadcast_id = b.id) as edit_count,
+            b.is_draft
+        FROM broadcasts b
+        LEFT JOIN users u ON b.sender_id = u.id
+        WHERE b.is_draft AND b.sender_id = $1 AND b.deleted_at IS NULL
+        ORDER BY b.created_at DESC
+        "#,
+    )
+    .bind(author_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(drafts)
+}
+
+/// Make a draft live. The publish time becomes its feed position, and anonymous
+/// drafts drop their author here. Returns false if it isn't the author's draft.
+pub async fn publish_broadcast(pool: &PgPool, broadcast_id: Uuid, author_id: Uuid) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE broadcasts
+        SET 
+            is_draft = false,
+            created_at = NOW(),
+            sender_id = CASE WHEN is_anonymous THEN NULL ELSE sender_id END
+        WHERE id = $1 AND sender_id = $2 AND is_draft AND deleted_at IS NULL
+        "#,
+    )
+    .bind(broadcast_id)
+    .bind(author_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Replace a broadcast's content, keeping the previous version in `broadcast_edits`.
+/// Only the (non-anonymous) sender can edit; returns false if nothing matched.
+pub async fn edit_broadcast(
+    pool: &PgPool,
+    broadcast_id: Uuid,
+    user_id: Uuid,
+    new_content: &str,
+) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+
+    let old_content: Option<String> = sqlx::query_scalar(
+        "SELECT content FROM broadcasts WHERE id = $1 AND sender_id = $2 AND deleted_at IS NULL FOR UPDATE",
+    )
+    .bind(broadcast_id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(old_content) = old_content else {
+        return Ok(false);
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO broadcast_edits (broadcast_id, old_content, edited_by)
+        VALUES ($1, $2, $3)
+        "#,
+    )
+    .bind(broadcast_id)
+    .bind(&old_content)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE broadcasts SET content = $2, edited_at = NOW() WHERE id = $1")
+        .bind(broadcast_id)
+        .bind(new_content)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Soft-delete a broadcast. Only its recorded sender can; returns false if
+/// nothing matched (not theirs, anonymous, or already deleted).
+pub async fn delete_broadcast(pool: &PgPool, broadcast_id: Uuid, user_id: Uuid) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE broadcasts
+        SET deleted_at = NOW()
+        WHERE id = $1 AND sender_id = $2 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(broadcast_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Prior versions of a broadcast, oldest first.
+pub async fn get_broadcast_history(
+    pool: &PgPool,
+    broadcast_id: Uuid,
+) -> Result<Vec<BroadcastEdit>> {
+    let edits = sqlx::query_as::<_, BroadcastEdit>(
+        r#"
+        SELECT old_content, edited_at
+        FROM broadcast_edits
+        WHERE broadcast_id = $1
+        ORDER BY edited_at ASC
+        "#,
+    )
+    .bind(broadcast_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(edits)
+}
+
+pub async fn track_broadcast_view(pool: &PgPool, broadcast_id: Uuid, user_id: Uuid) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO broadcast_views (broadcast_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+    )
+    .bind(broadcast_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Update the caller's profile. When `expected_updated_at` is given the
+/// update only applies if the row hasn't changed since then; `None` is
+/// returned when it has.
+pub async fn update_user_profile(
+    pool: &PgPool,
+    user_id: Uuid,
     username: Option<String>,
     bio: Option<String>,
     avatar_url: Option<String>,
-) -> Result<User> {
+    expected_updated_at: Option<OffsetDateTime>,
+) -> Result<Option<User>> {
     let user = sqlx::query_as::<_, User>(
         r#"
         UPDATE users
@@ -552,15 +1807,16 @@ pub async fn update_user_profile(
             bio = COALESCE($2, bio),
             avatar_url = COALESCE($3, avatar_url),
             updated_at = NOW()
-        WHERE id = $4
-        RETURNING id, username, password_hash, provider, provider_id, bio, avatar_url, created_at
+        WHERE id = $4 AND ($5::timestamptz IS NULL OR updated_at = $5)
+        RETURNING id, username, password_hash, provider, provider_id, bio, avatar_url, created_at, updated_at
         "#,
     )
     .bind(username)
     .bind(bio)
     .bind(avatar_url)
     .bind(user_id)
-    .fetch_one(pool)
+    .bind(expected_updated_at)
+    .fetch_optional(pool)
     .await?;
 
     Ok(user)
@@ -574,14 +1830,292 @@ pub async fn delete_user(pool: &PgPool, user_id: Uuid) -> Result<()> {
     Ok(())
 }
 
+/// What deleting an account would remove or detach, per the schema's
+/// foreign-key actions.
+#[derive(Debug, FromRow)]
+pub struct AccountDeletionImpact {
+    /// Deleted along with the account
+    pub messages_received: i64,
+    /// Kept for their recipients, with the sender unset
+    pub messages_sent: i64,
+    /// Kept, with the author unset
+    pub broadcasts: i64,
+    /// Deleted along with the account
+    pub comments: i64,
+    /// Deleted along with the account
+    pub reactions: i64,
+    /// Still-running schedules, deleted along with the account
+    pub scheduled_broadcasts: i64,
+}
+
+/// Count what `delete_user` would affect, without changing anything.
+pub async fn get_account_deletion_impact(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<AccountDeletionImpact> {
+    let impact = sqlx::query_as::<_, AccountDeletionImpact>(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM messages WHERE recipient_id = $1) AS messages_received,
+            (SELECT COUNT(*) FROM messages WHERE sender_id = $1 AND recipient_id <> $1) AS messages_sent,
+            (SELECT COUNT(*) FROM broadcasts WHERE author_id = $1 AND deleted_at IS NULL) AS broadcasts,
+            (SELECT COUNT(*) FROM broadcast_comments WHERE user_id = $1 AND deleted_at IS NULL) AS comments,
+            (SELECT COUNT(*) FROM message_reactions WHERE user_id = $1)
+                + (SELECT COUNT(*) FROM broadcast_comment_reactions WHERE user_id = $1) AS reactions,
+            (SELECT COUNT(*) FROM scheduled_broadcasts WHERE author_id = $1 AND next_run_at IS NOT NULL) AS scheduled_broadcasts
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(impact)
+}
+
+// ===== Scheduled Broadcasts =====
+
+const SCHEDULED_BROADCAST_COLUMNS: &str = "id, content, is_anonymous, frequency, weekday, \
+    time_of_day, timezone, ends_at, max_occurrences, occurrence_count, next_run_at, \
+    last_run_at, cancelled_at, created_at";
+
+/// Whether Postgres knows `name` as a time zone (e.g. `Europe/Berlin`).
+pub async fn is_known_timezone(pool: &PgPool, name: &str) -> Result<bool> {
+    let known: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1)")
+            .bind(name)
+            .fetch_one(pool)
+            .await?;
+    Ok(known)
+}
+
+/// Create a schedule whose first run is the next matching slot from now.
+/// Returns `None` (and stores nothing) if the schedule would end before it ever ran.
+pub async fn create_scheduled_broadcast(
+    pool: &PgPool,
+    author_id: Uuid,
+    schedule: &NewScheduledBroadcast<'_>,
+) -> Result<Option<ScheduledBroadcast>> {
+    let created = sqlx::query_as::<_, ScheduledBroadcast>(&format!(
+        r#"
+        INSERT INTO scheduled_broadcasts (
+            author_id, content, is_anonymous, frequency, weekday, time_of_day,
+            timezone, ends_at, max_occurrences, next_run_at
+        )
+        SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, first_run
+        FROM scheduled_broadcast_next_run($4, $5, $6, $7, NOW(), $8) AS first_run
+        WHERE first_run IS NOT NULL
+        RETURNING {SCHEDULED_BROADCAST_COLUMNS}
+        "#
+    ))
+    .bind(author_id)
+    .bind(schedule.content)
+    .bind(schedule.is_anonymous)
+    .bind(schedule.frequency)
+    .bind(schedule.weekday)
+    .bind(schedule.time_of_day)
+    .bind(schedule.timezone)
+    .bind(schedule.ends_at)
+    .bind(schedule.max_occurrences)
+    .fetch_optional(pool)
+    .await?;
+    Ok(created)
+}
+
+/// The author's schedules, including ended and cancelled ones, newest first.
+pub async fn get_scheduled_broadcasts(
+    pool: &PgPool,
+    author_id: Uuid,
+) -> Result<Vec<ScheduledBroadcast>> {
+    let schedules = sqlx::query_as::<_, ScheduledBroadcast>(&format!(
+        r#"
+        SELECT {SCHEDULED_BROADCAST_COLUMNS}
+        FROM scheduled_broadcasts
+        WHERE author_id = $1
+        ORDER BY created_at DESC
+        "#
+    ))
+    .bind(author_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(schedules)
+}
+
+/// Stop a schedule. Returns false if it isn't the author's or was already cancelled.
+pub async fn cancel_scheduled_broadcast(
+    pool: &PgPool,
+    schedule_id: Uuid,
+    author_id: Uuid,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE scheduled_broadcasts
+        SET cancelled_at = NOW(), next_run_at = NULL
+        WHERE id = $1 AND author_id = $2 AND cancelled_at IS NULL
+        "#,
+    )
+    .bind(schedule_id)
+    .bind(author_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Publish one broadcast for every schedule that is due and advance each to
+/// its next slot. A schedule that missed several slots (e.g. while the server
+/// was down) still produces a single broadcast. Returns the new broadcast ids.
+pub async fn run_due_scheduled_broadcasts(pool: &PgPool) -> Result<Vec<Uuid>> {
+    transaction(pool, |tx| {
+        Box::pin(async move {
+            let due = sqlx::query_as::<_, (Uuid, String, bool)>(
+                r#"
+                UPDATE scheduled_broadcasts s
+                SET occurrence_count = s.occurrence_count + 1,
+                    last_run_at = NOW(),
+                    next_run_at = CASE
+                        WHEN s.max_occurrences IS NOT NULL
+                             AND s.occurrence_count + 1 >= s.max_occurrences THEN NULL
+                        ELSE scheduled_broadcast_next_run(
+                            s.frequency, s.weekday, s.time_of_day, s.timezone,
+                            GREATEST(s.next_run_at, NOW()), s.ends_at
+                        )
+                    END
+                FROM (
+                    SELECT id FROM scheduled_broadcasts
+                    WHERE next_run_at <= NOW()
+                    FOR UPDATE SKIP LOCKED
+                ) due
+                WHERE s.id = due.id
+                RETURNING s.author_id, s.content, s.is_anonymous
+                "#,
+            )
+            .fetch_all(&mut **tx)
+            .await?;
+
+            let mut broadcast_ids = Vec::with_capacity(due.len());
+            for (author_id, content, is_anonymous) in due {
+                broadcast_ids.push(
+                    create_broadcast(&mut **tx, author_id, &content, is_anonymous, false, None)
+                        .await?,
+                );
+            }
+            Ok(broadcast_ids)
+        })
+    })
+    .await
+}
+
 // ===== Enhanced Features =====
 
+// Send Quotas
+
+/// Count one send against the user's quota for today (UTC). Returns false,
+/// counting nothing, if `daily_cap` sends were already made today.
+pub async fn consume_send_quota<'e>(
+    executor: impl PgExecutor<'e>,
+    user_id: Uuid,
+    daily_cap: i32,
+) -> Result<bool> {
+    let counted = sqlx::query_scalar::<_, i32>(
+        r#"
+        INSERT INTO send_quotas AS sq (user_id, day, count)
+        VALUES ($1, (NOW() AT TIME ZONE 'UTC')::date, 1)
+        ON CONFLICT (user_id) DO UPDATE SET
+            count = CASE WHEN sq.day = EXCLUDED.day THEN sq.count + 1 ELSE 1 END,
+            day = EXCLUDED.day
+        WHERE sq.day <> EXCLUDED.day OR sq.count < $2
+        RETURNING count
+        "#,
+    )
+    .bind(user_id)
+    .bind(daily_cap)
+    .fetch_optional(executor)
+    .await?;
+    Ok(counted.is_some())
+}
+
 // Message Search
+/// A message search match with its highlighted snippet.
+#[derive(Debug, FromRow)]
+pub struct MessageSearchHit {
+    #[sqlx(flatten)]
+    pub message: Message,
+    /// HTML: the content escaped, cut to the best fragments, with matched
+    /// terms in `<mark>`
+    pub highlight: String,
+}
+
+/// Best matches first (`ts_rank`, newest first on ties), over `search_doc`:
+/// the content, plus the sender's username on messages from the person a
+/// thread was started with. Snippets are only built for the rows returned,
+/// since `ts_headline` re-parses the content.
 pub async fn search_messages(
     pool: &PgPool,
     user_id: Uuid,
     query: &str,
     limit: i64,
+) -> Result<Vec<MessageSearchHit>> {
+    let messages = sqlx::query_as::<_, MessageSearchHit>(
+        r#"
+        WITH q AS (SELECT plainto_tsquery('english', $2) AS query),
+        hits AS (
+            SELECT
+                m.id,
+                m.thread_id,
+                m.sender_id,
+                m.recipient_id,
+                m.content,
+                m.created_at,
+                m.is_read,
+                m.edited_at,
+                ts_rank(m.search_doc, q.query) AS rank
+            FROM messages m, q
+            WHERE (m.recipient_id = $1 OR m.sender_id = $1)
+              AND m.deleted_at IS NULL
+              AND (m.expires_at IS NULL OR m.expires_at > NOW())
+              AND m.status = 'delivered'
+              AND NOT EXISTS (
+                SELECT 1 FROM thread_deletions td
+                WHERE td.thread_id = m.thread_id AND td.user_id = $1 AND m.created_at <= td.deleted_at
+              )
+              AND m.search_doc @@ q.query
+            ORDER BY rank DESC, m.created_at DESC
+            LIMIT $3
+        )
+        SELECT
+            h.id,
+            h.thread_id,
+            h.sender_id,
+            h.recipient_id,
+            h.content,
+            h.created_at,
+            h.is_read,
+            h.edited_at,
+            '{}'::json as reactions,
+            ts_headline(
+                'english',
+                replace(replace(replace(h.content, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'),
+                q.query,
+                'StartSel=<mark>, StopSel=</mark>, MaxWords=30, MinWords=10, MaxFragments=2'
+            ) AS highlight
+        FROM hits h, q
+        ORDER BY h.rank DESC, h.created_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .bind(query)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(messages)
+}
+
+// Search within a single thread, oldest match first so clients can step through
+pub async fn search_thread_messages(
+    pool: &PgPool,
+    thread_id: Uuid,
+    viewer_id: Uuid,
+    query: &str,
+    limit: i64,
 ) -> Result<Vec<Message>> {
     let messages = sqlx::query_as::<_, Message>(
         r#"
@@ -593,47 +2127,53 @@ pub async fn search_messages(
             m.content, 
             m.created_at, 
             m.is_read,
-            NULL::jsonb as reactions
+            m.edited_at,
+            '{}'::json as reactions
         FROM messages m
-        WHERE (m.recipient_id = $1 OR m.sender_id = $1)
+        WHERE m.thread_id = $1
           AND m.deleted_at IS NULL
-          AND to_tsvector('english', m.content) @@ plainto_tsquery('english', $2)
-        ORDER BY m.created_at DESC
+          AND (m.expires_at IS NULL OR m.expires_at > NOW())
+          AND m.status = 'delivered'
+          AND NOT EXISTS (
+            SELECT 1 FROM thread_deletions td
+            WHERE td.thread_id = m.thread_id AND td.user_id = $4 AND m.created_at <= td.deleted_at
+          )
+          AND m.search_doc @@ plainto_tsquery('english', $2)
+        ORDER BY m.created_at ASC
         LIMIT $3
         "#,
     )
-    .bind(user_id)
+    .bind(thread_id)
     .bind(query)
     .bind(limit)
+    .bind(viewer_id)
     .fetch_all(pool)
     .await?;
 
     Ok(messages)
 }
 
-// Message Deletion
-pub async fn delete_message(pool: &PgPool, message_id: Uuid, user_id: Uuid) -> Result<()> {
-    sqlx::query(
-        r#"
-        UPDATE messages
-        SET deleted_at = NOW(), deleted_by = $2
-        WHERE id = $1 AND (sender_id = $2 OR recipient_id = $2)
-        "#,
+/// Whether `user_id` has sent or received any message in the thread.
+pub async fn is_thread_participant(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<bool> {
+    let is_participant: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM messages WHERE thread_id = $1 AND (sender_id = $2 OR recipient_id = $2))",
     )
-    .bind(message_id)
+    .bind(thread_id)
     .bind(user_id)
-    .execute(pool)
+    .fetch_one(pool)
     .await?;
-    Ok(())
+    Ok(is_participant)
 }
 
-// Delete entire thread
-pub async fn delete_thread(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<()> {
+// Thread Archives
+/// Archive a thread for `user_id` only, until a newer message arrives.
+/// Archiving again moves the cutoff to now.
+pub async fn archive_thread(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<()> {
     sqlx::query(
         r#"
-        UPDATE messages
-        SET deleted_at = NOW(), deleted_by = $2
-        WHERE thread_id = $1 AND (sender_id = $2 OR recipient_id = $2)
+        INSERT INTO thread_archives (thread_id, user_id)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id, thread_id) DO UPDATE SET archived_at = NOW()
         "#,
     )
     .bind(thread_id)
@@ -643,68 +2183,295 @@ pub async fn delete_thread(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Res
     Ok(())
 }
 
-// Message Editing
-pub async fn edit_message(
+pub async fn unarchive_thread(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM thread_archives WHERE thread_id = $1 AND user_id = $2")
+        .bind(thread_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// Thread Mutes
+/// Mute a thread for `user_id` until `until`, or until unmuted if None.
+/// Muting again replaces the previous end.
+pub async fn mute_thread(
     pool: &PgPool,
-    message_id: Uuid,
+    thread_id: Uuid,
     user_id: Uuid,
-    new_content: &str,
+    until: Option<OffsetDateTime>,
 ) -> Result<()> {
-    // Get old content first
-    let old_content: String = sqlx::query_scalar(
-        "SELECT content FROM messages WHERE id = $1 AND sender_id = $2 AND deleted_at IS NULL",
-    )
-    .bind(message_id)
-    .bind(user_id)
-    .fetch_one(pool)
-    .await?;
-
-    // Store edit history
     sqlx::query(
         r#"
-        INSERT INTO message_edits (message_id, old_content, edited_by)
+        INSERT INTO thread_mutes (thread_id, user_id, muted_until)
         VALUES ($1, $2, $3)
+        ON CONFLICT (user_id, thread_id) DO UPDATE SET muted_until = EXCLUDED.muted_until
         "#,
     )
-    .bind(message_id)
-    .bind(&old_content)
+    .bind(thread_id)
     .bind(user_id)
+    .bind(until)
     .execute(pool)
     .await?;
+    Ok(())
+}
 
-    // Update message
-    sqlx::query(
+pub async fn unmute_thread(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM thread_mutes WHERE thread_id = $1 AND user_id = $2")
+        .bind(thread_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Whether `user_id` has the thread muted right now.
+pub async fn is_thread_muted(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<bool> {
+    sqlx::query_scalar(
         r#"
-        UPDATE messages
-        SET content = $2, edited_at = NOW()
-        WHERE id = $1 AND sender_id = $3 AND deleted_at IS NULL
+        SELECT EXISTS(
+            SELECT 1 FROM thread_mutes
+            WHERE thread_id = $1 AND user_id = $2
+              AND (muted_until IS NULL OR muted_until > NOW())
+        )
         "#,
     )
-    .bind(message_id)
-    .bind(new_content)
+    .bind(thread_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+// Thread Pauses
+pub async fn pause_thread(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO thread_pauses (thread_id, paused_by) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(thread_id)
     .bind(user_id)
     .execute(pool)
     .await?;
+    Ok(())
+}
 
+pub async fn resume_thread(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM thread_pauses WHERE thread_id = $1 AND paused_by = $2")
+        .bind(thread_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
     Ok(())
 }
 
-// Pin/Unpin Message
-pub async fn toggle_pin_message(pool: &PgPool, message_id: Uuid, user_id: Uuid) -> Result<bool> {
-    // Check if already pinned
-    let is_pinned: bool = sqlx::query_scalar(
-        "SELECT EXISTS(SELECT 1 FROM pinned_messages WHERE message_id = $1 AND user_id = $2)",
+/// Whether anyone has paused this thread.
+pub async fn is_thread_paused(pool: &PgPool, thread_id: Uuid) -> Result<bool> {
+    let is_paused: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM thread_pauses WHERE thread_id = $1)")
+            .bind(thread_id)
+            .fetch_one(pool)
+            .await?;
+    Ok(is_paused)
+}
+
+/// Whether the thread was paused by someone other than `user_id`, which
+/// means `user_id` may not post into it. The pauser can still post.
+pub async fn is_thread_paused_for<'e>(
+    executor: impl PgExecutor<'e>,
+    thread_id: Uuid,
+    user_id: Uuid,
+) -> Result<bool> {
+    let is_paused: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM thread_pauses WHERE thread_id = $1 AND paused_by <> $2)",
+    )
+    .bind(thread_id)
+    .bind(user_id)
+    .fetch_one(executor)
+    .await?;
+    Ok(is_paused)
+}
+
+// Message Deletion
+pub async fn delete_message(pool: &PgPool, message_id: Uuid, user_id: Uuid) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        UPDATE messages
+        SET deleted_at = NOW(), deleted_by = $2
+        WHERE id = $1 AND (sender_id = $2 OR recipient_id = $2)
+        "#,
+    )
+    .bind(message_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Hard-delete a message `sender_id` sent less than `window` ago, as if it
+/// was never sent. Returns its `(thread_id, recipient_id)`, or None if it
+/// isn't theirs, the window has passed, or it has a pending report: the
+/// moderators still need its author. Closed reports keep their snapshot.
+pub async fn unsend_message(
+    pool: &PgPool,
+    message_id: Uuid,
+    sender_id: Uuid,
+    window: std::time::Duration,
+) -> Result<Option<(Uuid, Uuid)>> {
+    let unsent = sqlx::query_as(
+        r#"
+        DELETE FROM messages
+        WHERE id = $1 AND sender_id = $2 AND status = 'delivered'
+          AND created_at > NOW() - make_interval(secs => $3)
+          AND NOT EXISTS (
+              SELECT 1 FROM message_reports
+              WHERE message_id = $1 AND status = 'pending'
+          )
+        RETURNING thread_id, recipient_id
+        "#,
     )
     .bind(message_id)
+    .bind(sender_id)
+    .bind(window.as_secs_f64())
+    .fetch_optional(pool)
+    .await?;
+    Ok(unsent)
+}
+
+// Delete entire thread
+/// Hide a thread from `user_id`'s views only. Messages sent after this point
+/// make the thread reappear for them; the other participant keeps it as is.
+pub async fn delete_thread(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO thread_deletions (thread_id, user_id)
+        VALUES ($1, $2)
+        ON CONFLICT (thread_id, user_id) DO UPDATE SET deleted_at = NOW()
+        "#,
+    )
+    .bind(thread_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// How many messages `delete_thread` would hide from `user_id` right now.
+pub async fn count_deletable_thread_messages(
+    pool: &PgPool,
+    thread_id: Uuid,
+    user_id: Uuid,
+) -> Result<i64> {
+    let count: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)
+        FROM messages m
+        WHERE m.thread_id = $1
+          AND m.deleted_at IS NULL
+          AND (m.expires_at IS NULL OR m.expires_at > NOW())
+          AND m.status = 'delivered'
+          AND (m.recipient_id = $2 OR m.sender_id = $2)
+          AND NOT EXISTS (
+            SELECT 1 FROM thread_deletions td
+            WHERE td.thread_id = m.thread_id AND td.user_id = $2 AND m.created_at <= td.deleted_at
+          )
+        "#,
+    )
+    .bind(thread_id)
     .bind(user_id)
     .fetch_one(pool)
     .await?;
+    Ok(count)
+}
+
+// Message Editing
+/// Result of `edit_message`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditOutcome {
+    Edited,
+    /// No live message with that id sent by the caller
+    NotFound,
+    /// The message is older than the edit window
+    WindowClosed,
+}
+
+/// Replace the content of a message `user_id` sent, keeping the old content
+/// in its edit history. With `window`, only messages sent less than that
+/// long ago can be edited; the age is checked in the database, on the
+/// locked row, so the edit can't land after the window closes.
+pub async fn edit_message(
+    pool: &PgPool,
+    message_id: Uuid,
+    user_id: Uuid,
+    new_content: &str,
+    window: Option<std::time::Duration>,
+) -> Result<EditOutcome> {
+    let mut tx = pool.begin().await?;
+
+    let current: Option<(String, bool)> = sqlx::query_as(
+        r#"
+        SELECT content, ($3::float8 IS NULL OR created_at > NOW() - make_interval(secs => $3))
+        FROM messages
+        WHERE id = $1 AND sender_id = $2 AND deleted_at IS NULL
+          AND (expires_at IS NULL OR expires_at > NOW())
+          AND status = 'delivered'
+        FOR UPDATE
+        "#,
+    )
+    .bind(message_id)
+    .bind(user_id)
+    .bind(window.map(|window| window.as_secs_f64()))
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let old_content = match current {
+        None => return Ok(EditOutcome::NotFound),
+        Some((_, false)) => return Ok(EditOutcome::WindowClosed),
+        Some((content, true)) => content,
+    };
+
+    // Store edit history
+    sqlx::query(
+        r#"
+        INSERT INTO message_edits (message_id, old_content, edited_by)
+        VALUES ($1, $2, $3)
+        "#,
+    )
+    .bind(message_id)
+    .bind(&old_content)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    // Update message
+    sqlx::query(
+        r#"
+        UPDATE messages
+        SET content = $2, edited_at = NOW()
+        WHERE id = $1 AND sender_id = $3 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(message_id)
+    .bind(new_content)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(EditOutcome::Edited)
+}
+
+// Pin/Unpin Message
+pub async fn toggle_pin_message(pool: &PgPool, message_id: Uuid, user_id: Uuid) -> Result<bool> {
+    // Pins are shared by both participants, so either one can unpin
+    let is_pinned: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM pinned_messages WHERE message_id = $1)")
+            .bind(message_id)
+            .fetch_one(pool)
+            .await?;
 
     if is_pinned {
         // Unpin
-        sqlx::query("DELETE FROM pinned_messages WHERE message_id = $1 AND user_id = $2")
+        sqlx::query("DELETE FROM pinned_messages WHERE message_id = $1")
             .bind(message_id)
-            .bind(user_id)
             .execute(pool)
             .await?;
         Ok(false)
@@ -762,6 +2529,84 @@ pub async fn get_pinned_threads(pool: &PgPool, user_id: Uuid) -> Result<Vec<Uuid
     Ok(thread_ids)
 }
 
+// Thread Labels
+
+/// A label and how many of the user's threads carry it.
+#[derive(Debug, FromRow)]
+pub struct LabelSummary {
+    pub label: String,
+    pub thread_count: i64,
+}
+
+/// All of the user's labels, alphabetical.
+pub async fn get_user_labels(pool: &PgPool, user_id: Uuid) -> Result<Vec<LabelSummary>> {
+    let labels = sqlx::query_as::<_, LabelSummary>(
+        r#"
+        SELECT label, count(*) as thread_count
+        FROM thread_labels
+        WHERE user_id = $1
+        GROUP BY label
+        ORDER BY label
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(labels)
+}
+
+/// The user's labels on one thread, alphabetical.
+pub async fn get_thread_labels(
+    pool: &PgPool,
+    user_id: Uuid,
+    thread_id: Uuid,
+) -> Result<Vec<String>> {
+    let labels = sqlx::query_scalar(
+        "SELECT label FROM thread_labels WHERE user_id = $1 AND thread_id = $2 ORDER BY label",
+    )
+    .bind(user_id)
+    .bind(thread_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(labels)
+}
+
+/// Label a thread for the user. A no-op if it already has the label.
+pub async fn add_thread_label(
+    pool: &PgPool,
+    user_id: Uuid,
+    thread_id: Uuid,
+    label: &str,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO thread_labels (user_id, thread_id, label) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+    )
+    .bind(user_id)
+    .bind(thread_id)
+    .bind(label)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Take a label off a thread. Returns false if it didn't have it.
+pub async fn remove_thread_label(
+    pool: &PgPool,
+    user_id: Uuid,
+    thread_id: Uuid,
+    label: &str,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "DELETE FROM thread_labels WHERE user_id = $1 AND thread_id = $2 AND label = $3",
+    )
+    .bind(user_id)
+    .bind(thread_id)
+    .bind(label)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
 // User Blocking
 pub async fn block_user(pool: &PgPool, blocker_id: Uuid, blocked_id: Uuid) -> Result<()> {
     sqlx::query(
@@ -792,7 +2637,43 @@ pub async fn get_blocked_users(pool: &PgPool, user_id: Uuid) -> Result<Vec<Uuid>
     Ok(blocked_ids)
 }
 
-#[allow(dead_code)]
+/// People the user knows by identity: the recipients of threads the user
+/// started. Anyone who messaged the user first stays anonymous, even after
+/// the user replies, so those threads never produce a contact. Users the
+/// caller blocked are left out.
+pub async fn get_contacts(pool: &PgPool, user_id: Uuid) -> Result<Vec<Contact>> {
+    let contacts = sqlx::query_as::<_, Contact>(
+        r#"
+        SELECT
+            u.id,
+            u.username,
+            u.avatar_url,
+            max(m.created_at) as last_interaction_at
+        FROM messages first
+        JOIN messages m ON m.thread_id = first.thread_id AND m.deleted_at IS NULL
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            AND m.status = 'delivered'
+        JOIN users u ON u.id = first.recipient_id
+        WHERE first.sender_id = $1
+          AND first.recipient_id <> $1
+          AND NOT EXISTS (
+            SELECT 1 FROM messages earlier
+            WHERE earlier.thread_id = first.thread_id AND earlier.created_at < first.created_at
+          )
+          AND NOT EXISTS (
+            SELECT 1 FROM user_blocks
+            WHERE blocker_id = $1 AND blocked_id = u.id
+          )
+        GROUP BY u.id, u.username, u.avatar_url
+        ORDER BY last_interaction_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(contacts)
+}
+
 pub async fn is_blocked(pool: &PgPool, blocker_id: Uuid, blocked_id: Uuid) -> Result<bool> {
     let blocked: bool = sqlx::query_scalar(
         "SELECT EXISTS(SELECT 1 FROM user_blocks WHERE blocker_id = $1 AND blocked_id = $2)",
@@ -805,30 +2686,50 @@ pub async fn is_blocked(pool: &PgPool, blocker_id: Uuid, blocked_id: Uuid) -> Re
 }
 
 // Typing Indicators
-pub async fn set_typing_indicator(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<()> {
-    sqlx::query(
+
+/// How long a typing indicator counts as live after it was (re)started.
+pub const TYPING_INDICATOR_WINDOW: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Start or refresh the user's typing indicator. Refreshes are debounced:
+/// an indicator started less than half a window ago is left alone.
+/// Returns its `started_at` and whether this call (re)started it.
+pub async fn set_typing_indicator(
+    pool: &PgPool,
+    thread_id: Uuid,
+    user_id: Uuid,
+) -> Result<(OffsetDateTime, bool)> {
+    let row = sqlx::query_as(
         r#"
-        INSERT INTO typing_indicators (thread_id, user_id, started_at)
-        VALUES ($1, $2, NOW())
-        ON CONFLICT (thread_id, user_id) 
-        DO UPDATE SET started_at = NOW()
+        WITH upserted AS (
+            INSERT INTO typing_indicators (thread_id, user_id, started_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (thread_id, user_id)
+            DO UPDATE SET started_at = NOW()
+            WHERE typing_indicators.started_at <= NOW() - $3 / 2
+            RETURNING started_at
+        )
+        SELECT started_at, true FROM upserted
+        UNION ALL
+        SELECT started_at, false FROM typing_indicators
+        WHERE thread_id = $1 AND user_id = $2 AND NOT EXISTS (SELECT 1 FROM upserted)
         "#,
     )
     .bind(thread_id)
     .bind(user_id)
-    .execute(pool)
+    .bind(TYPING_INDICATOR_WINDOW)
+    .fetch_one(pool)
     .await?;
-    Ok(())
+    Ok(row)
 }
 
-#[allow(dead_code)]
-pub async fn clear_typing_indicator(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<()> {
-    sqlx::query("DELETE FROM typing_indicators WHERE thread_id = $1 AND user_id = $2")
+/// Remove the user's typing indicator. False if they had none.
+pub async fn clear_typing_indicator(pool: &PgPool, thread_id: Uuid, user_id: Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM typing_indicators WHERE thread_id = $1 AND user_id = $2")
         .bind(thread_id)
         .bind(user_id)
         .execute(pool)
         .await?;
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
 #[allow(dead_code)]
@@ -837,37 +2738,154 @@ pub async fn get_typing_users(
     thread_id: Uuid,
     exclude_user_id: Uuid,
 ) -> Result<Vec<Uuid>> {
-    // Get users who started typing in last 5 seconds
+    // Get users who started typing within the last window
     let user_ids = sqlx::query_scalar(
         r#"
         SELECT user_id FROM typing_indicators 
         WHERE thread_id = $1 
           AND user_id != $2
-          AND started_at > NOW() - INTERVAL '5 seconds'
+          AND started_at > NOW() - $3
         "#,
     )
     .bind(thread_id)
     .bind(exclude_user_id)
+    .bind(TYPING_INDICATOR_WINDOW)
     .fetch_all(pool)
     .await?;
     Ok(user_ids)
 }
 
 // Clean old typing indicators (call periodically)
-pub async fn cleanup_typing_indicators(pool: &PgPool) -> Result<()> {
-    sqlx::query("DELETE FROM typing_indicators WHERE started_at < NOW() - INTERVAL '10 seconds'")
-        .execute(pool)
-        .await?;
-    Ok(())
+/// Returns the `(thread_id, user_id)` of each indicator removed.
+pub async fn cleanup_typing_indicators(pool: &PgPool) -> Result<Vec<(Uuid, Uuid)>> {
+    let removed = sqlx::query_as(
+        r#"
+        DELETE FROM typing_indicators
+        WHERE started_at < NOW() - INTERVAL '10 seconds'
+        RETURNING thread_id, user_id
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(removed)
+}
+
+/// The other participant in a thread (i.e. whoever isn't `user_id`), if known.
+pub async fn get_other_participant(
+    pool: &PgPool,
+    thread_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<Uuid>> {
+    let other_user_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT DISTINCT CASE 
+            WHEN sender_id IS NOT NULL AND sender_id != $2 THEN sender_id
+            WHEN recipient_id != $2 THEN recipient_id
+            ELSE NULL
+        END AS other_user
+        FROM messages 
+        WHERE thread_id = $1 AND (sender_id != $2 OR recipient_id != $2)
+        LIMIT 1
+        "#,
+    )
+    .bind(thread_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(other_user_id)
+}
+
+// Presence
+#[derive(Debug, FromRow)]
+pub struct Presence {
+    pub last_seen: Option<OffsetDateTime>,
+    pub share_presence: bool,
+}
+
+/// None if there's no such user.
+pub async fn get_presence(pool: &PgPool, user_id: Uuid) -> Result<Option<Presence>> {
+    sqlx::query_as::<_, Presence>(
+        r#"
+        SELECT u.last_seen, COALESCE(p.share_presence, true) as share_presence
+        FROM users u
+        LEFT JOIN user_preferences p ON p.user_id = u.id
+        WHERE u.id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Record that the user's streams opened or closed just now.
+pub async fn touch_last_seen(pool: &PgPool, user_id: Uuid) -> Result<OffsetDateTime> {
+    sqlx::query_scalar("UPDATE users SET last_seen = NOW() WHERE id = $1 RETURNING last_seen")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+}
+
+/// Who hears about the user coming online or going offline: whoever started
+/// a conversation with them, since those already know who they are. People
+/// the user wrote to first only ever saw an anonymous sender, so they are
+/// left out. Users the user blocked are left out too.
+pub async fn get_presence_watchers(pool: &PgPool, user_id: Uuid) -> Result<Vec<Uuid>> {
+    sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT first.sender_id FROM messages first
+        WHERE first.recipient_id = $1
+          AND first.sender_id IS NOT NULL
+          AND first.sender_id <> $1
+          AND NOT EXISTS (
+            SELECT 1 FROM messages earlier
+            WHERE earlier.thread_id = first.thread_id AND earlier.created_at < first.created_at
+          )
+          AND NOT EXISTS (
+            SELECT 1 FROM user_blocks
+            WHERE blocker_id = $1 AND blocked_id = first.sender_id
+          )
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Whether `viewer_id` is one of `user_id`'s presence watchers (see
+/// [`get_presence_watchers`]), without listing the rest.
+pub async fn is_presence_watcher(pool: &PgPool, user_id: Uuid, viewer_id: Uuid) -> Result<bool> {
+    sqlx::query_scalar(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM messages first
+            WHERE first.recipient_id = $1
+              AND first.sender_id = $2
+              AND first.sender_id <> $1
+              AND NOT EXISTS (
+                SELECT 1 FROM messages earlier
+                WHERE earlier.thread_id = first.thread_id AND earlier.created_at < first.created_at
+              )
+              AND NOT EXISTS (
+                SELECT 1 FROM user_blocks
+                WHERE blocker_id = $1 AND blocked_id = $2
+              )
+        )
+        "#,
+    )
+    .bind(user_id)
+    .bind(viewer_id)
+    .fetch_one(pool)
+    .await
 }
 
 // Read Receipts
-#[allow(dead_code)]
+/// Mark one message read. Only applies when `reader_id` is its recipient;
+/// an earlier `read_at` is never overwritten.
 pub async fn mark_message_read(pool: &PgPool, message_id: Uuid, reader_id: Uuid) -> Result<()> {
     sqlx::query(
         r#"
         UPDATE messages
-        SET is_read = true, read_at = NOW()
+        SET is_read = true, read_at = COALESCE(read_at, NOW())
         WHERE id = $1 AND recipient_id = $2 AND is_read = false
         "#,
     )
@@ -883,12 +2901,14 @@ pub async fn mark_message_read(pool: &PgPool, message_id: Uuid, reader_id: Uuid)
 pub struct BroadcastComment {
     pub id: Uuid,
     pub broadcast_id: Uuid,
-    pub user_id: Uuid,
+    /// None on "[deleted]" placeholders
+    pub user_id: Option<Uuid>,
     pub username: Option<String>,
     pub content: String,
     pub parent_comment_id: Option<Uuid>,
     pub created_at: OffsetDateTime,
-    pub reactions: Option<serde_json::Value>,
+    #[sqlx(json)]
+    pub reactions: ReactionCounts,
 }
 
 pub async fn create_broadcast_comment(
@@ -924,12 +2944,13 @@ pub async fn get_broadcast_comments(
         SELECT 
             bc.id,
             bc.broadcast_id,
-            bc.user_id,
-            u.username,
-            bc.content,
+            -- Deleted comments kept as "[deleted]" placeholders lose their author
+            CASE WHEN bc.deleted_at IS NULL THEN bc.user_id END as user_id,
+            CASE WHEN bc.deleted_at IS NULL THEN u.username END as username,
+            CASE WHEN bc.deleted_at IS NULL THEN bc.content ELSE '[deleted]' END as content,
             bc.parent_comment_id,
             bc.created_at,
-            (
+            COALESCE((
                 SELECT json_object_agg(emoji, count)
                 FROM (
                     SELECT emoji, count(*) as count
@@ -937,10 +2958,18 @@ pub async fn get_broadcast_comments(
                     WHERE comment_id = bc.id
                     GROUP BY emoji
                 ) s
-            ) as reactions
+            ), '{}') as reactions
         FROM broadcast_comments bc
         LEFT JOIN users u ON bc.user_id = u.id
-        WHERE bc.broadcast_id = $1 AND bc.deleted_at IS NULL
+        WHERE bc.broadcast_id = $1
+          AND (
+              bc.deleted_at IS NULL
+              -- Keep deleted comments that still have live replies so threads don't break
+              OR EXISTS (
+                  SELECT 1 FROM broadcast_comments r
+                  WHERE r.parent_comment_id = bc.id AND r.deleted_at IS NULL
+              )
+          )
         ORDER BY bc.created_at ASC
         "#,
     )
@@ -950,11 +2979,210 @@ pub async fn get_broadcast_comments(
     Ok(comments)
 }
 
-pub async fn react_to_comment(
+/// One page of a broadcast's comment threads, for exporting a discussion
+/// without loading all of it. Threads are keyed by their top-level comment,
+/// oldest first; `after`/`after_id` are the `created_at` and id of the
+/// previous page's last one. Returns the page's last key (None once there
+/// are no more threads) and every comment of its threads, listed as in
+/// `get_broadcast_comments`.
+pub async fn get_broadcast_comment_threads(
     pool: &PgPool,
-    comment_id: Uuid,
-    user_id: Uuid,
-    emoji: &str,
+    broadcast_id: Uuid,
+    limit: i64,
+    after: Option<OffsetDateTime>,
+    after_id: Option<Uuid>,
+) -> Result<(Option<(OffsetDateTime, Uuid)>, Vec<BroadcastComment>)> {
+    // Every top-level comment, listed or not: a deleted one can still have
+    // listed replies further down
+    let roots: Vec<(OffsetDateTime, Uuid)> = sqlx::query_as(
+        r#"
+        SELECT created_at, id FROM broadcast_comments
+        WHERE broadcast_id = $1 AND parent_comment_id IS NULL
+          AND ($2::timestamptz IS NULL OR (created_at, id) > ($2, $3))
+        ORDER BY created_at, id
+        LIMIT $4
+        "#,
+    )
+    .bind(broadcast_id)
+    .bind(after)
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    let Some(&last) = roots.last() else {
+        return Ok((None, Vec::new()));
+    };
+
+    let root_ids: Vec<Uuid> = roots.iter().map(|(_, id)| *id).collect();
+    let comments = sqlx::query_as::<_, BroadcastComment>(
+        r#"
+        WITH RECURSIVE thread AS (
+            SELECT id FROM broadcast_comments WHERE id = ANY($1)
+            UNION ALL
+            SELECT c.id FROM broadcast_comments c JOIN thread t ON c.parent_comment_id = t.id
+        )
+        SELECT
+            bc.id,
+            bc.broadcast_id,
+            CASE WHEN bc.deleted_at IS NULL THEN bc.user_id END as user_id,
+            CASE WHEN bc.deleted_at IS NULL THEN u.username END as username,
+            CASE WHEN bc.deleted_at IS NULL THEN bc.content ELSE '[deleted]' END as content,
+            bc.parent_comment_id,
+            bc.created_at,
+            COALESCE((
+                SELECT json_object_agg(emoji, count)
+                FROM (
+                    SELECT emoji, count(*) as count
+                    FROM broadcast_comment_reactions
+                    WHERE comment_id = bc.id
+                    GROUP BY emoji
+                ) s
+            ), '{}') as reactions
+        FROM thread
+        JOIN broadcast_comments bc ON bc.id = thread.id
+        LEFT JOIN users u ON bc.user_id = u.id
+        WHERE bc.deleted_at IS NULL
+           OR EXISTS (
+               SELECT 1 FROM broadcast_comments r
+               WHERE r.parent_comment_id = bc.id AND r.deleted_at IS NULL
+           )
+        ORDER BY bc.created_at ASC
+        "#,
+    )
+    .bind(&root_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok((Some(last), comments))
+}
+
+/// Someone who viewed a broadcast, and when they first did.
+#[derive(Debug, FromRow)]
+pub struct BroadcastViewer {
+    pub user_id: Uuid,
+    pub username: String,
+    pub viewed_at: OffsetDateTime,
+}
+
+/// A page of a broadcast's viewers, most recent first. `before`/`before_id`
+/// are the `viewed_at` and user id of the previous page's last viewer.
+pub async fn get_broadcast_viewers(
+    pool: &PgPool,
+    broadcast_id: Uuid,
+    limit: i64,
+    before: Option<OffsetDateTime>,
+    before_id: Option<Uuid>,
+) -> Result<Vec<BroadcastViewer>> {
+    let viewers = sqlx::query_as::<_, BroadcastViewer>(
+        r#"
+        SELECT bv.user_id, u.username, bv.viewed_at
+        FROM broadcast_views bv
+        JOIN users u ON u.id = bv.user_id
+        WHERE bv.broadcast_id = $1
+          AND (
+            $2::timestamptz IS NULL
+            OR ($3::uuid IS NULL AND bv.viewed_at < $2)
+            OR (bv.viewed_at, bv.user_id) < ($2, $3)
+          )
+        ORDER BY bv.viewed_at DESC, bv.user_id DESC
+        LIMIT $4
+        "#,
+    )
+    .bind(broadcast_id)
+    .bind(before)
+    .bind(before_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(viewers)
+}
+
+/// Views of a broadcast on one (UTC) day.
+#[derive(Debug, FromRow)]
+pub struct DailyViews {
+    /// `YYYY-MM-DD`
+    pub day: String,
+    pub views: i64,
+}
+
+pub async fn get_broadcast_view_timeline(
+    pool: &PgPool,
+    broadcast_id: Uuid,
+) -> Result<Vec<DailyViews>> {
+    let days = sqlx::query_as::<_, DailyViews>(
+        r#"
+        SELECT to_char(viewed_at AT TIME ZONE 'UTC', 'YYYY-MM-DD') as day, count(*) as views
+        FROM broadcast_views
+        WHERE broadcast_id = $1
+        GROUP BY day
+        ORDER BY day ASC
+        "#,
+    )
+    .bind(broadcast_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(days)
+}
+
+/// Total reactions per emoji across all of a broadcast's live comments.
+pub async fn get_broadcast_comment_reaction_totals(
+    pool: &PgPool,
+    broadcast_id: Uuid,
+) -> Result<Vec<(String, i64)>> {
+    let totals = sqlx::query_as::<_, (String, i64)>(
+        r#"
+        SELECT r.emoji, count(*)
+        FROM broadcast_comment_reactions r
+        JOIN broadcast_comments bc ON bc.id = r.comment_id
+        WHERE bc.broadcast_id = $1 AND bc.deleted_at IS NULL
+        GROUP BY r.emoji
+        ORDER BY count(*) DESC, r.emoji ASC
+        "#,
+    )
+    .bind(broadcast_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(totals)
+}
+
+/// One emoji's standing in a broadcast's reaction leaderboard.
+#[derive(Debug, FromRow)]
+pub struct ReactionTally {
+    pub emoji: String,
+    pub count: i64,
+    /// Whether the viewer used this emoji on any of the comments
+    pub mine: bool,
+}
+
+/// Reactions across a broadcast's live comments, most used first, flagging
+/// the ones `viewer_id` gave.
+pub async fn get_broadcast_reaction_leaderboard(
+    pool: &PgPool,
+    broadcast_id: Uuid,
+    viewer_id: Uuid,
+) -> Result<Vec<ReactionTally>> {
+    let tallies = sqlx::query_as::<_, ReactionTally>(
+        r#"
+        SELECT r.emoji, count(*) as count, bool_or(r.user_id = $2) as mine
+        FROM broadcast_comment_reactions r
+        JOIN broadcast_comments bc ON bc.id = r.comment_id
+        WHERE bc.broadcast_id = $1 AND bc.deleted_at IS NULL
+        GROUP BY r.emoji
+        ORDER BY count(*) DESC, r.emoji ASC
+        "#,
+    )
+    .bind(broadcast_id)
+    .bind(viewer_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(tallies)
+}
+
+pub async fn react_to_comment(
+    pool: &PgPool,
+    comment_id: Uuid,
+    user_id: Uuid,
+    emoji: &str,
 ) -> Result<()> {
     sqlx::query(
         r#"
@@ -972,6 +3200,31 @@ pub async fn react_to_comment(
     Ok(())
 }
 
+/// The emoji the user currently has on a comment, if any.
+pub async fn get_user_comment_reaction(
+    pool: &PgPool,
+    comment_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<String>> {
+    let emoji = sqlx::query_scalar::<_, String>(
+        "SELECT emoji FROM broadcast_comment_reactions WHERE comment_id = $1 AND user_id = $2",
+    )
+    .bind(comment_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(emoji)
+}
+
+pub async fn remove_comment_reaction(pool: &PgPool, comment_id: Uuid, user_id: Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM broadcast_comment_reactions WHERE comment_id = $1 AND user_id = $2")
+        .bind(comment_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 pub async fn delete_broadcast_comment(
     pool: &PgPool,
     comment_id: Uuid,
@@ -985,6 +3238,46 @@ pub async fn delete_broadcast_comment(
     Ok(())
 }
 
+/// Soft-delete every comment `user_id` left on a broadcast. Returns how many were deleted.
+pub async fn delete_user_broadcast_comments(
+    pool: &PgPool,
+    broadcast_id: Uuid,
+    user_id: Uuid,
+) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        UPDATE broadcast_comments
+        SET deleted_at = NOW()
+        WHERE broadcast_id = $1 AND user_id = $2 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(broadcast_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// How many of `user_id`'s comments `delete_user_broadcast_comments` would remove.
+pub async fn count_user_broadcast_comments(
+    pool: &PgPool,
+    broadcast_id: Uuid,
+    user_id: Uuid,
+) -> Result<i64> {
+    let count: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)
+        FROM broadcast_comments
+        WHERE broadcast_id = $1 AND user_id = $2 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(broadcast_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(count)
+}
+
 // User Preferences
 #[derive(Debug, FromRow)]
 pub struct UserPreferences {
@@ -995,6 +3288,9 @@ pub struct UserPreferences {
     pub browser_notifications: bool,
     pub show_read_receipts: bool,
     pub show_typing_indicators: bool,
+    pub public_profile: bool,
+    pub share_presence: bool,
+    pub version: i64,
 }
 
 pub async fn get_user_preferences(pool: &PgPool, user_id: Uuid) -> Result<Option<UserPreferences>> {
@@ -1006,38 +3302,807 @@ pub async fn get_user_preferences(pool: &PgPool, user_id: Uuid) -> Result<Option
     Ok(prefs)
 }
 
+/// Whether the user shares and receives read receipts (on unless turned off).
+pub async fn read_receipts_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool> {
+    let enabled: bool = sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(
+            (SELECT show_read_receipts FROM user_preferences WHERE user_id = $1),
+            true
+        )
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(enabled)
+}
+
+/// Whether the user lets others see when they are online (on unless turned
+/// off).
+pub async fn presence_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool> {
+    let enabled: bool = sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(
+            (SELECT share_presence FROM user_preferences WHERE user_id = $1),
+            true
+        )
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(enabled)
+}
+
+/// Whether the user wants notifications while the app is closed (on unless
+/// turned off).
+pub async fn browser_notifications_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool> {
+    let enabled: bool = sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(
+            (SELECT browser_notifications FROM user_preferences WHERE user_id = $1),
+            true
+        )
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(enabled)
+}
+
+/// Fields to change; `None` leaves the stored value as it is.
+#[derive(Debug)]
+pub struct PreferencesUpdate {
+    pub theme: Option<String>,
+    pub notification_sound: Option<bool>,
+    pub browser_notifications: Option<bool>,
+    pub show_read_receipts: Option<bool>,
+    pub show_typing_indicators: Option<bool>,
+    pub public_profile: Option<bool>,
+    pub share_presence: Option<bool>,
+}
+
+/// Apply a partial preferences update and return the new version.
+///
+/// Only the fields that are set get written, so two partial updates touching
+/// different fields never undo each other. With `expected_version`, the
+/// update is refused (`None`) if the stored version has moved on; it is
+/// ignored for users with nothing saved yet.
 pub async fn upsert_user_preferences(
     pool: &PgPool,
     user_id: Uuid,
-    theme: Option<String>,
-    notification_sound: Option<bool>,
-    browser_notifications: Option<bool>,
-    show_read_receipts: Option<bool>,
-    show_typing_indicators: Option<bool>,
-) -> Result<()> {
-    sqlx::query(
+    update: &PreferencesUpdate,
+    expected_version: Option<i64>,
+) -> Result<Option<UserPreferences>> {
+    // A first save fills unset fields with the same defaults GET reports
+    let prefs = sqlx::query_as::<_, UserPreferences>(
         r#"
         INSERT INTO user_preferences (
             user_id, theme, notification_sound, browser_notifications, 
-            show_read_receipts, show_typing_indicators
+            show_read_receipts, show_typing_indicators, public_profile, share_presence,
+            version
+        )
+        VALUES (
+            $1, COALESCE($2, 'dark'), COALESCE($3, true), COALESCE($4, true),
+            COALESCE($5, true), COALESCE($6, true), COALESCE($8, true), COALESCE($9, true), 1
         )
-        VALUES ($1, $2, $3, $4, $5, $6)
         ON CONFLICT (user_id) DO UPDATE SET
             theme = COALESCE($2, user_preferences.theme),
             notification_sound = COALESCE($3, user_preferences.notification_sound),
             browser_notifications = COALESCE($4, user_preferences.browser_notifications),
             show_read_receipts = COALESCE($5, user_preferences.show_read_receipts),
             show_typing_indicators = COALESCE($6, user_preferences.show_typing_indicators),
+            public_profile = COALESCE($8, user_preferences.public_profile),
+            share_presence = COALESCE($9, user_preferences.share_presence),
+            version = user_preferences.version + 1,
             updated_at = NOW()
+        WHERE $7::bigint IS NULL OR user_preferences.version = $7
+        RETURNING user_id, theme, notification_sound, browser_notifications,
+            show_read_receipts, show_typing_indicators, public_profile, share_presence, version
+        "#,
+    )
+    .bind(user_id)
+    .bind(&update.theme)
+    .bind(update.notification_sound)
+    .bind(update.browser_notifications)
+    .bind(update.show_read_receipts)
+    .bind(update.show_typing_indicators)
+    .bind(expected_version)
+    .bind(update.public_profile)
+    .bind(update.share_presence)
+    .fetch_optional(pool)
+    .await?;
+    Ok(prefs)
+}
+
+// ===== Diagnostics =====
+
+/// Connection pool occupancy at the moment of the call.
+#[derive(Debug)]
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: usize,
+    pub in_use: usize,
+}
+
+pub fn pool_stats(pool: &PgPool) -> PoolStats {
+    let size = pool.size();
+    let idle = pool.num_idle();
+    PoolStats {
+        size,
+        idle,
+        in_use: (size as usize).saturating_sub(idle),
+    }
+}
+
+/// Approximate row counts for the key tables.
+#[derive(Debug, FromRow)]
+pub struct TableCounts {
+    pub users: i64,
+    pub messages: i64,
+    pub broadcasts: i64,
+}
+
+/// Row counts from the planner's statistics (`pg_class.reltuples`) rather
+/// than `count(*)`, so they stay cheap on large tables. They are only as
+/// fresh as the last ANALYZE; never-analyzed tables report 0.
+pub async fn table_counts(pool: &PgPool) -> Result<TableCounts> {
+    let counts = sqlx::query_as::<_, TableCounts>(
+        r#"
+        SELECT
+            (SELECT GREATEST(reltuples, 0)::bigint FROM pg_class WHERE oid = 'users'::regclass) as users,
+            -- Summed over the partitions when messages is partitioned
+            (SELECT COALESCE(sum(GREATEST(reltuples, 0)), 0)::bigint FROM pg_class
+             WHERE relkind = 'r' AND (oid = 'messages'::regclass OR oid IN (
+                SELECT inhrelid FROM pg_inherits WHERE inhparent = 'messages'::regclass
+             ))) as messages,
+            (SELECT GREATEST(reltuples, 0)::bigint FROM pg_class WHERE oid = 'broadcasts'::regclass) as broadcasts
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(counts)
+}
+
+/// Convert messages to a table range-partitioned by month on `created_at`
+/// (the `partition_messages_table` migration function). Returns false if it
+/// already was.
+pub async fn partition_messages_table(pool: &PgPool) -> Result<bool> {
+    sqlx::query_scalar("SELECT partition_messages_table()")
+        .fetch_one(pool)
+        .await
+}
+
+/// Create any missing monthly message partitions from this month through
+/// `months_ahead` months out. Returns how many were created.
+pub async fn create_message_partitions(pool: &PgPool, months_ahead: i32) -> Result<i32> {
+    sqlx::query_scalar("SELECT create_messages_partitions($1)")
+        .bind(months_ahead)
+        .fetch_one(pool)
+        .await
+}
+
+/// Run one integrity check query (see `integrity.rs`) in a read-only
+/// transaction, cancelled after `timeout`. Returns the offending ids, at
+/// most `limit`.
+pub async fn find_integrity_violations(
+    pool: &PgPool,
+    sql: &str,
+    limit: i64,
+    timeout: std::time::Duration,
+) -> Result<Vec<String>> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SET TRANSACTION READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("SELECT set_config('statement_timeout', $1, true)")
+        .bind(timeout.as_millis().to_string())
+        .execute(&mut *tx)
+        .await?;
+    let ids = sqlx::query_scalar::<_, String>(sql)
+        .bind(limit)
+        .fetch_all(&mut *tx)
+        .await?;
+    tx.rollback().await?;
+    Ok(ids)
+}
+
+// ===== Web Push =====
+
+#[derive(Debug, FromRow)]
+pub struct PushSubscription {
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// Store a browser's push subscription for `user_id`. An endpoint is unique
+/// to one browser, so subscribing it again (from whichever account is now
+/// signed in there) replaces the old row.
+pub async fn save_push_subscription(
+    pool: &PgPool,
+    user_id: Uuid,
+    subscription: &PushSubscription,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO push_subscriptions (user_id, endpoint, p256dh, auth)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (endpoint) DO UPDATE
+        SET user_id = $1, p256dh = $3, auth = $4, created_at = NOW()
         "#,
     )
     .bind(user_id)
-    .bind(theme)
-    .bind(notification_sound)
-    .bind(browser_notifications)
-    .bind(show_read_receipts)
-    .bind(show_typing_indicators)
+    .bind(&subscription.endpoint)
+    .bind(&subscription.p256dh)
+    .bind(&subscription.auth)
     .execute(pool)
     .await?;
     Ok(())
 }
+
+pub async fn get_push_subscriptions(pool: &PgPool, user_id: Uuid) -> Result<Vec<PushSubscription>> {
+    sqlx::query_as::<_, PushSubscription>(
+        "SELECT endpoint, p256dh, auth FROM push_subscriptions WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn delete_push_subscription(pool: &PgPool, endpoint: &str) -> Result<()> {
+    sqlx::query("DELETE FROM push_subscriptions WHERE endpoint = $1")
+        .bind(endpoint)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// ===== Inbox Links =====
+
+#[derive(Debug, FromRow)]
+pub struct InboxToken {
+    pub token: String,
+    pub created_at: OffsetDateTime,
+}
+
+/// Make `token` the user's inbox link, revoking the one they had.
+pub async fn rotate_inbox_token(pool: &PgPool, user_id: Uuid, token: &str) -> Result<InboxToken> {
+    let token = token.to_string();
+    transaction(pool, |tx| {
+        Box::pin(async move {
+            sqlx::query(
+                "UPDATE inbox_tokens SET revoked = true WHERE user_id = $1 AND NOT revoked",
+            )
+            .bind(user_id)
+            .execute(&mut **tx)
+            .await?;
+            sqlx::query_as::<_, InboxToken>(
+                r#"
+                INSERT INTO inbox_tokens (token, user_id)
+                VALUES ($1, $2)
+                RETURNING token, created_at
+                "#,
+            )
+            .bind(token)
+            .bind(user_id)
+            .fetch_one(&mut **tx)
+            .await
+        })
+    })
+    .await
+}
+
+/// The user's active inbox link, if they have one.
+pub async fn get_inbox_token(pool: &PgPool, user_id: Uuid) -> Result<Option<InboxToken>> {
+    sqlx::query_as::<_, InboxToken>(
+        "SELECT token, created_at FROM inbox_tokens WHERE user_id = $1 AND NOT revoked",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Turn the user's inbox link off. False if they had none.
+pub async fn revoke_inbox_token(pool: &PgPool, user_id: Uuid) -> Result<bool> {
+    let result =
+        sqlx::query("UPDATE inbox_tokens SET revoked = true WHERE user_id = $1 AND NOT revoked")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Whose inbox an active token opens. None for unknown or revoked tokens and
+/// for banned accounts.
+pub async fn resolve_inbox_token(pool: &PgPool, token: &str) -> Result<Option<Uuid>> {
+    sqlx::query_scalar(
+        r#"
+        SELECT t.user_id FROM inbox_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.token = $1 AND NOT t.revoked AND u.banned_at IS NULL
+        "#,
+    )
+    .bind(token)
+    .fetch_optional(pool)
+    .await
+}
+
+// ===== Webhooks =====
+
+#[derive(Debug, FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub created_at: OffsetDateTime,
+}
+
+/// Where to deliver one event, and the key to sign it with.
+#[derive(Debug, FromRow)]
+pub struct WebhookTarget {
+    pub id: Uuid,
+    pub url: String,
+    pub secret: String,
+}
+
+pub async fn create_webhook(
+    pool: &PgPool,
+    created_by: Uuid,
+    url: &str,
+    secret: &str,
+    events: &[String],
+) -> Result<Webhook> {
+    sqlx::query_as::<_, Webhook>(
+        r#"
+        INSERT INTO webhooks (url, secret, events, created_by)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, url, events, created_at
+        "#,
+    )
+    .bind(url)
+    .bind(secret)
+    .bind(events)
+    .bind(created_by)
+    .fetch_one(pool)
+    .await
+}
+
+/// Every registered webhook, oldest first. Secrets are left out.
+pub async fn get_webhooks(pool: &PgPool) -> Result<Vec<Webhook>> {
+    sqlx::query_as::<_, Webhook>(
+        "SELECT id, url, events, created_at FROM webhooks ORDER BY created_at",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns false if there was no such webhook.
+pub async fn delete_webhook(pool: &PgPool, webhook_id: Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+        .bind(webhook_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Webhooks subscribed to `event`.
+pub async fn get_webhook_targets(pool: &PgPool, event: &str) -> Result<Vec<WebhookTarget>> {
+    sqlx::query_as::<_, WebhookTarget>(
+        "SELECT id, url, secret FROM webhooks WHERE events @> ARRAY[$1::text]",
+    )
+    .bind(event)
+    .fetch_all(pool)
+    .await
+}
+
+// ===== Reports =====
+
+/// File a report on a message, keeping its current content. None if this
+/// reporter already reported it.
+pub async fn create_message_report(
+    pool: &PgPool,
+    message_id: Uuid,
+    reporter_id: Uuid,
+    reason: &str,
+) -> Result<Option<Uuid>> {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO message_reports (message_id, reporter_id, reason, content_snapshot)
+        SELECT $1, $2, $3, content FROM messages WHERE id = $1
+        ON CONFLICT (message_id, reporter_id) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(message_id)
+    .bind(reporter_id)
+    .bind(reason)
+    .fetch_optional(pool)
+    .await
+}
+
+/// File a report on a broadcast, keeping its current content. None if this
+/// reporter already reported it.
+pub async fn create_broadcast_report(
+    pool: &PgPool,
+    broadcast_id: Uuid,
+    reporter_id: Uuid,
+    reason: &str,
+) -> Result<Option<Uuid>> {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO broadcast_reports (broadcast_id, reporter_id, reason, content_snapshot)
+        SELECT $1, $2, $3, content FROM broadcasts WHERE id = $1
+        ON CONFLICT (broadcast_id, reporter_id) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(broadcast_id)
+    .bind(reporter_id)
+    .bind(reason)
+    .fetch_optional(pool)
+    .await
+}
+
+/// A pending report with what it's about, for the moderation queue.
+#[derive(Debug, FromRow)]
+pub struct ModerationReport {
+    pub id: Uuid,
+    /// `message` or `broadcast`
+    pub kind: String,
+    pub target_id: Uuid,
+    pub reason: String,
+    pub created_at: OffsetDateTime,
+    /// The content when the report was filed
+    pub content: Option<String>,
+    /// Whether the content has been deleted since (or is gone)
+    pub content_deleted: bool,
+    /// Who wrote it, for moderators only: the message's sender (None when
+    /// sent anonymously) or the broadcast's author, anonymous or not
+    pub author_id: Option<Uuid>,
+    /// How many users have a pending report on the same item
+    pub reporter_count: i64,
+}
+
+/// Pending message and broadcast reports, oldest first, paged by
+/// `(created_at, id)`: pass the last report's key to get the ones after it.
+pub async fn get_pending_reports(
+    pool: &PgPool,
+    limit: i64,
+    after: Option<OffsetDateTime>,
+    after_id: Option<Uuid>,
+) -> Result<Vec<ModerationReport>> {
+    sqlx::query_as::<_, ModerationReport>(
+        r#"
+        SELECT * FROM (
+            SELECT
+                r.id,
+                'message' AS kind,
+                r.message_id AS target_id,
+                r.reason,
+                r.created_at,
+                r.content_snapshot AS content,
+                COALESCE(m.deleted_at IS NOT NULL, true) AS content_deleted,
+                m.sender_id AS author_id,
+                (
+                    SELECT COUNT(*) FROM message_reports o
+                    WHERE o.message_id = r.message_id AND o.status = 'pending'
+                ) AS reporter_count
+            FROM message_reports r
+            LEFT JOIN messages m ON m.id = r.message_id
+            WHERE r.status = 'pending'
+            UNION ALL
+            SELECT
+                r.id,
+                'broadcast',
+                r.broadcast_id,
+                r.reason,
+                r.created_at,
+                r.content_snapshot,
+                b.deleted_at IS NOT NULL,
+                b.author_id,
+                (
+                    SELECT COUNT(*) FROM broadcast_reports o
+                    WHERE o.broadcast_id = r.broadcast_id AND o.status = 'pending'
+                )
+            FROM broadcast_reports r
+            JOIN broadcasts b ON b.id = r.broadcast_id
+            WHERE r.status = 'pending'
+        ) reports
+        WHERE $2::timestamptz IS NULL OR (created_at, id) > ($2, $3)
+        ORDER BY created_at, id
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .bind(after)
+    .bind(after_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// What an admin does with a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportAction {
+    /// Nothing wrong: close this report, leave the content
+    Dismiss,
+    /// Soft-delete the content and close every pending report on it
+    DeleteContent,
+    /// As `DeleteContent`, and ban the content's author
+    BanUser,
+}
+
+impl ReportAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Dismiss => "dismiss",
+            Self::DeleteContent => "delete_content",
+            Self::BanUser => "ban_user",
+        }
+    }
+}
+
+/// Result of `resolve_report`.
+#[derive(Debug)]
+pub enum ResolveOutcome {
+    /// `banned` is the author as they were before, if this banned them
+    Resolved { banned: Option<Box<User>> },
+    /// No such pending report
+    NotFound,
+    /// `BanUser` on content with no known author (an anonymous message);
+    /// nothing was changed
+    NoAuthor,
+}
+
+/// The reported item of a pending report, locked until the transaction ends.
+struct ReportTarget {
+    /// `message_reports` or `broadcast_reports`
+    table: &'static str,
+    target_id: Uuid,
+    author_id: Option<Uuid>,
+}
+
+async fn lock_report_target(
+    tx: &mut Transaction<'static, Postgres>,
+    report_id: Uuid,
+) -> Result<Option<ReportTarget>> {
+    let message: Option<(Uuid, Option<Uuid>)> = sqlx::query_as(
+        r#"
+        SELECT r.message_id, m.sender_id
+        FROM message_reports r
+        LEFT JOIN messages m ON m.id = r.message_id
+        WHERE r.id = $1 AND r.status = 'pending'
+        FOR UPDATE OF r
+        "#,
+    )
+    .bind(report_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    if let Some((target_id, author_id)) = message {
+        return Ok(Some(ReportTarget {
+            table: "message_reports",
+            target_id,
+            author_id,
+        }));
+    }
+
+    let broadcast: Option<(Uuid, Uuid)> = sqlx::query_as(
+        r#"
+        SELECT r.broadcast_id, b.author_id
+        FROM broadcast_reports r
+        JOIN broadcasts b ON b.id = r.broadcast_id
+        WHERE r.id = $1 AND r.status = 'pending'
+        FOR UPDATE OF r
+        "#,
+    )
+    .bind(report_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    Ok(broadcast.map(|(target_id, author_id)| ReportTarget {
+        table: "broadcast_reports",
+        target_id,
+        author_id: Some(author_id),
+    }))
+}
+
+/// Resolve a pending report with `action`, recording `admin_id` as who did
+/// it. Everything happens in one transaction: a dismissal closes only this
+/// report; deleting (or banning) also soft-deletes the content and closes
+/// every pending report on it, and a ban sets the author's `banned_at`.
+/// Ending a banned user's sessions is left to the caller.
+pub async fn resolve_report(
+    pool: &PgPool,
+    report_id: Uuid,
+    admin_id: Uuid,
+    action: ReportAction,
+) -> Result<ResolveOutcome> {
+    transaction(pool, |tx| {
+        Box::pin(async move {
+            let Some(target) = lock_report_target(tx, report_id).await? else {
+                return Ok(ResolveOutcome::NotFound);
+            };
+            let author_id = match (action, target.author_id) {
+                (ReportAction::BanUser, None) => return Ok(ResolveOutcome::NoAuthor),
+                (ReportAction::BanUser, author_id) => author_id,
+                _ => None,
+            };
+            let (status, target_column) = match (action, target.table) {
+                (ReportAction::Dismiss, _) => ("dismissed", "id"),
+                (_, "message_reports") => ("resolved", "message_id"),
+                _ => ("resolved", "broadcast_id"),
+            };
+            let target_key = if action == ReportAction::Dismiss {
+                report_id
+            } else {
+                target.target_id
+            };
+
+            sqlx::query(&format!(
+                r#"
+                UPDATE {}
+                SET status = $2, action = $3, resolved_by = $4, resolved_at = NOW()
+                WHERE {} = $1 AND status = 'pending'
+                "#,
+                target.table, target_column
+            ))
+            .bind(target_key)
+            .bind(status)
+            .bind(action.as_str())
+            .bind(admin_id)
+            .execute(&mut **tx)
+            .await?;
+
+            if action == ReportAction::Dismiss {
+                return Ok(ResolveOutcome::Resolved { banned: None });
+            }
+            if target.table == "message_reports" {
+                sqlx::query(
+                    r#"
+                    UPDATE messages SET deleted_at = NOW(), deleted_by = $2
+                    WHERE id = $1 AND deleted_at IS NULL
+                    "#,
+                )
+                .bind(target.target_id)
+                .bind(admin_id)
+                .execute(&mut **tx)
+                .await?;
+            } else {
+                sqlx::query(
+                    "UPDATE broadcasts SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL",
+                )
+                .bind(target.target_id)
+                .execute(&mut **tx)
+                .await?;
+            }
+
+            let banned = match author_id {
+                Some(author_id) => ban_user(&mut **tx, author_id).await?.map(Box::new),
+                None => None,
+            };
+            Ok(ResolveOutcome::Resolved { banned })
+        })
+    })
+    .await
+}
+
+// ===== Bans =====
+
+/// Ban a user. Returns the user as they were before, or None if there's no
+/// such user or they're already banned.
+pub async fn ban_user<'e>(executor: impl PgExecutor<'e>, user_id: Uuid) -> Result<Option<User>> {
+    sqlx::query_as::<_, User>(
+        r#"
+        UPDATE users SET banned_at = NOW()
+        WHERE id = $1 AND banned_at IS NULL
+        RETURNING id, username, password_hash, provider, provider_id, bio, avatar_url, created_at, updated_at
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Returns false if the user doesn't exist or isn't banned.
+pub async fn unban_user(pool: &PgPool, user_id: Uuid) -> Result<bool> {
+    let result =
+        sqlx::query("UPDATE users SET banned_at = NULL WHERE id = $1 AND banned_at IS NOT NULL")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// ===== Retention =====
+
+/// Hard-delete up to `batch` messages created more than `days` days ago,
+/// with their reports. Reactions, edits and pins go with them through the
+/// foreign keys, or the `messages_delete_dependents` trigger once messages
+/// is partitioned. Returns how many messages were deleted.
+pub async fn purge_old_messages(pool: &PgPool, days: i32, batch: i64) -> Result<u64> {
+    let purged = sqlx::query_scalar::<_, i64>(
+        r#"
+        WITH batch AS (
+            SELECT id, created_at FROM messages
+            WHERE created_at < NOW() - make_interval(days => $1)
+            LIMIT $2
+        ), purged AS (
+            DELETE FROM messages m
+            USING batch
+            WHERE m.id = batch.id AND m.created_at = batch.created_at
+            RETURNING m.id
+        ), reports AS (
+            DELETE FROM message_reports
+            WHERE message_id IN (SELECT id FROM purged)
+        )
+        SELECT COUNT(*) FROM purged
+        "#,
+    )
+    .bind(days)
+    .bind(batch)
+    .fetch_one(pool)
+    .await?;
+    Ok(purged as u64)
+}
+
+/// Hard-delete up to `batch` broadcasts created more than `days` days ago.
+/// Views, comments, edits and reports cascade. Returns how many broadcasts
+/// were deleted.
+pub async fn purge_old_broadcasts(pool: &PgPool, days: i32, batch: i64) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM broadcasts
+        WHERE id IN (
+            SELECT id FROM broadcasts
+            WHERE created_at < NOW() - make_interval(days => $1)
+            LIMIT $2
+        )
+        "#,
+    )
+    .bind(days)
+    .bind(batch)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// A message removed by `delete_expired_messages`, for telling its
+/// participants.
+#[derive(Debug, FromRow)]
+pub struct ExpiredMessage {
+    pub id: Uuid,
+    pub thread_id: Uuid,
+    pub sender_id: Option<Uuid>,
+    pub recipient_id: Uuid,
+}
+
+/// Hard-delete up to `batch` messages whose `expires_at` has passed. Reads
+/// already leave them out; this removes them for good.
+pub async fn delete_expired_messages(pool: &PgPool, batch: i64) -> Result<Vec<ExpiredMessage>> {
+    sqlx::query_as::<_, ExpiredMessage>(
+        r#"
+        WITH batch AS (
+            SELECT id, created_at FROM messages
+            WHERE expires_at <= NOW() AND status = 'delivered'
+            LIMIT $1
+        ), expired AS (
+            DELETE FROM messages m
+            USING batch
+            WHERE m.id = batch.id AND m.created_at = batch.created_at
+            RETURNING m.id, m.thread_id, m.sender_id, m.recipient_id
+        ), reports AS (
+            DELETE FROM message_reports
+            WHERE message_id IN (SELECT id FROM expired)
+        )
+        SELECT * FROM expired
+        "#,
+    )
+    .bind(batch)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,853 @@
+//! Queries checked against a real database. Each `#[sqlx::test]` gets a
+//! fresh database with every migration applied; set `DATABASE_URL` to a
+//! server the tests may create databases on.
+
+use super::*;
+
+async fn user(pool: &PgPool, username: &str) -> User {
+    create_local_user(pool, username, "not-a-real-hash")
+        .await
+        .unwrap()
+}
+
+/// A message from `sender` (None for anonymous) to `recipient`.
+async fn message(pool: &PgPool, sender: Option<Uuid>, recipient: Uuid, content: &str) -> Uuid {
+    let (message_id, _) = create_message(
+        pool,
+        sender,
+        recipient,
+        content,
+        None,
+        MessageExpiry::default(),
+        None,
+    )
+    .await
+    .unwrap();
+    message_id
+}
+
+// ===== Provider sign-in =====
+
+#[sqlx::test]
+async fn a_provider_sign_in_never_takes_over_a_matching_username(pool: PgPool) {
+    let local = user(&pool, "alice").await;
+
+    let github = upsert_user(&pool, "Alice", "github", Some("1".into()))
+        .await
+        .unwrap();
+    assert_ne!(github.id, local.id);
+    assert_eq!(github.username, "Alice-2");
+
+    let local_now = get_user_by_id(&pool, local.id).await.unwrap();
+    assert_eq!(local_now.provider, "local");
+    assert_eq!(local_now.provider_id, None);
+    // Signing in again finds the same account rather than minting another
+    let again = upsert_user(&pool, "Alice", "github", Some("1".into()))
+        .await
+        .unwrap();
+    assert_eq!(again.id, github.id);
+}
+
+#[sqlx::test]
+async fn provider_accounts_with_the_same_name_stay_separate(pool: PgPool) {
+    let first = upsert_user(&pool, "bob", "github", Some("1".into()))
+        .await
+        .unwrap();
+    let second = upsert_user(&pool, "bob", "github", Some("2".into()))
+        .await
+        .unwrap();
+    assert_ne!(first.id, second.id);
+    assert_eq!(second.username, "bob-2");
+
+    // Renaming to the other's name on the provider's side doesn't take it
+    let renamed = upsert_user(&pool, "BOB", "github", Some("2".into()))
+        .await
+        .unwrap();
+    assert_eq!(renamed.id, second.id);
+    assert_eq!(renamed.username, "bob-2");
+}
+
+#[sqlx::test]
+async fn only_an_explicit_link_joins_a_provider_to_a_local_account(pool: PgPool) {
+    let local = user(&pool, "carol").await;
+    assert!(link_provider(&pool, local.id, "github", "3").await.unwrap());
+
+    let signed_in = upsert_user(&pool, "carol-gh", "github", Some("3".into()))
+        .await
+        .unwrap();
+    assert_eq!(signed_in.id, local.id);
+    // The name chosen locally is kept
+    assert_eq!(signed_in.username, "carol");
+}
+
+// ===== Reports =====
+
+async fn report_status(pool: &PgPool, report_id: Uuid) -> (String, Option<String>, Option<Uuid>) {
+    sqlx::query_as("SELECT status, action, resolved_by FROM message_reports WHERE id = $1")
+        .bind(report_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn is_banned(pool: &PgPool, user_id: Uuid) -> bool {
+    sqlx::query_scalar("SELECT banned_at IS NOT NULL FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn is_message_deleted(pool: &PgPool, message_id: Uuid) -> bool {
+    sqlx::query_scalar("SELECT deleted_at IS NOT NULL FROM messages WHERE id = $1")
+        .bind(message_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn dismissing_a_report_closes_only_that_report(pool: PgPool) {
+    let (admin, sender, recipient, other) = (
+        user(&pool, "admin").await,
+        user(&pool, "sender").await,
+        user(&pool, "recipient").await,
+        user(&pool, "other").await,
+    );
+    let message_id = message(&pool, Some(sender.id), recipient.id, "hello").await;
+    let report = create_message_report(&pool, message_id, recipient.id, "spam")
+        .await
+        .unwrap()
+        .unwrap();
+    let second = create_message_report(&pool, message_id, other.id, "spam")
+        .await
+        .unwrap()
+        .unwrap();
+
+    let outcome = resolve_report(&pool, report, admin.id, ReportAction::Dismiss)
+        .await
+        .unwrap();
+
+    assert!(matches!(outcome, ResolveOutcome::Resolved { banned: None }));
+    assert_eq!(
+        report_status(&pool, report).await,
+        ("dismissed".into(), Some("dismiss".into()), Some(admin.id))
+    );
+    assert_eq!(report_status(&pool, second).await.0, "pending");
+    assert!(!is_message_deleted(&pool, message_id).await);
+    assert!(!is_banned(&pool, sender.id).await);
+}
+
+#[sqlx::test]
+async fn deleting_content_resolves_every_report_on_it(pool: PgPool) {
+    let (admin, sender, recipient, other) = (
+        user(&pool, "admin").await,
+        user(&pool, "sender").await,
+        user(&pool, "recipient").await,
+        user(&pool, "other").await,
+    );
+    let message_id = message(&pool, Some(sender.id), recipient.id, "hello").await;
+    let report = create_message_report(&pool, message_id, recipient.id, "spam")
+        .await
+        .unwrap()
+        .unwrap();
+    let second = create_message_report(&pool, message_id, other.id, "abuse")
+        .await
+        .unwrap()
+        .unwrap();
+
+    let outcome = resolve_report(&pool, report, admin.id, ReportAction::DeleteContent)
+        .await
+        .unwrap();
+
+    assert!(matches!(outcome, ResolveOutcome::Resolved { banned: None }));
+    for id in [report, second] {
+        assert_eq!(
+            report_status(&pool, id).await,
+            (
+                "resolved".into(),
+                Some("delete_content".into()),
+                Some(admin.id)
+            )
+        );
+    }
+    assert!(is_message_deleted(&pool, message_id).await);
+    assert!(!is_banned(&pool, sender.id).await);
+}
+
+#[sqlx::test]
+async fn banning_deletes_the_content_and_bans_its_author(pool: PgPool) {
+    let (admin, sender, recipient) = (
+        user(&pool, "admin").await,
+        user(&pool, "sender").await,
+        user(&pool, "recipient").await,
+    );
+    let message_id = message(&pool, Some(sender.id), recipient.id, "hello").await;
+    let report = create_message_report(&pool, message_id, recipient.id, "abuse")
+        .await
+        .unwrap()
+        .unwrap();
+
+    let outcome = resolve_report(&pool, report, admin.id, ReportAction::BanUser)
+        .await
+        .unwrap();
+
+    let ResolveOutcome::Resolved {
+        banned: Some(banned),
+    } = outcome
+    else {
+        panic!("expected a ban, got {outcome:?}");
+    };
+    assert_eq!(banned.id, sender.id);
+    assert!(is_banned(&pool, sender.id).await);
+    assert!(is_message_deleted(&pool, message_id).await);
+    assert_eq!(
+        report_status(&pool, report).await,
+        ("resolved".into(), Some("ban_user".into()), Some(admin.id))
+    );
+}
+
+#[sqlx::test]
+async fn banning_the_author_of_a_broadcast(pool: PgPool) {
+    let (admin, author, reporter) = (
+        user(&pool, "admin").await,
+        user(&pool, "author").await,
+        user(&pool, "reporter").await,
+    );
+    let broadcast_id = create_broadcast(&pool, author.id, "hello all", true, false, None)
+        .await
+        .unwrap();
+    let report = create_broadcast_report(&pool, broadcast_id, reporter.id, "abuse")
+        .await
+        .unwrap()
+        .unwrap();
+
+    let outcome = resolve_report(&pool, report, admin.id, ReportAction::BanUser)
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        outcome,
+        ResolveOutcome::Resolved { banned: Some(ref banned) } if banned.id == author.id
+    ));
+    assert!(is_banned(&pool, author.id).await);
+    let deleted: bool =
+        sqlx::query_scalar("SELECT deleted_at IS NOT NULL FROM broadcasts WHERE id = $1")
+            .bind(broadcast_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert!(deleted);
+}
+
+#[sqlx::test]
+async fn banning_over_an_anonymous_message_changes_nothing(pool: PgPool) {
+    let (admin, recipient) = (user(&pool, "admin").await, user(&pool, "recipient").await);
+    let message_id = message(&pool, None, recipient.id, "hello").await;
+    let report = create_message_report(&pool, message_id, recipient.id, "abuse")
+        .await
+        .unwrap()
+        .unwrap();
+
+    let outcome = resolve_report(&pool, report, admin.id, ReportAction::BanUser)
+        .await
+        .unwrap();
+
+    assert!(matches!(outcome, ResolveOutcome::NoAuthor));
+    assert_eq!(
+        report_status(&pool, report).await,
+        ("pending".into(), None, None)
+    );
+    assert!(!is_message_deleted(&pool, message_id).await);
+}
+
+#[sqlx::test]
+async fn resolving_twice_finds_nothing_the_second_time(pool: PgPool) {
+    let (admin, sender, recipient) = (
+        user(&pool, "admin").await,
+        user(&pool, "sender").await,
+        user(&pool, "recipient").await,
+    );
+    let message_id = message(&pool, Some(sender.id), recipient.id, "hello").await;
+    let report = create_message_report(&pool, message_id, recipient.id, "spam")
+        .await
+        .unwrap()
+        .unwrap();
+
+    resolve_report(&pool, report, admin.id, ReportAction::Dismiss)
+        .await
+        .unwrap();
+    let again = resolve_report(&pool, report, admin.id, ReportAction::BanUser)
+        .await
+        .unwrap();
+
+    assert!(matches!(again, ResolveOutcome::NotFound));
+    assert!(!is_banned(&pool, sender.id).await);
+}
+
+#[sqlx::test]
+async fn the_queue_shows_the_reported_content_and_reporter_count(pool: PgPool) {
+    let (sender, recipient, other) = (
+        user(&pool, "sender").await,
+        user(&pool, "recipient").await,
+        user(&pool, "other").await,
+    );
+    let message_id = message(&pool, Some(sender.id), recipient.id, "as reported").await;
+    create_message_report(&pool, message_id, recipient.id, "spam")
+        .await
+        .unwrap();
+    create_message_report(&pool, message_id, other.id, "spam")
+        .await
+        .unwrap();
+    sqlx::query("UPDATE messages SET content = 'edited since' WHERE id = $1")
+        .bind(message_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let queue = get_pending_reports(&pool, 10, None, None).await.unwrap();
+
+    assert_eq!(queue.len(), 2);
+    for report in &queue {
+        assert_eq!(report.content.as_deref(), Some("as reported"));
+        assert_eq!(report.reporter_count, 2);
+        assert_eq!(report.author_id, Some(sender.id));
+    }
+}
+
+#[sqlx::test]
+async fn the_queue_pages_oldest_first(pool: PgPool) {
+    let (sender, recipient) = (user(&pool, "sender").await, user(&pool, "recipient").await);
+    let mut reports = Vec::new();
+    for i in 0..3 {
+        let message_id = message(&pool, Some(sender.id), recipient.id, &format!("m{i}")).await;
+        let id = create_message_report(&pool, message_id, recipient.id, "spam")
+            .await
+            .unwrap()
+            .unwrap();
+        reports.push(id);
+    }
+
+    let first = get_pending_reports(&pool, 2, None, None).await.unwrap();
+    let last = first.last().unwrap();
+    let rest = get_pending_reports(&pool, 2, Some(last.created_at), Some(last.id))
+        .await
+        .unwrap();
+
+    let mut seen: Vec<Uuid> = first.iter().chain(&rest).map(|r| r.id).collect();
+    assert_eq!(seen.len(), 3);
+    seen.sort();
+    reports.sort();
+    assert_eq!(seen, reports);
+}
+
+// ===== Indexes =====
+
+/// The plan Postgres picks for `query`, as EXPLAIN prints it.
+async fn explain(pool: &PgPool, query: &str) -> String {
+    let lines: Vec<String> = sqlx::query_scalar(&format!("EXPLAIN {query}"))
+        .fetch_all(pool)
+        .await
+        .unwrap();
+    lines.join("\n")
+}
+
+/// Enough rows, a third of them soft-deleted and the messages spread over
+/// 50 recipients, that an index beats a sequential scan, with fresh
+/// statistics. Returns one of the recipients and the commented broadcast.
+async fn seed_for_plans(pool: &PgPool) -> (Uuid, Uuid) {
+    let (sender, recipient) = (user(pool, "sender").await, user(pool, "recipient").await);
+    let recipients: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        INSERT INTO users (id, username, provider)
+        SELECT uuid_generate_v4(), 'user' || i, 'local' FROM generate_series(1, 49) i
+        RETURNING id
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap();
+    let recipients: Vec<Uuid> = recipients.into_iter().chain([recipient.id]).collect();
+    sqlx::query(
+        r#"
+        INSERT INTO messages (id, thread_id, sender_id, recipient_id, content, created_at, deleted_at)
+        SELECT uuid_generate_v4(), uuid_generate_v4(), $1, $2[1 + i % 50], 'message ' || i,
+               NOW() - make_interval(mins => i),
+               CASE WHEN i % 3 = 0 THEN NOW() END
+        FROM generate_series(1, 5000) i
+        "#,
+    )
+    .bind(sender.id)
+    .bind(&recipients)
+    .execute(pool)
+    .await
+    .unwrap();
+    let broadcast = create_broadcast(pool, sender.id, "hello", false, false, None)
+        .await
+        .unwrap();
+    sqlx::query(
+        r#"
+        INSERT INTO broadcasts (id, sender_id, author_id, content, created_at, deleted_at)
+        SELECT uuid_generate_v4(), $1, $1, 'broadcast ' || i,
+               NOW() - make_interval(mins => i),
+               CASE WHEN i % 3 = 0 THEN NOW() END
+        FROM generate_series(1, 5000) i
+        "#,
+    )
+    .bind(sender.id)
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        r#"
+        INSERT INTO broadcast_comments (id, broadcast_id, user_id, content, created_at, deleted_at)
+        SELECT uuid_generate_v4(), $1, $2, 'comment ' || i,
+               NOW() - make_interval(mins => i),
+               CASE WHEN i % 3 = 0 THEN NOW() END
+        FROM generate_series(1, 5000) i
+        "#,
+    )
+    .bind(broadcast)
+    .bind(recipient.id)
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query("ANALYZE").execute(pool).await.unwrap();
+    (recipient.id, broadcast)
+}
+
+#[sqlx::test]
+async fn live_row_reads_use_the_partial_indexes(pool: PgPool) {
+    let (recipient, broadcast) = seed_for_plans(&pool).await;
+
+    let inbox = explain(
+        &pool,
+        &format!(
+            "SELECT id FROM messages WHERE recipient_id = '{recipient}' AND deleted_at IS NULL \
+             ORDER BY created_at DESC LIMIT 50"
+        ),
+    )
+    .await;
+    assert!(inbox.contains("messages_recipient_live_idx"), "{inbox}");
+
+    let feed = explain(
+        &pool,
+        "SELECT id FROM broadcasts WHERE deleted_at IS NULL AND NOT is_draft \
+         ORDER BY created_at DESC LIMIT 50",
+    )
+    .await;
+    assert!(feed.contains("broadcasts_live_idx"), "{feed}");
+
+    let comments = explain(
+        &pool,
+        &format!(
+            "SELECT id FROM broadcast_comments WHERE broadcast_id = '{broadcast}' \
+             AND deleted_at IS NULL ORDER BY created_at LIMIT 50"
+        ),
+    )
+    .await;
+    assert!(
+        comments.contains("broadcast_comments_live_idx"),
+        "{comments}"
+    );
+}
+
+// ===== Search =====
+
+/// Ids of `user_id`'s messages matching `query`.
+async fn search_ids(pool: &PgPool, user_id: Uuid, query: &str) -> Vec<Uuid> {
+    search_messages(pool, user_id, query, 20)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|hit| hit.message.id)
+        .collect()
+}
+
+/// Start a thread from `sender` to `recipient` and have `recipient` reply.
+/// Returns the opening message and the reply.
+async fn exchange(pool: &PgPool, sender: Option<Uuid>, recipient: Uuid) -> (Uuid, Uuid) {
+    let (opening, thread_id) = create_message(
+        pool,
+        sender,
+        recipient,
+        "are you coming tonight",
+        None,
+        MessageExpiry::default(),
+        None,
+    )
+    .await
+    .unwrap();
+    let reply = create_reply(
+        pool,
+        thread_id,
+        recipient,
+        sender.unwrap_or(recipient),
+        "see you there",
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    (opening, reply)
+}
+
+#[sqlx::test]
+async fn message_search_matches_the_username_of_whoever_the_thread_was_started_with(pool: PgPool) {
+    let (alice, bob) = (user(&pool, "alice").await, user(&pool, "bob").await);
+    let (opening, reply) = exchange(&pool, Some(alice.id), bob.id).await;
+
+    assert_eq!(search_ids(&pool, alice.id, "bob").await, vec![reply]);
+    // Alice started the thread, so Bob never learned who she is
+    assert!(search_ids(&pool, bob.id, "alice").await.is_empty());
+    assert!(search_ids(&pool, alice.id, "alice").await.is_empty());
+    // Content still matches for both sides
+    assert_eq!(search_ids(&pool, bob.id, "tonight").await, vec![opening]);
+}
+
+#[sqlx::test]
+async fn message_search_never_matches_anonymous_senders(pool: PgPool) {
+    let bob = user(&pool, "bob").await;
+    let (opening, _) = exchange(&pool, None, bob.id).await;
+
+    let doc: String = sqlx::query_scalar("SELECT search_doc::text FROM messages WHERE id = $1")
+        .bind(opening)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    // Only content, which is weight A (left unmarked in the text form)
+    assert!(!doc.contains('B'), "{doc}");
+}
+
+#[sqlx::test]
+async fn message_search_follows_a_rename(pool: PgPool) {
+    let (alice, bob) = (user(&pool, "alice").await, user(&pool, "bob").await);
+    let (_, reply) = exchange(&pool, Some(alice.id), bob.id).await;
+
+    sqlx::query("UPDATE users SET username = 'robert' WHERE id = $1")
+        .bind(bob.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    assert_eq!(search_ids(&pool, alice.id, "robert").await, vec![reply]);
+    assert!(search_ids(&pool, alice.id, "bob").await.is_empty());
+}
+
+#[sqlx::test]
+async fn broadcast_search_matches_named_authors_only(pool: PgPool) {
+    let alice = user(&pool, "alice").await;
+    let named = create_broadcast(&pool, alice.id, "hello world", false, false, None)
+        .await
+        .unwrap();
+    create_broadcast(&pool, alice.id, "hello again", true, false, None)
+        .await
+        .unwrap();
+
+    let hits = search_broadcasts(&pool, "alice", 20).await.unwrap();
+    assert_eq!(
+        hits.into_iter().map(|b| b.id).collect::<Vec<_>>(),
+        vec![named]
+    );
+}
+
+#[sqlx::test]
+async fn message_search_docs_are_kept_once_messages_is_partitioned(pool: PgPool) {
+    let (alice, bob) = (user(&pool, "alice").await, user(&pool, "bob").await);
+    assert!(partition_messages_table(&pool).await.unwrap());
+    create_message_partitions(&pool, 1).await.unwrap();
+
+    let (_, reply) = exchange(&pool, Some(alice.id), bob.id).await;
+
+    assert_eq!(search_ids(&pool, alice.id, "bob").await, vec![reply]);
+}
+
+#[sqlx::test]
+async fn messages_across_partition_boundaries_stay_one_thread(pool: PgPool) {
+    let (alice, bob) = (user(&pool, "alice").await, user(&pool, "bob").await);
+    assert!(partition_messages_table(&pool).await.unwrap());
+    assert_eq!(create_message_partitions(&pool, 2).await.unwrap(), 2);
+    let thread_id = Uuid::new_v4();
+
+    // Just before next month (still messages_legacy), its first instant, the
+    // month after, and past every partition (messages_default)
+    sqlx::query(
+        r#"
+        INSERT INTO messages (thread_id, sender_id, recipient_id, content, created_at)
+        SELECT $1, $2, $3, 'hello', date_trunc('month', NOW()) + INTERVAL '1 month' + offset_by
+        FROM unnest(ARRAY[
+            INTERVAL '-1 microsecond', INTERVAL '0', INTERVAL '1 month', INTERVAL '6 months'
+        ]) AS offset_by
+        "#,
+    )
+    .bind(thread_id)
+    .bind(alice.id)
+    .bind(bob.id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let placed: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT tableoid::regclass::text, 'messages_' || to_char(created_at, 'YYYY_MM')
+        FROM messages WHERE thread_id = $1
+        ORDER BY created_at
+        "#,
+    )
+    .bind(thread_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    let partitions: Vec<&str> = placed.iter().map(|(p, _)| p.as_str()).collect();
+    assert_eq!(
+        partitions,
+        [
+            "messages_legacy",
+            placed[1].1.as_str(),
+            placed[2].1.as_str(),
+            "messages_default"
+        ]
+    );
+
+    let thread = get_thread_messages(&pool, thread_id, bob.id, false, false)
+        .await
+        .unwrap();
+    assert_eq!(thread.len(), 4);
+    assert!(thread.windows(2).all(|w| w[0].created_at < w[1].created_at));
+}
+
+// ===== Stats =====
+
+#[sqlx::test]
+async fn pool_stats_count_a_checked_out_connection(pool: PgPool) {
+    let before = pool_stats(&pool).in_use;
+    let connection = pool.acquire().await.unwrap();
+
+    let stats = pool_stats(&pool);
+    assert_eq!(stats.in_use, before + 1);
+    assert_eq!(stats.in_use + stats.idle, stats.size as usize);
+
+    drop(connection);
+}
+
+// ===== Recurring broadcasts =====
+
+async fn daily_schedule(pool: &PgPool, author: Uuid, max_occurrences: Option<i32>) -> Uuid {
+    let schedule = NewScheduledBroadcast {
+        content: "good morning",
+        is_anonymous: false,
+        frequency: "daily",
+        weekday: None,
+        time_of_day: sqlx::types::time::Time::from_hms(9, 0, 0).unwrap(),
+        timezone: "America/New_York",
+        ends_at: None,
+        max_occurrences,
+    };
+    create_scheduled_broadcast(pool, author, &schedule)
+        .await
+        .unwrap()
+        .unwrap()
+        .id
+}
+
+/// Make a schedule due, as if `ago` had passed since its next run.
+async fn make_due(pool: &PgPool, schedule_id: Uuid, ago: &str) {
+    sqlx::query("UPDATE scheduled_broadcasts SET next_run_at = NOW() - $2::interval WHERE id = $1")
+        .bind(schedule_id)
+        .bind(ago)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn next_run(pool: &PgPool, schedule_id: Uuid) -> Option<OffsetDateTime> {
+    sqlx::query_scalar("SELECT next_run_at FROM scheduled_broadcasts WHERE id = $1")
+        .bind(schedule_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn a_due_schedule_publishes_once_per_interval(pool: PgPool) {
+    let alice = user(&pool, "alice").await;
+    let schedule = daily_schedule(&pool, alice.id, None).await;
+
+    assert!(run_due_scheduled_broadcasts(&pool)
+        .await
+        .unwrap()
+        .is_empty());
+
+    make_due(&pool, schedule, "1 minute").await;
+    assert_eq!(run_due_scheduled_broadcasts(&pool).await.unwrap().len(), 1);
+    // Already advanced to the next slot, so a second pass publishes nothing
+    assert!(run_due_scheduled_broadcasts(&pool)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(next_run(&pool, schedule).await.unwrap() > OffsetDateTime::now_utc());
+
+    make_due(&pool, schedule, "1 minute").await;
+    assert_eq!(run_due_scheduled_broadcasts(&pool).await.unwrap().len(), 1);
+}
+
+#[sqlx::test]
+async fn missed_slots_publish_a_single_broadcast(pool: PgPool) {
+    let alice = user(&pool, "alice").await;
+    let schedule = daily_schedule(&pool, alice.id, None).await;
+    make_due(&pool, schedule, "3 days").await;
+
+    assert_eq!(run_due_scheduled_broadcasts(&pool).await.unwrap().len(), 1);
+    assert!(run_due_scheduled_broadcasts(&pool)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[sqlx::test]
+async fn a_schedule_ends_after_max_occurrences(pool: PgPool) {
+    let alice = user(&pool, "alice").await;
+    let schedule = daily_schedule(&pool, alice.id, Some(2)).await;
+
+    for _ in 0..2 {
+        make_due(&pool, schedule, "1 minute").await;
+        assert_eq!(run_due_scheduled_broadcasts(&pool).await.unwrap().len(), 1);
+    }
+
+    assert_eq!(next_run(&pool, schedule).await, None);
+}
+
+#[sqlx::test]
+async fn daily_slots_keep_their_local_time_across_dst(pool: PgPool) {
+    // New York springs forward on 2026-03-08: 09:00 is 14:00 UTC the day
+    // before and 13:00 UTC on the day
+    let next: OffsetDateTime = sqlx::query_scalar(
+        "SELECT scheduled_broadcast_next_run('daily', NULL, '09:00', 'America/New_York', \
+         '2026-03-07 15:00Z', NULL)",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let expected = OffsetDateTime::parse(
+        "2026-03-08T13:00:00Z",
+        &time::format_description::well_known::Rfc3339,
+    )
+    .unwrap();
+    assert_eq!(next, expected);
+}
+
+// ===== Reaction counts =====
+
+#[sqlx::test]
+async fn reaction_counts_are_sorted_by_emoji_and_empty_as_an_object(pool: PgPool) {
+    let (alice, bob) = (user(&pool, "alice").await, user(&pool, "bob").await);
+    let (reacted, thread_id) = create_message(
+        &pool,
+        Some(alice.id),
+        bob.id,
+        "hello",
+        None,
+        MessageExpiry::default(),
+        None,
+    )
+    .await
+    .unwrap();
+    let plain = create_reply(&pool, thread_id, bob.id, alice.id, "hi", None, None)
+        .await
+        .unwrap();
+    for (user_id, emoji) in [
+        (bob.id, "🎉"),
+        (alice.id, "👍"),
+        (bob.id, "👍"),
+        (bob.id, "❤"),
+    ] {
+        add_message_reaction(&pool, reacted, user_id, emoji)
+            .await
+            .unwrap();
+    }
+
+    let messages = get_thread_messages(&pool, thread_id, bob.id, false, false)
+        .await
+        .unwrap();
+    let reactions = |id| {
+        let message = messages.iter().find(|m| m.id == id).unwrap();
+        serde_json::to_string(&message.reactions).unwrap()
+    };
+
+    assert_eq!(reactions(reacted), r#"{"❤":1,"🎉":1,"👍":2}"#);
+    assert_eq!(reactions(plain), "{}");
+}
+
+// ===== Read receipts =====
+
+async fn read_at(pool: &PgPool, message_id: Uuid) -> Option<OffsetDateTime> {
+    sqlx::query_scalar("SELECT read_at FROM messages WHERE id = $1")
+        .bind(message_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn a_second_device_never_moves_read_at(pool: PgPool) {
+    let (alice, bob) = (user(&pool, "alice").await, user(&pool, "bob").await);
+    let (message_id, thread_id) = create_message(
+        &pool,
+        Some(alice.id),
+        bob.id,
+        "hello",
+        None,
+        MessageExpiry::default(),
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Two devices open the thread at once; only one of them reads it first
+    let (phone, laptop) = tokio::join!(
+        mark_thread_as_read(&pool, thread_id, bob.id),
+        mark_thread_as_read(&pool, thread_id, bob.id),
+    );
+    let (phone, laptop) = (phone.unwrap(), laptop.unwrap());
+    assert_eq!(phone.len() + laptop.len(), 1);
+    let first = read_at(&pool, message_id).await.expect("read_at is set");
+
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    assert!(mark_thread_as_read(&pool, thread_id, bob.id)
+        .await
+        .unwrap()
+        .is_empty());
+    mark_message_read(&pool, message_id, bob.id).await.unwrap();
+
+    assert_eq!(read_at(&pool, message_id).await, Some(first));
+}
+
+// ===== Send quotas =====
+
+async fn quota_count(pool: &PgPool, user_id: Uuid) -> i32 {
+    sqlx::query_scalar("SELECT count FROM send_quotas WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn a_full_quota_starts_again_on_a_new_day(pool: PgPool) {
+    let alice = user(&pool, "alice").await;
+
+    for _ in 0..3 {
+        assert!(consume_send_quota(&pool, alice.id, 3).await.unwrap());
+    }
+    assert!(!consume_send_quota(&pool, alice.id, 3).await.unwrap());
+    assert_eq!(quota_count(&pool, alice.id).await, 3);
+
+    // The row now belongs to yesterday
+    sqlx::query("UPDATE send_quotas SET day = day - 1")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    assert!(consume_send_quota(&pool, alice.id, 3).await.unwrap());
+    assert_eq!(quota_count(&pool, alice.id).await, 1);
+}
@@ -0,0 +1,115 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use tracing::warn;
+
+/// Error returned by request handlers.
+/// Rendered as `{ "error": "<code>", "message": "<human text>" }` with the matching status.
+#[derive(Debug)]
+pub enum ApiError {
+    /// 400 — the request is malformed or fails validation
+    Validation(String),
+    /// 401 — no valid session
+    Unauthorized,
+    /// 401 — login failed; deliberately doesn't say which part was wrong
+    InvalidCredentials,
+    /// 403 — authenticated, but not allowed to do this
+    Forbidden(String),
+    /// 404
+    NotFound(String),
+    /// 409
+    Conflict(String),
+    /// 412 — a conditional request's precondition no longer holds
+    PreconditionFailed(String),
+    /// 413
+    PayloadTooLarge(String),
+    /// 422 — well-formed, but can't be carried out
+    Unprocessable(String),
+    /// 423
+    Locked(String),
+    /// 429
+    TooManyRequests(String),
+    /// 500 — the cause is logged where it happens, never sent to the client
+    Internal,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Validation(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized | ApiError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Locked(_) => StatusCode::LOCKED,
+            ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Stable machine-readable code for the `error` field.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Validation(_) => "validation_error",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::InvalidCredentials => "invalid_credentials",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PreconditionFailed(_) => "precondition_failed",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::Unprocessable(_) => "unprocessable",
+            ApiError::Locked(_) => "locked",
+            ApiError::TooManyRequests(_) => "too_many_requests",
+            ApiError::Internal => "internal_error",
+        }
+    }
+
+    fn message(self) -> String {
+        match self {
+            ApiError::Validation(msg)
+            | ApiError::Forbidden(msg)
+            | ApiError::NotFound(msg)
+            | ApiError::Conflict(msg)
+            | ApiError::PreconditionFailed(msg)
+            | ApiError::PayloadTooLarge(msg)
+            | ApiError::Unprocessable(msg)
+            | ApiError::Locked(msg)
+            | ApiError::TooManyRequests(msg) => msg,
+            ApiError::Unauthorized => "Authentication required".to_string(),
+            ApiError::InvalidCredentials => "Invalid username or password".to_string(),
+            ApiError::Internal => "Internal server error".to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let body = ErrorBody {
+            error: self.code(),
+            message: self.message(),
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+/// Lets `?` and [`crate::db::transaction`] surface database failures directly;
+/// prefer an explicit `map_err` with context where there is some to log.
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        warn!("Database error: {}", e);
+        ApiError::Internal
+    }
+}
@@ -0,0 +1,160 @@
+//! Keyed origin fingerprints for correlating anonymous abuse.
+//!
+//! A fingerprint is an HMAC-SHA256 of the client IP and user agent under a
+//! server-side key, so the same origin always maps to the same value but the
+//! value can't be reversed or looked up in a precomputed table without the key.
+//! Only anonymous messages and broadcasts get one, and only when a key is set.
+
+use axum::extract::{ConnectInfo, FromRef, FromRequestParts};
+use axum::http::{header, request::Parts};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::convert::Infallible;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::config::ApiConfig;
+
+/// Shortest key accepted for `ABUSE_FINGERPRINT_KEY`, in bytes.
+pub const MIN_KEY_LENGTH: usize = 32;
+
+/// Secret HMAC key. Debug output never shows it.
+#[derive(Clone)]
+pub struct FingerprintKey(Vec<u8>);
+
+impl FingerprintKey {
+    pub fn new(key: &str) -> Self {
+        Self(key.as_bytes().to_vec())
+    }
+
+    /// Hex HMAC of the origin. Fields are length-prefixed so no two distinct
+    /// (ip, user agent) pairs hash the same input.
+    pub fn fingerprint(&self, ip: IpAddr, user_agent: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts keys of any length");
+        let ip = ip.to_string();
+        mac.update(&(ip.len() as u64).to_be_bytes());
+        mac.update(ip.as_bytes());
+        mac.update(user_agent.as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+}
+
+impl fmt::Debug for FingerprintKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FingerprintKey([redacted])")
+    }
+}
+
+/// The client's IP: the socket peer, or with `trust_forwarded_for` the last
+/// `X-Forwarded-For` hop (the one our own proxy appended).
+pub fn client_ip(parts: &Parts, trust_forwarded_for: bool) -> Option<IpAddr> {
+    if trust_forwarded_for {
+        let forwarded = parts
+            .headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|hop| hop.trim().parse::<IpAddr>().ok())
+            .next_back();
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+    parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// The caller's IP as [`client_ip`] sees it, or `None` if unknown.
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    Arc<ApiConfig>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = Arc::<ApiConfig>::from_ref(state);
+        Ok(Self(client_ip(parts, config.trust_forwarded_for)))
+    }
+}
+
+/// The caller's origin fingerprint, or `None` when fingerprinting is off or
+/// the client IP is unknown. Never sent back to clients.
+pub struct OriginFingerprint(pub Option<String>);
+
+impl<S> FromRequestParts<S> for OriginFingerprint
+where
+    Arc<ApiConfig>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = Arc::<ApiConfig>::from_ref(state);
+        let Some(key) = &config.abuse_fingerprint_key else {
+            return Ok(Self(None));
+        };
+        let Some(ip) = client_ip(parts, config.trust_forwarded_for) else {
+            return Ok(Self(None));
+        };
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+        Ok(Self(Some(key.fingerprint(ip, user_agent))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn the_same_origin_gets_the_same_fingerprint() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let key = FingerprintKey::new(KEY);
+
+        assert_eq!(
+            key.fingerprint(ip, "Firefox"),
+            FingerprintKey::new(KEY).fingerprint(ip, "Firefox")
+        );
+        assert_eq!(key.fingerprint(ip, "Firefox").len(), 64);
+    }
+
+    #[test]
+    fn another_origin_or_key_gets_another_fingerprint() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let key = FingerprintKey::new(KEY);
+        let fingerprint = key.fingerprint(ip, "Firefox");
+
+        assert_ne!(
+            fingerprint,
+            key.fingerprint("203.0.113.8".parse().unwrap(), "Firefox")
+        );
+        assert_ne!(fingerprint, key.fingerprint(ip, "Chrome"));
+        assert_ne!(
+            fingerprint,
+            FingerprintKey::new("another key, just as long as the first")
+                .fingerprint(ip, "Firefox")
+        );
+        // Moving bytes between the fields changes the input
+        assert_ne!(
+            key.fingerprint("::1".parse().unwrap(), "1x"),
+            key.fingerprint("::11".parse().unwrap(), "x")
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Periodic read-only checks of data invariants.
+//!
+//! Each check is a query returning the ids of rows that break an invariant.
+//! Queries run one at a time in read-only transactions with a statement
+//! timeout and a row limit, so a run stays cheap even on large tables. The
+//! latest report is logged, kept in memory for `GET /api/admin/integrity`,
+//! and exported as the `anonyma_integrity_anomalies` gauge.
+//!
+//! To add a check, append to [`CHECKS`]. Its query must select one column
+//! castable to text, take the row limit as `$1`, and must not write.
+
+use serde::Serialize;
+use sqlx::{types::time::OffsetDateTime, PgPool};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::telemetry;
+
+/// Rows reported per check; the count says when there were more.
+pub const SAMPLE_LIMIT: i64 = 20;
+
+/// Longest any single check may run before it's cancelled.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Check {
+    pub name: &'static str,
+    pub description: &'static str,
+    sql: &'static str,
+}
+
+pub const CHECKS: &[Check] = &[
+    Check {
+        name: "message_sender_missing",
+        description: "Messages whose sender_id names a user that doesn't exist",
+        sql: r#"
+            SELECT m.id::text FROM messages m
+            WHERE m.sender_id IS NOT NULL
+              AND NOT EXISTS (SELECT 1 FROM users u WHERE u.id = m.sender_id)
+            LIMIT $1
+        "#,
+    },
+    Check {
+        name: "message_recipient_missing",
+        description: "Messages whose recipient doesn't exist",
+        sql: r#"
+            SELECT m.id::text FROM messages m
+            WHERE NOT EXISTS (SELECT 1 FROM users u WHERE u.id = m.recipient_id)
+            LIMIT $1
+        "#,
+    },
+    Check {
+        // Without foreign keys on a partitioned messages table, a missed
+        // cascade shows up here
+        name: "reaction_message_missing",
+        description: "Reactions on messages that don't exist",
+        sql: r#"
+            SELECT r.id::text FROM message_reactions r
+            WHERE NOT EXISTS (SELECT 1 FROM messages m WHERE m.id = r.message_id)
+            LIMIT $1
+        "#,
+    },
+    Check {
+        name: "reaction_after_delete",
+        description: "Reactions added to a message after it was deleted",
+        sql: r#"
+            SELECT r.id::text FROM message_reactions r
+            JOIN messages m ON m.id = r.message_id
+            WHERE m.deleted_at IS NOT NULL AND r.created_at > m.deleted_at
+            LIMIT $1
+        "#,
+    },
+    Check {
+        name: "thread_without_messages",
+        description:
+            "Threads that are pinned, labelled, paused, muted or deleted but have no messages",
+        sql: r#"
+            SELECT DISTINCT t.thread_id::text FROM (
+                SELECT thread_id FROM pinned_threads
+                UNION ALL SELECT thread_id FROM thread_labels
+                UNION ALL SELECT thread_id FROM thread_pauses
+                UNION ALL SELECT thread_id FROM thread_mutes
+                UNION ALL SELECT thread_id FROM thread_deletions
+            ) t
+            WHERE NOT EXISTS (SELECT 1 FROM messages m WHERE m.thread_id = t.thread_id)
+            LIMIT $1
+        "#,
+    },
+    Check {
+        // Unread counts go by is_read, receipts by read_at; they must agree
+        name: "read_state_mismatch",
+        description: "Messages whose is_read flag disagrees with read_at",
+        sql: r#"
+            SELECT m.id::text FROM messages m
+            WHERE m.is_read <> (m.read_at IS NOT NULL)
+            LIMIT $1
+        "#,
+    },
+];
+
+/// What one check found.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Offending rows found, capped at `SAMPLE_LIMIT + 1`
+    pub count: usize,
+    /// Ids of the first `SAMPLE_LIMIT` offending rows
+    pub sample: Vec<String>,
+    /// Set when the check couldn't run (a timeout, say); `count` is then 0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    #[serde(with = "time::serde::rfc3339")]
+    pub checked_at: OffsetDateTime,
+    /// Every check, clean ones included
+    pub checks: Vec<CheckResult>,
+}
+
+impl Report {
+    pub fn anomalies(&self) -> usize {
+        self.checks.iter().map(|check| check.count).sum()
+    }
+}
+
+/// The most recent report, shared between the job and the admin endpoint.
+pub type LatestReport = Arc<RwLock<Option<Report>>>;
+
+/// Run every check once.
+pub async fn run_checks(pool: &PgPool) -> Report {
+    let mut checks = Vec::with_capacity(CHECKS.len());
+    for check in CHECKS {
+        let result =
+            crate::db::find_integrity_violations(pool, check.sql, SAMPLE_LIMIT + 1, CHECK_TIMEOUT)
+                .await;
+        let (count, sample, error) = match result {
+            Ok(mut ids) => {
+                let count = ids.len();
+                ids.truncate(SAMPLE_LIMIT as usize);
+                (count, ids, None)
+            }
+            Err(e) => {
+                warn!("Integrity check {} failed: {}", check.name, e);
+                (0, Vec::new(), Some(e.to_string()))
+            }
+        };
+        if count > 0 {
+            warn!(
+                "Integrity check {} found {} anomalies, e.g. {:?}",
+                check.name, count, sample
+            );
+        }
+        metrics::gauge!(telemetry::INTEGRITY_ANOMALIES, "check" => check.name).set(count as f64);
+        checks.push(CheckResult {
+            name: check.name,
+            description: check.description,
+            count,
+            sample,
+            error,
+        });
+    }
+    Report {
+        checked_at: OffsetDateTime::now_utc(),
+        checks,
+    }
+}
+
+/// Job body: run the checks and keep the report for the admin endpoint.
+pub async fn run_and_store(pool: &PgPool, latest: &LatestReport) -> Result<(), String> {
+    let report = run_checks(pool).await;
+    let failed = report.checks.iter().filter(|c| c.error.is_some()).count();
+    if failed == 0 && report.anomalies() == 0 {
+        info!("Integrity checks passed");
+    }
+    *latest.write().unwrap_or_else(|e| e.into_inner()) = Some(report);
+    if failed > 0 {
+        return Err(format!("{failed} integrity checks couldn't run"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MessageExpiry;
+    use uuid::Uuid;
+
+    fn found<'a>(report: &'a Report, name: &str) -> &'a CheckResult {
+        report.checks.iter().find(|c| c.name == name).unwrap()
+    }
+
+    #[sqlx::test]
+    async fn injected_inconsistencies_are_reported(pool: PgPool) {
+        let alice = crate::db::create_local_user(&pool, "alice", "hash")
+            .await
+            .unwrap();
+        let bob = crate::db::create_local_user(&pool, "bob", "hash")
+            .await
+            .unwrap();
+        let (message_id, _) = crate::db::create_message(
+            &pool,
+            Some(alice.id),
+            bob.id,
+            "hi",
+            None,
+            MessageExpiry::default(),
+            None,
+        )
+        .await
+        .unwrap();
+        let latest = LatestReport::default();
+        run_and_store(&pool, &latest).await.unwrap();
+        assert_eq!(latest.read().unwrap().as_ref().unwrap().anomalies(), 0);
+
+        sqlx::query("UPDATE messages SET is_read = true WHERE id = $1")
+            .bind(message_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let orphan = Uuid::new_v4();
+        sqlx::query("INSERT INTO pinned_threads (thread_id, user_id) VALUES ($1, $2)")
+            .bind(orphan)
+            .bind(alice.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        run_and_store(&pool, &latest).await.unwrap();
+        let report = latest.read().unwrap().clone().unwrap();
+        assert_eq!(report.anomalies(), 2);
+        let read_state = found(&report, "read_state_mismatch");
+        assert_eq!(read_state.sample, [message_id.to_string()]);
+        let empty_thread = found(&report, "thread_without_messages");
+        assert_eq!(empty_thread.sample, [orphan.to_string()]);
+    }
+}
@@ -0,0 +1,150 @@
+use futures_util::future::BoxFuture;
+use sqlx::PgPool;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+use crate::state::NotificationHub;
+
+/// What a job gets to work with on each run.
+#[derive(Clone)]
+pub struct JobContext {
+    pub pool: Arc<PgPool>,
+    pub hub: NotificationHub,
+}
+
+type JobFn = Arc<dyn Fn(JobContext) -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+struct Job {
+    name: &'static str,
+    interval: Duration,
+    run: JobFn,
+}
+
+/// Runs named background jobs on fixed intervals.
+///
+/// Each run happens in its own task inside a `job` span, so a failure or a
+/// panic is logged and the job simply runs again at its next tick. A run that
+/// overruns its interval delays the next one rather than triggering a burst.
+pub struct Scheduler {
+    context: JobContext,
+    jobs: Vec<Job>,
+}
+
+impl Scheduler {
+    pub fn new(pool: Arc<PgPool>, hub: NotificationHub) -> Self {
+        Self {
+            context: JobContext { pool, hub },
+            jobs: Vec::new(),
+        }
+    }
+
+    /// Add a job that runs every `interval`, starting once the scheduler starts.
+    pub fn register<F, Fut, E>(mut self, name: &'static str, interval: Duration, job: F) -> Self
+    where
+        F: Fn(JobContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        let run: JobFn = Arc::new(move |ctx| {
+            let fut = job(ctx);
+            Box::pin(async move { fut.await.map_err(|e| e.to_string()) })
+        });
+        self.jobs.push(Job {
+            name,
+            interval,
+            run,
+        });
+        self
+    }
+
+    /// Spawn one loop per registered job.
+    pub fn start(&self) {
+        for job in &self.jobs {
+            let name = job.name;
+            let run = job.run.clone();
+            let ctx = self.context.clone();
+            let mut interval = tokio::time::interval(job.interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            info!("Starting job {} every {:?}", name, job.interval);
+            tokio::spawn(async move {
+                loop {
+                    interval.tick().await;
+                    run_job(name, &run, ctx.clone()).await;
+                }
+            });
+        }
+    }
+
+    /// Run a job once, right now, outside its schedule. Returns false if no
+    /// job has that name.
+    #[cfg(test)]
+    pub async fn run_now(&self, name: &str) -> bool {
+        match self.jobs.iter().find(|job| job.name == name) {
+            Some(job) => {
+                run_job(job.name, &job.run, self.context.clone()).await;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+async fn run_job(name: &'static str, run: &JobFn, ctx: JobContext) {
+    let span = info_span!("job", name);
+    // A separate task turns a panic into a JoinError instead of killing the loop
+    let handle = tokio::spawn(run(ctx).instrument(span.clone()));
+    async move {
+        match handle.await {
+            Ok(Ok(())) => debug!("Job {} finished", name),
+            Ok(Err(e)) => warn!("Job {} failed: {}", name, e),
+            Err(e) => error!("Job {} panicked: {}", name, e),
+        }
+    }
+    .instrument(span)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::EventLog;
+    use crate::state::Hub;
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A scheduler whose pool never connects; these jobs don't query.
+    fn scheduler() -> Scheduler {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let hub = Arc::new(Hub::new(EventLog::new(10, Duration::from_secs(60))));
+        Scheduler::new(Arc::new(pool), hub)
+    }
+
+    #[tokio::test]
+    async fn run_now_runs_the_named_job_once() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counted = runs.clone();
+        let scheduler = scheduler().register("count", Duration::from_secs(3600), move |_| {
+            let counted = counted.clone();
+            async move {
+                counted.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, String>(())
+            }
+        });
+
+        assert!(scheduler.run_now("count").await);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(scheduler.run_now("count").await);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn run_now_reports_an_unknown_job() {
+        assert!(!scheduler().run_now("missing").await);
+    }
+}
@@ -2,13 +2,23 @@ use axum::{response::Redirect, routing::get, Router};
 use config::Config;
 use dotenvy::dotenv;
 use std::sync::Arc;
-use std::time::Duration;
 use tower_cookies::CookieManagerLayer;
 use tower_http::trace::TraceLayer;
 
 mod auth;
 mod config;
 mod db;
+mod error;
+mod fingerprint;
+mod integrity;
+mod jobs;
+mod pagination;
+mod push;
+mod ratelimit;
+mod replay;
+mod retention;
+mod sessions;
+mod webhooks;
 
 use db::init_db;
 
@@ -16,13 +26,17 @@ use db::init_db;
 use authkestra::axum::AuthkestraAxumExt;
 use authkestra::flow::{Authkestra, OAuth2Flow};
 use authkestra::providers::github::GithubProvider;
+use authkestra::providers::google::GoogleProvider;
 use authkestra::session::memory::MemoryStore;
 use authkestra::session::SessionConfig;
 
 mod api;
 mod state;
+mod telemetry;
+#[cfg(test)]
+mod testing;
 
-use state::AppState;
+use state::{AnonMessageLimiter, AppState};
 
 #[tokio::main]
 async fn main() {
@@ -30,28 +44,76 @@ async fn main() {
 
     // Configure structured logging
     Config::setup_tracing();
+    let metrics = telemetry::install();
 
     // initialize configurations
     let config = Config::init();
-    tracing::info!("Configured Redirect URI: {}", config.redirect_uri);
+    tracing::info!(
+        "Configured GitHub Redirect URI: {}",
+        config.redirect_uri("github")
+    );
     tracing::info!("Configured Client ID: {}", config.client_id);
+    let password = &config.api.password;
+    tracing::info!(
+        "Argon2id password hashing: {} KiB memory, {} iterations, {} lanes",
+        password.memory_kib,
+        password.iterations,
+        password.parallelism
+    );
+    tracing::info!(
+        "SSE keep-alive every {} s",
+        config.api.sse_keepalive.as_secs()
+    );
 
     let pool = init_db(&config.database_url)
         .await
         .expect("Failed to initialize database");
 
+    if config.messages_partitioning {
+        match db::partition_messages_table(&pool).await {
+            Ok(true) => tracing::info!("Converted messages to a partitioned table"),
+            Ok(false) => {}
+            Err(e) => panic!("Failed to partition messages: {e}"),
+        }
+    }
+
     // Setup Authkestra
 
-    let github_provider =
-        GithubProvider::new(config.client_id, config.client_secret, config.redirect_uri);
+    let github_provider = GithubProvider::new(
+        config.client_id.clone(),
+        config.client_secret.clone(),
+        config.redirect_uri("github"),
+    );
     let github_flow = OAuth2Flow::new(github_provider)
         .with_scopes(vec!["read:user".to_string(), "user:email".to_string()]);
-    let session_store = Arc::new(MemoryStore::default());
+    let session_store = Arc::new(sessions::IndexedSessionStore::new(Arc::new(
+        MemoryStore::default(),
+    )));
 
     // Create Authkestra instance
-    let authkestra = Authkestra::builder()
+    let mut authkestra = Authkestra::builder()
         .session_store(session_store.clone())
-        .provider(github_flow)
+        .provider(github_flow);
+    if let (Some(client_id), Some(client_secret)) =
+        (&config.google_client_id, &config.google_client_secret)
+    {
+        tracing::info!(
+            "Configured Google Redirect URI: {}",
+            config.redirect_uri("google")
+        );
+        let google_provider = GoogleProvider::new(
+            client_id.clone(),
+            client_secret.clone(),
+            config.redirect_uri("google"),
+        );
+        let google_flow = OAuth2Flow::new(google_provider).with_scopes(vec![
+            "openid".to_string(),
+            "profile".to_string(),
+            "email".to_string(),
+        ]);
+        authkestra = authkestra.provider(google_flow);
+    }
+    let authkestra = authkestra
         .session_config(SessionConfig {
             secure: false, // Must be false for HTTP localhost
             ..SessionConfig::default()
@@ -59,22 +121,35 @@ async fn main() {
         .build();
 
     // Create custom app state
+    let db_pool = Arc::new(pool);
     let state = AppState {
         authkestra: authkestra.clone(),
-        db_pool: Arc::new(pool),
-        notification_hub: std::sync::Arc::new(tokio::sync::Mutex::new(
-            std::collections::HashMap::new(),
-        )),
+        sessions: session_store,
+        db_pool: db_pool.clone(),
+        notification_hub: Arc::new(state::Hub::new(replay::EventLog::new(
+            config.sse_replay_limit,
+            config.sse_replay_max_age,
+        ))),
         frontend_url: config.frontend_url.clone(),
+        api_config: Arc::new(config.api.clone()),
+        auth_limiter: Arc::new(ratelimit::RateLimiter::new(
+            config.auth_rate_limit,
+            config.auth_rate_limit_window,
+        )),
+        anon_limiter: AnonMessageLimiter(Arc::new(ratelimit::RateLimiter::new(
+            config.anon_message_rate_limit,
+            config.anon_message_rate_limit_window,
+        ))),
+        metrics,
+        integrity: Arc::default(),
+        push: Arc::new(push::PushNotifier::new(config.vapid.clone())),
+        webhooks: webhooks::WebhookDispatcher::start(db_pool),
     };
 
     // CORS configuration
+    tracing::info!("CORS allowed origins: {:?}", config.cors_origins);
     let cors = tower_http::cors::CorsLayer::new()
-        .allow_origin(vec![config
-            .frontend_url
-            .clone()
-            .parse::<axum::http::HeaderValue>()
-            .unwrap()])
+        .allow_origin(config.cors_origins.clone())
         .allow_methods(vec![
             axum::http::Method::GET,
             axum::http::Method::POST,
@@ -87,27 +162,164 @@ async fn main() {
             axum::http::header::AUTHORIZATION,
             axum::http::header::ACCEPT,
             axum::http::header::COOKIE,
+            axum::http::header::IF_MATCH,
         ])
+        .expose_headers(vec![axum::http::header::ETAG])
         .allow_credentials(true);
 
-    // Spawn a task to clean up typing indicators periodically
-    let pool_clone = state.db_pool.clone();
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_secs(10));
-        loop {
-            interval.tick().await;
-            let _ = crate::db::cleanup_typing_indicators(&pool_clone).await;
+    // Periodic background work
+    let scheduler = jobs::Scheduler::new(state.db_pool.clone(), state.notification_hub.clone())
+        .register(
+            "typing_cleanup",
+            config.typing_cleanup_interval,
+            |ctx| async move { api::expire_typing_indicators(&ctx.pool, &ctx.hub).await },
+        );
+    let scheduler = {
+        let webhooks = state.webhooks.clone();
+        scheduler.register(
+            "scheduled_broadcasts",
+            config.scheduled_broadcast_interval,
+            move |ctx| {
+                let webhooks = webhooks.clone();
+                async move {
+                    api::publish_scheduled_broadcasts(&ctx.pool, &ctx.hub, &webhooks).await
+                }
+            },
+        )
+    };
+    let scheduler = {
+        let push = state.push.clone();
+        let webhooks = state.webhooks.clone();
+        scheduler.register(
+            "scheduled_messages",
+            config.scheduled_message_interval,
+            move |ctx| {
+                let (push, webhooks) = (push.clone(), webhooks.clone());
+                async move {
+                    api::deliver_scheduled_messages(&ctx.pool, &ctx.hub, &push, &webhooks).await
+                }
+            },
+        )
+    };
+    let scheduler = scheduler.register(
+        "expired_messages",
+        config.expired_message_interval,
+        |ctx| async move { api::expire_messages(&ctx.pool, &ctx.hub).await },
+    );
+    let scheduler = scheduler.register(
+        "sse_replay_prune",
+        config.sse_replay_max_age,
+        |ctx| async move {
+            ctx.hub.replay.prune();
+            Ok::<_, std::convert::Infallible>(())
+        },
+    );
+    let scheduler = scheduler.register("auth_rate_limit_cleanup", config.auth_rate_limit_window, {
+        let limiter = state.auth_limiter.clone();
+        move |_| {
+            limiter.prune();
+            std::future::ready(Ok::<_, std::convert::Infallible>(()))
+        }
+    });
+    let scheduler = scheduler.register(
+        "anon_rate_limit_cleanup",
+        config.anon_message_rate_limit_window,
+        {
+            let limiter = state.anon_limiter.0.clone();
+            move |_| {
+                limiter.prune();
+                std::future::ready(Ok::<_, std::convert::Infallible>(()))
+            }
+        },
+    );
+    let scheduler = if config.messages_partitioning {
+        let months_ahead = config.message_partitions_ahead;
+        scheduler.register(
+            "message_partitions",
+            config.message_partition_interval,
+            move |ctx| async move {
+                let created = db::create_message_partitions(&ctx.pool, months_ahead).await?;
+                if created > 0 {
+                    tracing::info!("Created {} message partitions", created);
+                }
+                Ok::<_, sqlx::Error>(())
+            },
+        )
+    } else {
+        scheduler
+    };
+    let scheduler = if config.integrity_check_interval.is_zero() {
+        scheduler
+    } else {
+        let latest = state.integrity.clone();
+        scheduler.register(
+            "integrity_check",
+            config.integrity_check_interval,
+            move |ctx| {
+                let latest = latest.clone();
+                async move { integrity::run_and_store(&ctx.pool, &latest).await }
+            },
+        )
+    };
+    let scheduler = if config.retention.is_enabled() {
+        let policy = config.retention;
+        scheduler.register(
+            "retention_purge",
+            config.retention_purge_interval,
+            move |ctx| async move { retention::purge(&ctx.pool, policy).await },
+        )
+    } else {
+        scheduler
+    };
+    scheduler.start();
+
+    let notification_hub = state.notification_hub.clone();
+
+    let app = app(state).layer(cors).layer(TraceLayer::new_for_http());
+
+    let listener = tokio::net::TcpListener::bind(&config.bind_address)
+        .await
+        .expect("failed to bind TCP listener");
+    tracing::info!("Server starting on {}", config.base_url);
+
+    // On SIGTERM/ctrl-c stop accepting connections, end SSE streams and give
+    // in-flight requests a bounded time to finish
+    let shutdown_started = Arc::new(tokio::sync::Notify::new());
+    // Peer addresses feed the origin fingerprint (see fingerprint.rs)
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown({
+        let shutdown_started = shutdown_started.clone();
+        async move {
+            shutdown_signal().await;
+            tracing::info!("Shutting down, draining connections");
+            api::close_sse_streams(&notification_hub);
+            shutdown_started.notify_one();
         }
     });
 
-    // Build app with routes and merge Authkestra router
-    let app = Router::new()
+    tokio::select! {
+        result = server => result.expect("server error"),
+        _ = async {
+            shutdown_started.notified().await;
+            tokio::time::sleep(config.shutdown_grace_period).await;
+        } => tracing::warn!(
+            "Requests still running after {:?}, exiting anyway",
+            config.shutdown_grace_period
+        ),
+    }
+}
+
+/// Every route, with the middleware that needs the state. CORS and request
+/// tracing go on top in `main`.
+fn app(state: AppState) -> Router {
+    let frontend_url = state.frontend_url.clone();
+    Router::new()
         .route(
             "/",
-            get({
-                let frontend_url = config.frontend_url.clone();
-                move || async move { Redirect::to(&format!("{frontend_url}/dashboard")) }
-            }),
+            get(move || async move { Redirect::to(&format!("{frontend_url}/dashboard")) }),
         )
         .route("/auth/login", axum::routing::post(auth::login_handler))
         .route(
@@ -115,18 +327,51 @@ async fn main() {
             axum::routing::post(auth::register_handler),
         )
         .route("/logout", get(auth::logout_handler))
+        .route(
+            "/auth/link/complete",
+            axum::routing::get(auth::link_complete_handler),
+        )
+        .route(
+            "/auth/link/{provider}",
+            axum::routing::post(auth::link_start_handler),
+        )
+        .route(
+            "/auth/logout-all",
+            axum::routing::post(auth::logout_all_handler),
+        )
+        .route("/metrics", get(telemetry::metrics_handler))
         .merge(api::public_router())
         .nest("/api", api::api_router())
-        .merge(authkestra.axum_router())
+        .merge(state.authkestra.axum_router())
+        .route_layer(axum::middleware::from_fn(telemetry::track_http))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth::renew_session,
+        ))
         .layer(CookieManagerLayer::new())
-        .layer(cors)
-        .layer(TraceLayer::new_for_http())
-        .with_state(state);
+        .with_state(state)
+}
 
-    let listener = tokio::net::TcpListener::bind(&config.bind_address)
-        .await
-        .expect("failed to bind TCP listener");
-    tracing::info!("Server starting on {}", config.base_url);
+/// Resolves on ctrl-c, or SIGTERM on Unix (what orchestrators send on deploy).
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for ctrl-c");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    axum::serve(listener, app).await.unwrap();
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
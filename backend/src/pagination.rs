@@ -0,0 +1,196 @@
+//! Cursor pagination shared by list endpoints.
+//!
+//! Lists are ordered by a `(timestamp, id)` key, newest first except where
+//! an endpoint says otherwise (the moderation queue runs oldest first). A
+//! page's `next_cursor` packs that key of its last item into an opaque
+//! base64url string; clients pass it back as `?cursor=` for the next page
+//! and never look inside. A cursor carries a version byte and a short checksum,
+//! so one that was truncated, edited or made up is a clean 400, not a 500
+//! or a silently wrong page.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::types::time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+
+/// Largest `limit` a client may ask for.
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// Bumped whenever the cursor layout changes, so old cursors fail cleanly.
+const CURSOR_VERSION: u8 = 1;
+
+/// Version, nanosecond timestamp (i128), id.
+const PAYLOAD_LEN: usize = 1 + 16 + 16;
+
+/// Bytes of SHA-256 appended to the payload.
+const CHECKSUM_LEN: usize = 4;
+
+/// Where a page ends: the sort key of its last item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub at: OffsetDateTime,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(PAYLOAD_LEN + CHECKSUM_LEN);
+        bytes.push(CURSOR_VERSION);
+        bytes.extend_from_slice(&self.at.unix_timestamp_nanos().to_be_bytes());
+        bytes.extend_from_slice(self.id.as_bytes());
+        let checksum = Sha256::digest(&bytes);
+        bytes.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// None for anything `encode` didn't produce.
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        if bytes.len() != PAYLOAD_LEN + CHECKSUM_LEN || bytes[0] != CURSOR_VERSION {
+            return None;
+        }
+        let (payload, checksum) = bytes.split_at(PAYLOAD_LEN);
+        if Sha256::digest(payload)[..CHECKSUM_LEN] != *checksum {
+            return None;
+        }
+        let nanos = i128::from_be_bytes(payload[1..17].try_into().ok()?);
+        Some(Self {
+            at: OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()?,
+            id: Uuid::from_slice(&payload[17..]).ok()?,
+        })
+    }
+}
+
+/// `?limit=&cursor=` on a paged endpoint.
+#[derive(Deserialize, Debug)]
+pub struct PageQuery {
+    #[serde(default = "default_page_size")]
+    limit: i64,
+    /// `next_cursor` from the previous page; absent for the first
+    cursor: Option<String>,
+}
+
+fn default_page_size() -> i64 {
+    50
+}
+
+impl PageQuery {
+    /// The requested page size, clamped to `1..=MAX_PAGE_SIZE`.
+    pub fn limit(&self) -> i64 {
+        self.limit.clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// The decoded cursor; 400 if one was given but isn't valid.
+    pub fn cursor(&self) -> Result<Option<Cursor>, ApiError> {
+        self.cursor
+            .as_deref()
+            .map(|cursor| {
+                Cursor::decode(cursor).ok_or_else(|| ApiError::Validation("Invalid cursor".into()))
+            })
+            .transpose()
+    }
+}
+
+/// One page of a list. `next_cursor` is null on the last page.
+#[derive(Debug, Serialize)]
+pub struct PaginatedResponse<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> PaginatedResponse<T> {
+    /// Build a page from `rows` fetched with `LIMIT limit`. `key` gives a
+    /// row's sort key, `item` turns it into the response type. A short page
+    /// means there's nothing left, so it gets no cursor.
+    pub fn new<R>(
+        rows: Vec<R>,
+        limit: i64,
+        key: impl Fn(&R) -> (OffsetDateTime, Uuid),
+        item: impl FnMut(R) -> T,
+    ) -> Self {
+        let next_cursor = rows
+            .last()
+            .filter(|_| rows.len() as i64 == limit)
+            .map(|row| {
+                let (at, id) = key(row);
+                Cursor { at, id }.encode()
+            });
+        Self {
+            items: rows.into_iter().map(item).collect(),
+            next_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor() -> Cursor {
+        Cursor {
+            at: OffsetDateTime::from_unix_timestamp_nanos(1_760_000_000_123_456_789).unwrap(),
+            id: Uuid::new_v4(),
+        }
+    }
+
+    /// `cursor`'s bytes after `edit`, re-signed so only the edit is wrong.
+    fn resigned(cursor: &Cursor, edit: impl FnOnce(&mut Vec<u8>)) -> String {
+        let mut bytes = URL_SAFE_NO_PAD.decode(cursor.encode()).unwrap();
+        bytes.truncate(PAYLOAD_LEN);
+        edit(&mut bytes);
+        let checksum = Sha256::digest(&bytes);
+        bytes.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    #[test]
+    fn a_cursor_decodes_to_what_was_encoded() {
+        let cursor = cursor();
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+    }
+
+    #[test]
+    fn a_truncated_cursor_is_rejected() {
+        let encoded = cursor().encode();
+        for len in [0, 1, encoded.len() / 2, encoded.len() - 1] {
+            assert_eq!(Cursor::decode(&encoded[..len]), None, "{len} characters");
+        }
+    }
+
+    #[test]
+    fn a_flipped_byte_is_rejected() {
+        let bytes = URL_SAFE_NO_PAD.decode(cursor().encode()).unwrap();
+        for i in 0..bytes.len() {
+            let mut edited = bytes.clone();
+            edited[i] ^= 0x01;
+            assert_eq!(
+                Cursor::decode(&URL_SAFE_NO_PAD.encode(edited)),
+                None,
+                "byte {i}"
+            );
+        }
+    }
+
+    #[test]
+    fn another_version_is_rejected_even_with_a_valid_checksum() {
+        let cursor = cursor();
+        assert_eq!(
+            Cursor::decode(&resigned(&cursor, |_| {})),
+            Some(cursor),
+            "re-signing alone changes nothing"
+        );
+        let other_version = resigned(&cursor, |bytes| bytes[0] = CURSOR_VERSION + 1);
+        assert_eq!(Cursor::decode(&other_version), None);
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        for garbage in ["garbage", "not base64!", "===="] {
+            assert_eq!(Cursor::decode(garbage), None, "{garbage}");
+        }
+    }
+}
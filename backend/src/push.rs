@@ -0,0 +1,141 @@
+//! Web Push for new messages, when the recipient has no SSE stream open.
+//!
+//! Off unless `VAPID_PUBLIC_KEY` and `VAPID_PRIVATE_KEY` are set. Pushes are
+//! sent from a spawned task, so a slow push service never holds up the
+//! request that caused them. The payload only says which thread and message
+//! are new; the client fetches the content itself.
+
+use serde::Serialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+use uuid::Uuid;
+use web_push::{
+    ContentEncoding, HyperWebPushClient, PartialVapidSignatureBuilder, SubscriptionInfo,
+    VapidSignatureBuilder, WebPushClient, WebPushError, WebPushMessageBuilder, URL_SAFE_NO_PAD,
+};
+
+/// How long a push service should keep trying to deliver a push.
+const PUSH_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// VAPID keys identifying this server to push services.
+#[derive(Clone)]
+pub struct VapidConfig {
+    /// Uncompressed P-256 public key, base64url; browsers subscribe with it
+    pub public_key: String,
+    /// The matching private key, base64url
+    pub private_key: String,
+    /// Contact push services may use, `mailto:` or `https:`
+    pub subject: String,
+}
+
+/// What the service worker receives.
+#[derive(Debug, Serialize)]
+pub struct PushPayload {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub thread_id: Uuid,
+    pub message_id: Uuid,
+}
+
+struct Vapid {
+    public_key: String,
+    subject: String,
+    signer: PartialVapidSignatureBuilder,
+}
+
+pub struct PushNotifier {
+    vapid: Option<Vapid>,
+    client: HyperWebPushClient,
+}
+
+impl PushNotifier {
+    /// Panics if the private key isn't a valid VAPID key, so a bad key fails
+    /// at startup rather than on the first push.
+    pub fn new(config: Option<VapidConfig>) -> Self {
+        let vapid = config.map(|config| {
+            let signer =
+                VapidSignatureBuilder::from_base64_no_sub(&config.private_key, URL_SAFE_NO_PAD)
+                    .unwrap_or_else(|e| panic!("VAPID_PRIVATE_KEY is not a valid key: {e}"));
+            Vapid {
+                public_key: config.public_key,
+                subject: config.subject,
+                signer,
+            }
+        });
+        Self {
+            vapid,
+            client: HyperWebPushClient::new(),
+        }
+    }
+
+    /// The key browsers need to subscribe; None when push is off.
+    pub fn public_key(&self) -> Option<&str> {
+        self.vapid.as_ref().map(|v| v.public_key.as_str())
+    }
+
+    /// Push `payload` to every browser `user_id` subscribed, unless push is
+    /// off or they turned browser notifications off. Returns immediately.
+    pub fn notify(self: &Arc<Self>, pool: &PgPool, user_id: Uuid, payload: PushPayload) {
+        if self.vapid.is_none() {
+            return;
+        }
+        let notifier = self.clone();
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            if let Err(e) = notifier.deliver(&pool, user_id, &payload).await {
+                warn!("Failed to push to user {}: {}", user_id, e);
+            }
+        });
+    }
+
+    async fn deliver(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        payload: &PushPayload,
+    ) -> Result<(), sqlx::Error> {
+        let Some(vapid) = &self.vapid else {
+            return Ok(());
+        };
+        if !crate::db::browser_notifications_enabled(pool, user_id).await? {
+            return Ok(());
+        }
+        let body = serde_json::to_vec(payload).expect("push payloads always serialize");
+
+        for subscription in crate::db::get_push_subscriptions(pool, user_id).await? {
+            let info = SubscriptionInfo::new(
+                &subscription.endpoint,
+                &subscription.p256dh,
+                &subscription.auth,
+            );
+            match self.send(vapid, &info, &body).await {
+                Ok(()) => debug!("Pushed to user {}", user_id),
+                // The browser unsubscribed or the subscription expired
+                Err(WebPushError::EndpointNotValid | WebPushError::EndpointNotFound) => {
+                    debug!("Dropping dead push subscription of user {}", user_id);
+                    crate::db::delete_push_subscription(pool, &subscription.endpoint).await?;
+                }
+                Err(e) => warn!("Push to user {} failed: {}", user_id, e),
+            }
+        }
+        Ok(())
+    }
+
+    async fn send(
+        &self,
+        vapid: &Vapid,
+        info: &SubscriptionInfo,
+        body: &[u8],
+    ) -> Result<(), WebPushError> {
+        let mut signer = vapid.signer.clone().add_sub_info(info);
+        signer.add_claim("sub", vapid.subject.as_str());
+
+        let mut message = WebPushMessageBuilder::new(info);
+        message.set_payload(ContentEncoding::Aes128Gcm, body);
+        message.set_ttl(PUSH_TTL.as_secs() as u32);
+        message.set_vapid_signature(signer.build()?);
+        self.client.send(message.build()?).await
+    }
+}
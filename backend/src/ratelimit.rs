@@ -0,0 +1,57 @@
+//! In-memory fixed-window rate limiting for the password endpoints.
+//!
+//! Counters live in this process only, so each instance enforces the limit
+//! on its own and a restart clears them. That's enough to stop one client
+//! from hammering Argon2 and the users table.
+
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+/// Allows `limit` hits per key in each `window`, counted from the key's
+/// first hit in that window.
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    /// Key -> (window start, hits so far)
+    hits: DashMap<String, (Instant, u32)>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            hits: DashMap::new(),
+        }
+    }
+
+    /// Count a hit for `key`. Returns how long until the key may try again
+    /// if it's over the limit; a rejected hit isn't counted.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut entry = self.hits.entry(key.to_string()).or_insert((now, 0));
+        let (started, count) = &mut *entry;
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+        if *count >= self.limit {
+            return Err(self.window - now.duration_since(*started));
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    /// Forget `key`'s hits, e.g. after it proved itself with a good password.
+    pub fn reset(&self, key: &str) {
+        self.hits.remove(key);
+    }
+
+    /// Drop keys whose window has passed. Run periodically so one-off
+    /// clients don't pile up.
+    pub fn prune(&self) {
+        let now = Instant::now();
+        self.hits
+            .retain(|_, (started, _)| now.duration_since(*started) < self.window);
+    }
+}
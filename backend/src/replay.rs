@@ -0,0 +1,233 @@
+//! Recent SSE events, kept so a client that reconnects can catch up.
+//!
+//! Every replayable event gets an id when it's sent, and the stream sets it
+//! as the SSE `id`. A reconnecting `EventSource` sends the last id it saw as
+//! `Last-Event-ID`, and the stream replays everything newer before going
+//! live. Events for one user are kept per user; events sent to everyone
+//! (new broadcasts) are kept once, globally.
+//!
+//! Each buffer holds at most `limit` events, none older than `max_age`. Ids
+//! are at least the send time in microseconds since the epoch and always
+//! increase, so they keep increasing across restarts and an id tells
+//! roughly when its event was sent. After a `Last-Event-ID` older than
+//! `max_age`, or older than an event a full buffer dropped, some events may
+//! be gone; the stream then also tells the client to refetch.
+//!
+//! Buffers are in memory: each instance only replays what it sent itself,
+//! and a restart empties them.
+
+use dashmap::DashMap;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::state::SseEvent;
+
+#[derive(Default)]
+struct Buffer {
+    events: VecDeque<(u64, SseEvent)>,
+    /// Id of the newest event dropped to stay within the limit
+    dropped_through: u64,
+}
+
+impl Buffer {
+    fn push(&mut self, id: u64, event: SseEvent, limit: usize) {
+        self.events.push_back((id, event));
+        while self.events.len() > limit {
+            if let Some((dropped, _)) = self.events.pop_front() {
+                self.dropped_through = dropped;
+            }
+        }
+    }
+
+    /// Drop events sent before the id `oldest`.
+    fn expire(&mut self, oldest: u64) {
+        while self.events.front().is_some_and(|(id, _)| *id < oldest) {
+            self.events.pop_front();
+        }
+    }
+}
+
+/// What replaying after a `Last-Event-ID` found.
+pub struct Replay {
+    /// Events newer than the id, oldest first
+    pub events: Vec<(u64, SseEvent)>,
+    /// Whether some events newer than the id are no longer kept
+    pub incomplete: bool,
+}
+
+pub struct EventLog {
+    /// Events kept per buffer; 0 turns replay off
+    limit: usize,
+    max_age: Duration,
+    last_id: AtomicU64,
+    users: DashMap<Uuid, Buffer>,
+    global: Mutex<Buffer>,
+}
+
+impl EventLog {
+    pub fn new(limit: usize, max_age: Duration) -> Self {
+        Self {
+            limit,
+            max_age,
+            last_id: AtomicU64::new(0),
+            users: DashMap::new(),
+            global: Mutex::default(),
+        }
+    }
+
+    /// A fresh id, greater than every one handed out before.
+    fn next_id(&self) -> u64 {
+        let now = now_micros();
+        let previous = self
+            .last_id
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+                Some(now.max(last + 1))
+            })
+            .expect("the update always returns Some");
+        now.max(previous + 1)
+    }
+
+    /// Id and keep an event sent to `user_id`. None for events that make no
+    /// sense later (see [`SseEvent::is_replayable`]) or when replay is off.
+    pub fn record_for(&self, user_id: Uuid, event: &SseEvent) -> Option<u64> {
+        if self.limit == 0 || !event.is_replayable() {
+            return None;
+        }
+        let id = self.next_id();
+        self.users
+            .entry(user_id)
+            .or_default()
+            .push(id, event.clone(), self.limit);
+        Some(id)
+    }
+
+    /// Id and keep an event sent to every user.
+    pub fn record_global(&self, event: &SseEvent) -> Option<u64> {
+        if self.limit == 0 || !event.is_replayable() {
+            return None;
+        }
+        let id = self.next_id();
+        self.global
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(id, event.clone(), self.limit);
+        Some(id)
+    }
+
+    /// Everything `user_id` was sent after the event with id `after`.
+    pub fn replay(&self, user_id: Uuid, after: u64) -> Replay {
+        let oldest = self.oldest_kept_id();
+        let mut incomplete = after < oldest;
+        let mut events = Vec::new();
+
+        let mut collect = |buffer: &Buffer| {
+            incomplete |= after < buffer.dropped_through;
+            events.extend(
+                buffer
+                    .events
+                    .iter()
+                    .filter(|(id, _)| *id > after && *id >= oldest)
+                    .cloned(),
+            );
+        };
+        if let Some(buffer) = self.users.get(&user_id) {
+            collect(&buffer);
+        }
+        collect(&self.global.lock().unwrap_or_else(|e| e.into_inner()));
+
+        events.sort_by_key(|(id, _)| *id);
+        Replay { events, incomplete }
+    }
+
+    /// Drop events past `max_age`, and the buffers of users left with none.
+    /// Run periodically so users who never reconnect don't pile up.
+    pub fn prune(&self) {
+        let oldest = self.oldest_kept_id();
+        self.users.retain(|_, buffer| {
+            buffer.expire(oldest);
+            !buffer.events.is_empty()
+        });
+        self.global
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .expire(oldest);
+    }
+
+    /// Ids below this belong to events past `max_age`.
+    fn oldest_kept_id(&self) -> u64 {
+        now_micros().saturating_sub(self.max_age.as_micros() as u64)
+    }
+}
+
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> SseEvent {
+        SseEvent::MessageUnsent {
+            thread_id: Uuid::new_v4(),
+            message_id: Uuid::new_v4(),
+        }
+    }
+
+    fn ids(replay: &Replay) -> Vec<u64> {
+        replay.events.iter().map(|(id, _)| *id).collect()
+    }
+
+    #[test]
+    fn a_replay_has_the_users_and_global_events_after_the_id() {
+        let log = EventLog::new(10, Duration::from_secs(60));
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let first = log.record_for(alice, &event()).unwrap();
+        let second = log.record_for(alice, &event()).unwrap();
+        log.record_for(bob, &event()).unwrap();
+        let global = log.record_global(&event()).unwrap();
+        let third = log.record_for(alice, &event()).unwrap();
+
+        let replay = log.replay(alice, first);
+        assert_eq!(ids(&replay), vec![second, global, third]);
+        assert!(!replay.incomplete);
+        assert!(log.replay(alice, third).events.is_empty());
+    }
+
+    #[test]
+    fn events_dropped_past_the_limit_make_a_replay_incomplete() {
+        let log = EventLog::new(2, Duration::from_secs(60));
+        let alice = Uuid::new_v4();
+        let sent: Vec<u64> = (0..4)
+            .map(|_| log.record_for(alice, &event()).unwrap())
+            .collect();
+
+        // The second event was dropped, so a client that saw only the first missed it
+        let replay = log.replay(alice, sent[0]);
+        assert_eq!(ids(&replay), sent[2..]);
+        assert!(replay.incomplete);
+        // One that saw the second missed nothing that's gone
+        let replay = log.replay(alice, sent[1]);
+        assert_eq!(ids(&replay), sent[2..]);
+        assert!(!replay.incomplete);
+    }
+
+    #[test]
+    fn prune_drops_buffers_left_with_only_expired_events() {
+        let log = EventLog::new(10, Duration::from_millis(50));
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        log.record_for(alice, &event()).unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        let kept = log.record_for(bob, &event()).unwrap();
+
+        log.prune();
+        assert!(!log.users.contains_key(&alice));
+        assert_eq!(ids(&log.replay(bob, 0)), vec![kept]);
+    }
+}
@@ -0,0 +1,70 @@
+//! Retention: hard-delete messages and broadcasts past a configured age.
+//!
+//! Unlike soft deletion this removes the rows, along with everything that
+//! hangs off them. `MESSAGE_RETENTION_DAYS` and `BROADCAST_RETENTION_DAYS`
+//! are independent; 0 keeps that kind forever.
+//!
+//! Rows go in batches of [`BATCH_SIZE`], each its own statement, so a large
+//! backlog never holds locks for long and concurrent writes keep flowing
+//! between batches. A run ends when a batch comes back short.
+
+use sqlx::PgPool;
+use std::future::Future;
+use std::time::Duration;
+use tracing::info;
+
+/// Rows deleted per statement.
+pub const BATCH_SIZE: i64 = 1000;
+
+/// Pause between batches, so a backlog is worked off without saturating the
+/// database.
+const BATCH_PAUSE: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    /// Days messages are kept; 0 keeps them forever
+    pub message_days: i32,
+    /// Days broadcasts are kept; 0 keeps them forever
+    pub broadcast_days: i32,
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.message_days > 0 || self.broadcast_days > 0
+    }
+}
+
+/// Job body: purge everything past its retention window.
+pub async fn purge(pool: &PgPool, policy: RetentionPolicy) -> Result<(), sqlx::Error> {
+    if policy.message_days > 0 {
+        let days = policy.message_days;
+        let purged =
+            purge_in_batches(|| crate::db::purge_old_messages(pool, days, BATCH_SIZE)).await?;
+        info!("Purged {} messages older than {} days", purged, days);
+    }
+    if policy.broadcast_days > 0 {
+        let days = policy.broadcast_days;
+        let purged =
+            purge_in_batches(|| crate::db::purge_old_broadcasts(pool, days, BATCH_SIZE)).await?;
+        info!("Purged {} broadcasts older than {} days", purged, days);
+    }
+    Ok(())
+}
+
+/// Run `batch` until it deletes fewer than `BATCH_SIZE` rows. Returns the
+/// total deleted.
+async fn purge_in_batches<F, Fut>(batch: F) -> Result<u64, sqlx::Error>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<u64, sqlx::Error>>,
+{
+    let mut total = 0;
+    loop {
+        let deleted = batch().await?;
+        total += deleted;
+        if deleted < BATCH_SIZE as u64 {
+            return Ok(total);
+        }
+        tokio::time::sleep(BATCH_PAUSE).await;
+    }
+}
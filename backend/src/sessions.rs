@@ -0,0 +1,99 @@
+//! Session store wrapper that can find every session of one identity.
+//!
+//! Authkestra's `SessionStore` only works with single session ids, so
+//! "log out of all devices" needs an index from identity to session ids on
+//! the side. The index is kept in this process alongside whatever store it
+//! wraps: that's complete for the in-memory store, but with several instances
+//! sharing a persistent store each one only knows the sessions it created or
+//! touched, and the index starts empty after a restart. Revoking across
+//! instances needs a store that is indexed by identity itself, like a
+//! Postgres session table with an `external_id` column.
+
+use async_trait::async_trait;
+use authkestra::session::{AuthError, Session, SessionStore};
+use dashmap::DashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// `(provider_id, external_id)` of a session's identity.
+type IdentityKey = (String, String);
+
+pub struct IndexedSessionStore {
+    inner: Arc<dyn SessionStore>,
+    by_identity: DashMap<IdentityKey, HashSet<String>>,
+    /// Reverse of `by_identity`, so a delete by id can find its entry
+    identity_of: DashMap<String, IdentityKey>,
+}
+
+impl IndexedSessionStore {
+    pub fn new(inner: Arc<dyn SessionStore>) -> Self {
+        Self {
+            inner,
+            by_identity: DashMap::new(),
+            identity_of: DashMap::new(),
+        }
+    }
+
+    /// Delete every session of the identity. Returns how many were still
+    /// live; already-expired ones are cleaned up but not counted.
+    pub async fn delete_identity_sessions(
+        &self,
+        provider_id: &str,
+        external_id: &str,
+    ) -> Result<usize, AuthError> {
+        let key = (provider_id.to_string(), external_id.to_string());
+        let Some((_, ids)) = self.by_identity.remove(&key) else {
+            return Ok(0);
+        };
+
+        let now = chrono::Utc::now();
+        let mut live = 0;
+        for id in ids {
+            self.identity_of.remove(&id);
+            if let Some(session) = self.inner.load_session(&id).await? {
+                if session.expires_at > now {
+                    live += 1;
+                }
+            }
+            self.inner.delete_session(&id).await?;
+        }
+        Ok(live)
+    }
+
+    fn forget(&self, id: &str) {
+        let Some((_, key)) = self.identity_of.remove(id) else {
+            return;
+        };
+        self.by_identity.remove_if_mut(&key, |_, ids| {
+            ids.remove(id);
+            ids.is_empty()
+        });
+    }
+}
+
+#[async_trait]
+impl SessionStore for IndexedSessionStore {
+    async fn load_session(&self, id: &str) -> Result<Option<Session>, AuthError> {
+        self.inner.load_session(id).await
+    }
+
+    async fn save_session(&self, session: &Session) -> Result<(), AuthError> {
+        self.inner.save_session(session).await?;
+        let key = (
+            session.identity.provider_id.clone(),
+            session.identity.external_id.clone(),
+        );
+        self.identity_of.insert(session.id.clone(), key.clone());
+        self.by_identity
+            .entry(key)
+            .or_default()
+            .insert(session.id.clone());
+        Ok(())
+    }
+
+    async fn delete_session(&self, id: &str) -> Result<(), AuthError> {
+        self.inner.delete_session(id).await?;
+        self.forget(id);
+        Ok(())
+    }
+}
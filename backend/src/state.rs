@@ -2,37 +2,212 @@ use authkestra::axum::AuthkestraAxumError;
 use authkestra::flow::{Authkestra, Configured, Missing, SessionStoreState};
 use authkestra::session::{SessionConfig, SessionStore};
 use axum::extract::FromRef;
-use sqlx::PgPool;
-use std::collections::HashMap;
+use dashmap::DashMap;
+use metrics_exporter_prometheus::PrometheusHandle;
+use serde::Serialize;
+use sqlx::{types::time::OffsetDateTime, PgPool};
 use std::sync::Arc;
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
+use crate::config::ApiConfig;
+use crate::integrity::LatestReport;
+use crate::push::PushNotifier;
+use crate::ratelimit::RateLimiter;
+use crate::replay::EventLog;
+use crate::sessions::IndexedSessionStore;
+use crate::webhooks::WebhookDispatcher;
+
 /// Concrete Authkestra type: session store configured, no token manager.
 pub type AuthkestraInstance = Authkestra<Configured<Arc<dyn SessionStore>>, Missing>;
 
-/// SSE event payload sent to connected clients.
-#[derive(Debug, Clone)]
-pub struct SseEvent {
-    /// Event type: "new_message" or "new_broadcast"
-    pub event_type: String,
-    /// JSON payload string
-    pub data: String,
+/// SSE event sent to connected clients. Each variant's fields are its JSON
+/// `data`; the SSE `event` name comes from [`SseEvent::event_type`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum SseEvent {
+    NewMessage {
+        thread_id: Uuid,
+        message_id: Uuid,
+        content: String,
+        /// The recipient's unread total including this message, for the badge
+        #[serde(skip_serializing_if = "Option::is_none")]
+        unread_total: Option<i64>,
+    },
+    NewBroadcast {
+        broadcast_id: Uuid,
+    },
+    NewComment {
+        broadcast_id: Uuid,
+        comment_id: Uuid,
+    },
+    Typing {
+        thread_id: Uuid,
+        user_id: Uuid,
+        username: String,
+    },
+    TypingStopped {
+        thread_id: Uuid,
+        user_id: Uuid,
+    },
+    /// Tells a sender their messages were read
+    ReadReceipt {
+        thread_id: Uuid,
+        message_ids: Vec<Uuid>,
+        #[serde(with = "time::serde::rfc3339")]
+        read_at: OffsetDateTime,
+    },
+    /// An ephemeral or timed message disappeared; drop it from the thread
+    MessageExpired {
+        thread_id: Uuid,
+        message_id: Uuid,
+    },
+    /// The sender took a message back right after sending it; drop it
+    MessageUnsent {
+        thread_id: Uuid,
+        message_id: Uuid,
+    },
+    /// The other participant pinned or unpinned a message in a shared thread
+    MessagePinned {
+        thread_id: Uuid,
+        message_id: Uuid,
+        pinned: bool,
+    },
+    /// Someone who knows the user came online or went offline
+    Presence {
+        user_id: Uuid,
+        online: bool,
+        #[serde(with = "time::serde::rfc3339::option")]
+        last_seen: Option<OffsetDateTime>,
+    },
+    /// The user's own preferences changed, possibly on another device
+    PreferencesUpdated(crate::api::PreferencesResponse),
+    /// The stream fell behind and dropped `missed` events (0 when unknown,
+    /// after a reconnect too late to replay everything); refetch state
+    Resync {
+        missed: u64,
+    },
+    /// Last event before the server stops; reconnect after a moment
+    ServerShuttingDown {},
+}
+
+impl SseEvent {
+    /// Every `event` name [`SseEvent::event_type`] can return.
+    pub const EVENT_TYPES: &'static [&'static str] = &[
+        "new_message",
+        "new_broadcast",
+        "new_comment",
+        "typing",
+        "typing_stopped",
+        "read_receipt",
+        "message_expired",
+        "message_unsent",
+        "message_pinned",
+        "presence",
+        "preferences_updated",
+        "resync",
+        "server_shutting_down",
+    ];
+
+    /// The SSE `event` name clients listen for.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            SseEvent::NewMessage { .. } => "new_message",
+            SseEvent::NewBroadcast { .. } => "new_broadcast",
+            SseEvent::NewComment { .. } => "new_comment",
+            SseEvent::Typing { .. } => "typing",
+            SseEvent::TypingStopped { .. } => "typing_stopped",
+            SseEvent::ReadReceipt { .. } => "read_receipt",
+            SseEvent::MessageExpired { .. } => "message_expired",
+            SseEvent::MessageUnsent { .. } => "message_unsent",
+            SseEvent::MessagePinned { .. } => "message_pinned",
+            SseEvent::Presence { .. } => "presence",
+            SseEvent::PreferencesUpdated(_) => "preferences_updated",
+            SseEvent::Resync { .. } => "resync",
+            SseEvent::ServerShuttingDown {} => "server_shutting_down",
+        }
+    }
+
+    /// Whether every stream gets this event, whatever it filtered on: it's
+    /// about the stream itself rather than anything in the app.
+    pub fn is_control(&self) -> bool {
+        matches!(
+            self,
+            SseEvent::Resync { .. } | SseEvent::ServerShuttingDown {}
+        )
+    }
+
+    /// Whether a reconnecting client should still get this event. Typing is
+    /// stale within seconds, and the stream makes the rest up itself.
+    pub fn is_replayable(&self) -> bool {
+        !matches!(
+            self,
+            SseEvent::Typing { .. } | SseEvent::Resync { .. } | SseEvent::ServerShuttingDown {}
+        )
+    }
+
+    /// The `(event, data)` pair for an SSE frame.
+    pub fn to_parts(&self) -> (&'static str, String) {
+        let data = serde_json::to_string(self).expect("SSE payloads always serialize");
+        (self.event_type(), data)
+    }
 }
 
-/// Per-user notification hub. Maps user UUID → broadcast sender.
-/// Each connected user has a channel; when they connect a receiver is created.
-pub type NotificationHub = Arc<Mutex<HashMap<Uuid, broadcast::Sender<SseEvent>>>>;
+/// An event as it travels to streams: with its replay id, if it was kept.
+pub type StampedEvent = (Option<u64>, SseEvent);
+
+/// Per-user notification hub. `channels` maps user UUID → broadcast sender.
+/// Each connected user has a channel; when they connect a receiver is created,
+/// and the channel is removed once their last stream closes.
+/// The map is sharded, so sends to different users don't contend on one lock.
+/// Don't hold an entry across an `.await`: it keeps its shard locked.
+pub struct Hub {
+    pub channels: DashMap<Uuid, broadcast::Sender<StampedEvent>>,
+    /// Recent events, for streams that reconnect with `Last-Event-ID`
+    pub replay: EventLog,
+}
+
+impl Hub {
+    pub fn new(replay: EventLog) -> Self {
+        Self {
+            channels: DashMap::new(),
+            replay,
+        }
+    }
+}
+
+pub type NotificationHub = Arc<Hub>;
+
+/// Rate limiter for `POST /api/anon/{token}`. A newtype so handlers can
+/// extract it apart from the auth limiter.
+#[derive(Clone)]
+pub struct AnonMessageLimiter(pub Arc<RateLimiter>);
 
 /// Application state with a concrete Authkestra type.
 #[derive(Clone)]
 pub struct AppState {
     pub authkestra: AuthkestraInstance,
+    /// The same store Authkestra uses, for revoking all of a user's sessions
+    pub sessions: Arc<IndexedSessionStore>,
     pub db_pool: Arc<PgPool>,
     /// SSE notification hub for real-time push
     pub notification_hub: NotificationHub,
     /// Frontend URL for redirects
     pub frontend_url: String,
+    /// Handler-level settings
+    pub api_config: Arc<ApiConfig>,
+    /// Attempts per IP and per username on login and registration
+    pub auth_limiter: Arc<RateLimiter>,
+    /// Messages per IP and per link through anonymous inbox links
+    pub anon_limiter: AnonMessageLimiter,
+    /// Renders the Prometheus scrape for `/metrics`
+    pub metrics: PrometheusHandle,
+    /// Last data integrity report, for `/api/admin/integrity`
+    pub integrity: LatestReport,
+    /// Web Push for recipients without an SSE stream
+    pub push: Arc<PushNotifier>,
+    /// Queue for outbound webhook deliveries
+    pub webhooks: WebhookDispatcher,
 }
 
 // Implement FromRef for Authkestra (required for axum_router and AuthSession)
@@ -69,3 +244,158 @@ impl FromRef<AppState> for NotificationHub {
         state.notification_hub.clone()
     }
 }
+
+// Implement FromRef for the metrics handle
+impl FromRef<AppState> for PrometheusHandle {
+    fn from_ref(state: &AppState) -> Self {
+        state.metrics.clone()
+    }
+}
+
+// Implement FromRef for handler settings
+impl FromRef<AppState> for Arc<ApiConfig> {
+    fn from_ref(state: &AppState) -> Self {
+        state.api_config.clone()
+    }
+}
+
+// Implement FromRef for the latest integrity report
+impl FromRef<AppState> for LatestReport {
+    fn from_ref(state: &AppState) -> Self {
+        state.integrity.clone()
+    }
+}
+
+// Implement FromRef for Web Push
+impl FromRef<AppState> for Arc<PushNotifier> {
+    fn from_ref(state: &AppState) -> Self {
+        state.push.clone()
+    }
+}
+
+// Implement FromRef for outbound webhooks
+impl FromRef<AppState> for WebhookDispatcher {
+    fn from_ref(state: &AppState) -> Self {
+        state.webhooks.clone()
+    }
+}
+
+// Implement FromRef for the indexed session store
+impl FromRef<AppState> for Arc<IndexedSessionStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.sessions.clone()
+    }
+}
+
+// Implement FromRef for the anonymous inbox link limiter
+impl FromRef<AppState> for AnonMessageLimiter {
+    fn from_ref(state: &AppState) -> Self {
+        state.anon_limiter.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One event of every variant. The match fails to compile when a
+    /// variant is added, until it's listed here too.
+    fn one_of_each() -> Vec<SseEvent> {
+        let id = Uuid::nil();
+        let events = vec![
+            SseEvent::NewMessage {
+                thread_id: id,
+                message_id: id,
+                content: String::new(),
+                unread_total: None,
+            },
+            SseEvent::NewBroadcast { broadcast_id: id },
+            SseEvent::NewComment {
+                broadcast_id: id,
+                comment_id: id,
+            },
+            SseEvent::Typing {
+                thread_id: id,
+                user_id: id,
+                username: String::new(),
+            },
+            SseEvent::TypingStopped {
+                thread_id: id,
+                user_id: id,
+            },
+            SseEvent::ReadReceipt {
+                thread_id: id,
+                message_ids: Vec::new(),
+                read_at: OffsetDateTime::UNIX_EPOCH,
+            },
+            SseEvent::MessageExpired {
+                thread_id: id,
+                message_id: id,
+            },
+            SseEvent::MessageUnsent {
+                thread_id: id,
+                message_id: id,
+            },
+            SseEvent::MessagePinned {
+                thread_id: id,
+                message_id: id,
+                pinned: true,
+            },
+            SseEvent::Presence {
+                user_id: id,
+                online: true,
+                last_seen: None,
+            },
+            SseEvent::PreferencesUpdated(
+                crate::db::UserPreferences {
+                    user_id: id,
+                    theme: String::new(),
+                    notification_sound: true,
+                    browser_notifications: true,
+                    show_read_receipts: true,
+                    show_typing_indicators: true,
+                    public_profile: true,
+                    share_presence: true,
+                    version: 1,
+                }
+                .into(),
+            ),
+            SseEvent::Resync { missed: 0 },
+            SseEvent::ServerShuttingDown {},
+        ];
+        for event in &events {
+            match event {
+                SseEvent::NewMessage { .. }
+                | SseEvent::NewBroadcast { .. }
+                | SseEvent::NewComment { .. }
+                | SseEvent::Typing { .. }
+                | SseEvent::TypingStopped { .. }
+                | SseEvent::ReadReceipt { .. }
+                | SseEvent::MessageExpired { .. }
+                | SseEvent::MessageUnsent { .. }
+                | SseEvent::MessagePinned { .. }
+                | SseEvent::Presence { .. }
+                | SseEvent::PreferencesUpdated(_)
+                | SseEvent::Resync { .. }
+                | SseEvent::ServerShuttingDown {} => {}
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn event_types_lists_every_variants_event_type() {
+        let mut types: Vec<_> = one_of_each().iter().map(SseEvent::event_type).collect();
+        for event_type in &types {
+            assert!(
+                SseEvent::EVENT_TYPES.contains(event_type),
+                "{event_type} is missing from EVENT_TYPES"
+            );
+        }
+        types.sort();
+        types.dedup();
+        let mut listed = SseEvent::EVENT_TYPES.to_vec();
+        listed.sort();
+        assert_eq!(types, listed, "EVENT_TYPES lists names no variant has");
+    }
+}
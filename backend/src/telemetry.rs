@@ -0,0 +1,88 @@
+//! Prometheus metrics, served at `/metrics`.
+//!
+//! `/metrics` needs no auth, so keep it off the public internet (firewall it or
+//! only expose it to the scraper's network).
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::warn;
+
+pub const MESSAGES_SENT: &str = "anonyma_messages_sent_total";
+pub const BROADCASTS_CREATED: &str = "anonyma_broadcasts_created_total";
+pub const SSE_CONNECTIONS_ACTIVE: &str = "anonyma_sse_connections_active";
+pub const HTTP_REQUEST_DURATION: &str = "anonyma_http_request_duration_seconds";
+pub const INTEGRITY_ANOMALIES: &str = "anonyma_integrity_anomalies";
+pub const WEBHOOK_DELIVERIES: &str = "anonyma_webhook_deliveries_total";
+pub const DB_POOL_CONNECTIONS: &str = "anonyma_db_pool_connections";
+pub const TABLE_ROWS_ESTIMATE: &str = "anonyma_table_rows_estimate";
+
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Install the global recorder. Call once at startup.
+pub fn install() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(HTTP_REQUEST_DURATION.to_string()),
+            LATENCY_BUCKETS,
+        )
+        .expect("latency buckets are not empty")
+        .install_recorder()
+        .expect("metrics recorder is installed once")
+}
+
+/// Time each request that matched a route. The label is the route template
+/// (`/api/messages/{id}/reply`), not the raw path, so ids don't multiply series.
+pub async fn track_http(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let method = request.method().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    if let Some(route) = route {
+        metrics::histogram!(
+            HTTP_REQUEST_DURATION,
+            "method" => method,
+            "route" => route,
+            "status" => response.status().as_u16().to_string(),
+        )
+        .record(started.elapsed().as_secs_f64());
+    }
+    response
+}
+
+/// Render every metric, after sampling the pool and table gauges.
+pub async fn metrics_handler(
+    State(handle): State<PrometheusHandle>,
+    State(pool): State<Arc<PgPool>>,
+) -> String {
+    let stats = crate::db::pool_stats(&pool);
+    metrics::gauge!(DB_POOL_CONNECTIONS, "state" => "idle").set(stats.idle as f64);
+    metrics::gauge!(DB_POOL_CONNECTIONS, "state" => "in_use").set(stats.in_use as f64);
+
+    match crate::db::table_counts(&pool).await {
+        Ok(counts) => {
+            for (table, rows) in [
+                ("users", counts.users),
+                ("messages", counts.messages),
+                ("broadcasts", counts.broadcasts),
+            ] {
+                metrics::gauge!(TABLE_ROWS_ESTIMATE, "table" => table).set(rows as f64);
+            }
+        }
+        // Keep serving the other metrics; the gauges keep their last values
+        Err(e) => warn!("Failed to fetch table counts for metrics: {}", e),
+    }
+
+    handle.render()
+}
@@ -0,0 +1,310 @@
+//! The app as `main` wires it, over a test database, for tests that go
+//! through HTTP. Users sign up through `/auth/register` and send their
+//! session cookie like a browser would.
+
+use authkestra::flow::Authkestra;
+use authkestra::session::memory::MemoryStore;
+use authkestra::session::SessionConfig;
+use axum::body::{Body, BodyDataStream};
+use axum::extract::ConnectInfo;
+use axum::http::{header, HeaderMap, Method, Request, StatusCode};
+use axum::Router;
+use futures_util::StreamExt;
+use metrics_exporter_prometheus::PrometheusBuilder;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::Service;
+use uuid::Uuid;
+
+use crate::config::{ApiConfig, PasswordConfig};
+use crate::ratelimit::RateLimiter;
+use crate::replay::EventLog;
+use crate::sessions::IndexedSessionStore;
+use crate::state::{AnonMessageLimiter, AppState, Hub};
+use crate::webhooks::WebhookDispatcher;
+
+/// `ApiConfig` with `Config::init`'s defaults, except for cheap password
+/// hashing so signing up doesn't dominate test time.
+pub fn api_config() -> ApiConfig {
+    ApiConfig {
+        broadcast_history_public: false,
+        max_content_length: 4000,
+        sse_max_connections_per_user: 5,
+        sse_keepalive: Duration::from_secs(15),
+        abuse_fingerprint_key: None,
+        trust_forwarded_for: false,
+        login_max_failures: 5,
+        login_lockout: Duration::from_secs(15 * 60),
+        daily_send_quota: 0,
+        send_quota_exempt: Vec::new(),
+        password: PasswordConfig {
+            memory_kib: 8,
+            iterations: 1,
+            parallelism: 1,
+        },
+        admin_users: Vec::new(),
+        ephemeral_read_ttl: Duration::from_secs(30),
+        edit_window: Some(Duration::from_secs(15 * 60)),
+        unsend_window: Duration::from_secs(30),
+    }
+}
+
+pub struct TestApp {
+    pub state: AppState,
+    router: Router,
+}
+
+/// A signed-up user and the cookie that authenticates them.
+pub struct TestUser {
+    pub id: Uuid,
+    pub cookie: String,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl TestResponse {
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or_else(|e| {
+            panic!(
+                "{} is not JSON ({e}): {}",
+                self.status,
+                String::from_utf8_lossy(&self.body)
+            )
+        })
+    }
+}
+
+impl TestApp {
+    pub fn new(pool: PgPool) -> Self {
+        Self::with_config(pool, api_config())
+    }
+
+    pub fn with_config(pool: PgPool, api_config: ApiConfig) -> Self {
+        let auth_limiter = RateLimiter::new(1000, Duration::from_secs(60));
+        Self::with_auth_limiter(pool, api_config, auth_limiter)
+    }
+
+    /// `with_config`, with `auth_limiter` guarding the password endpoints.
+    pub fn with_auth_limiter(
+        pool: PgPool,
+        api_config: ApiConfig,
+        auth_limiter: RateLimiter,
+    ) -> Self {
+        let sessions = Arc::new(IndexedSessionStore::new(Arc::new(MemoryStore::default())));
+        let authkestra = Authkestra::builder()
+            .session_store(sessions.clone())
+            .session_config(SessionConfig {
+                secure: false,
+                ..SessionConfig::default()
+            })
+            .build();
+        let db_pool = Arc::new(pool);
+        let state = AppState {
+            authkestra,
+            sessions,
+            db_pool: db_pool.clone(),
+            notification_hub: Arc::new(Hub::new(EventLog::new(100, Duration::from_secs(300)))),
+            frontend_url: "http://localhost:5173".into(),
+            api_config: Arc::new(api_config),
+            auth_limiter: Arc::new(auth_limiter),
+            anon_limiter: AnonMessageLimiter(Arc::new(RateLimiter::new(
+                1000,
+                Duration::from_secs(60),
+            ))),
+            metrics: PrometheusBuilder::new().build_recorder().handle(),
+            integrity: Arc::default(),
+            push: Arc::new(crate::push::PushNotifier::new(None)),
+            webhooks: WebhookDispatcher::start(db_pool),
+        };
+        Self {
+            router: crate::app(state.clone()),
+            state,
+        }
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.state.db_pool
+    }
+
+    /// Sign up `username` with a password and keep their session cookie.
+    pub async fn user(&self, username: &str) -> TestUser {
+        let response = self
+            .request(
+                Method::POST,
+                "/auth/register",
+                None,
+                Some(serde_json::json!({ "username": username, "password": "password" })),
+            )
+            .await;
+        assert_eq!(
+            response.status,
+            StatusCode::CREATED,
+            "signing up {username}"
+        );
+        let id = response.json()["user"]["id"]
+            .as_str()
+            .and_then(|id| id.parse().ok())
+            .expect("sign-up returns the user id");
+        let cookie = session_cookie(&response.headers).expect("sign-up sets a session cookie");
+        TestUser { id, cookie }
+    }
+
+    pub async fn get(&self, path: &str, user: &TestUser) -> TestResponse {
+        self.request(Method::GET, path, Some(user), None).await
+    }
+
+    pub async fn post(&self, path: &str, user: &TestUser, body: Value) -> TestResponse {
+        self.request(Method::POST, path, Some(user), Some(body))
+            .await
+    }
+
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        user: Option<&TestUser>,
+        body: Option<Value>,
+    ) -> TestResponse {
+        self.send(method, path, user, &[], body).await
+    }
+
+    /// Open `/api/events` (plus `query`) as `user`. The stream stays open
+    /// until the returned value is dropped.
+    pub async fn events(&self, user: &TestUser, query: &str) -> (StatusCode, EventStream) {
+        self.events_with(user, query, &[]).await
+    }
+
+    /// `events` with extra headers.
+    pub async fn events_with(
+        &self,
+        user: &TestUser,
+        query: &str,
+        headers: &[(header::HeaderName, &str)],
+    ) -> (StatusCode, EventStream) {
+        let mut request = Request::builder()
+            .uri(format!("/api/events{query}"))
+            .header(header::COOKIE, &user.cookie);
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+        let response = self.router.clone().call(request).await.unwrap();
+        let status = response.status();
+        let stream = EventStream {
+            data: response.into_body().into_data_stream(),
+            buffer: String::new(),
+        };
+        (status, stream)
+    }
+
+    /// `request` with extra headers.
+    pub async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        user: Option<&TestUser>,
+        headers: &[(header::HeaderName, &str)],
+        body: Option<Value>,
+    ) -> TestResponse {
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(user) = user {
+            request = request.header(header::COOKIE, &user.cookie);
+        }
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let mut request = request.body(body).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+
+        let response = self.router.clone().call(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        TestResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body: body.to_vec(),
+        }
+    }
+}
+
+/// An open SSE stream, read an event at a time.
+pub struct EventStream {
+    data: BodyDataStream,
+    buffer: String,
+}
+
+impl EventStream {
+    /// The next event's type and data, skipping keep-alive comments. Panics
+    /// if none comes within a second.
+    pub async fn next(&mut self) -> (String, Value) {
+        tokio::time::timeout(Duration::from_secs(1), self.read())
+            .await
+            .expect("an SSE event within a second")
+    }
+
+    /// The next event of type `event_type`, skipping others.
+    pub async fn next_of(&mut self, event_type: &str) -> Value {
+        loop {
+            let (name, data) = self.next().await;
+            if name == event_type {
+                return data;
+            }
+        }
+    }
+
+    async fn read(&mut self) -> (String, Value) {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let frame: String = self.buffer.drain(..end + 2).collect();
+                let field = |name: &str| {
+                    frame
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .map(str::to_string)
+                };
+                if let (Some(event), Some(data)) = (field("event: "), field("data: ")) {
+                    return (event, serde_json::from_str(&data).unwrap());
+                }
+                continue;
+            }
+            let chunk = self
+                .data
+                .next()
+                .await
+                .expect("the SSE stream stays open")
+                .unwrap();
+            self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+}
+
+/// The `name=value` of the session cookie a response sets, if any.
+pub fn session_cookie(headers: &HeaderMap) -> Option<String> {
+    let name = SessionConfig::default().cookie_name;
+    headers
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| value.split(';').next())
+        .find(|pair| pair.starts_with(&format!("{name}=")))
+        .map(str::to_string)
+}
@@ -0,0 +1,217 @@
+//! Outbound webhooks: POST a signed JSON payload to admin-registered URLs
+//! when something happens.
+//!
+//! Handlers hand events to [`WebhookDispatcher::emit`], which only queues
+//! them on a channel. A background task looks up the subscribed webhooks and
+//! delivers to each from its own task, so neither a request nor other hooks
+//! wait on a slow endpoint. Each attempt times out after
+//! [`DELIVERY_TIMEOUT`]; timeouts, connection errors and 5xx answers are
+//! retried with exponential backoff, anything else is final.
+//!
+//! Payloads carry ids only. Message content and senders never leave the
+//! server, and anonymous broadcasts stay anonymous.
+//!
+//! The queue is in memory: events still queued or retrying when the server
+//! stops are lost.
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::{types::time::OffsetDateTime, PgPool};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::telemetry;
+
+/// Event types a webhook can subscribe to.
+pub const EVENT_TYPES: &[&str] = &[BROADCAST_PUBLISHED, MESSAGE_CREATED];
+pub const BROADCAST_PUBLISHED: &str = "broadcast.published";
+pub const MESSAGE_CREATED: &str = "message.created";
+
+/// Shortest secret accepted when registering a webhook, in bytes.
+pub const MIN_SECRET_LENGTH: usize = 16;
+
+/// Header holding `sha256=<hex HMAC of the body>`.
+pub const SIGNATURE_HEADER: &str = "X-Anonyma-Signature";
+
+/// Longest one delivery attempt may take.
+pub const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Attempts per delivery, the first included.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry; doubled before each further one.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Events waiting for the dispatcher. When full, new events are dropped.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Something webhooks can subscribe to. Serializes as the payload's `data`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum WebhookEvent {
+    /// A broadcast became visible in the feed: created, published from a
+    /// draft, or run from a schedule
+    BroadcastPublished { broadcast_id: Uuid },
+    /// A new message or reply was sent
+    MessageCreated { thread_id: Uuid, message_id: Uuid },
+}
+
+impl WebhookEvent {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            WebhookEvent::BroadcastPublished { .. } => BROADCAST_PUBLISHED,
+            WebhookEvent::MessageCreated { .. } => MESSAGE_CREATED,
+        }
+    }
+}
+
+/// The JSON body POSTed to a webhook.
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    /// Unique per event and kept across retries, for deduplication
+    id: Uuid,
+    event: &'static str,
+    #[serde(with = "time::serde::rfc3339")]
+    occurred_at: OffsetDateTime,
+    data: &'a WebhookEvent,
+}
+
+/// Cheap to clone; every clone feeds the same background task.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    queue: mpsc::Sender<(WebhookEvent, OffsetDateTime)>,
+}
+
+impl WebhookDispatcher {
+    /// Spawn the dispatch task. Call from within the Tokio runtime.
+    pub fn start(pool: Arc<PgPool>) -> Self {
+        let (queue, events) = mpsc::channel(QUEUE_CAPACITY);
+        let client = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .build()
+            .expect("the webhook HTTP client builds");
+        tokio::spawn(dispatch(pool, client, events));
+        Self { queue }
+    }
+
+    /// Queue `event` for every webhook subscribed to it. Never waits.
+    pub fn emit(&self, event: WebhookEvent) {
+        let event_type = event.event_type();
+        if let Err(e) = self.queue.try_send((event, OffsetDateTime::now_utc())) {
+            warn!("Dropping webhook event {}: {}", event_type, e);
+            metrics::counter!(telemetry::WEBHOOK_DELIVERIES, "outcome" => "dropped").increment(1);
+        }
+    }
+}
+
+async fn dispatch(
+    pool: Arc<PgPool>,
+    client: reqwest::Client,
+    mut events: mpsc::Receiver<(WebhookEvent, OffsetDateTime)>,
+) {
+    while let Some((event, occurred_at)) = events.recv().await {
+        let event_type = event.event_type();
+        let targets = match crate::db::get_webhook_targets(&pool, event_type).await {
+            Ok(targets) => targets,
+            Err(e) => {
+                warn!("Failed to look up webhooks for {}: {}", event_type, e);
+                continue;
+            }
+        };
+        if targets.is_empty() {
+            continue;
+        }
+
+        let payload = Payload {
+            id: Uuid::new_v4(),
+            event: event_type,
+            occurred_at,
+            data: &event,
+        };
+        let body: Arc<[u8]> = serde_json::to_vec(&payload)
+            .expect("webhook payloads always serialize")
+            .into();
+        for target in targets {
+            let client = client.clone();
+            let body = body.clone();
+            let delivery_id = payload.id;
+            tokio::spawn(async move {
+                let outcome = deliver(&client, &target, event_type, delivery_id, &body).await;
+                metrics::counter!(telemetry::WEBHOOK_DELIVERIES, "outcome" => outcome).increment(1);
+            });
+        }
+    }
+}
+
+/// POST `body` to one webhook until it's accepted, refused, or out of
+/// attempts. Returns the outcome label for the metric.
+async fn deliver(
+    client: &reqwest::Client,
+    target: &crate::db::WebhookTarget,
+    event_type: &str,
+    delivery_id: Uuid,
+    body: &[u8],
+) -> &'static str {
+    let signature = format!("sha256={}", sign(&target.secret, body));
+    let mut backoff = INITIAL_BACKOFF;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let result = client
+            .post(&target.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .header("X-Anonyma-Event", event_type)
+            .header("X-Anonyma-Delivery", delivery_id.to_string())
+            .body(body.to_vec())
+            .send()
+            .await;
+
+        let retryable = match result {
+            Ok(response) if response.status().is_success() => {
+                debug!("Delivered {} to webhook {}", event_type, target.id);
+                return "delivered";
+            }
+            Ok(response) => {
+                warn!(
+                    "Webhook {} answered {} to {} (attempt {})",
+                    target.id,
+                    response.status(),
+                    event_type,
+                    attempt
+                );
+                response.status().is_server_error()
+            }
+            Err(e) => {
+                warn!(
+                    "Webhook {} failed on {} (attempt {}): {}",
+                    target.id, event_type, attempt, e
+                );
+                true
+            }
+        };
+        if !retryable {
+            return "rejected";
+        }
+        if attempt < MAX_ATTEMPTS {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+    "failed"
+}
+
+/// Hex HMAC-SHA256 of `body` under the webhook's secret.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
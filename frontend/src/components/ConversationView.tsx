@@ -35,7 +35,7 @@ const ConversationView = ({ messageId, threadId, toUsername, isOpen, onClose }:
     const { draft, saveDraft, clearDraft } = useDrafts(threadId);
     
     // Typing indicator
-    const { handleTyping } = useTypingIndicator(threadId);
+    const { handleTyping, stopTyping } = useTypingIndicator(threadId);
 
     // Load draft on mount
     useEffect(() => {
@@ -135,6 +135,7 @@ const ConversationView = ({ messageId, threadId, toUsername, isOpen, onClose }:
 
     const handleSend = () => {
         if (!reply.trim() || replyMutation.isPending) return;
+        stopTyping();
         replyMutation.mutate(reply);
     };
 
@@ -188,10 +189,19 @@ const ConversationView = ({ messageId, threadId, toUsername, isOpen, onClose }:
             }
         };
 
+        const handleTypingStopped = (event: CustomEvent) => {
+            if (event.detail?.thread_id === threadId) {
+                setIsTyping(false);
+                setTypingUsername(undefined);
+            }
+        };
+
         window.addEventListener('typing-indicator', handleTypingEvent as EventListener);
+        window.addEventListener('typing-stopped', handleTypingStopped as EventListener);
         
         return () => {
             window.removeEventListener('typing-indicator', handleTypingEvent as EventListener);
+            window.removeEventListener('typing-stopped', handleTypingStopped as EventListener);
         };
     }, [threadId, toUsername]);
 
@@ -82,9 +82,11 @@ export function MessageSearch({ onSelectThread }: MessageSearchProps) {
                     <div className="flex items-start gap-3">
                       <MessageCircle className="h-4 w-4 text-primary mt-1 flex-shrink-0" />
                       <div className="flex-1 min-w-0">
-                        <p className="text-sm font-mono text-foreground truncate">
-                          {msg.content}
-                        </p>
+                        {/* The server escapes the content; only its <mark> tags are markup */}
+                        <p
+                          className="text-sm font-mono text-foreground truncate [&_mark]:bg-primary/30 [&_mark]:text-foreground"
+                          dangerouslySetInnerHTML={{ __html: msg.highlight }}
+                        />
                         <p className="text-xs text-muted-foreground font-mono mt-1">
                           {new Date(msg.created_at).toLocaleString()}
                         </p>
@@ -1,5 +1,5 @@
 import { useEffect, useState, useRef } from 'react';
-import { preferences } from '@/lib/api';
+import { preferences, UserPreferences } from '@/lib/api';
 
 export function useNotificationSound() {
   const [enabled, setEnabled] = useState(true);
@@ -23,6 +23,13 @@ export function useNotificationSound() {
     // Preload audio
     audioRef.current = new Audio('/sounds/notification.mp3');
     audioRef.current.volume = 0.5;
+
+    // Setting changed on another device (dispatched by useRealtimeEvents)
+    const handlePreferencesUpdated = (e: Event) => {
+      setEnabled((e as CustomEvent<UserPreferences>).detail.notification_sound);
+    };
+    window.addEventListener('preferences-updated', handlePreferencesUpdated);
+    return () => window.removeEventListener('preferences-updated', handlePreferencesUpdated);
   }, []);
 
   const play = () => {
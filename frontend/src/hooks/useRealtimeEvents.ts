@@ -15,6 +15,8 @@ export function useRealtimeEvents() {
     const esRef = useRef<EventSource | null>(null);
     const { play: playNotificationSound } = useNotificationSound();
     const reconnectTimeoutRef = useRef<NodeJS.Timeout | null>(null);
+    // Id of the last event seen, so a reconnect replays what was missed
+    const lastEventIdRef = useRef<string | null>(null);
 
     useEffect(() => {
         function connect() {
@@ -24,15 +26,31 @@ export function useRealtimeEvents() {
                 reconnectTimeoutRef.current = null;
             }
 
-            // EventSource with credentials (cookies) — supported by all modern browsers
-            const es = new EventSource(`${API_URL}/api/events`, { withCredentials: true });
+            // EventSource with credentials (cookies) — supported by all modern browsers.
+            // A new EventSource can't send Last-Event-ID, so pass it in the URL
+            const lastEventId = lastEventIdRef.current;
+            const url = lastEventId
+                ? `${API_URL}/api/events?last_event_id=${encodeURIComponent(lastEventId)}`
+                : `${API_URL}/api/events`;
+            const es = new EventSource(url, { withCredentials: true });
             esRef.current = es;
 
+            // Like es.addEventListener, but remembers each event's id
+            const on = (type: string, handler: (event: MessageEvent) => void) => {
+                es.addEventListener(type, (event) => {
+                    const message = event as MessageEvent;
+                    if (message.lastEventId) {
+                        lastEventIdRef.current = message.lastEventId;
+                    }
+                    handler(message);
+                });
+            };
+
             es.onopen = () => {
                 console.log('SSE connection established');
             };
 
-            es.addEventListener("new_message", (event) => {
+            on("new_message", (event) => {
                 console.log('New message event received:', event.data);
                 
                 // Refresh both the flat inbox and the conversations/thread list
@@ -70,7 +88,7 @@ export function useRealtimeEvents() {
                 }
             });
 
-            es.addEventListener("message_reaction", (event) => {
+            on("message_reaction", (event) => {
                 console.log('Message reaction event received:', event.data);
                 try {
                     const data = JSON.parse(event.data);
@@ -83,7 +101,7 @@ export function useRealtimeEvents() {
                 }
             });
 
-            es.addEventListener("typing", (event) => {
+            on("typing", (event) => {
                 console.log('Typing event received:', event.data);
                 // Typing events are handled by individual conversation components
                 // We dispatch a custom event that components can listen to
@@ -97,7 +115,17 @@ export function useRealtimeEvents() {
                 }
             });
 
-            es.addEventListener("read_receipt", (event) => {
+            on("typing_stopped", (event) => {
+                console.log('Typing stopped event received:', event.data);
+                try {
+                    const data = JSON.parse(event.data);
+                    window.dispatchEvent(new CustomEvent('typing-stopped', { detail: data }));
+                } catch (e) {
+                    console.error('Failed to parse typing stopped event data:', e);
+                }
+            });
+
+            on("read_receipt", (event) => {
                 console.log('Read receipt event received:', event.data);
                 try {
                     const data = JSON.parse(event.data);
@@ -110,7 +138,79 @@ export function useRealtimeEvents() {
                 }
             });
 
-            es.addEventListener("new_broadcast", () => {
+            on("message_expired", (event) => {
+                console.log('Message expired event received:', event.data);
+                // An ephemeral or timed message is gone for both participants
+                try {
+                    const data = JSON.parse(event.data);
+                    if (data.thread_id) {
+                        queryClient.invalidateQueries({ queryKey: ["thread", data.thread_id] });
+                    }
+                    queryClient.invalidateQueries({ queryKey: ["conversations"] });
+                } catch (e) {
+                    console.error('Failed to parse expiry event data:', e);
+                }
+            });
+
+            on("message_unsent", (event) => {
+                console.log('Message unsent event received:', event.data);
+                // The sender took the message back; it's gone from the server
+                try {
+                    const data = JSON.parse(event.data);
+                    if (data.thread_id) {
+                        queryClient.invalidateQueries({ queryKey: ["thread", data.thread_id] });
+                    }
+                    queryClient.invalidateQueries({ queryKey: ["conversations"] });
+                    queryClient.invalidateQueries({ queryKey: ["inbox"] });
+                } catch (e) {
+                    console.error('Failed to parse unsend event data:', e);
+                }
+            });
+
+            on("message_pinned", (event) => {
+                console.log('Message pinned event received:', event.data);
+                try {
+                    const data = JSON.parse(event.data);
+                    if (data.thread_id) {
+                        queryClient.invalidateQueries({ queryKey: ["thread", data.thread_id] });
+                    }
+                } catch (e) {
+                    console.error('Failed to parse pin event data:', e);
+                }
+            });
+
+            on("presence", (event) => {
+                console.log('Presence event received:', event.data);
+                try {
+                    const data = JSON.parse(event.data);
+                    queryClient.setQueryData(["presence", data.user_id], {
+                        online: data.online,
+                        last_seen: data.last_seen,
+                    });
+                } catch (e) {
+                    console.error('Failed to parse presence event data:', e);
+                }
+            });
+
+            on("preferences_updated", (event) => {
+                console.log('Preferences updated event received:', event.data);
+                // Settings changed on another device (or this one); hooks that
+                // hold preference state listen for this and resync
+                try {
+                    const data = JSON.parse(event.data);
+                    window.dispatchEvent(new CustomEvent('preferences-updated', { detail: data }));
+                } catch (e) {
+                    console.error('Failed to parse preferences event data:', e);
+                }
+            });
+
+            on("resync", (event) => {
+                console.warn('SSE stream fell behind, refetching:', event.data);
+                // Some events were dropped, so any cached view may be stale
+                queryClient.invalidateQueries();
+            });
+
+            on("new_broadcast", () => {
                 console.log('New broadcast event received');
                 queryClient.invalidateQueries({ queryKey: ["broadcasts"] });
                 
@@ -135,7 +235,7 @@ export function useRealtimeEvents() {
                 }
             });
 
-            es.addEventListener("new_comment", (event) => {
+            on("new_comment", (event) => {
                 console.log('New comment event received:', event.data);
                 try {
                     const data = JSON.parse(event.data);
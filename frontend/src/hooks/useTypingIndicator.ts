@@ -3,18 +3,38 @@ import { conversations } from '@/lib/api';
 
 export function useTypingIndicator(threadId: string, enabled: boolean = true) {
   const timeoutRef = useRef<NodeJS.Timeout | null>(null);
-  const lastSentRef = useRef<number>(0);
+  // Earliest time another send can refresh the indicator (server debounces before that)
+  const nextSendAtRef = useRef<number>(0);
 
   const sendTypingIndicator = useCallback(() => {
     if (!enabled || !threadId) return;
 
     const now = Date.now();
-    // Only send if last sent was more than 3 seconds ago
-    if (now - lastSentRef.current < 3000) return;
+    if (now < nextSendAtRef.current) return;
+
+    // Hold off until the response says how long the indicator lasts
+    nextSendAtRef.current = now + 3000;
+    conversations.sendTyping(threadId)
+      .then(({ expires_in_ms }) => {
+        // Re-send once half the window is left, when the server will refresh it
+        nextSendAtRef.current = Date.now() + expires_in_ms / 2;
+      })
+      .catch(err => {
+        console.error('Failed to send typing indicator:', err);
+      });
+  }, [threadId, enabled]);
+
+  // Tell the other side right away instead of letting the indicator lapse
+  const stopTyping = useCallback(() => {
+    if (timeoutRef.current) {
+      clearTimeout(timeoutRef.current);
+      timeoutRef.current = null;
+    }
+    if (!enabled || !threadId || nextSendAtRef.current === 0) return;
 
-    lastSentRef.current = now;
-    conversations.sendTyping(threadId).catch(err => {
-      console.error('Failed to send typing indicator:', err);
+    nextSendAtRef.current = 0;
+    conversations.stopTyping(threadId).catch(err => {
+      console.error('Failed to clear typing indicator:', err);
     });
   }, [threadId, enabled]);
 
@@ -26,11 +46,9 @@ export function useTypingIndicator(threadId: string, enabled: boolean = true) {
       clearTimeout(timeoutRef.current);
     }
 
-    // Set new timeout to stop typing after 3 seconds
-    timeoutRef.current = setTimeout(() => {
-      // Typing stopped
-    }, 3000);
-  }, [sendTypingIndicator]);
+    // Stop typing after 3 idle seconds
+    timeoutRef.current = setTimeout(stopTyping, 3000);
+  }, [sendTypingIndicator, stopTyping]);
 
   useEffect(() => {
     return () => {
@@ -40,5 +58,5 @@ export function useTypingIndicator(threadId: string, enabled: boolean = true) {
     };
   }, []);
 
-  return { handleTyping };
+  return { handleTyping, stopTyping };
 }
@@ -8,6 +8,19 @@ export interface User {
   created_at: string;
 }
 
+export interface UsersPage {
+  users: User[];
+  /** Every user matching the search, not just this page */
+  total: number;
+}
+
+/** One page of a paged list, newest first */
+export interface Page<T> {
+  items: T[];
+  /** Opaque; pass back as `cursor` to load older items. Null on the last page */
+  next_cursor: string | null;
+}
+
 export interface Message {
   id: string;
   thread_id: string;
@@ -15,28 +28,94 @@ export interface Message {
   is_mine: boolean;
   created_at: string;
   is_read: boolean;
-  reactions?: Record<string, number>;
+  /** Count per emoji; `{}` when there are none */
+  reactions: Record<string, number>;
   /** Number of unread messages in this thread (only in conversation list) */
   unread_count?: number;
   /** Recipient's username — only populated if the current user is the sender */
   to_username?: string;
-  /** Timestamp when message was edited */
-  edited_at?: string;
+  /** Whether the thread is paused (conversation list and thread view) */
+  paused?: boolean;
+  /** Your private labels on the thread (conversation list only) */
+  labels?: string[];
+  /** Conversation list: you archived the thread */
+  archived?: boolean;
+  /** Conversation list: you muted the thread */
+  muted?: boolean;
+  /** Whether the content was edited after sending */
+  edited: boolean;
+  /** Timestamp of the latest edit; null if never edited */
+  edited_at: string | null;
   /** Timestamp when message was read */
   read_at?: string;
   /** Whether message is deleted */
   deleted_at?: string;
+  /** Thread view: message pinned by either participant (pinned messages come first).
+   *  Conversation list: you pinned the thread */
+  is_pinned?: boolean;
+  /** The message this one quotes (thread view only) */
+  reply_to_message_id?: string;
+  /** Quoted part of it in code points, end exclusive; absent for a whole-message quote */
+  quote_range?: QuoteRange;
+  /** First 100 characters of the quoted part (or whole message); absent once it's deleted */
+  reply_to_preview?: string;
+  /** Thread view: a forwarded copy of another message */
+  forwarded?: boolean;
+  /** When the message disappears (thread view only) */
+  expires_at?: string;
+  /** Thread view, your own messages: when editing stops being allowed; absent = no limit */
+  editable_until?: string;
+}
+
+/** Optional extras when sending a new message */
+export interface SendOptions {
+  /** Disappear shortly after the recipient opens the thread */
+  ephemeral?: boolean;
+  /** Disappear this many seconds after sending, read or not (at most a week) */
+  expires_in_secs?: number;
+  /** Hold the message until this RFC 3339 instant (at most 30 days ahead) */
+  deliver_at?: string;
+}
+
+/** A message waiting for its `deliver_at` */
+export interface ScheduledMessage {
+  id: string;
+  recipient_id: string;
+  recipient_username: string;
+  content: string;
+  deliver_at: string;
+  allow_reply: boolean;
+  ephemeral: boolean;
+}
+
+/** A message search match */
+export interface SearchResult extends Message {
+  /** HTML snippet: content escaped by the server, matched terms in `<mark>` */
+  highlight: string;
+}
+
+/** Character offsets (Unicode code points, not UTF-16 units), end exclusive */
+export interface QuoteRange {
+  start: number;
+  end: number;
+}
+
+export interface ReplyQuote {
+  reply_to_message_id?: string;
+  quote_range?: QuoteRange;
 }
 
 export interface BroadcastComment {
   id: string;
   broadcast_id: string;
-  user_id: string;
+  /** null on "[deleted]" placeholders, like `username` */
+  user_id: string | null;
   username: string | null;
   content: string;
   parent_comment_id: string | null;
   created_at: string;
-  reactions?: Record<string, number>;
+  /** Count per emoji; `{}` when there are none */
+  reactions: Record<string, number>;
 }
 
 export interface UserPreferences {
@@ -45,6 +124,25 @@ export interface UserPreferences {
   browser_notifications: boolean;
   show_read_receipts: boolean;
   show_typing_indicators: boolean;
+  /** Whether strangers can look you up by username */
+  public_profile: boolean;
+  /** Whether others can see when you're online */
+  share_presence: boolean;
+  /** Bumped on every update; pass as `expected_version` to detect conflicts */
+  version: number;
+}
+
+export interface PublicProfile {
+  username: string;
+  bio: string | null;
+  avatar_url: string | null;
+  /** They have an inbox link, so anyone can message them */
+  accepts_anonymous_messages: boolean;
+}
+
+export interface Presence {
+  online: boolean;
+  last_seen: string | null;
 }
 
 export interface Broadcast {
@@ -54,11 +152,33 @@ export interface Broadcast {
   is_anonymous: boolean;
   created_at: string;
   view_count: number;
+  /** Timestamp of the latest edit, if any */
+  edited_at?: string | null;
+  edit_count: number;
+  /** Whether you wrote it — true for your own anonymous broadcasts too */
+  is_mine: boolean;
 }
 
 // API Client
+export interface InboxLink {
+  token: string;
+  created_at: string;
+}
+
 export const API_URL = import.meta.env.VITE_API_URL || 'http://localhost:3000';
 export const FRONTEND_URL = import.meta.env.VITE_FRONTEND_URL || 'http://localhost:8080';
+// Only offer Google sign-in when the backend has GOOGLE_CLIENT_ID set
+export const GOOGLE_LOGIN_ENABLED = import.meta.env.VITE_GOOGLE_LOGIN === 'true';
+
+export class ApiError extends Error {
+  constructor(
+    public status: number,
+    public code: string,
+    message: string,
+  ) {
+    super(message);
+  }
+}
 
 async function apiRequest<T>(endpoint: string, options?: RequestInit): Promise<T> {
   const response = await fetch(`${API_URL}${endpoint}`, {
@@ -71,7 +191,13 @@ async function apiRequest<T>(endpoint: string, options?: RequestInit): Promise<T
   });
 
   if (!response.ok) {
-    throw new Error(`API Error: ${response.status} ${response.statusText}`);
+    // Error bodies look like { "error": "<code>", "message": "<human text>" }
+    const body = await response.json().catch(() => null);
+    throw new ApiError(
+      response.status,
+      body?.error ?? 'unknown',
+      body?.message ?? `API Error: ${response.status} ${response.statusText}`,
+    );
   }
 
   // Handle empty responses (like 201 Created)
@@ -97,6 +223,22 @@ export const auth = {
     apiRequest<void>('/api/me', {
       method: 'DELETE',
     }),
+  previewDeleteAccount: () =>
+    apiRequest<{
+      dry_run: true;
+      messages_received: number;
+      messages_sent: number;
+      broadcasts: number;
+      comments: number;
+      reactions: number;
+      scheduled_broadcasts: number;
+    }>('/api/me?dry_run=true', {
+      method: 'DELETE',
+    }),
+  getInboxLink: () => apiRequest<InboxLink>('/api/me/inbox-link'),
+  /** Creates the link, or replaces it: the old token stops working */
+  rotateInboxLink: () => apiRequest<InboxLink>('/api/me/inbox-link', { method: 'POST' }),
+  revokeInboxLink: () => apiRequest<void>('/api/me/inbox-link', { method: 'DELETE' }),
   login: (username: string, password: string) => apiRequest<void>('/auth/login', {
     method: 'POST',
     body: JSON.stringify({ username, password }),
@@ -105,13 +247,25 @@ export const auth = {
     method: 'POST',
     body: JSON.stringify({ username, password }),
   }),
-  loginUrl: () => `${API_URL}/auth/github?success_url=${encodeURIComponent(`${FRONTEND_URL}/dashboard`)}`,
+  loginUrl: (provider: 'github' | 'google' = 'github') =>
+    `${API_URL}/auth/${provider}?success_url=${encodeURIComponent(`${FRONTEND_URL}/dashboard`)}`,
   logoutUrl: () => `${API_URL}/logout`,
+  logoutAll: () =>
+    apiRequest<{ invalidated: number }>('/auth/logout-all', {
+      method: 'POST',
+    }),
 };
 
 // Users API
 export const users = {
-  list: () => apiRequest<User[]>('/api/users'),
+  list: (filters: { q?: string; limit?: number; offset?: number } = {}) => {
+    const params = new URLSearchParams();
+    if (filters.q) params.set('q', filters.q);
+    if (filters.limit !== undefined) params.set('limit', String(filters.limit));
+    if (filters.offset !== undefined) params.set('offset', String(filters.offset));
+    const query = params.toString();
+    return apiRequest<UsersPage>(query ? `/api/users?${query}` : '/api/users');
+  },
   block: (userId: string) =>
     apiRequest<void>(`/api/users/${userId}/block`, {
       method: 'POST',
@@ -121,23 +275,45 @@ export const users = {
       method: 'POST',
     }),
   getBlocked: () => apiRequest<string[]>('/api/users/blocked'),
+  presence: (userId: string) => apiRequest<Presence>(`/api/users/${userId}/presence`),
+  /** Works signed out; 404 if there's no such user or they hid their profile */
+  publicProfile: (username: string) =>
+    apiRequest<PublicProfile>(`/api/users/by-username/${encodeURIComponent(username)}`),
 };
 
 // Messages API
 export const messages = {
-  inbox: () => apiRequest<Message[]>('/api/messages/inbox'),
+  inbox: (cursor?: string) =>
+    apiRequest<Page<Message>>(
+      `/api/messages/inbox${cursor ? `?cursor=${encodeURIComponent(cursor)}` : ''}`
+    ),
+  unreadCount: () => apiRequest<{ total: number; threads: number }>('/api/messages/unread-count'),
   search: (query: string, limit = 50) =>
-    apiRequest<Message[]>(`/api/messages/search?q=${encodeURIComponent(query)}&limit=${limit}`),
-  send: (recipientId: string, content: string) =>
+    apiRequest<SearchResult[]>(`/api/messages/search?q=${encodeURIComponent(query)}&limit=${limit}`),
+  send: (recipientId: string, content: string, allowReply = true, options: SendOptions = {}) =>
     apiRequest<void>('/api/messages', {
       method: 'POST',
-      body: JSON.stringify({ recipient_id: recipientId, content }),
+      body: JSON.stringify({ recipient_id: recipientId, content, allow_reply: allowReply, ...options }),
     }),
-  reply: (messageId: string, content: string) =>
-    apiRequest<void>(`/api/messages/${messageId}/reply`, {
+  /** Through someone's inbox link; works signed out */
+  sendViaInboxLink: (token: string, content: string) =>
+    apiRequest<void>(`/api/anon/${encodeURIComponent(token)}`, {
       method: 'POST',
       body: JSON.stringify({ content }),
     }),
+  scheduled: () => apiRequest<ScheduledMessage[]>('/api/messages/scheduled'),
+  cancelScheduled: (messageId: string) =>
+    apiRequest<void>(`/api/messages/scheduled/${messageId}`, { method: 'DELETE' }),
+  reply: (messageId: string, content: string, quote: ReplyQuote = {}) =>
+    apiRequest<void>(`/api/messages/${messageId}/reply`, {
+      method: 'POST',
+      body: JSON.stringify({ content, ...quote }),
+    }),
+  forward: (messageId: string, recipientId: string) =>
+    apiRequest<{ message_id: string; thread_id: string }>(`/api/messages/${messageId}/forward`, {
+      method: 'POST',
+      body: JSON.stringify({ recipient_id: recipientId }),
+    }),
   edit: (messageId: string, content: string) =>
     apiRequest<void>(`/api/messages/${messageId}/edit`, {
       method: 'POST',
@@ -147,12 +323,21 @@ export const messages = {
     apiRequest<void>(`/api/messages/${messageId}/delete`, {
       method: 'DELETE',
     }),
+  unsend: (messageId: string) =>
+    apiRequest<{ unsent: boolean }>(`/api/messages/${messageId}/unsend`, {
+      method: 'DELETE',
+    }),
   pin: (messageId: string) =>
     apiRequest<{ pinned: boolean }>(`/api/messages/${messageId}/pin`, {
       method: 'POST',
     }),
+  report: (messageId: string, reason: string) =>
+    apiRequest<{ id: string }>(`/api/messages/${messageId}/report`, {
+      method: 'POST',
+      body: JSON.stringify({ reason }),
+    }),
   react: (messageId: string, emoji: string) =>
-    apiRequest<void>(`/api/messages/${messageId}/react`, {
+    apiRequest<{ reacted: boolean }>(`/api/messages/${messageId}/react`, {
       method: 'POST',
       body: JSON.stringify({ emoji }),
     }),
@@ -160,34 +345,113 @@ export const messages = {
 
 // Conversations API
 export const conversations = {
-  list: () => apiRequest<Message[]>('/api/conversations'),
+  list: (filters: { label?: string; unreadOnly?: boolean; includeArchived?: boolean } = {}) => {
+    const params = new URLSearchParams();
+    if (filters.label) params.set('label', filters.label);
+    if (filters.unreadOnly) params.set('unread_only', 'true');
+    if (filters.includeArchived) params.set('include_archived', 'true');
+    const query = params.toString();
+    return apiRequest<Message[]>(query ? `/api/conversations?${query}` : '/api/conversations');
+  },
+  pinned: () => apiRequest<Message[]>('/api/conversations/pinned'),
+  archived: () => apiRequest<Message[]>('/api/conversations/archived'),
+  archive: (threadId: string) =>
+    apiRequest<{ archived: boolean }>(`/api/conversations/${threadId}/archive`, { method: 'POST' }),
+  unarchive: (threadId: string) =>
+    apiRequest<{ archived: boolean }>(`/api/conversations/${threadId}/unarchive`, { method: 'POST' }),
+  /** Mute until `mutedUntil` (ISO 8601), or until unmuted when omitted */
+  mute: (threadId: string, mutedUntil?: string) =>
+    apiRequest<{ muted: boolean; muted_until: string | null }>(`/api/conversations/${threadId}/mute`, {
+      method: 'POST',
+      body: JSON.stringify(mutedUntil ? { muted_until: mutedUntil } : {}),
+    }),
+  unmute: (threadId: string) =>
+    apiRequest<{ muted: boolean }>(`/api/conversations/${threadId}/unmute`, { method: 'POST' }),
+  labels: () => apiRequest<{ label: string; thread_count: number }[]>('/api/conversations/labels'),
+  addLabel: (threadId: string, label: string) =>
+    apiRequest<{ labels: string[] }>(`/api/conversations/${threadId}/labels`, {
+      method: 'POST',
+      body: JSON.stringify({ label }),
+    }),
+  removeLabel: (threadId: string, label: string) =>
+    apiRequest<{ labels: string[] }>(
+      `/api/conversations/${threadId}/labels/${encodeURIComponent(label)}`,
+      { method: 'DELETE' },
+    ),
+  start: (recipientUsername: string, content: string, anonymous = false) =>
+    apiRequest<{ thread_id: string; message_id: string; recipient_id: string }>(
+      '/api/conversations',
+      {
+        method: 'POST',
+        body: JSON.stringify({ recipient_username: recipientUsername, content, anonymous }),
+      },
+    ),
+  import: (messages: Pick<Message, 'content' | 'is_mine' | 'created_at'>[], otherParty?: string) =>
+    apiRequest<{ thread_id: string; imported: number; other_party: string }>(
+      '/api/conversations/import',
+      {
+        method: 'POST',
+        body: JSON.stringify({ messages, other_party: otherParty }),
+      },
+    ),
   getThread: (threadId: string) => apiRequest<Message[]>(`/api/conversations/${threadId}`),
+  pinnedMessages: (threadId: string) =>
+    apiRequest<Message[]>(`/api/conversations/${threadId}/pinned`),
+  reply: (threadId: string, content: string, quote: ReplyQuote = {}) =>
+    apiRequest<{ message_id: string }>(`/api/conversations/${threadId}/reply`, {
+      method: 'POST',
+      body: JSON.stringify({ content, ...quote }),
+    }),
   deleteThread: (threadId: string) =>
     apiRequest<void>(`/api/conversations/${threadId}/delete`, {
       method: 'DELETE',
     }),
+  previewDeleteThread: (threadId: string) =>
+    apiRequest<{ dry_run: true; messages: number }>(
+      `/api/conversations/${threadId}/delete?dry_run=true`,
+      { method: 'DELETE' },
+    ),
   pinThread: (threadId: string) =>
     apiRequest<{ pinned: boolean }>(`/api/conversations/${threadId}/pin`, {
       method: 'POST',
     }),
   sendTyping: (threadId: string) =>
-    apiRequest<void>(`/api/conversations/${threadId}/typing`, {
+    apiRequest<{ expires_in_ms: number }>(`/api/conversations/${threadId}/typing`, {
       method: 'POST',
     }),
+  stopTyping: (threadId: string) =>
+    apiRequest<void>(`/api/conversations/${threadId}/typing`, {
+      method: 'DELETE',
+    }),
 };
 
 // Broadcasts API
 export const broadcasts = {
-  list: () => apiRequest<Broadcast[]>('/api/broadcasts'),
+  list: (cursor?: string) =>
+    apiRequest<Page<Broadcast>>(
+      `/api/broadcasts${cursor ? `?cursor=${encodeURIComponent(cursor)}` : ''}`
+    ),
+  unseenCount: () => apiRequest<{ count: number }>('/api/broadcasts/unseen-count'),
+  search: (query: string, limit = 50) =>
+    apiRequest<Broadcast[]>(`/api/broadcasts/search?q=${encodeURIComponent(query)}&limit=${limit}`),
   create: (content: string, isAnonymous: boolean) =>
     apiRequest<void>('/api/broadcasts', {
       method: 'POST',
       body: JSON.stringify({ content, is_anonymous: isAnonymous }),
     }),
+  report: (broadcastId: string, reason: string) =>
+    apiRequest<{ id: string }>(`/api/broadcasts/${broadcastId}/report`, {
+      method: 'POST',
+      body: JSON.stringify({ reason }),
+    }),
   trackView: (broadcastId: string) =>
     apiRequest<void>(`/api/broadcasts/${broadcastId}/view`, {
       method: 'POST',
     }),
+  delete: (broadcastId: string) =>
+    apiRequest<void>(`/api/broadcasts/${broadcastId}`, {
+      method: 'DELETE',
+    }),
 };
 
 // Broadcast Comments API
@@ -199,8 +463,13 @@ export const broadcastComments = {
       method: 'POST',
       body: JSON.stringify({ content, parent_comment_id: parentCommentId }),
     }),
+  topReactions: (broadcastId: string) =>
+    apiRequest<{
+      total: number;
+      reactions: { emoji: string; count: number; mine: boolean }[];
+    }>(`/api/broadcasts/${broadcastId}/reactions/top`),
   react: (commentId: string, emoji: string) =>
-    apiRequest<void>(`/api/broadcasts/comments/${commentId}/react`, {
+    apiRequest<{ reacted: boolean }>(`/api/broadcasts/comments/${commentId}/react`, {
       method: 'POST',
       body: JSON.stringify({ emoji }),
     }),
@@ -214,8 +483,19 @@ export const broadcastComments = {
 export const preferences = {
   get: () => apiRequest<UserPreferences>('/api/preferences'),
   update: (prefs: Partial<UserPreferences>) =>
-    apiRequest<void>('/api/preferences', {
+    apiRequest<UserPreferences>('/api/preferences', {
       method: 'POST',
       body: JSON.stringify(prefs),
     }),
 };
+
+// Web Push API
+export const push = {
+  /** 404 when the server has no VAPID keys configured */
+  publicKey: () => apiRequest<{ public_key: string }>('/api/push/public-key'),
+  subscribe: (subscription: PushSubscription) =>
+    apiRequest<void>('/api/push/subscribe', {
+      method: 'POST',
+      body: JSON.stringify(subscription.toJSON()),
+    }),
+};
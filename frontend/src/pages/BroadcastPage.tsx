@@ -90,7 +90,8 @@ const BroadcastPage = () => {
 
   const { data: broadcastList = [], isLoading } = useQuery({
     queryKey: ['broadcasts'],
-    queryFn: broadcasts.list,
+    queryFn: () => broadcasts.list(),
+    select: (page) => page.items,
     refetchInterval: 5000, // Refetch every 5s to sync view counts
   });
 
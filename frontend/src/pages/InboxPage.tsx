@@ -17,13 +17,14 @@ const InboxPage = () => {
   // Inbox: messages received by the current user (where they are recipient)
   const { data: receivedMessages = [], isLoading: inboxLoading } = useQuery({
     queryKey: ['inbox'],
-    queryFn: messages.inbox,
+    queryFn: () => messages.inbox(),
+    select: (page) => page.items,
   });
 
   // Conversations: all threads where user participates (both sent and received)
   const { data: allConversations = [], isLoading: convLoading } = useQuery({
     queryKey: ['conversations'],
-    queryFn: conversations.list,
+    queryFn: () => conversations.list(),
   });
 
   const isLoading = inboxLoading || convLoading;
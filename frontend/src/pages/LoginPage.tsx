@@ -1,11 +1,11 @@
 import { useState } from "react";
 import { Link, useNavigate } from "react-router-dom";
-import { Shield, Lock, User, Github, Loader2, RefreshCw } from "lucide-react";
+import { Shield, Lock, User, Github, Globe, Loader2, RefreshCw } from "lucide-react";
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { Label } from "@/components/ui/label";
 import { toast } from "sonner";
-import { auth } from "@/lib/api";
+import { auth, ApiError, GOOGLE_LOGIN_ENABLED } from "@/lib/api";
 
 const LoginPage = () => {
   const [isLogin, setIsLogin] = useState(true);
@@ -33,12 +33,8 @@ const LoginPage = () => {
       window.location.href = "/dashboard";
     } catch (error: unknown) {
       console.error("Auth error:", error);
-      const err = error as Error;
-      const message = err.message || (isLogin ? "Login failed" : "Registration failed");
       toast.error(isLogin ? "Login failed" : "Registration failed", { 
-        description: message.includes("409") ? "Codename already taken." : 
-                     message.includes("400") ? "Invalid input. Password must be 6+ chars." :
-                     "Check your credentials or try again." 
+        description: error instanceof ApiError ? error.message : "Check your credentials or try again." 
       });
     } finally {
       setIsLoading(false);
@@ -49,6 +45,10 @@ const LoginPage = () => {
     window.location.href = auth.loginUrl();
   };
 
+  const handleGoogleLogin = () => {
+    window.location.href = auth.loginUrl('google');
+  };
+
   return (
     <div className="flex min-h-screen flex-col items-center justify-center bg-background p-4 relative overflow-hidden">
       {/* Background Elements */}
@@ -153,6 +153,17 @@ const LoginPage = () => {
           GitHub
         </Button>
 
+        {GOOGLE_LOGIN_ENABLED && (
+          <Button
+            variant="outline"
+            className="w-full border-muted bg-background hover:bg-muted transition-colors"
+            onClick={handleGoogleLogin}
+          >
+            <Globe className="mr-2 h-4 w-4" />
+            Google
+          </Button>
+        )}
+
         <div className="text-center text-sm">
           <span className="text-muted-foreground">
             {isLogin ? "New to the network? " : "Already initialized? "}
@@ -3,6 +3,7 @@ import { useQuery, useMutation, useQueryClient } from "@tanstack/react-query";
 import { Users, Loader2, Send, ShieldOff } from "lucide-react";
 import { users } from "@/lib/api";
 import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
 import SendMessageModal from "@/components/SendMessageModal";
 import { motion } from "framer-motion";
 import { toast } from "sonner";
@@ -10,12 +11,15 @@ import { toast } from "sonner";
 const UsersPage = () => {
   const [selectedUserId, setSelectedUserId] = useState<string | null>(null);
   const [selectedUsername, setSelectedUsername] = useState<string>("");
+  const [search, setSearch] = useState("");
   const queryClient = useQueryClient();
 
-  const { data: usersList = [], isLoading } = useQuery({
-    queryKey: ['users'],
-    queryFn: users.list,
+  const q = search.trim();
+  const { data, isLoading } = useQuery({
+    queryKey: ['users', q],
+    queryFn: () => users.list({ q }),
   });
+  const usersList = data?.users ?? [];
 
   const blockMutation = useMutation({
     mutationFn: (userId: string) => users.block(userId),
@@ -41,8 +45,15 @@ const UsersPage = () => {
           <h1 className="font-mono text-2xl font-bold text-foreground">Active Agents</h1>
         </div>
         <p className="text-sm text-muted-foreground font-mono">
-          <span className="text-secondary">{usersList.length}</span> agents currently in the shadows
+          Showing <span className="text-secondary">{usersList.length}</span> of{" "}
+          <span className="text-secondary">{data?.total ?? 0}</span> agents in the shadows
         </p>
+        <Input
+          value={search}
+          onChange={(e) => setSearch(e.target.value)}
+          placeholder="Search by username"
+          className="mt-4 max-w-sm font-mono"
+        />
       </div>
 
       {isLoading ? (
@@ -1,5 +1,5 @@
 import { createContext, useContext, useEffect, useState, ReactNode } from 'react';
-import { preferences } from '@/lib/api';
+import { preferences, UserPreferences } from '@/lib/api';
 
 type Theme = 'light' | 'dark';
 
@@ -71,8 +71,18 @@ export function ThemeProvider({ children }: { children: ReactNode }) {
 
     mediaQuery.addEventListener('change', handleChange);
 
+    // Theme changed on another device (dispatched by useRealtimeEvents)
+    const handlePreferencesUpdated = (e: Event) => {
+      const newTheme = (e as CustomEvent<UserPreferences>).detail.theme as Theme;
+      setTheme(newTheme);
+      applyTheme(newTheme);
+      localStorage.setItem('theme', newTheme);
+    };
+    window.addEventListener('preferences-updated', handlePreferencesUpdated);
+
     return () => {
       mediaQuery.removeEventListener('change', handleChange);
+      window.removeEventListener('preferences-updated', handlePreferencesUpdated);
     };
   }, []);
 